@@ -28,6 +28,9 @@ pub enum Error {
     /// The maximum number of sessions has been reached.
     #[error("The maximum number of sessions has been reached")]
     MaxSessionsExceeded,
+    /// The max number of measurements of the session exceeds the capability of the UWBS.
+    #[error("The max number of measurements exceeds the capability of the UWBS")]
+    MaxNumberOfMeasurementsExceeded,
     /// Max ranging round retries reached.
     #[error("Max ranging round retries reached")]
     MaxRrRetryReached,
@@ -49,6 +52,36 @@ pub enum Error {
     /// Packet Tx Error
     #[error("The packet send failed with an error")]
     PacketTxError,
+    /// The UCI version of the UWBS is not supported by this library.
+    #[error("The UCI version of the UWBS is not supported")]
+    UnsupportedUciVersion,
+    /// The version of the UWBS doesn't match the expected one.
+    #[error("The version of the UWBS doesn't match the expected one")]
+    HalVersionMismatch,
+    /// The read-only app config is set.
+    #[error("The read-only app config is set")]
+    ReadOnlyAppConfig,
+    /// The blocking method is called inside the async runtime, which should use the async API
+    /// instead.
+    #[error("The blocking method is called inside the async runtime")]
+    ReentrantCall,
+    /// The wait is cancelled before it completes.
+    #[error("The wait is cancelled")]
+    Cancelled,
+    /// The UCI HAL is closed before the response is received.
+    #[error("The UCI HAL is closed")]
+    HalClosed,
+    /// The transport of the UCI HAL, e.g. the socket or the device file, fails.
+    #[error("The UCI HAL transport fails: {0:?}")]
+    Transport(std::io::ErrorKind),
+    /// The UWBS reports DEVICE_STATE_ERROR unexpectedly, e.g. the firmware crashes. The commands
+    /// fail with this error until the UCI HAL is reopened, or the UWBS is reset and reports
+    /// DEVICE_STATE_READY again.
+    #[error("The UWBS is in the error state")]
+    DeviceError,
+    /// The requested feature is not supported by the UWBS.
+    #[error("The feature is not supported by the UWBS")]
+    NotSupported,
     /// The unknown error.
     #[error("The unknown error")]
     Unknown,
@@ -63,3 +96,26 @@ pub enum Error {
 ///
 /// This type is broadly used by the methods in this library which may produce an error.
 pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Transport(err.kind())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io_error() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "socket closed");
+        assert_eq!(Error::from(io_error), Error::Transport(std::io::ErrorKind::BrokenPipe));
+
+        let read_packet = || -> Result<()> {
+            Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+            Ok(())
+        };
+        assert_eq!(read_packet(), Err(Error::Transport(std::io::ErrorKind::UnexpectedEof)));
+    }
+}
@@ -19,12 +19,16 @@ pub(super) mod utils;
 pub mod app_config_params;
 pub mod ccc_app_config_params;
 pub mod ccc_started_app_config_params;
+pub mod device_config_params;
 pub mod fira_app_config_params;
+pub mod phy_params;
 pub mod uci_packets;
 
 // Re-export params from all of the sub-modules.
 pub use app_config_params::*;
 pub use ccc_app_config_params::*;
 pub use ccc_started_app_config_params::*;
+pub use device_config_params::*;
 pub use fira_app_config_params::*;
+pub use phy_params::*;
 pub use uci_packets::*;
@@ -50,6 +50,28 @@ impl AppConfigParams {
         ))
     }
 
+    /// Generate the updated TLV list from the difference between this and the previous params,
+    /// and the TLV list which restores the same configs to the previous params. Both lists are
+    /// sorted by the config id.
+    pub fn generate_updated_and_rollback_tlvs(
+        &self,
+        prev_params: &Self,
+        session_state: SessionState,
+    ) -> Option<(Vec<AppConfigTlv>, Vec<AppConfigTlv>)> {
+        let updated_config_map = self.generate_updated_config_map(prev_params, session_state)?;
+        let mut prev_config_map = prev_params.generate_config_map();
+
+        let mut cfg_ids = updated_config_map.keys().copied().collect::<Vec<_>>();
+        cfg_ids.sort_by_key(|cfg_id| u8::from(*cfg_id));
+        let rollback_tlvs = cfg_ids
+            .iter()
+            .filter_map(|cfg_id| Some(AppConfigTlv::new(*cfg_id, prev_config_map.remove(cfg_id)?)))
+            .collect();
+        let mut updated_tlvs = Self::config_map_to_tlvs(updated_config_map);
+        updated_tlvs.sort_by_key(|tlv| u8::from(tlv.cfg_id));
+        Some((updated_tlvs, rollback_tlvs))
+    }
+
     fn config_map_to_tlvs(config_map: AppConfigTlvMap) -> Vec<AppConfigTlv> {
         config_map.into_iter().map(|(cfg_id, v)| AppConfigTlv::new(cfg_id, v)).collect()
     }
@@ -92,12 +114,14 @@ impl AppConfigParams {
     }
 
     pub fn is_type_matched(&self, session_type: SessionType) -> bool {
+        self.applicable_session_types().contains(&session_type)
+    }
+
+    /// The session types that the params could be used for.
+    pub fn applicable_session_types(&self) -> Vec<SessionType> {
         match self {
-            Self::Fira(_) => {
-                session_type == SessionType::FiraDataTransfer
-                    || session_type == SessionType::FiraRangingSession
-            }
-            Self::Ccc(_) | Self::CccStarted(_) => session_type == SessionType::Ccc,
+            Self::Fira(_) => vec![SessionType::FiraRangingSession, SessionType::FiraDataTransfer],
+            Self::Ccc(_) | Self::CccStarted(_) => vec![SessionType::Ccc],
         }
     }
 
@@ -120,3 +144,60 @@ impl AppConfigParams {
         updated_config_map
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::params::ccc_app_config_params::{
+        CccAppConfigParamsBuilder, MINIMUM_BLOCK_DURATION_MS,
+    };
+    use crate::session::session_manager::test_utils::{generate_ccc_params, generate_params};
+
+    #[test]
+    fn test_applicable_session_types() {
+        let fira_params = generate_params();
+        assert_eq!(
+            fira_params.applicable_session_types(),
+            vec![SessionType::FiraRangingSession, SessionType::FiraDataTransfer]
+        );
+        assert!(fira_params.is_type_matched(SessionType::FiraDataTransfer));
+        assert!(!fira_params.is_type_matched(SessionType::Ccc));
+
+        let ccc_params = generate_ccc_params();
+        assert_eq!(ccc_params.applicable_session_types(), vec![SessionType::Ccc]);
+        assert!(ccc_params.is_type_matched(SessionType::Ccc));
+        assert!(!ccc_params.is_type_matched(SessionType::FiraRangingSession));
+    }
+
+    #[test]
+    fn test_generate_updated_tlvs_ccc() {
+        let ccc_params = generate_ccc_params();
+        let updated_params = CccAppConfigParamsBuilder::from_params(&ccc_params)
+            .unwrap()
+            .ran_multiplier(5)
+            .build()
+            .unwrap();
+
+        let tlvs = updated_params
+            .generate_updated_tlvs(&ccc_params, SessionState::SessionStateIdle)
+            .unwrap();
+        assert_eq!(
+            tlvs,
+            vec![AppConfigTlv::new(
+                AppConfigTlvType::RangingInterval,
+                (5 * MINIMUM_BLOCK_DURATION_MS).to_le_bytes().to_vec()
+            )]
+        );
+
+        // Unchanged params emit no TLV.
+        assert_eq!(
+            ccc_params.generate_updated_tlvs(&ccc_params, SessionState::SessionStateIdle),
+            Some(vec![])
+        );
+        // The ranging interval cannot be updated at active state.
+        assert!(updated_params
+            .generate_updated_tlvs(&ccc_params, SessionState::SessionStateActive)
+            .is_none());
+    }
+}
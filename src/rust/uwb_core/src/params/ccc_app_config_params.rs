@@ -53,6 +53,7 @@ pub struct CccAppConfigParams {
     slots_per_rr: u8,
     sync_code_index: u8,
     hopping_mode: CccHoppingMode,
+    sts_index: Option<u32>,
 }
 
 #[allow(missing_docs)]
@@ -68,6 +69,7 @@ impl CccAppConfigParams {
     getter_field!(slots_per_rr, u8);
     getter_field!(sync_code_index, u8);
     getter_field!(hopping_mode, CccHoppingMode);
+    getter_field!(sts_index, Option<u32>);
 
     pub fn is_config_updatable(config_map: &AppConfigTlvMap, session_state: SessionState) -> bool {
         match session_state {
@@ -82,7 +84,7 @@ impl CccAppConfigParams {
     pub fn generate_config_map(&self) -> AppConfigTlvMap {
         debug_assert!(self.is_valid().is_some());
 
-        HashMap::from([
+        let mut config_map = HashMap::from([
             (AppConfigTlvType::DeviceType, u8_to_bytes(CCC_DEVICE_TYPE as u8)),
             (AppConfigTlvType::StsConfig, u8_to_bytes(CCC_STS_CONFIG as u8)),
             (AppConfigTlvType::MultiNodeMode, u8_to_bytes(CCC_MULTI_NODE_MODE as u8)),
@@ -106,7 +108,12 @@ impl CccAppConfigParams {
             (AppConfigTlvType::CccUwbConfigId, u16_to_bytes(self.uwb_config as u16)),
             (AppConfigTlvType::CccPulseshapeCombo, self.pulse_shape_combo.clone().into()),
             (AppConfigTlvType::CccUrskTtl, u16_to_bytes(CCC_URSK_TTL)),
-        ])
+        ]);
+        // The initial STS index is decided by the UWBS when it's not specified.
+        if let Some(sts_index) = self.sts_index {
+            config_map.insert(AppConfigTlvType::StsIndex, u32_to_bytes(sts_index));
+        }
+        config_map
     }
 
     fn is_valid(&self) -> Option<()> {
@@ -135,6 +142,7 @@ pub struct CccAppConfigParamsBuilder {
     slots_per_rr: Option<u8>,
     sync_code_index: Option<u8>,
     hopping_mode: Option<CccHoppingMode>,
+    sts_index: Option<u32>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -151,6 +159,7 @@ impl CccAppConfigParamsBuilder {
             slots_per_rr: None,
             sync_code_index: None,
             hopping_mode: None,
+            sts_index: None,
         }
     }
 
@@ -166,6 +175,7 @@ impl CccAppConfigParamsBuilder {
             slots_per_rr: self.slots_per_rr?,
             sync_code_index: self.sync_code_index?,
             hopping_mode: self.hopping_mode?,
+            sts_index: self.sts_index,
         };
         params.is_valid()?;
         Some(AppConfigParams::Ccc(params))
@@ -184,6 +194,7 @@ impl CccAppConfigParamsBuilder {
                 slots_per_rr: Some(params.slots_per_rr),
                 sync_code_index: Some(params.sync_code_index),
                 hopping_mode: Some(params.hopping_mode),
+                sts_index: params.sts_index,
             }),
             _ => None,
         }
@@ -200,6 +211,7 @@ impl CccAppConfigParamsBuilder {
     builder_field!(slots_per_rr, u8, Some);
     builder_field!(sync_code_index, u8, Some);
     builder_field!(hopping_mode, CccHoppingMode, Some);
+    builder_field!(sts_index, u32, Some);
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -285,6 +297,35 @@ pub enum CccHoppingMode {
     ContinuousAes = 5,
 }
 
+impl CccHoppingMode {
+    /// Combine the hopping config mode and the hopping sequence into the value of the
+    /// HOPPING_MODE TLV. The hopping sequence is ignored when the hopping is disabled.
+    pub fn new(config_mode: HoppingConfigMode, sequence: HoppingSequence) -> Self {
+        match (config_mode, sequence) {
+            (HoppingConfigMode::None, _) => Self::Disable,
+            (HoppingConfigMode::Adaptive, HoppingSequence::Default) => Self::AdaptiveDefault,
+            (HoppingConfigMode::Continuous, HoppingSequence::Default) => Self::ContinuousDefault,
+            (HoppingConfigMode::Adaptive, HoppingSequence::Aes) => Self::AdaptiveAes,
+            (HoppingConfigMode::Continuous, HoppingSequence::Aes) => Self::ContinuousAes,
+        }
+    }
+
+    pub fn config_mode(&self) -> HoppingConfigMode {
+        match self {
+            Self::Disable => HoppingConfigMode::None,
+            Self::AdaptiveDefault | Self::AdaptiveAes => HoppingConfigMode::Adaptive,
+            Self::ContinuousDefault | Self::ContinuousAes => HoppingConfigMode::Continuous,
+        }
+    }
+
+    pub fn sequence(&self) -> HoppingSequence {
+        match self {
+            Self::AdaptiveAes | Self::ContinuousAes => HoppingSequence::Aes,
+            _ => HoppingSequence::Default,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -394,4 +435,65 @@ mod tests {
             .generate_updated_config_map(&params, SessionState::SessionStateActive)
             .is_none());
     }
+
+    #[test]
+    fn test_hopping_mode() {
+        for (config_mode, sequence, hopping_mode) in [
+            (
+                HoppingConfigMode::Adaptive,
+                HoppingSequence::Default,
+                CccHoppingMode::AdaptiveDefault,
+            ),
+            (
+                HoppingConfigMode::Continuous,
+                HoppingSequence::Default,
+                CccHoppingMode::ContinuousDefault,
+            ),
+            (HoppingConfigMode::Adaptive, HoppingSequence::Aes, CccHoppingMode::AdaptiveAes),
+            (HoppingConfigMode::Continuous, HoppingSequence::Aes, CccHoppingMode::ContinuousAes),
+        ] {
+            assert_eq!(CccHoppingMode::new(config_mode, sequence), hopping_mode);
+            assert_eq!(hopping_mode.config_mode(), config_mode);
+            assert_eq!(hopping_mode.sequence(), sequence);
+        }
+        assert_eq!(
+            CccHoppingMode::new(HoppingConfigMode::None, HoppingSequence::Aes),
+            CccHoppingMode::Disable
+        );
+    }
+
+    #[test]
+    fn test_hopping_mode_sync_code_and_sts_index_tlvs() {
+        let mut builder = CccAppConfigParamsBuilder::new();
+        builder
+            .uwb_config(CccUwbConfig::Config1)
+            .pulse_shape_combo(CccPulseShapeCombo {
+                initiator_tx: PulseShape::SymmetricalRootRaisedCosine,
+                responder_tx: PulseShape::PrecursorFree,
+            })
+            .ran_multiplier(4)
+            .channel_number(CccUwbChannel::Channel5)
+            .chaps_per_slot(ChapsPerSlot::Value6)
+            .num_responder_nodes(2)
+            .slots_per_rr(8)
+            .sync_code_index(10)
+            .hopping_mode(CccHoppingMode::new(HoppingConfigMode::Adaptive, HoppingSequence::Aes));
+
+        // The STS index is absent unless it's specified.
+        let config_map = builder.build().unwrap().generate_config_map();
+        assert!(!config_map.contains_key(&AppConfigTlvType::StsIndex));
+
+        let config_map = builder.sts_index(0x12345678).build().unwrap().generate_config_map();
+        assert_eq!(config_map[&AppConfigTlvType::HoppingMode], vec![0x04]);
+        assert_eq!(config_map[&AppConfigTlvType::PreambleCodeIndex], vec![0x0a]);
+        assert_eq!(config_map[&AppConfigTlvType::StsIndex], vec![0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(config_map[&AppConfigTlvType::CccUwbConfigId], vec![0x01, 0x00]);
+        assert_eq!(config_map[&AppConfigTlvType::CccPulseshapeCombo], vec![0x01]);
+        assert_eq!(config_map[&AppConfigTlvType::SlotDuration], vec![0x60, 0x09]);
+        assert_eq!(config_map[&AppConfigTlvType::RangingInterval], vec![0x80, 0x01, 0x00, 0x00]);
+
+        // The sync code index is between 1 and 32.
+        assert!(builder.sync_code_index(0).build().is_none());
+        assert!(builder.sync_code_index(33).build().is_none());
+    }
 }
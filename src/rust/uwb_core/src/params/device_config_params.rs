@@ -0,0 +1,119 @@
+// Copyright 2022, The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use num_derive::{FromPrimitive, ToPrimitive};
+
+use crate::params::uci_packets::{DeviceConfigId, DeviceConfigTlv, DeviceState};
+use crate::params::utils::{u8_to_bytes, validate};
+use crate::utils::builder_field;
+
+/// The builder of the device config TLVs, which are set by UciManager::core_set_config().
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeviceConfigBuilder {
+    device_state: Option<DeviceState>,
+    low_power_mode: Option<LowPowerMode>,
+}
+
+impl DeviceConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the device config TLVs, in the order of DeviceConfigId. Return None if no config is
+    /// set or any value is invalid.
+    pub fn build(&self) -> Option<Vec<DeviceConfigTlv>> {
+        self.is_valid()?;
+
+        let mut tlvs = vec![];
+        if let Some(device_state) = self.device_state {
+            tlvs.push(DeviceConfigTlv {
+                cfg_id: DeviceConfigId::DeviceState,
+                v: u8_to_bytes(device_state.into()),
+            });
+        }
+        if let Some(low_power_mode) = self.low_power_mode {
+            tlvs.push(DeviceConfigTlv {
+                cfg_id: DeviceConfigId::LowPowerMode,
+                v: u8_to_bytes(low_power_mode as u8),
+            });
+        }
+        Some(tlvs)
+    }
+
+    fn is_valid(&self) -> Option<()> {
+        validate(
+            self.device_state.is_some() || self.low_power_mode.is_some(),
+            "At least one device config should be set",
+        )?;
+        validate(
+            self.device_state != Some(DeviceState::DeviceStateError),
+            "device_state should not be set to DEVICE_STATE_ERROR",
+        )?;
+        Some(())
+    }
+
+    builder_field!(device_state, DeviceState, Some);
+    builder_field!(low_power_mode, LowPowerMode, Some);
+}
+
+/// This configuration is used to enable/disable the low power mode of the UWBS.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+pub enum LowPowerMode {
+    /// Disable
+    Disable = 0,
+    /// Enable (default)
+    Enable = 1,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use uwb_uci_packets::Packet;
+
+    #[test]
+    fn test_build_device_config_tlvs() {
+        let tlvs = DeviceConfigBuilder::new()
+            .low_power_mode(LowPowerMode::Disable)
+            .device_state(DeviceState::DeviceStateActive)
+            .build()
+            .unwrap();
+        assert_eq!(
+            tlvs,
+            vec![
+                DeviceConfigTlv { cfg_id: DeviceConfigId::DeviceState, v: vec![0x02] },
+                DeviceConfigTlv { cfg_id: DeviceConfigId::LowPowerMode, v: vec![0x00] },
+            ]
+        );
+
+        // CORE_SET_CONFIG_CMD, with the number of the TLVs and then each TLV.
+        let packet: uwb_uci_packets::UciControlPacket =
+            uwb_uci_packets::SetConfigCmdBuilder { tlvs }.build().into();
+        let packets: Vec<uwb_uci_packets::UciControlPacketHal> = packet.into();
+        assert_eq!(
+            packets[0].clone().to_vec(),
+            vec![0x20, 0x04, 0x00, 0x07, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_build_invalid_device_config_tlvs() {
+        assert!(DeviceConfigBuilder::new().build().is_none());
+        assert!(DeviceConfigBuilder::new()
+            .device_state(DeviceState::DeviceStateError)
+            .build()
+            .is_none());
+    }
+}
@@ -17,13 +17,14 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 
-use log::warn;
+use log::{error, warn};
 use num_derive::{FromPrimitive, ToPrimitive};
+use sha2::{Digest, Sha256};
 use zeroize::Zeroize;
 
 use crate::params::app_config_params::{AppConfigParams, AppConfigTlvMap};
-use crate::params::uci_packets::{AppConfigTlvType, SessionState, SubSessionId};
-use crate::params::utils::{u16_to_bytes, u32_to_bytes, u8_to_bytes, validate};
+use crate::params::uci_packets::{AntennaPair, AppConfigTlvType, SessionState, SubSessionId};
+use crate::params::utils::{bytes_to_u8, u16_to_bytes, u32_to_bytes, u8_to_bytes, validate};
 use crate::utils::{builder_field, getter_field};
 
 // The default value of each parameters.
@@ -72,6 +73,35 @@ const DEFAULT_STS_LENGTH: StsLength = StsLength::Length64;
 const DEFAULT_NUMBER_OF_RANGE_MEASUREMENTS: u8 = 0;
 const DEFAULT_NUMBER_OF_AOA_AZIMUTH_MEASUREMENTS: u8 = 0;
 const DEFAULT_NUMBER_OF_AOA_ELEVATION_MEASUREMENTS: u8 = 0;
+const DEFAULT_ENABLE_DIAGNOSTICS: bool = false;
+const DEFAULT_DIAGRAMS_FRAME_REPORTS_FIELDS: u8 = 0;
+// The maximum number of times the UWBS retransmits a data message of the data transfer.
+const MAX_DATA_REPETITION_COUNT: u8 = 10;
+
+// The app configs which are mandatory in the FiRa spec, so they don't have the default value.
+const MANDATORY_APP_CONFIG_IDS: [AppConfigTlvType; 8] = [
+    AppConfigTlvType::DeviceType,
+    AppConfigTlvType::MultiNodeMode,
+    AppConfigTlvType::NoOfControlee,
+    AppConfigTlvType::DeviceMacAddress,
+    AppConfigTlvType::DstMacAddress,
+    AppConfigTlvType::DeviceRole,
+    AppConfigTlvType::VendorId,
+    AppConfigTlvType::StaticStsIv,
+];
+
+// The number of RSTU (Ranging Scheduling Time Unit) in 1 millisecond.
+const RSTU_PER_MS: u32 = 1200;
+
+/// The hopping schedule of the ranging rounds when the FiRa hopping is enabled. The hopping
+/// sequence is derived inside the UWBS, so the schedule is vendor-specific and supplied by the
+/// integrator of the UWBS.
+pub trait HoppingSchedule: Send + Sync {
+    /// The UWB channel used by the ranging round with |ranging_round_index| of the session
+    /// configured with |params|, or None if it can't be predicted.
+    fn channel(&self, params: &FiraAppConfigParams, ranging_round_index: u32)
+        -> Option<UwbChannel>;
+}
 
 /// The FiRa's application configuration parameters.
 /// Ref: FiRa Consortium UWB Command Interface Generic Techinal Specification Version 1.1.0.
@@ -127,6 +157,11 @@ pub struct FiraAppConfigParams {
     number_of_range_measurements: u8,
     number_of_aoa_azimuth_measurements: u8,
     number_of_aoa_elevation_measurements: u8,
+    enable_diagnostics: bool,
+    diagrams_frame_reports_fields: u8,
+
+    // Vendor-specific app config, which is appended after the standard config.
+    vendor_tlvs: AppConfigTlvMap,
 }
 
 /// Explicitly implement Debug trait to prevent logging PII data.
@@ -185,6 +220,9 @@ impl std::fmt::Debug for FiraAppConfigParams {
                 "number_of_aoa_elevation_measurements",
                 &self.number_of_aoa_elevation_measurements,
             )
+            .field("enable_diagnostics", &self.enable_diagnostics)
+            .field("diagrams_frame_reports_fields", &self.diagrams_frame_reports_fields)
+            .field("vendor_tlvs", &self.vendor_tlvs)
             .finish()
     }
 }
@@ -247,6 +285,68 @@ impl FiraAppConfigParams {
     getter_field!(number_of_range_measurements, u8);
     getter_field!(number_of_aoa_azimuth_measurements, u8);
     getter_field!(number_of_aoa_elevation_measurements, u8);
+    getter_field!(enable_diagnostics, bool);
+    getter_field!(diagrams_frame_reports_fields, u8);
+    getter_field!(vendor_tlvs, AppConfigTlvMap);
+
+    /// The UWB channel used by the ranging round with |ranging_round_index|. It is channel_number
+    /// when the hopping is disabled, otherwise it is predicted by the vendor-specific |schedule|.
+    pub fn channel_for_ranging_round(
+        &self,
+        ranging_round_index: u32,
+        schedule: &dyn HoppingSchedule,
+    ) -> Option<UwbChannel> {
+        match self.hopping_mode {
+            HoppingMode::Disable => Some(self.channel_number),
+            HoppingMode::FiraHoppingEnable => schedule.channel(self, ranging_round_index),
+        }
+    }
+
+    /// The AoA antenna pair attached as the vendor-specific app config TLV |id|, or None if it is
+    /// not attached, i.e. the UWBS selects the antennas itself.
+    pub fn aoa_antenna_pair(&self, id: AppConfigTlvType) -> Option<AntennaPair> {
+        self.vendor_tlvs.get(&id).and_then(|value| AntennaPair::from_bytes(value))
+    }
+
+    /// The number of the retransmissions of each data message of the data transfer, attached as
+    /// the vendor-specific app config TLV |id|, or None if it is not attached.
+    pub fn data_repetition_count(&self, id: AppConfigTlvType) -> Option<u8> {
+        self.vendor_tlvs.get(&id).and_then(|value| bytes_to_u8(value.clone()))
+    }
+
+    /// The interval of reporting the ranging results in the unit of ranging rounds, attached as
+    /// the vendor-specific app config TLV |id|, or None if it is not attached, i.e. the results
+    /// are reported every ranging round.
+    pub fn report_repetition_interval(&self, id: AppConfigTlvType) -> Option<u8> {
+        self.vendor_tlvs.get(&id).and_then(|value| bytes_to_u8(value.clone()))
+    }
+
+    /// The params with the FiRa spec default value of each field. The mandatory fields, which
+    /// don't have the default value, are filled with the zero value (e.g. Controlee, Responder
+    /// and the short address 0x0000).
+    pub fn defaults() -> Self {
+        match FiraAppConfigParamsBuilder::new()
+            .device_type(DeviceType::Controlee)
+            .multi_node_mode(MultiNodeMode::Unicast)
+            .device_mac_address(UwbAddress::Short([0; 2]))
+            .dst_mac_address(vec![UwbAddress::Short([0; 2])])
+            .device_role(DeviceRole::Responder)
+            .vendor_id([0; 2])
+            .static_sts_iv([0; 6])
+            .build()
+        {
+            Some(AppConfigParams::Fira(params)) => params,
+            _ => unreachable!("The default FiRa app config params should be valid"),
+        }
+    }
+
+    /// The default value of each app config which has the default value in the FiRa spec, i.e.
+    /// the mandatory app configs are not listed.
+    pub fn default_config_map() -> AppConfigTlvMap {
+        let mut config_map = Self::defaults().generate_config_map();
+        config_map.retain(|id, _| !MANDATORY_APP_CONFIG_IDS.contains(id));
+        config_map
+    }
 
     /// validate if the params are valid.
     fn is_valid(&self) -> Option<()> {
@@ -264,6 +364,27 @@ impl FiraAppConfigParams {
                 warn!("block_stride_length is ignored by a controlee");
             }
         }
+        // Without the separate CM, the control information is carried by the ranging initiation
+        // message, so the controller should be the initiator.
+        if !self.ranging_round_control.control_message {
+            validate(
+                (self.device_type == DeviceType::Controller)
+                    == (self.device_role == DeviceRole::Initiator),
+                "The controller should be the initiator when control_message is disabled",
+            )?;
+        }
+        // The MRM is only scheduled in the deferred mode.
+        if self.device_type == DeviceType::Controller
+            && self.ranging_round_control.measurement_report_message
+        {
+            validate(
+                matches!(
+                    self.ranging_round_usage,
+                    RangingRoundUsage::SsTwr | RangingRoundUsage::DsTwr
+                ),
+                "measurement_report_message should be false in the non-deferred mode",
+            )?;
+        }
         if self.ranging_time_struct != RangingTimeStruct::BlockBasedScheduling
             && self.block_stride_length != 0
         {
@@ -295,6 +416,13 @@ impl FiraAppConfigParams {
             (1..=10).contains(&self.in_band_termination_attempt_count),
             "in_band_termination_attempt_count should be between 1 to 10",
         )?;
+        if let Some(block_duration_ms) = self.block_duration_ms() {
+            validate(
+                block_duration_ms as u64 * RSTU_PER_MS as u64 >= self.ranging_round_duration_rstu(),
+                "The block duration should cover slots_per_rr * slot_duration_rstu when \
+                 BlockBasedScheduling",
+            )?;
+        }
 
         match self.mac_address_mode {
             MacAddressMode::MacAddress2Bytes | MacAddressMode::MacAddress8Bytes2BytesHeader => {
@@ -319,28 +447,15 @@ impl FiraAppConfigParams {
             }
         }
 
-        match self.prf_mode {
-            PrfMode::Bprf => {
-                validate(
-                    (9..=12).contains(&self.preamble_code_index),
-                    "preamble_code_index should be between 9 to 12 when BPRF",
-                )?;
-                validate([0, 2].contains(&self.sfd_id), "sfd_id should be 0 or 2 when BPRF")?;
-                validate(
-                    self.preamble_duration == PreambleDuration::T64Symbols,
-                    "preamble_duration should be 64 symbols when BPRF",
-                )?;
-            }
-            _ => {
-                validate(
-                    (25..=32).contains(&self.preamble_code_index),
-                    "preamble_code_index should be between 25 to 32 when HPRF",
-                )?;
-                validate(
-                    (1..=4).contains(&self.sfd_id),
-                    "sfd_id should be between 1 to 4 when HPRF",
-                )?;
-            }
+        if let Err(e) = self.prf_mode.check_compatibility(self.preamble_code_index, self.sfd_id) {
+            error!("{}", e);
+            return None;
+        }
+        if self.prf_mode == PrfMode::Bprf {
+            validate(
+                self.preamble_duration == PreambleDuration::T64Symbols,
+                "preamble_duration should be 64 symbols when BPRF",
+            )?;
         }
 
         match self.rframe_config {
@@ -381,9 +496,69 @@ impl FiraAppConfigParams {
             }
         }
 
+        for id in self.vendor_tlvs.keys() {
+            validate(
+                is_vendor_specific_tlv_type(*id),
+                &format!("{:?} is not a vendor-specific app config", id),
+            )?;
+        }
+
         Some(())
     }
 
+    /// Return the duration of one ranging block in milliseconds when the ranging_time_struct is
+    /// BlockBasedScheduling, otherwise None.
+    pub fn block_duration_ms(&self) -> Option<u32> {
+        match self.ranging_time_struct {
+            RangingTimeStruct::BlockBasedScheduling => Some(self.ranging_interval_ms),
+            _ => None,
+        }
+    }
+
+    /// Estimate the fraction of the time the radio is active, from 0.0 to 1.0, for the battery
+    /// planning before the session starts. One ranging round of slots_per_rr slots runs every
+    /// ranging interval, or every block_stride_length + 1 blocks when BlockBasedScheduling. The
+    /// frames of HPRF take less air time at the higher mean PRF, so the active time is scaled by
+    /// 62.4 MHz / the mean PRF.
+    pub fn estimated_duty_cycle(&self) -> f32 {
+        let period_ms = match self.block_duration_ms() {
+            Some(block_duration_ms) => {
+                block_duration_ms as u64 * (self.block_stride_length as u64 + 1)
+            }
+            None => self.ranging_interval_ms as u64,
+        };
+        let period_rstu = period_ms * RSTU_PER_MS as u64;
+        if period_rstu == 0 {
+            return 1.0;
+        }
+        let prf_factor = match self.prf_mode {
+            PrfMode::Bprf => 1.0,
+            PrfMode::HprfWith124_8MHz => 0.5,
+            PrfMode::HprfWith249_6MHz => 0.25,
+        };
+        let duty_cycle = self.ranging_round_duration_rstu() as f32 / period_rstu as f32;
+        duty_cycle.min(1.0) * prf_factor
+    }
+
+    /// The HMAC-SHA256 of the STS key derivation inputs, i.e. vendor_id followed by
+    /// static_sts_iv, keyed with |nonce|, in lowercase hex. It confirms two devices are
+    /// provisioned with the matching inputs without logging the inputs themselves.
+    ///
+    /// The inputs only have 64 bits, so the hash is reversible by brute force to anyone who knows
+    /// the nonce. The auditor should pick a fresh random nonce for each audit and keep it out of
+    /// the logs which contain the hash.
+    pub fn sts_key_inputs_hash(&self, nonce: &[u8]) -> String {
+        hmac_sha256(nonce, &[&self.vendor_id, &self.static_sts_iv])
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    // The duration of one ranging round, in RSTU.
+    fn ranging_round_duration_rstu(&self) -> u64 {
+        self.slots_per_rr as u64 * self.slot_duration_rstu as u64
+    }
+
     fn is_any_number_of_measurement_set(&self) -> bool {
         self.number_of_range_measurements != DEFAULT_NUMBER_OF_RANGE_MEASUREMENTS
             || self.number_of_aoa_azimuth_measurements != DEFAULT_NUMBER_OF_AOA_AZIMUTH_MEASUREMENTS
@@ -413,7 +588,7 @@ impl FiraAppConfigParams {
     pub fn generate_config_map(&self) -> AppConfigTlvMap {
         debug_assert!(self.is_valid().is_some());
 
-        HashMap::from([
+        let mut config_map = HashMap::from([
             (AppConfigTlvType::DeviceType, u8_to_bytes(self.device_type as u8)),
             (AppConfigTlvType::RangingRoundUsage, u8_to_bytes(self.ranging_round_usage as u8)),
             (AppConfigTlvType::StsConfig, u8_to_bytes(self.sts_config as u8)),
@@ -489,7 +664,31 @@ impl FiraAppConfigParams {
                 AppConfigTlvType::NbOfElevationMeasurements,
                 u8_to_bytes(self.number_of_aoa_elevation_measurements),
             ),
-        ])
+        ]);
+        // The UWBS only sends the diagnostics notifications when they are enabled, so the
+        // diagnostics configs are not sent at all by default.
+        if self.enable_diagnostics {
+            config_map.insert(AppConfigTlvType::EnableDiagnostics, u8_to_bytes(1));
+            config_map.insert(
+                AppConfigTlvType::DiagramsFrameReportsFields,
+                u8_to_bytes(self.diagrams_frame_reports_fields),
+            );
+        }
+        config_map.extend(self.vendor_tlvs.clone());
+        config_map
+    }
+}
+
+// The vendor-specific range of AppConfigTlvType, excluding the ones generated from the
+// Android-specific app config.
+fn is_vendor_specific_tlv_type(id: AppConfigTlvType) -> bool {
+    match id {
+        AppConfigTlvType::NbOfRangeMeasurements
+        | AppConfigTlvType::NbOfAzimuthMeasurements
+        | AppConfigTlvType::NbOfElevationMeasurements
+        | AppConfigTlvType::EnableDiagnostics
+        | AppConfigTlvType::DiagramsFrameReportsFields => false,
+        _ => matches!(u8::from(id), 0xa0..=0xdf | 0xe3..=0xff),
     }
 }
 
@@ -542,6 +741,9 @@ pub struct FiraAppConfigParamsBuilder {
     number_of_range_measurements: u8,
     number_of_aoa_azimuth_measurements: u8,
     number_of_aoa_elevation_measurements: u8,
+    enable_diagnostics: bool,
+    diagrams_frame_reports_fields: u8,
+    vendor_tlvs: AppConfigTlvMap,
 }
 
 #[allow(clippy::new_without_default)]
@@ -597,6 +799,9 @@ impl FiraAppConfigParamsBuilder {
             number_of_range_measurements: DEFAULT_NUMBER_OF_RANGE_MEASUREMENTS,
             number_of_aoa_azimuth_measurements: DEFAULT_NUMBER_OF_AOA_AZIMUTH_MEASUREMENTS,
             number_of_aoa_elevation_measurements: DEFAULT_NUMBER_OF_AOA_ELEVATION_MEASUREMENTS,
+            enable_diagnostics: DEFAULT_ENABLE_DIAGNOSTICS,
+            diagrams_frame_reports_fields: DEFAULT_DIAGRAMS_FRAME_REPORTS_FIELDS,
+            vendor_tlvs: HashMap::new(),
         }
     }
 
@@ -650,12 +855,16 @@ impl FiraAppConfigParamsBuilder {
                 number_of_range_measurements: params.number_of_range_measurements,
                 number_of_aoa_azimuth_measurements: params.number_of_aoa_azimuth_measurements,
                 number_of_aoa_elevation_measurements: params.number_of_aoa_elevation_measurements,
+                enable_diagnostics: params.enable_diagnostics,
+                diagrams_frame_reports_fields: params.diagrams_frame_reports_fields,
+                vendor_tlvs: params.vendor_tlvs.clone(),
             }),
             _ => None,
         }
     }
 
     pub fn build(&self) -> Option<AppConfigParams> {
+        let (vendor_id, static_sts_iv) = self.static_sts_fields()?;
         let params = FiraAppConfigParams {
             device_type: self.device_type?,
             ranging_round_usage: self.ranging_round_usage,
@@ -688,8 +897,8 @@ impl FiraAppConfigParamsBuilder {
             key_rotation_rate: self.key_rotation_rate,
             session_priority: self.session_priority,
             mac_address_mode: self.mac_address_mode,
-            vendor_id: self.vendor_id?,
-            static_sts_iv: self.static_sts_iv?,
+            vendor_id,
+            static_sts_iv,
             number_of_sts_segments: self.number_of_sts_segments,
             max_rr_retry: self.max_rr_retry,
             uwb_initiation_time_ms: self.uwb_initiation_time_ms,
@@ -704,12 +913,36 @@ impl FiraAppConfigParamsBuilder {
             number_of_range_measurements: self.number_of_range_measurements,
             number_of_aoa_azimuth_measurements: self.number_of_aoa_azimuth_measurements,
             number_of_aoa_elevation_measurements: self.number_of_aoa_elevation_measurements,
+            enable_diagnostics: self.enable_diagnostics,
+            diagrams_frame_reports_fields: self.diagrams_frame_reports_fields,
+            vendor_tlvs: self.vendor_tlvs.clone(),
         };
 
         params.is_valid()?;
         Some(AppConfigParams::Fira(params))
     }
 
+    // The vendor ID and the static STS IV are required for the static STS config. They are not
+    // used by the other STS configs, so they are zero if not set.
+    fn static_sts_fields(&self) -> Option<([u8; 2], [u8; 6])> {
+        if self.sts_config == StsConfig::Static {
+            if self.vendor_id.is_none() || self.static_sts_iv.is_none() {
+                error!("vendor_id and static_sts_iv are required for the static STS config");
+            }
+            return Some((self.vendor_id?, self.static_sts_iv?));
+        }
+
+        let vendor_id = self.vendor_id.unwrap_or_default();
+        let static_sts_iv = self.static_sts_iv.unwrap_or_default();
+        if vendor_id != [0; 2] || static_sts_iv != [0; 6] {
+            warn!(
+                "vendor_id and static_sts_iv are not used by the STS config {:?}",
+                self.sts_config
+            );
+        }
+        Some((vendor_id, static_sts_iv))
+    }
+
     // Generate the setter methods for all the fields.
     builder_field!(device_type, DeviceType, Some);
     builder_field!(ranging_round_usage, RangingRoundUsage);
@@ -758,6 +991,88 @@ impl FiraAppConfigParamsBuilder {
     builder_field!(number_of_range_measurements, u8);
     builder_field!(number_of_aoa_azimuth_measurements, u8);
     builder_field!(number_of_aoa_elevation_measurements, u8);
+    builder_field!(enable_diagnostics, bool);
+    builder_field!(diagrams_frame_reports_fields, u8);
+
+    /// Attach the vendor-specific app config TLV, which replaces the previous value of the same
+    /// |id|. The |id| should be in the vendor-specific range of AppConfigTlvType. It is the way
+    /// to set the options which the FiRa UCI spec doesn't define, e.g. reporting the ranging
+    /// results every N ranging rounds on the UWBS which supports it.
+    pub fn vendor_tlv(&mut self, id: AppConfigTlvType, value: Vec<u8>) -> &mut Self {
+        self.vendor_tlvs.insert(id, value);
+        self
+    }
+
+    /// Attach the AoA antenna pair as the vendor-specific app config TLV |id|, i.e. the id of
+    /// the azimuth or elevation antenna pair config of the UWBS, which is not defined by the FiRa
+    /// UCI spec. Return None and keep the TLVs unchanged if the pair is not two different antennas.
+    pub fn aoa_antenna_pair(
+        &mut self,
+        id: AppConfigTlvType,
+        pair: AntennaPair,
+    ) -> Option<&mut Self> {
+        if pair.first == pair.second {
+            error!("The antenna pair should be two different antennas: {:?}", pair);
+            return None;
+        }
+        Some(self.vendor_tlv(id, pair.to_bytes()))
+    }
+
+    /// Attach the number of the retransmissions of each data message of the data transfer as the
+    /// vendor-specific app config TLV |id|, i.e. the id of the data repetition config of the
+    /// UWBS, which is not defined by the FiRa UCI spec. Return None and keep the TLVs unchanged if
+    /// the count exceeds MAX_DATA_REPETITION_COUNT.
+    pub fn data_repetition_count(&mut self, id: AppConfigTlvType, count: u8) -> Option<&mut Self> {
+        if count > MAX_DATA_REPETITION_COUNT {
+            error!(
+                "The data repetition count should be at most {}: {}",
+                MAX_DATA_REPETITION_COUNT, count
+            );
+            return None;
+        }
+        Some(self.vendor_tlv(id, u8_to_bytes(count)))
+    }
+
+    /// Attach the interval of reporting the ranging results, i.e. every |interval| ranging rounds,
+    /// as the vendor-specific app config TLV |id|, which is not defined by the FiRa UCI spec.
+    /// Return None and keep the TLVs unchanged if the interval is 0.
+    pub fn report_repetition_interval(
+        &mut self,
+        id: AppConfigTlvType,
+        interval: u8,
+    ) -> Option<&mut Self> {
+        if interval == 0 {
+            error!("The report repetition interval should be at least 1 ranging round");
+            return None;
+        }
+        Some(self.vendor_tlv(id, u8_to_bytes(interval)))
+    }
+
+    /// Set the dst_mac_address field from the integer addresses, which are converted to the
+    /// short or extended addresses in little-endian according to the mac_address_mode field, so
+    /// mac_address_mode should be set first. Return None and keep the field unchanged if any
+    /// address doesn't fit in the address mode.
+    pub fn dst_addresses_u64(&mut self, addresses: &[u64]) -> Option<&mut Self> {
+        let dst_mac_address = addresses
+            .iter()
+            .map(|&address| match self.mac_address_mode {
+                MacAddressMode::MacAddress2Bytes | MacAddressMode::MacAddress8Bytes2BytesHeader => {
+                    match u16::try_from(address) {
+                        Ok(address) => Some(UwbAddress::Short(address.to_le_bytes())),
+                        Err(_) => {
+                            error!("The address {:#x} doesn't fit in the short address", address);
+                            None
+                        }
+                    }
+                }
+                MacAddressMode::MacAddress8Bytes => {
+                    Some(UwbAddress::Extended(address.to_le_bytes()))
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        self.dst_mac_address = dst_mac_address;
+        Some(self)
+    }
 }
 
 /// The device type.
@@ -841,6 +1156,18 @@ impl From<UwbAddress> for Vec<u8> {
     }
 }
 
+impl From<[u8; 2]> for UwbAddress {
+    fn from(item: [u8; 2]) -> Self {
+        UwbAddress::Short(item)
+    }
+}
+
+impl From<[u8; 8]> for UwbAddress {
+    fn from(item: [u8; 8]) -> Self {
+        UwbAddress::Extended(item)
+    }
+}
+
 impl TryFrom<Vec<u8>> for UwbAddress {
     type Error = &'static str;
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
@@ -1025,6 +1352,41 @@ pub enum PrfMode {
     HprfWith249_6MHz = 2,
 }
 
+impl PrfMode {
+    /// Check if the preamble code index and the SFD id are allowed in the PRF mode.
+    pub fn check_compatibility(
+        &self,
+        preamble_code_index: u8,
+        sfd_id: u8,
+    ) -> std::result::Result<(), PrfCompatibilityError> {
+        let (preamble_code_indexes, sfd_ids) = match self {
+            Self::Bprf => (9..=24, [0, 2].as_slice()),
+            _ => (25..=32, [1, 2, 3, 4].as_slice()),
+        };
+        if !preamble_code_indexes.contains(&preamble_code_index) {
+            return Err(PrfCompatibilityError::InvalidPreambleCodeIndex {
+                prf_mode: *self,
+                preamble_code_index,
+            });
+        }
+        if !sfd_ids.contains(&sfd_id) {
+            return Err(PrfCompatibilityError::InvalidSfdId { prf_mode: *self, sfd_id });
+        }
+        Ok(())
+    }
+}
+
+/// The error of the PHY parameters which are incompatible with the PRF mode.
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PrfCompatibilityError {
+    /// BPRF allows the preamble code index 9 to 24, and HPRF allows 25 to 32.
+    #[error("preamble_code_index {preamble_code_index} is not allowed when {prf_mode:?}")]
+    InvalidPreambleCodeIndex { prf_mode: PrfMode, preamble_code_index: u8 },
+    /// BPRF allows the SFD id 0 and 2, and HPRF allows 1 to 4.
+    #[error("sfd_id {sfd_id} is not allowed when {prf_mode:?}")]
+    InvalidSfdId { prf_mode: PrfMode, sfd_id: u8 },
+}
+
 /// This parameter is used to set the Multinode Ranging Type.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
@@ -1131,12 +1493,54 @@ pub enum StsLength {
     Length128 = 2,
 }
 
+// The HMAC-SHA256 (RFC 2104) of the concatenation of |data|, keyed with |key|.
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(key_block.map(|b| b ^ 0x36));
+    for part in data {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(key_block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use crate::utils::init_test_logging;
 
+    // The builder with the mandatory fields set, which builds the valid params.
+    fn valid_builder() -> FiraAppConfigParamsBuilder {
+        let mut builder = FiraAppConfigParamsBuilder::new();
+        builder
+            .device_type(DeviceType::Controller)
+            .multi_node_mode(MultiNodeMode::Unicast)
+            .device_mac_address(UwbAddress::Short([1, 2]))
+            .dst_mac_address(vec![UwbAddress::Short([3, 4])])
+            .device_role(DeviceRole::Initiator)
+            .vendor_id([0xFE, 0xDC])
+            .static_sts_iv([0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56]);
+        builder
+    }
+
+    fn unwrap_fira(params: AppConfigParams) -> FiraAppConfigParams {
+        match params {
+            AppConfigParams::Fira(params) => params,
+            _ => panic!("The params should be FiRa params"),
+        }
+    }
+
     #[test]
     fn test_ok() {
         init_test_logging();
@@ -1332,7 +1736,110 @@ mod tests {
     }
 
     #[test]
-    fn test_update_config() {
+    fn test_defaults() {
+        let params = FiraAppConfigParams::defaults();
+        let config_map = params.generate_config_map();
+        let expected_default_config_map = HashMap::from([
+            (AppConfigTlvType::RangingRoundUsage, vec![0x02]),
+            (AppConfigTlvType::StsConfig, vec![0x00]),
+            (AppConfigTlvType::ChannelNumber, vec![0x09]),
+            (AppConfigTlvType::SlotDuration, vec![0x60, 0x09]),
+            (AppConfigTlvType::RangingInterval, vec![0xc8, 0x00, 0x00, 0x00]),
+            (AppConfigTlvType::MacFcsType, vec![0x00]),
+            (AppConfigTlvType::RangingRoundControl, vec![0x03]),
+            (AppConfigTlvType::AoaResultReq, vec![0x01]),
+            (AppConfigTlvType::RngDataNtf, vec![0x01]),
+            (AppConfigTlvType::RngDataNtfProximityNear, vec![0x00, 0x00]),
+            (AppConfigTlvType::RngDataNtfProximityFar, vec![0x20, 0x4e]),
+            (AppConfigTlvType::RframeConfig, vec![0x03]),
+            (AppConfigTlvType::PreambleCodeIndex, vec![0x0a]),
+            (AppConfigTlvType::SfdId, vec![0x02]),
+            (AppConfigTlvType::PsduDataRate, vec![0x00]),
+            (AppConfigTlvType::PreambleDuration, vec![0x01]),
+            (AppConfigTlvType::RangingTimeStruct, vec![0x01]),
+            (AppConfigTlvType::SlotsPerRr, vec![0x19]),
+            (AppConfigTlvType::TxAdaptivePayloadPower, vec![0x00]),
+            (AppConfigTlvType::ResponderSlotIndex, vec![0x01]),
+            (AppConfigTlvType::PrfMode, vec![0x00]),
+            (AppConfigTlvType::ScheduledMode, vec![0x01]),
+            (AppConfigTlvType::KeyRotation, vec![0x00]),
+            (AppConfigTlvType::KeyRotationRate, vec![0x00]),
+            (AppConfigTlvType::SessionPriority, vec![0x32]),
+            (AppConfigTlvType::MacAddressMode, vec![0x00]),
+            (AppConfigTlvType::NumberOfStsSegments, vec![0x01]),
+            (AppConfigTlvType::MaxRrRetry, vec![0x00, 0x00]),
+            (AppConfigTlvType::UwbInitiationTime, vec![0x00, 0x00, 0x00, 0x00]),
+            (AppConfigTlvType::HoppingMode, vec![0x00]),
+            (AppConfigTlvType::BlockStrideLength, vec![0x00]),
+            (AppConfigTlvType::ResultReportConfig, vec![0x01]),
+            (AppConfigTlvType::InBandTerminationAttemptCount, vec![0x01]),
+            (AppConfigTlvType::SubSessionId, vec![0x00, 0x00, 0x00, 0x00]),
+            (AppConfigTlvType::BprfPhrDataRate, vec![0x00]),
+            (AppConfigTlvType::MaxNumberOfMeasurements, vec![0x00, 0x00]),
+            (AppConfigTlvType::StsLength, vec![0x01]),
+            (AppConfigTlvType::NbOfRangeMeasurements, vec![0x00]),
+            (AppConfigTlvType::NbOfAzimuthMeasurements, vec![0x00]),
+            (AppConfigTlvType::NbOfElevationMeasurements, vec![0x00]),
+        ]);
+        assert_eq!(FiraAppConfigParams::default_config_map(), expected_default_config_map);
+
+        let mut expected_config_map = expected_default_config_map;
+        expected_config_map.extend([
+            (AppConfigTlvType::DeviceType, vec![0x00]),
+            (AppConfigTlvType::MultiNodeMode, vec![0x00]),
+            (AppConfigTlvType::NoOfControlee, vec![0x01]),
+            (AppConfigTlvType::DeviceMacAddress, vec![0x00, 0x00]),
+            (AppConfigTlvType::DstMacAddress, vec![0x00, 0x00]),
+            (AppConfigTlvType::DeviceRole, vec![0x00]),
+            (AppConfigTlvType::VendorId, vec![0x00, 0x00]),
+            (AppConfigTlvType::StaticStsIv, vec![0x00; 6]),
+        ]);
+        assert_eq!(config_map, expected_config_map);
+    }
+
+    #[test]
+    fn test_ranging_round_control() {
+        let build = |device_type, device_role, ranging_round_usage, control_message, mrm| {
+            FiraAppConfigParamsBuilder::new()
+                .device_type(device_type)
+                .multi_node_mode(MultiNodeMode::Unicast)
+                .device_mac_address(UwbAddress::Short([1, 2]))
+                .dst_mac_address(vec![UwbAddress::Short([3, 4])])
+                .device_role(device_role)
+                .vendor_id([0xFE, 0xDC])
+                .static_sts_iv([0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56])
+                .ranging_round_usage(ranging_round_usage)
+                .ranging_round_control(RangingRoundControl {
+                    ranging_result_report_message: true,
+                    control_message,
+                    measurement_report_message: mrm,
+                })
+                .build()
+        };
+        use DeviceRole::{Initiator, Responder};
+        use DeviceType::{Controlee, Controller};
+        use RangingRoundUsage::{DsTwr, DsTwrNon, SsTwr, SsTwrNon};
+
+        // The separate CM can be disabled only when the controller is the initiator.
+        assert!(build(Controller, Initiator, DsTwr, false, false).is_some());
+        assert!(build(Controlee, Responder, DsTwr, false, false).is_some());
+        assert!(build(Controller, Responder, DsTwr, false, false).is_none());
+        assert!(build(Controlee, Initiator, DsTwr, false, false).is_none());
+        assert!(build(Controller, Responder, DsTwr, true, false).is_some());
+        assert!(build(Controlee, Initiator, DsTwr, true, false).is_some());
+
+        // The MRM from the responder to the initiator is only valid in the deferred mode.
+        assert!(build(Controller, Initiator, SsTwr, true, true).is_some());
+        assert!(build(Controller, Responder, DsTwr, true, true).is_some());
+        assert!(build(Controller, Initiator, SsTwrNon, true, true).is_none());
+        assert!(build(Controller, Responder, DsTwrNon, true, true).is_none());
+        assert!(build(Controller, Initiator, DsTwrNon, true, false).is_some());
+        // The MRM bit is ignored by a controlee.
+        assert!(build(Controlee, Responder, SsTwrNon, true, true).is_some());
+    }
+
+    #[test]
+    fn test_static_sts_fields() {
         let mut builder = FiraAppConfigParamsBuilder::new();
         builder
             .device_type(DeviceType::Controller)
@@ -1340,8 +1847,82 @@ mod tests {
             .device_mac_address(UwbAddress::Short([1, 2]))
             .dst_mac_address(vec![UwbAddress::Short([3, 4])])
             .device_role(DeviceRole::Initiator)
-            .vendor_id([0xFE, 0xDC])
-            .static_sts_iv([0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56]);
+            .sts_config(StsConfig::Static)
+            .vendor_id([0xFE, 0xDC]);
+        // The static STS config requires the static STS IV.
+        assert!(builder.build().is_none());
+        builder.static_sts_iv([0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56]);
+        assert!(builder.build().is_some());
+
+        // The dynamic STS config with the static STS IV is built with a warning.
+        builder.sts_config(StsConfig::Dynamic);
+        let params = unwrap_fira(builder.build().unwrap());
+        assert_eq!(params.vendor_id(), &[0xFE, 0xDC]);
+        assert_eq!(params.static_sts_iv(), &[0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56]);
+
+        // The dynamic STS config doesn't require them.
+        let mut builder = FiraAppConfigParamsBuilder::new();
+        builder
+            .device_type(DeviceType::Controller)
+            .multi_node_mode(MultiNodeMode::Unicast)
+            .device_mac_address(UwbAddress::Short([1, 2]))
+            .dst_mac_address(vec![UwbAddress::Short([3, 4])])
+            .device_role(DeviceRole::Initiator)
+            .sts_config(StsConfig::Dynamic);
+        let params = unwrap_fira(builder.build().unwrap());
+        assert_eq!(params.vendor_id(), &[0; 2]);
+        assert_eq!(params.static_sts_iv(), &[0; 6]);
+    }
+
+    #[test]
+    fn test_sts_key_inputs_hash() {
+        let build_params = |vendor_id, static_sts_iv| {
+            unwrap_fira(
+                valid_builder().vendor_id(vendor_id).static_sts_iv(static_sts_iv).build().unwrap(),
+            )
+        };
+        let vendor_id = [0xFE, 0xDC];
+        let static_sts_iv = [0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56];
+        let nonce = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let hash = build_params(vendor_id, static_sts_iv).sts_key_inputs_hash(&nonce);
+        assert_eq!(hash, "e406f45f5522b9f1be87c367d58023be219813d39ef5bafd0dd6bc71c0ee12e1");
+        assert_eq!(build_params(vendor_id, static_sts_iv).sts_key_inputs_hash(&nonce), hash);
+        assert_ne!(build_params([0xFE, 0xDD], static_sts_iv).sts_key_inputs_hash(&nonce), hash);
+        assert_ne!(
+            build_params(vendor_id, [0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x57])
+                .sts_key_inputs_hash(&nonce),
+            hash
+        );
+        // The same inputs hash differently in another audit.
+        assert_ne!(build_params(vendor_id, static_sts_iv).sts_key_inputs_hash(&[9; 8]), hash);
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // The test cases 2 and 6 of RFC 4231.
+        assert_eq!(
+            hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]).to_vec(),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43,
+            ]
+        );
+        assert_eq!(
+            hmac_sha256(&[0xaa; 131], &[b"Test Using Larger Than Block-Size Key - Hash Key First"])
+                .to_vec(),
+            [
+                0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+                0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+                0x0e, 0xe3, 0x7f, 0x54,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_config() {
+        let mut builder = valid_builder();
         let params = builder.build().unwrap();
 
         builder.multi_node_mode(MultiNodeMode::OneToMany);
@@ -1357,20 +1938,314 @@ mod tests {
     }
 
     #[test]
-    fn test_redacted_pii_fields() {
-        let mut builder = FiraAppConfigParamsBuilder::new();
+    fn test_block_based_scheduling() {
+        let mut builder = valid_builder();
         builder
-            .device_type(DeviceType::Controller)
-            .multi_node_mode(MultiNodeMode::Unicast)
-            .device_mac_address(UwbAddress::Short([1, 2]))
-            .dst_mac_address(vec![UwbAddress::Short([3, 4])])
-            .device_role(DeviceRole::Initiator)
-            .vendor_id([0xFE, 0xDC])
-            .static_sts_iv([0xDF, 0xCE, 0xAB, 0x12, 0x34, 0x56]);
+            .ranging_time_struct(RangingTimeStruct::BlockBasedScheduling)
+            .slot_duration_rstu(2400)
+            .slots_per_rr(25)
+            .ranging_interval_ms(50);
+        // 25 slots of 2 ms fit exactly into a 50 ms block.
+        assert_eq!(unwrap_fira(builder.build().unwrap()).block_duration_ms(), Some(50));
+
+        // 25 slots of 2 ms don't fit into a 40 ms block.
+        assert!(builder.ranging_interval_ms(40).build().is_none());
+
+        // The block duration is not applicable to the interval-based scheduling.
+        builder.ranging_time_struct(RangingTimeStruct::IntervalBasedScheduling);
+        assert_eq!(unwrap_fira(builder.build().unwrap()).block_duration_ms(), None);
+    }
+
+    #[test]
+    fn test_estimated_duty_cycle() {
+        let mut builder = valid_builder();
+        let duty_cycle = |builder: &FiraAppConfigParamsBuilder| {
+            unwrap_fira(builder.build().unwrap()).estimated_duty_cycle()
+        };
+
+        // The defaults: 25 slots of 2 ms every 200 ms.
+        assert_eq!(duty_cycle(&builder), 0.25);
+        // Skipping every other block halves the duty cycle.
+        assert_eq!(duty_cycle(builder.block_stride_length(1)), 0.125);
+        // 10 slots of 2 ms every 100 ms, at the half air time of 124.8 MHz PRF.
+        builder
+            .block_stride_length(0)
+            .slots_per_rr(10)
+            .ranging_interval_ms(100)
+            .prf_mode(PrfMode::HprfWith124_8MHz)
+            .preamble_code_index(25)
+            .sfd_id(2);
+        assert_eq!(duty_cycle(&builder), 0.1);
+        // The ranging round longer than the interval keeps the radio always active.
+        builder
+            .ranging_time_struct(RangingTimeStruct::IntervalBasedScheduling)
+            .prf_mode(PrfMode::Bprf)
+            .preamble_code_index(10)
+            .sfd_id(0)
+            .ranging_interval_ms(10);
+        assert_eq!(duty_cycle(&builder), 1.0);
+    }
+
+    #[test]
+    fn test_prf_compatibility() {
+        assert_eq!(PrfMode::Bprf.check_compatibility(9, 0), Ok(()));
+        assert_eq!(PrfMode::Bprf.check_compatibility(24, 2), Ok(()));
+        assert_eq!(
+            PrfMode::Bprf.check_compatibility(25, 2),
+            Err(PrfCompatibilityError::InvalidPreambleCodeIndex {
+                prf_mode: PrfMode::Bprf,
+                preamble_code_index: 25
+            })
+        );
+        assert_eq!(
+            PrfMode::Bprf.check_compatibility(10, 1),
+            Err(PrfCompatibilityError::InvalidSfdId { prf_mode: PrfMode::Bprf, sfd_id: 1 })
+        );
+
+        for prf_mode in [PrfMode::HprfWith124_8MHz, PrfMode::HprfWith249_6MHz] {
+            assert_eq!(prf_mode.check_compatibility(25, 1), Ok(()));
+            assert_eq!(prf_mode.check_compatibility(32, 4), Ok(()));
+            assert_eq!(
+                prf_mode.check_compatibility(12, 1),
+                Err(PrfCompatibilityError::InvalidPreambleCodeIndex {
+                    prf_mode,
+                    preamble_code_index: 12
+                })
+            );
+            assert_eq!(
+                prf_mode.check_compatibility(25, 0),
+                Err(PrfCompatibilityError::InvalidSfdId { prf_mode, sfd_id: 0 })
+            );
+        }
+
+        let mut builder = valid_builder();
+        builder.prf_mode(PrfMode::Bprf).preamble_code_index(20).sfd_id(2);
+        assert!(builder.build().is_some());
+        assert!(builder.preamble_code_index(26).build().is_none());
+        assert!(builder.prf_mode(PrfMode::HprfWith124_8MHz).sfd_id(0).build().is_none());
+        assert!(builder.sfd_id(3).build().is_some());
+    }
+
+    #[test]
+    fn test_vendor_tlvs() {
+        let vendor_tlv_type = AppConfigTlvType::try_from(0xe6).unwrap();
+        let mut builder = valid_builder();
+        builder
+            .vendor_tlv(vendor_tlv_type, vec![0x01])
+            .vendor_tlv(vendor_tlv_type, vec![0x02, 0x03]);
+        let params = builder.build().unwrap();
+        let config_map = params.generate_config_map();
+        assert_eq!(config_map.get(&vendor_tlv_type), Some(&vec![0x02, 0x03]));
+        assert_eq!(config_map.get(&AppConfigTlvType::DeviceType), Some(&vec![0x01]));
+
+        // The builder restored from the params keeps the vendor TLVs.
+        let rebuilt = FiraAppConfigParamsBuilder::from_params(&params).unwrap().build().unwrap();
+        assert_eq!(rebuilt, params);
+
+        // The standard app config can't be overridden by the vendor TLVs.
+        assert!(builder.vendor_tlv(AppConfigTlvType::DeviceType, vec![0x00]).build().is_none());
+        let mut builder = FiraAppConfigParamsBuilder::from_params(&params).unwrap();
+        assert!(builder
+            .vendor_tlv(AppConfigTlvType::NbOfAzimuthMeasurements, vec![0x00])
+            .build()
+            .is_none());
+    }
+
+    #[test]
+    fn test_diagnostics_tlvs() {
+        let mut builder = valid_builder();
+
+        // The diagnostics TLVs are not sent when the diagnostics are disabled.
+        let params = builder.diagrams_frame_reports_fields(0x07).build().unwrap();
+        let config_map = params.generate_config_map();
+        assert_eq!(config_map.get(&AppConfigTlvType::EnableDiagnostics), None);
+        assert_eq!(config_map.get(&AppConfigTlvType::DiagramsFrameReportsFields), None);
+
+        let params = builder.enable_diagnostics(true).build().unwrap();
+        let config_map = params.generate_config_map();
+        assert_eq!(config_map.get(&AppConfigTlvType::EnableDiagnostics), Some(&vec![0x01]));
+        assert_eq!(
+            config_map.get(&AppConfigTlvType::DiagramsFrameReportsFields),
+            Some(&vec![0x07])
+        );
+        let rebuilt = FiraAppConfigParamsBuilder::from_params(&params).unwrap().build().unwrap();
+        assert_eq!(rebuilt, params);
+
+        // The diagnostics configs can't be set as the vendor TLVs.
+        assert!(builder
+            .vendor_tlv(AppConfigTlvType::EnableDiagnostics, vec![0x01])
+            .build()
+            .is_none());
+    }
+
+    #[test]
+    fn test_aoa_antenna_pair_tlvs() {
+        let azimuth_id = AppConfigTlvType::try_from(0xf0).unwrap();
+        let elevation_id = AppConfigTlvType::try_from(0xf1).unwrap();
+        let mut builder = valid_builder();
+
+        // The antenna pairs are not sent when they are not set.
+        let params = builder.build().unwrap();
+        assert_eq!(params.generate_config_map().get(&azimuth_id), None);
+
+        builder
+            .aoa_antenna_pair(azimuth_id, AntennaPair { first: 0, second: 1 })
+            .unwrap()
+            .aoa_antenna_pair(elevation_id, AntennaPair { first: 0, second: 2 })
+            .unwrap();
+        let params = builder.build().unwrap();
+        let config_map = params.generate_config_map();
+        assert_eq!(config_map.get(&azimuth_id), Some(&vec![0, 1]));
+        assert_eq!(config_map.get(&elevation_id), Some(&vec![0, 2]));
+        assert_eq!(
+            unwrap_fira(params.clone()).aoa_antenna_pair(azimuth_id),
+            Some(AntennaPair { first: 0, second: 1 })
+        );
+        let rebuilt = FiraAppConfigParamsBuilder::from_params(&params).unwrap().build().unwrap();
+        assert_eq!(rebuilt, params);
+
+        // The pair of the same antenna is invalid.
+        assert!(builder
+            .aoa_antenna_pair(elevation_id, AntennaPair { first: 2, second: 2 })
+            .is_none());
+        assert_eq!(builder.build().unwrap(), params);
+    }
+
+    #[test]
+    fn test_data_repetition_count_tlv() {
+        let id = AppConfigTlvType::try_from(0xf2).unwrap();
+        let mut builder = valid_builder();
+
+        // The count is not sent when it is not set.
+        let params = builder.build().unwrap();
+        assert_eq!(params.generate_config_map().get(&id), None);
+
+        builder.data_repetition_count(id, 3).unwrap();
+        let params = builder.build().unwrap();
+        assert_eq!(params.generate_config_map().get(&id), Some(&vec![3]));
+        assert_eq!(unwrap_fira(params.clone()).data_repetition_count(id), Some(3));
+
+        // The count out of the range is rejected.
+        assert!(builder.data_repetition_count(id, MAX_DATA_REPETITION_COUNT + 1).is_none());
+        assert_eq!(builder.build().unwrap(), params);
+    }
+
+    #[test]
+    fn test_report_repetition_interval_tlv() {
+        let id = AppConfigTlvType::try_from(0xf3).unwrap();
+        let mut builder = valid_builder();
+
+        // The results are reported every round when it is not set.
+        let params = builder.build().unwrap();
+        assert_eq!(params.generate_config_map().get(&id), None);
+
+        builder.report_repetition_interval(id, 4).unwrap();
         let params = builder.build().unwrap();
+        assert_eq!(params.generate_config_map().get(&id), Some(&vec![4]));
+        assert_eq!(unwrap_fira(params.clone()).report_repetition_interval(id), Some(4));
+
+        // The zero interval is rejected.
+        assert!(builder.report_repetition_interval(id, 0).is_none());
+        assert_eq!(builder.build().unwrap(), params);
+    }
+
+    #[test]
+    fn test_channel_for_ranging_round() {
+        // The test schedule alternates between the channel 5 and 9.
+        struct TestHoppingSchedule;
+        impl HoppingSchedule for TestHoppingSchedule {
+            fn channel(
+                &self,
+                _: &FiraAppConfigParams,
+                ranging_round_index: u32,
+            ) -> Option<UwbChannel> {
+                match ranging_round_index % 2 {
+                    0 => Some(UwbChannel::Channel5),
+                    _ => Some(UwbChannel::Channel9),
+                }
+            }
+        }
+        struct UnknownHoppingSchedule;
+        impl HoppingSchedule for UnknownHoppingSchedule {
+            fn channel(&self, _: &FiraAppConfigParams, _: u32) -> Option<UwbChannel> {
+                None
+            }
+        }
+
+        let mut builder = valid_builder();
+        builder.channel_number(UwbChannel::Channel6);
+        // The schedule is not used when the hopping is disabled.
+        let params = unwrap_fira(builder.build().unwrap());
+        for ranging_round_index in [0, 1, 7] {
+            assert_eq!(
+                params.channel_for_ranging_round(ranging_round_index, &TestHoppingSchedule),
+                Some(UwbChannel::Channel6)
+            );
+        }
+
+        let params =
+            unwrap_fira(builder.hopping_mode(HoppingMode::FiraHoppingEnable).build().unwrap());
+        assert_eq!(
+            params.channel_for_ranging_round(0, &TestHoppingSchedule),
+            Some(UwbChannel::Channel5)
+        );
+        assert_eq!(
+            params.channel_for_ranging_round(7, &TestHoppingSchedule),
+            Some(UwbChannel::Channel9)
+        );
+        assert_eq!(params.channel_for_ranging_round(0, &UnknownHoppingSchedule), None);
+    }
+
+    #[test]
+    fn test_redacted_pii_fields() {
+        let params = valid_builder().build().unwrap();
 
         let format_str = format!("{params:?}");
         assert!(format_str.contains("vendor_id: \"redacted\""));
         assert!(format_str.contains("static_sts_iv: \"redacted\""));
     }
+
+    #[test]
+    fn test_uwb_address_from_bytes() {
+        let short: UwbAddress = [0x01, 0x02].into();
+        assert_eq!(short, UwbAddress::Short([0x01, 0x02]));
+        assert_eq!(Vec::<u8>::from(short), vec![0x01, 0x02]);
+
+        let extended: UwbAddress = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08].into();
+        assert_eq!(
+            extended,
+            UwbAddress::Extended([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08])
+        );
+        assert_eq!(Vec::<u8>::from(extended), vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+    }
+
+    #[test]
+    fn test_dst_addresses_u64() {
+        let mut builder = FiraAppConfigParamsBuilder::new();
+
+        // The short addresses.
+        builder.mac_address_mode(MacAddressMode::MacAddress2Bytes);
+        assert!(builder.dst_addresses_u64(&[0x1234, 0xffff]).is_some());
+        assert_eq!(
+            builder.dst_mac_address,
+            vec![UwbAddress::Short([0x34, 0x12]), UwbAddress::Short([0xff, 0xff])]
+        );
+        // The address overflows the short address, and the field is unchanged.
+        assert!(builder.dst_addresses_u64(&[0x5678, 0x10000]).is_none());
+        assert_eq!(
+            builder.dst_mac_address,
+            vec![UwbAddress::Short([0x34, 0x12]), UwbAddress::Short([0xff, 0xff])]
+        );
+
+        // The extended addresses, which fit any u64 value.
+        builder.mac_address_mode(MacAddressMode::MacAddress8Bytes);
+        assert!(builder.dst_addresses_u64(&[0x1234, u64::MAX]).is_some());
+        assert_eq!(
+            builder.dst_mac_address,
+            vec![
+                UwbAddress::Extended([0x34, 0x12, 0, 0, 0, 0, 0, 0]),
+                UwbAddress::Extended([0xff; 8])
+            ]
+        );
+    }
 }
@@ -0,0 +1,104 @@
+// Copyright 2022, The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use num_traits::FromPrimitive;
+
+use crate::params::app_config_params::AppConfigTlvMap;
+use crate::params::fira_app_config_params::{PreambleDuration, PrfMode, PsduDataRate, UwbChannel};
+use crate::params::uci_packets::AppConfigTlvType;
+use crate::params::utils::bytes_to_u8;
+
+/// The PHY parameters in effect on the UWBS, which may be adapted from the requested ones after
+/// the ranging is started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhyParams {
+    /// The UWB channel number.
+    pub channel_number: UwbChannel,
+    /// The mean PRF.
+    pub prf_mode: PrfMode,
+    /// The preamble code index.
+    pub preamble_code_index: u8,
+    /// The SFD id.
+    pub sfd_id: u8,
+    /// The data rate of the PSDU.
+    pub psdu_data_rate: PsduDataRate,
+    /// The preamble duration.
+    pub preamble_duration: PreambleDuration,
+}
+
+impl PhyParams {
+    /// The types of the app configs which are read back for the PHY parameters.
+    pub const TLV_TYPES: [AppConfigTlvType; 6] = [
+        AppConfigTlvType::ChannelNumber,
+        AppConfigTlvType::PrfMode,
+        AppConfigTlvType::PreambleCodeIndex,
+        AppConfigTlvType::SfdId,
+        AppConfigTlvType::PsduDataRate,
+        AppConfigTlvType::PreambleDuration,
+    ];
+
+    /// Decode the PHY parameters from the result of SESSION_GET_APP_CONFIG_CMD.
+    pub fn from_config_map(mut config_map: AppConfigTlvMap) -> Option<Self> {
+        let mut take_u8 = |tlv_type| bytes_to_u8(config_map.remove(&tlv_type)?);
+        Some(Self {
+            channel_number: UwbChannel::from_u8(take_u8(AppConfigTlvType::ChannelNumber)?)?,
+            prf_mode: PrfMode::from_u8(take_u8(AppConfigTlvType::PrfMode)?)?,
+            preamble_code_index: take_u8(AppConfigTlvType::PreambleCodeIndex)?,
+            sfd_id: take_u8(AppConfigTlvType::SfdId)?,
+            psdu_data_rate: PsduDataRate::from_u8(take_u8(AppConfigTlvType::PsduDataRate)?)?,
+            preamble_duration: PreambleDuration::from_u8(take_u8(
+                AppConfigTlvType::PreambleDuration,
+            )?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::params::utils::u8_to_bytes;
+
+    #[test]
+    fn test_from_config_map() {
+        let mut config_map = HashMap::from([
+            (AppConfigTlvType::ChannelNumber, u8_to_bytes(5)),
+            (AppConfigTlvType::PrfMode, u8_to_bytes(1)),
+            (AppConfigTlvType::PreambleCodeIndex, u8_to_bytes(25)),
+            (AppConfigTlvType::SfdId, u8_to_bytes(2)),
+            (AppConfigTlvType::PsduDataRate, u8_to_bytes(1)),
+            (AppConfigTlvType::PreambleDuration, u8_to_bytes(0)),
+        ]);
+        assert_eq!(
+            PhyParams::from_config_map(config_map.clone()),
+            Some(PhyParams {
+                channel_number: UwbChannel::Channel5,
+                prf_mode: PrfMode::HprfWith124_8MHz,
+                preamble_code_index: 25,
+                sfd_id: 2,
+                psdu_data_rate: PsduDataRate::Rate7m80,
+                preamble_duration: PreambleDuration::T32Symbols,
+            })
+        );
+
+        // The unknown channel number.
+        config_map.insert(AppConfigTlvType::ChannelNumber, u8_to_bytes(7));
+        assert_eq!(PhyParams::from_config_map(config_map.clone()), None);
+        // The missing config.
+        config_map.remove(&AppConfigTlvType::ChannelNumber);
+        assert_eq!(PhyParams::from_config_map(config_map), None);
+    }
+}
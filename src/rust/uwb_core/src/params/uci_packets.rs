@@ -21,14 +21,15 @@ use std::iter::FromIterator;
 // Re-export enums and structs from uwb_uci_packets.
 pub use uwb_uci_packets::{
     AppConfigStatus, AppConfigTlv as RawAppConfigTlv, AppConfigTlvType, CapTlv, CapTlvType,
-    Controlee, ControleeStatus, Controlees, CreditAvailability, DataRcvStatusCode,
-    DataTransferNtfStatusCode, DeviceConfigId, DeviceConfigStatus, DeviceConfigTlv, DeviceState,
+    CoexistenceSource, Controlee, ControleeStatus, Controlees, CreditAvailability,
+    DataRcvStatusCode, DataTransferNtfStatusCode, DeviceConfigId, DeviceConfigStatus,
+    DeviceConfigTlv, DeviceState, DlTdoaRangingMeasurement,
     ExtendedAddressDlTdoaRangingMeasurement, ExtendedAddressOwrAoaRangingMeasurement,
     ExtendedAddressTwoWayRangingMeasurement, FiraComponent, GroupId, MessageType,
-    MulticastUpdateStatusCode, PowerStats, RangingMeasurementType, ReasonCode, ResetConfig,
-    SessionState, SessionType, ShortAddressDlTdoaRangingMeasurement,
-    ShortAddressOwrAoaRangingMeasurement, ShortAddressTwoWayRangingMeasurement, StatusCode,
-    UpdateMulticastListAction,
+    MulticastUpdateStatusCode, ParsedFrameReport, PhaseList, PowerProfile, PowerStats,
+    RangingMeasurementType, ReasonCode, ResetConfig, SessionState, SessionType,
+    ShortAddressDlTdoaRangingMeasurement, ShortAddressOwrAoaRangingMeasurement,
+    ShortAddressTwoWayRangingMeasurement, StatusCode, UpdateMulticastListAction,
 };
 pub(crate) use uwb_uci_packets::{UciControlPacket, UciDataPacket, UciDataPacketHal};
 
@@ -42,8 +43,11 @@ pub type SubSessionId = u32;
 pub type SessionHandle = u32;
 /// Generic type used to represent either a session id or session handle.
 pub type SessionToken = u32;
+/// The GID and OID of a UCI control packet.
+pub type UciOpcode = (GroupId, u8);
 
-/// Wrap the original AppConfigTlv type to redact the PII fields when logging.
+/// Wrap the original AppConfigTlv type to redact the PII fields when logging. The STS-related
+/// values are zeroized by the wrapped type when it is dropped.
 #[derive(Clone, PartialEq)]
 pub struct AppConfigTlv {
     tlv: RawAppConfigTlv,
@@ -143,6 +147,61 @@ pub struct SetAppConfigResponse {
     pub config_status: Vec<AppConfigStatus>,
 }
 
+impl SetAppConfigResponse {
+    /// The type and the status code of the config TLVs which are rejected by the UWBS.
+    pub fn rejected(&self) -> Vec<(AppConfigTlvType, StatusCode)> {
+        self.config_status
+            .iter()
+            .filter(|status| status.status != StatusCode::UciStatusOk)
+            .map(|status| (status.cfg_id, status.status))
+            .collect()
+    }
+}
+
+/// A config which the UWBS doesn't hold as it is set, found by
+/// UciManagerImpl::validate_params_on_device().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMismatch {
+    /// The type of the config.
+    pub cfg_id: AppConfigTlvType,
+    /// The value which is set.
+    pub requested: Vec<u8>,
+    /// The value read back from the UWBS, or None if the config is rejected or not reported.
+    pub actual: Option<Vec<u8>>,
+    /// The status of the config in SESSION_SET_APP_CONFIG_RSP.
+    pub status: StatusCode,
+}
+
+/// The outcome of a UCI command, which keeps the status code of the response even when the
+/// command succeeds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutcome {
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The rest of the response payload after the status code.
+    pub payload: Vec<u8>,
+}
+
+impl CommandOutcome {
+    /// Return true if the status code of the response is UCI_STATUS_OK.
+    pub fn is_ok(&self) -> bool {
+        self.status == StatusCode::UciStatusOk
+    }
+}
+
+/// A failed UCI command, which is recorded by UciManagerImpl for the post-mortem debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandError {
+    /// The GID of the command.
+    pub gid: GroupId,
+    /// The OID of the command.
+    pub oid: u8,
+    /// The error of the command, which is converted from the status code of the response.
+    pub error: Error,
+    /// The time when the command failed.
+    pub timestamp: std::time::SystemTime,
+}
+
 /// The response from UciManager::session_update_dt_tag_ranging_rounds() method.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SessionUpdateDtTagRangingRoundsResponse {
@@ -152,6 +211,44 @@ pub struct SessionUpdateDtTagRangingRoundsResponse {
     pub ranging_round_indexes: Vec<u8>,
 }
 
+/// A phase of the hybrid UWB scheduling (HUS), in which a sub-session owns the slots of the
+/// ranging block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HybridSessionPhase {
+    /// The session id of the sub-session.
+    pub session_id: SessionId,
+    /// The index of the first slot of the phase.
+    pub start_slot_index: u16,
+    /// The index of the last slot of the phase.
+    pub end_slot_index: u16,
+}
+
+/// The hybrid UWB scheduling (HUS) config of a controller session, which is set by
+/// UciManager::session_set_hybrid_config() method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HybridSessionConfig {
+    /// The UWB time when the config takes effect, in the unit of us. 0 means the next ranging
+    /// block.
+    pub update_time: u64,
+    /// The phases of the sub-sessions, in the order of the slots.
+    pub phases: Vec<HybridSessionPhase>,
+}
+
+impl HybridSessionConfig {
+    /// Check if the config could be encoded into SESSION_SET_HUS_CONFIG_CMD. The phases should
+    /// be non-empty, and should not overlap with each other.
+    pub fn is_valid(&self) -> bool {
+        if self.phases.is_empty() || self.phases.len() > u8::MAX as usize {
+            return false;
+        }
+        self.phases.iter().all(|phase| phase.start_slot_index <= phase.end_slot_index)
+            && self
+                .phases
+                .windows(2)
+                .all(|phases| phases[0].end_slot_index < phases[1].start_slot_index)
+    }
+}
+
 /// The country code struct that contains 2 uppercase ASCII characters.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CountryCode([u8; 2]);
@@ -200,6 +297,192 @@ pub struct GetDeviceInfoResponse {
     pub vendor_spec_info: Vec<u8>,
 }
 
+/// The versions of the UWBS reported in the GetDeviceInfoResponse, which are compared by
+/// UciManagerSync::verify_hal_version() to pin the known-good firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HalVersion {
+    /// The UCI version.
+    pub uci_version: u16,
+    /// The MAC version.
+    pub mac_version: u16,
+    /// The physical version.
+    pub phy_version: u16,
+    /// The UCI test version.
+    pub uci_test_version: u16,
+}
+
+impl From<&GetDeviceInfoResponse> for HalVersion {
+    fn from(device_info: &GetDeviceInfoResponse) -> Self {
+        Self {
+            uci_version: device_info.uci_version,
+            mac_version: device_info.mac_version,
+            phy_version: device_info.phy_version,
+            uci_test_version: device_info.uci_test_version,
+        }
+    }
+}
+
+/// How the ranging of a session is stopped, decoded from the reason code of SESSION_STATUS_NTF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionTermination {
+    /// Stopped by the session management commands of the host.
+    Command,
+    /// Stopped by the in-band termination signal, within IN_BAND_TERMINATION_ATTEMPT_COUNT.
+    InBandSignal,
+    /// Stopped after the ranging round retries are exhausted, e.g. the controlee dropped out.
+    RetryExhausted,
+    /// Stopped after the max number of measurements is reached.
+    MaxMeasurementsReached,
+}
+
+impl SessionTermination {
+    /// Decode the reason code of the session stopping. Return None if the reason code is not
+    /// about the termination, e.g. an error of the app config.
+    pub fn from_reason_code(reason_code: ReasonCode) -> Option<Self> {
+        match reason_code {
+            ReasonCode::StateChangeWithSessionManagementCommands => Some(Self::Command),
+            ReasonCode::SessionStoppedDueToInbandSignal => Some(Self::InBandSignal),
+            ReasonCode::MaxRangingRoundRetryCountReached => Some(Self::RetryExhausted),
+            ReasonCode::MaxNumberOfMeasurementsReached => Some(Self::MaxMeasurementsReached),
+            _ => None,
+        }
+    }
+
+    /// Whether the session is terminated gracefully, i.e. not by the exhaustion of the retries.
+    pub fn is_graceful(&self) -> bool {
+        *self != Self::RetryExhausted
+    }
+}
+
+/// The role of the device in the data transfer of a FIRA_DATA_TRANSFER session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataTransferRole {
+    /// Sends the application data, i.e. the controller of the session.
+    Sender,
+    /// Receives the application data, i.e. the controlee of the session.
+    Receiver,
+}
+
+impl DataTransferRole {
+    /// Decode the roles from the capability |cap_id|, which is a bitmask with bit 0 for Sender and
+    /// bit 1 for Receiver. Return None if the capability is not found.
+    pub fn from_caps(caps: &[CapTlv], cap_id: CapTlvType) -> Option<Vec<Self>> {
+        let tlv = caps.iter().find(|tlv| tlv.t == cap_id)?;
+        let bitmask = tlv.v.first().copied().unwrap_or(0);
+        Some(
+            [Self::Sender, Self::Receiver]
+                .into_iter()
+                .filter(|role| bitmask & role.cap_bit() != 0)
+                .collect(),
+        )
+    }
+
+    /// Get the role from the value of the DEVICE_TYPE app config.
+    pub fn from_device_type(device_type: &[u8]) -> Option<Self> {
+        match device_type {
+            [0x00] => Some(Self::Receiver),
+            [0x01] => Some(Self::Sender),
+            _ => None,
+        }
+    }
+
+    fn cap_bit(&self) -> u8 {
+        match self {
+            Self::Sender => 0x01,
+            Self::Receiver => 0x02,
+        }
+    }
+}
+
+/// The ids of the capabilities which the FiRa UCI spec doesn't define, so they are
+/// vendor-specific and supplied by the integrator of the UWBS. The capability is not read when
+/// its id is None.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VendorCapIds {
+    /// The bitmask of the supported data transfer roles, see DataTransferRole::from_caps().
+    pub data_transfer_roles: Option<CapTlvType>,
+    /// The clock accuracy of the UWBS in the unit of 0.01 ppm, which is a 2-byte value in
+    /// little-endian.
+    pub clock_accuracy: Option<CapTlvType>,
+    /// The upper bound of the MAX_NUMBER_OF_MEASUREMENTS app config, which is a 2-byte value in
+    /// little-endian.
+    pub max_number_of_measurements: Option<CapTlvType>,
+}
+
+/// The (GID, OID) of the vendor notifications which report the events the UCI spec doesn't
+/// define, supplied by the integrator of the UWBS. The notification is forwarded as a raw vendor
+/// notification when its id is None.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VendorNotificationIds {
+    /// The STS key of a session is rotated. The payload is the session token followed by the
+    /// number of the rotations since the session is started, both 4-byte values in little-endian.
+    pub key_rotation: Option<(u32, u32)>,
+    /// The UWB radio is preempted by or released from another radio. The payload is the
+    /// CoexistenceSource, 1 if preempted or 0 if released, and the expected duration of the
+    /// preemption in milliseconds as a 2-byte value in little-endian.
+    pub coexistence: Option<(u32, u32)>,
+}
+
+/// The (GID, OID) of the vendor commands which query the information the UCI spec doesn't
+/// define, supplied by the integrator of the UWBS. The method sending the command returns
+/// Error::NotSupported when its id is None.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VendorCommandIds {
+    /// Query the indexes of the ranging rounds which could be used by the DL-TDoA session. The
+    /// command payload is the session token as a 4-byte value in little-endian. The response
+    /// payload is the status, the number of the indexes, and the 1-byte indexes.
+    pub possible_ranging_rounds: Option<(u32, u32)>,
+}
+
+/// The pair of the RX antennas used to measure the AoA on one axis. The antennas are identified
+/// by their indexes, starting from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntennaPair {
+    /// The index of the first antenna.
+    pub first: u8,
+    /// The index of the second antenna.
+    pub second: u8,
+}
+
+impl AntennaPair {
+    /// Decode the value of the antenna pair app config.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [first, second] => Some(Self { first: *first, second: *second }),
+            _ => None,
+        }
+    }
+
+    /// Encode the pair into the value of the app config.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![self.first, self.second]
+    }
+
+    /// Whether both antennas exist on the UWBS with |antenna_count| antennas.
+    pub fn fits_antenna_count(&self, antenna_count: u8) -> bool {
+        self.first < antenna_count && self.second < antenna_count
+    }
+
+    /// Get the number of the antennas from the capability |cap_id|. Return None if the
+    /// capability is not found.
+    pub fn antenna_count_from_caps(caps: &[CapTlv], cap_id: CapTlvType) -> Option<u8> {
+        let tlv = caps.iter().find(|tlv| tlv.t == cap_id)?;
+        tlv.v.first().copied()
+    }
+}
+
+/// The ids of the AoA antenna pair selection. The FiRa UCI spec doesn't define them, so they are
+/// vendor-specific and supplied by the integrator of the UWBS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntennaPairIds {
+    /// The app config id of the antenna pair used to measure the AoA azimuth.
+    pub azimuth: AppConfigTlvType,
+    /// The app config id of the antenna pair used to measure the AoA elevation.
+    pub elevation: AppConfigTlvType,
+    /// The capability id of the number of the antennas of the UWBS.
+    pub antenna_count: CapTlvType,
+}
+
 /// The raw UCI message for the vendor commands.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawUciMessage {
@@ -225,6 +508,8 @@ impl From<UciControlPacket> for RawUciMessage {
 mod tests {
     use super::*;
 
+    use zeroize::Zeroize;
+
     #[test]
     fn test_redacted_app_config_tlv() {
         // The value of VendorId and StaticStsIv should be redacted.
@@ -242,6 +527,21 @@ mod tests {
         assert_eq!(format_str, "AppConfigTlv { cfg_id: DeviceType, v: [12, 34] }");
     }
 
+    #[test]
+    fn test_into_inner_zeroize() {
+        // The wrapped value handed out by into_inner() is zeroized the same way when it is
+        // dropped.
+        let mut raw_tlv =
+            AppConfigTlv::new(AppConfigTlvType::SessionKey, vec![0xab; 16]).into_inner();
+        assert_eq!(raw_tlv.v, vec![0xab; 16]);
+        raw_tlv.zeroize();
+        assert!(raw_tlv.v.is_empty());
+
+        let mut raw_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![1]).into_inner();
+        raw_tlv.zeroize();
+        assert_eq!(raw_tlv.v, vec![1]);
+    }
+
     #[test]
     fn test_country_code() {
         let _country_code_ascii: CountryCode = String::from("US").try_into().unwrap();
@@ -251,4 +551,24 @@ mod tests {
         let country_code_invalid_2: Result<CountryCode, Error> = String::from("ÀÈ").try_into();
         country_code_invalid_2.unwrap_err();
     }
+
+    #[test]
+    fn test_hybrid_session_config_is_valid() {
+        let phase = |session_id, start_slot_index, end_slot_index| HybridSessionPhase {
+            session_id,
+            start_slot_index,
+            end_slot_index,
+        };
+        let config =
+            HybridSessionConfig { update_time: 0, phases: vec![phase(1, 0, 9), phase(2, 10, 19)] };
+        assert!(config.is_valid());
+
+        // No phase.
+        assert!(!HybridSessionConfig { phases: vec![], ..config.clone() }.is_valid());
+        // The phase ends before it starts.
+        assert!(!HybridSessionConfig { phases: vec![phase(1, 9, 0)], ..config.clone() }.is_valid());
+        // The phases overlap.
+        assert!(!HybridSessionConfig { phases: vec![phase(1, 0, 10), phase(2, 10, 19)], ..config }
+            .is_valid());
+    }
 }
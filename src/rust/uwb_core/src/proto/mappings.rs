@@ -14,6 +14,8 @@
 
 //! Provide the conversion between the uwb_core's elements and protobuf bindings.
 
+#[cfg(feature = "latency-stats")]
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 
 use protobuf::RepeatedField;
@@ -27,36 +29,49 @@ use crate::params::fira_app_config_params::{
     RangingRoundControl, RangingRoundUsage, RangingTimeStruct, ResultReportConfig, RframeConfig,
     ScheduledMode, StsConfig, StsLength, TxAdaptivePayloadPower, UwbAddress, UwbChannel,
 };
+#[cfg(feature = "latency-stats")]
+use crate::params::uci_packets::UciOpcode;
 use crate::params::uci_packets::{
-    Controlee, DeviceState, ExtendedAddressDlTdoaRangingMeasurement,
-    ExtendedAddressOwrAoaRangingMeasurement, ExtendedAddressTwoWayRangingMeasurement, PowerStats,
-    RangingMeasurementType, ReasonCode, SessionState, SessionType,
-    ShortAddressDlTdoaRangingMeasurement, ShortAddressOwrAoaRangingMeasurement,
-    ShortAddressTwoWayRangingMeasurement, StatusCode, UpdateMulticastListAction,
+    AppConfigStatus, AppConfigTlvType, CoexistenceSource, Controlee, DeviceState,
+    ExtendedAddressDlTdoaRangingMeasurement, ExtendedAddressOwrAoaRangingMeasurement,
+    ExtendedAddressTwoWayRangingMeasurement, PowerStats, RangingMeasurementType, ReasonCode,
+    SessionState, SessionType, SetAppConfigResponse, ShortAddressDlTdoaRangingMeasurement,
+    ShortAddressOwrAoaRangingMeasurement, ShortAddressTwoWayRangingMeasurement, StatusCode,
+    UpdateMulticastListAction,
 };
 use crate::params::AppConfigParams;
 use crate::proto::bindings::{
-    AoaResultRequest as ProtoAoaResultRequest, BprfPhrDataRate as ProtoBprfPhrDataRate,
-    Controlee as ProtoControlee, DeviceRole as ProtoDeviceRole, DeviceState as ProtoDeviceState,
-    DeviceType as ProtoDeviceType, DlTDoARangingMeasurement as ProtoDlTDoARangingMeasurement,
+    AoaResultRequest as ProtoAoaResultRequest, AppConfigStatus as ProtoAppConfigStatus,
+    AppConfigTlv as ProtoAppConfigTlv, BprfPhrDataRate as ProtoBprfPhrDataRate,
+    CoexistenceSource as ProtoCoexistenceSource, Controlee as ProtoControlee,
+    DeviceRole as ProtoDeviceRole, DeviceState as ProtoDeviceState, DeviceType as ProtoDeviceType,
+    DlTDoARangingMeasurement as ProtoDlTDoARangingMeasurement,
     FiraAppConfigParams as ProtoFiraAppConfigParams, HoppingMode as ProtoHoppingMode,
-    KeyRotation as ProtoKeyRotation, MacAddressMode as ProtoMacAddressMode,
-    MacFcsType as ProtoMacFcsType, MultiNodeMode as ProtoMultiNodeMode,
-    OwrAoaRangingMeasurement as ProtoOwrAoaRangingMeasurement, PowerStats as ProtoPowerStats,
-    PreambleDuration as ProtoPreambleDuration, PrfMode as ProtoPrfMode,
-    PsduDataRate as ProtoPsduDataRate, RangeDataNtfConfig as ProtoRangeDataNtfConfig,
+    KeyRotation as ProtoKeyRotation, LinkQuality as ProtoLinkQuality,
+    MacAddressMode as ProtoMacAddressMode, MacFcsType as ProtoMacFcsType,
+    MultiNodeMode as ProtoMultiNodeMode, OwrAoaRangingMeasurement as ProtoOwrAoaRangingMeasurement,
+    PowerStats as ProtoPowerStats, PreambleDuration as ProtoPreambleDuration,
+    PrfMode as ProtoPrfMode, PsduDataRate as ProtoPsduDataRate,
+    RangeDataNtfConfig as ProtoRangeDataNtfConfig,
     RangingMeasurementType as ProtoRangingMeasurementType,
     RangingRoundControl as ProtoRangingRoundControl, RangingRoundUsage as ProtoRangingRoundUsage,
     RangingTimeStruct as ProtoRangingTimeStruct, ReasonCode as ProtoReasonCode,
     ResultReportConfig as ProtoResultReportConfig, RframeConfig as ProtoRframeConfig,
     ScheduledMode as ProtoScheduledMode, SessionRangeData as ProtoSessionRangeData,
-    SessionState as ProtoSessionState, SessionType as ProtoSessionType, Status as ProtoStatus,
+    SessionState as ProtoSessionState, SessionType as ProtoSessionType,
+    SetAppConfigResponse as ProtoSetAppConfigResponse, Status as ProtoStatus,
     StatusCode as ProtoStatusCode, StsConfig as ProtoStsConfig, StsLength as ProtoStsLength,
     TwoWayRangingMeasurement as ProtoTwoWayRangingMeasurement,
     TxAdaptivePayloadPower as ProtoTxAdaptivePayloadPower, UciLoggerMode as ProtoUciLoggerMode,
     UpdateMulticastListAction as ProtoUpdateMulticastListAction, UwbChannel as ProtoUwbChannel,
 };
-use crate::uci::notification::{RangingMeasurements, SessionRangeData};
+#[cfg(feature = "latency-stats")]
+use crate::proto::bindings::{
+    CommandLatencyStats as ProtoCommandLatencyStats, LatencyStatsReport as ProtoLatencyStatsReport,
+};
+#[cfg(feature = "latency-stats")]
+use crate::uci::latency_stats::LatencyStats;
+use crate::uci::notification::{LinkQuality, RangingMeasurements, SessionRangeData};
 use crate::uci::uci_logger::UciLoggerMode;
 
 /// Generate the conversion functions between 2 enum types, which field is 1-to-1 mapping.
@@ -685,6 +700,59 @@ enum_mapping! {
     ADD_CONTROLEE_WITH_LONG_SUB_SESSION_KEY => AddControleeWithLongSubSessionKey,
 }
 
+enum_mapping! {
+    ProtoCoexistenceSource => CoexistenceSource,
+    WIFI => Wifi,
+    BLUETOOTH => Bluetooth,
+}
+
+// Generate to_spec_value() of the proto enums, which returns the numeric value of the mapped
+// uwb_core enum in the FiRa/UCI spec, i.e. the value on the wire rather than the protobuf tag.
+// The uwb_core enums are fieldless with the spec values as the discriminants.
+macro_rules! spec_value_mapping {
+    ( $( $proto:ty => $enum:ty, )+ ) => {
+        $(
+            impl $proto {
+                /// The numeric value in the FiRa/UCI spec, which differs from the protobuf tag.
+                pub fn to_spec_value(&self) -> u8 {
+                    <$enum>::from(*self) as u8
+                }
+            }
+        )+
+    };
+}
+
+// UciLoggerMode is not in the spec, so it has no spec value.
+spec_value_mapping! {
+    ProtoDeviceState => DeviceState,
+    ProtoSessionState => SessionState,
+    ProtoRangingMeasurementType => RangingMeasurementType,
+    ProtoSessionType => SessionType,
+    ProtoDeviceType => DeviceType,
+    ProtoRangingRoundUsage => RangingRoundUsage,
+    ProtoStsConfig => StsConfig,
+    ProtoMultiNodeMode => MultiNodeMode,
+    ProtoUwbChannel => UwbChannel,
+    ProtoMacFcsType => MacFcsType,
+    ProtoAoaResultRequest => AoaResultRequest,
+    ProtoRangeDataNtfConfig => RangeDataNtfConfig,
+    ProtoDeviceRole => DeviceRole,
+    ProtoRframeConfig => RframeConfig,
+    ProtoPsduDataRate => PsduDataRate,
+    ProtoPreambleDuration => PreambleDuration,
+    ProtoRangingTimeStruct => RangingTimeStruct,
+    ProtoTxAdaptivePayloadPower => TxAdaptivePayloadPower,
+    ProtoPrfMode => PrfMode,
+    ProtoScheduledMode => ScheduledMode,
+    ProtoKeyRotation => KeyRotation,
+    ProtoMacAddressMode => MacAddressMode,
+    ProtoHoppingMode => HoppingMode,
+    ProtoBprfPhrDataRate => BprfPhrDataRate,
+    ProtoStsLength => StsLength,
+    ProtoUpdateMulticastListAction => UpdateMulticastListAction,
+    ProtoCoexistenceSource => CoexistenceSource,
+}
+
 pub enum ProtoRangingMeasurements {
     TwoWay(Vec<ProtoTwoWayRangingMeasurement>),
     OwrAoa(ProtoOwrAoaRangingMeasurement),
@@ -703,6 +771,14 @@ impl<T> From<Result<T>> for ProtoStatus {
             Err(Error::Timeout) => Self::TIMEOUT,
             Err(Error::CommandRetry) => Self::COMMAND_RETRY,
             Err(Error::DuplicatedSessionId) => Self::DUPLICATED_SESSION_ID,
+            Err(Error::HalClosed) => Self::HAL_CLOSED,
+            Err(Error::Transport(_)) => Self::TRANSPORT,
+            Err(Error::DeviceError) => Self::DEVICE_ERROR,
+            Err(Error::ReadOnlyAppConfig) => Self::BAD_PARAMETERS,
+            Err(Error::MaxNumberOfMeasurementsExceeded) => Self::BAD_PARAMETERS,
+            Err(Error::NotSupported) => Self::NOT_SUPPORTED,
+            Err(Error::UnsupportedUciVersion) => Self::NOT_SUPPORTED,
+            Err(Error::HalVersionMismatch) => Self::VERSION_MISMATCH,
             Err(_) => Self::UNKNOWN,
         }
     }
@@ -860,6 +936,16 @@ impl From<ExtendedAddressDlTdoaRangingMeasurement> for ProtoDlTDoARangingMeasure
     }
 }
 
+impl From<LinkQuality> for ProtoLinkQuality {
+    fn from(item: LinkQuality) -> Self {
+        let mut result = Self::new();
+        result.set_noise_floor_dbm(item.noise_floor_dbm.into());
+        result.set_cir_quality(item.cir_quality.into());
+        result.set_rssi_per_receiver(item.rssi_per_receiver.into_iter().map(i32::from).collect());
+        result
+    }
+}
+
 impl From<SessionRangeData> for ProtoSessionRangeData {
     fn from(item: SessionRangeData) -> Self {
         let mut result = Self::new();
@@ -867,11 +953,22 @@ impl From<SessionRangeData> for ProtoSessionRangeData {
         result.set_session_id(item.session_token);
         result.set_current_ranging_interval_ms(item.current_ranging_interval_ms);
         result.set_ranging_measurement_type(item.ranging_measurement_type.into());
+        let mut link_qualities = item.link_qualities.into_iter();
         match to_proto_ranging_measurements(item.ranging_measurements) {
-            ProtoRangingMeasurements::TwoWay(twoway_measurements) => {
+            ProtoRangingMeasurements::TwoWay(mut twoway_measurements) => {
+                for (measurement, link_quality) in
+                    twoway_measurements.iter_mut().zip(link_qualities)
+                {
+                    if let Some(link_quality) = link_quality {
+                        measurement.set_link_quality(link_quality.into());
+                    }
+                }
                 result.set_twoway_ranging_measurements(RepeatedField::from_vec(twoway_measurements))
             }
-            ProtoRangingMeasurements::OwrAoa(owraoa_measurement) => {
+            ProtoRangingMeasurements::OwrAoa(mut owraoa_measurement) => {
+                if let Some(Some(link_quality)) = link_qualities.next() {
+                    owraoa_measurement.set_link_quality(link_quality.into());
+                }
                 result.set_owraoa_ranging_measurement(owraoa_measurement)
             }
             ProtoRangingMeasurements::DlTDoa(dltdoa_measurements) => {
@@ -955,6 +1052,28 @@ fn to_uwb_address(bytes: Vec<u8>, mode: ProtoMacAddressMode) -> Option<UwbAddres
     }
 }
 
+fn to_dst_mac_address(
+    addresses: Vec<Vec<u8>>,
+    mode: ProtoMacAddressMode,
+) -> std::result::Result<Vec<UwbAddress>, String> {
+    let expected_len = addresses.first().map(|addr| addr.len());
+    let mut dst_mac_address = vec![];
+    for (idx, addr) in addresses.into_iter().enumerate() {
+        let len = addr.len();
+        if Some(len) != expected_len {
+            return Err(format!(
+                "dst_mac_address[{}] has length {}, which differs from dst_mac_address[0]",
+                idx, len
+            ));
+        }
+        let addr = to_uwb_address(addr, mode).ok_or_else(|| {
+            format!("Failed to convert dst_mac_address[{}] with length {}", idx, len)
+        })?;
+        dst_mac_address.push(addr);
+    }
+    Ok(dst_mac_address)
+}
+
 impl TryFrom<ProtoControlee> for Controlee {
     type Error = String;
     fn try_from(item: ProtoControlee) -> std::result::Result<Self, Self::Error> {
@@ -979,6 +1098,66 @@ impl From<PowerStats> for ProtoPowerStats {
     }
 }
 
+#[cfg(feature = "latency-stats")]
+impl From<HashMap<UciOpcode, LatencyStats>> for ProtoLatencyStatsReport {
+    fn from(item: HashMap<UciOpcode, LatencyStats>) -> Self {
+        let mut item = item.into_iter().collect::<Vec<_>>();
+        item.sort_by_key(|((gid, oid), _)| (u8::from(*gid), *oid));
+
+        let mut res = Self::new();
+        res.set_stats(
+            item.into_iter()
+                .map(|((gid, oid), stats)| {
+                    let mut proto_stats = ProtoCommandLatencyStats::new();
+                    proto_stats.set_gid(u8::from(gid).into());
+                    proto_stats.set_oid(oid.into());
+                    proto_stats.set_count(stats.count);
+                    proto_stats.set_min_us(stats.min.as_micros() as u64);
+                    proto_stats.set_max_us(stats.max.as_micros() as u64);
+                    proto_stats.set_total_us(stats.total.as_micros() as u64);
+                    proto_stats.set_histogram(stats.histogram.to_vec());
+                    proto_stats
+                })
+                .collect(),
+        );
+        res
+    }
+}
+
+impl From<SetAppConfigResponse> for ProtoSetAppConfigResponse {
+    fn from(item: SetAppConfigResponse) -> Self {
+        let mut res = Self::new();
+        res.set_status(item.status.into());
+        res.set_config_status(
+            item.config_status
+                .into_iter()
+                .map(|config_status| {
+                    let mut status = ProtoAppConfigStatus::new();
+                    status.set_cfg_id(u8::from(config_status.cfg_id).into());
+                    status.set_status(config_status.status.into());
+                    status
+                })
+                .collect(),
+        );
+        res
+    }
+}
+
+impl TryFrom<ProtoSetAppConfigResponse> for SetAppConfigResponse {
+    type Error = String;
+    fn try_from(item: ProtoSetAppConfigResponse) -> std::result::Result<Self, Self::Error> {
+        let mut config_status = vec![];
+        for status in item.config_status.iter() {
+            let cfg_id = u8::try_from(status.cfg_id)
+                .ok()
+                .and_then(|id| AppConfigTlvType::try_from(id).ok())
+                .ok_or(format!("Failed to convert config_status cfg_id {}", status.cfg_id))?;
+            config_status.push(AppConfigStatus { cfg_id, status: status.status.into() });
+        }
+        Ok(Self { status: item.status.into(), config_status })
+    }
+}
+
 impl From<FiraAppConfigParams> for ProtoFiraAppConfigParams {
     fn from(item: FiraAppConfigParams) -> Self {
         let mut res = Self::new();
@@ -1042,6 +1221,20 @@ impl From<FiraAppConfigParams> for ProtoFiraAppConfigParams {
         res.set_number_of_aoa_elevation_measurements(
             (*item.number_of_aoa_elevation_measurements()).into(),
         );
+        res.set_enable_diagnostics(*item.enable_diagnostics());
+        res.set_diagrams_frame_reports_fields((*item.diagrams_frame_reports_fields()).into());
+        let mut vendor_tlvs = item
+            .vendor_tlvs()
+            .iter()
+            .map(|(id, v)| {
+                let mut tlv = ProtoAppConfigTlv::new();
+                tlv.set_cfg_id(u8::from(*id).into());
+                tlv.set_v(v.clone());
+                tlv
+            })
+            .collect::<Vec<_>>();
+        vendor_tlvs.sort_by_key(|tlv| tlv.cfg_id);
+        res.set_vendor_tlvs(vendor_tlvs.into());
 
         res
     }
@@ -1053,12 +1246,8 @@ impl TryFrom<ProtoFiraAppConfigParams> for AppConfigParams {
         let device_mac_address =
             to_uwb_address(item.device_mac_address.clone(), item.mac_address_mode)
                 .ok_or("Failed to convert device_mac_address")?;
-        let mut dst_mac_address = vec![];
-        for addr in item.dst_mac_address.clone().into_iter() {
-            let addr = to_uwb_address(addr, item.mac_address_mode)
-                .ok_or("Failed to convert dst_mac_address")?;
-            dst_mac_address.push(addr);
-        }
+        let dst_mac_address =
+            to_dst_mac_address(item.dst_mac_address.to_vec(), item.mac_address_mode)?;
 
         let mut builder = FiraAppConfigParamsBuilder::new();
         builder
@@ -1179,7 +1368,20 @@ impl TryFrom<ProtoFiraAppConfigParams> for AppConfigParams {
                 item.number_of_aoa_elevation_measurements
                     .try_into()
                     .map_err(|_| "Failed to convert number_of_aoa_elevation_measurements")?,
+            )
+            .enable_diagnostics(item.enable_diagnostics)
+            .diagrams_frame_reports_fields(
+                item.diagrams_frame_reports_fields
+                    .try_into()
+                    .map_err(|_| "Failed to convert diagrams_frame_reports_fields")?,
             );
+        for tlv in item.vendor_tlvs.iter() {
+            let id = u8::try_from(tlv.cfg_id)
+                .ok()
+                .and_then(|id| AppConfigTlvType::try_from(id).ok())
+                .ok_or(format!("Failed to convert vendor_tlvs cfg_id {}", tlv.cfg_id))?;
+            builder.vendor_tlv(id, tlv.v.clone());
+        }
 
         Ok(builder.build().ok_or("Failed to build FiraAppConfigParam from builder")?)
     }
@@ -1193,3 +1395,310 @@ impl Drop for ProtoFiraAppConfigParams {
         self.sub_session_id.zeroize();
     }
 }
+
+impl AppConfigParams {
+    /// Dump the parameters in the protobuf text format, i.e. the content of a .textproto file, for
+    /// debugging. Only the FiRa parameters have the protobuf form, Error::BadParameters is
+    /// returned for the other types. The PII fields, which are redacted by the Debug trait, are
+    /// left out.
+    pub fn to_textproto(&self) -> Result<String> {
+        match self {
+            Self::Fira(params) => {
+                let mut proto_params: ProtoFiraAppConfigParams = params.clone().into();
+                proto_params.vendor_id.zeroize();
+                proto_params.static_sts_iv.zeroize();
+                proto_params.sub_session_id.zeroize();
+                Ok(protobuf::text_format::print_to_string(&proto_params))
+            }
+            _ => Err(Error::BadParameters),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::proto::utils::{parse_from_bytes, write_to_bytes};
+
+    #[test]
+    fn test_to_spec_value() {
+        assert_eq!(ProtoSessionType::CCC.to_spec_value(), 0xA0);
+        assert_eq!(ProtoSessionType::DEVICE_TEST_MODE.to_spec_value(), 0xD0);
+        assert_eq!(ProtoDeviceState::DEVICE_STATE_ERROR.to_spec_value(), 0xFF);
+        assert_eq!(ProtoSessionState::IDLE.to_spec_value(), 0x03);
+        assert_eq!(ProtoUwbChannel::CHANNEL_9.to_spec_value(), 9);
+        assert_eq!(ProtoRangingRoundUsage::DS_TWR.to_spec_value(), 2);
+        assert_eq!(ProtoAoaResultRequest::REQ_AOA_RESULTS_INTERLEAVED.to_spec_value(), 0xF0);
+        assert_eq!(ProtoCoexistenceSource::BLUETOOTH.to_spec_value(), 0x01);
+    }
+
+    #[test]
+    fn test_dst_mac_address_mixed_width() {
+        let mut item = ProtoFiraAppConfigParams::new();
+        item.set_mac_address_mode(ProtoMacAddressMode::MAC_ADDRESS_2_BYTES);
+        item.set_device_mac_address(vec![0xab, 0xcd]);
+        item.set_dst_mac_address(RepeatedField::from_vec(vec![
+            vec![0x12, 0x34],
+            vec![0x56, 0x78],
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+        ]));
+        let result: std::result::Result<AppConfigParams, String> = item.try_into();
+        assert_eq!(
+            result.unwrap_err(),
+            "dst_mac_address[2] has length 8, which differs from dst_mac_address[0]"
+        );
+    }
+
+    #[test]
+    fn test_dst_mac_address_wrong_width() {
+        let result = to_dst_mac_address(
+            vec![vec![1, 2, 3, 4, 5, 6, 7, 8], vec![1, 2, 3, 4, 5, 6, 7, 9]],
+            ProtoMacAddressMode::MAC_ADDRESS_2_BYTES,
+        );
+        assert_eq!(result.unwrap_err(), "Failed to convert dst_mac_address[0] with length 8");
+    }
+
+    #[test]
+    fn test_fira_app_config_params_vendor_tlvs() {
+        let vendor_tlv_type = AppConfigTlvType::try_from(0xd0).unwrap();
+        let params = FiraAppConfigParamsBuilder::new()
+            .device_type(DeviceType::Controller)
+            .multi_node_mode(MultiNodeMode::Unicast)
+            .device_mac_address(UwbAddress::Short([1, 2]))
+            .dst_mac_address(vec![UwbAddress::Short([3, 4])])
+            .device_role(DeviceRole::Initiator)
+            .vendor_id([0xfe, 0xdc])
+            .static_sts_iv([0xdf, 0xce, 0xab, 0x12, 0x34, 0x56])
+            .vendor_tlv(vendor_tlv_type, vec![0x12, 0x34])
+            .build()
+            .unwrap();
+        let fira_params = match &params {
+            AppConfigParams::Fira(fira_params) => fira_params.clone(),
+            _ => panic!("Unexpected params type"),
+        };
+        assert_eq!(
+            fira_params.generate_config_map().get(&vendor_tlv_type),
+            Some(&vec![0x12, 0x34])
+        );
+
+        let proto_params: ProtoFiraAppConfigParams = fira_params.into();
+        assert_eq!(proto_params.vendor_tlvs.len(), 1);
+        assert_eq!(proto_params.vendor_tlvs[0].cfg_id, 0xd0);
+        let result: std::result::Result<AppConfigParams, String> = proto_params.try_into();
+        assert_eq!(result.unwrap(), params);
+    }
+
+    #[test]
+    fn test_app_config_params_to_textproto() {
+        let params = FiraAppConfigParamsBuilder::new()
+            .device_type(DeviceType::Controller)
+            .multi_node_mode(MultiNodeMode::Unicast)
+            .device_mac_address(UwbAddress::Short([1, 2]))
+            .dst_mac_address(vec![UwbAddress::Short([3, 4])])
+            .device_role(DeviceRole::Initiator)
+            .vendor_id([0xfe, 0xdc])
+            .static_sts_iv([0xdf, 0xce, 0xab, 0x12, 0x34, 0x56])
+            .sub_session_id(0x1234_5678)
+            .build()
+            .unwrap();
+        let textproto = params.to_textproto().unwrap();
+        assert!(textproto.contains("device_type: CONTROLLER"));
+        assert!(textproto.contains("device_role: INITIATOR"));
+
+        // The PII fields are not dumped.
+        assert!(!textproto.contains("vendor_id"));
+        assert!(!textproto.contains("static_sts_iv"));
+        assert!(!textproto.contains("sub_session_id"));
+        assert!(!textproto.contains("\\376\\334")); // vendor_id [0xfe, 0xdc] in octal.
+        assert!(!textproto.contains(&0x1234_5678u32.to_string()));
+
+        // The protobuf library doesn't parse the text format, round-trip the params through the
+        // binary form and check the dumped text is the same.
+        let proto_params: ProtoFiraAppConfigParams = match &params {
+            AppConfigParams::Fira(fira_params) => fira_params.clone().into(),
+            _ => panic!("Unexpected params type"),
+        };
+        let bytes = write_to_bytes(&proto_params).unwrap();
+        let parsed_params: AppConfigParams =
+            parse_from_bytes::<ProtoFiraAppConfigParams>(&bytes).unwrap().try_into().unwrap();
+        assert_eq!(parsed_params, params);
+        assert_eq!(parsed_params.to_textproto().unwrap(), textproto);
+    }
+
+    #[test]
+    fn test_status_from_result() {
+        assert_eq!(ProtoStatus::from(Ok::<(), Error>(())), ProtoStatus::OK);
+        assert_eq!(ProtoStatus::from(Err::<(), _>(Error::Timeout)), ProtoStatus::TIMEOUT);
+        assert_eq!(ProtoStatus::from(Err::<(), _>(Error::HalClosed)), ProtoStatus::HAL_CLOSED);
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::Transport(std::io::ErrorKind::BrokenPipe))),
+            ProtoStatus::TRANSPORT
+        );
+        assert_eq!(ProtoStatus::from(Err::<(), _>(Error::DeviceError)), ProtoStatus::DEVICE_ERROR);
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::ReadOnlyAppConfig)),
+            ProtoStatus::BAD_PARAMETERS
+        );
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::MaxNumberOfMeasurementsExceeded)),
+            ProtoStatus::BAD_PARAMETERS
+        );
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::NotSupported)),
+            ProtoStatus::NOT_SUPPORTED
+        );
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::UnsupportedUciVersion)),
+            ProtoStatus::NOT_SUPPORTED
+        );
+        assert_eq!(
+            ProtoStatus::from(Err::<(), _>(Error::HalVersionMismatch)),
+            ProtoStatus::VERSION_MISMATCH
+        );
+        assert_eq!(ProtoStatus::from(Err::<(), _>(Error::Unknown)), ProtoStatus::UNKNOWN);
+    }
+
+    #[test]
+    fn test_set_app_config_response() {
+        let response = SetAppConfigResponse {
+            status: StatusCode::UciStatusInvalidParam,
+            config_status: vec![
+                AppConfigStatus {
+                    cfg_id: AppConfigTlvType::DeviceType,
+                    status: StatusCode::UciStatusInvalidParam,
+                },
+                AppConfigStatus {
+                    cfg_id: AppConfigTlvType::RangingInterval,
+                    status: StatusCode::UciStatusReadOnly,
+                },
+            ],
+        };
+
+        let proto_response: ProtoSetAppConfigResponse = response.clone().into();
+        assert_eq!(proto_response.status, ProtoStatusCode::UCI_STATUS_INVALID_PARAM);
+        assert_eq!(proto_response.config_status.len(), 2);
+        assert_eq!(proto_response.config_status[1].cfg_id, 0x09);
+        assert_eq!(proto_response.config_status[1].status, ProtoStatusCode::UCI_STATUS_READ_ONLY);
+
+        let restored = SetAppConfigResponse::try_from(proto_response).unwrap();
+        assert_eq!(restored, response);
+        assert_eq!(
+            restored.rejected(),
+            vec![
+                (AppConfigTlvType::DeviceType, StatusCode::UciStatusInvalidParam),
+                (AppConfigTlvType::RangingInterval, StatusCode::UciStatusReadOnly),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ccc_session_range_data() {
+        // The UWBS reports the CCC ranging results in the Two-Way measurement format, with the
+        // AoA and the FoM fields of each responder.
+        let measurement = ShortAddressTwoWayRangingMeasurement {
+            mac_address: 0x1234,
+            status: StatusCode::UciStatusOk,
+            nlos: 0,
+            distance: 120,
+            aoa_azimuth: 0x0a00,
+            aoa_azimuth_fom: 90,
+            aoa_elevation: 0xf600,
+            aoa_elevation_fom: 80,
+            aoa_destination_azimuth: 0x0b00,
+            aoa_destination_azimuth_fom: 70,
+            aoa_destination_elevation: 0xf700,
+            aoa_destination_elevation_fom: 60,
+            slot_index: 3,
+            rssi: 0x50,
+        };
+        let range_data = SessionRangeData {
+            sequence_number: 7,
+            session_token: 0x123,
+            current_ranging_interval_ms: 96,
+            ranging_measurement_type: RangingMeasurementType::TwoWay,
+            ranging_measurements: RangingMeasurements::ShortAddressTwoWay(vec![
+                measurement.clone(),
+                ShortAddressTwoWayRangingMeasurement {
+                    mac_address: 0x5678,
+                    status: StatusCode::UciStatusRangingRxTimeout,
+                    ..measurement
+                },
+            ]),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![
+                Some(LinkQuality {
+                    noise_floor_dbm: -90,
+                    cir_quality: 75,
+                    rssi_per_receiver: vec![-70, -72],
+                }),
+                None,
+            ],
+            received_at: None,
+        };
+
+        let proto_range_data: ProtoSessionRangeData = range_data.into();
+        assert_eq!(proto_range_data.sequence_number, 7);
+        assert_eq!(proto_range_data.session_id, 0x123);
+        assert_eq!(proto_range_data.current_ranging_interval_ms, 96);
+        let measurements = &proto_range_data.twoway_ranging_measurements;
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements[0].mac_address, 0x1234);
+        assert_eq!(measurements[0].status, ProtoStatusCode::UCI_STATUS_OK);
+        assert_eq!(measurements[0].distance, 120);
+        assert_eq!(measurements[0].aoa_azimuth, 0x0a00);
+        assert_eq!(measurements[0].aoa_azimuth_fom, 90);
+        assert_eq!(measurements[0].aoa_elevation, 0xf600);
+        assert_eq!(measurements[0].aoa_elevation_fom, 80);
+        assert_eq!(measurements[0].aoa_destination_azimuth, 0x0b00);
+        assert_eq!(measurements[0].aoa_destination_azimuth_fom, 70);
+        assert_eq!(measurements[0].aoa_destination_elevation, 0xf700);
+        assert_eq!(measurements[0].aoa_destination_elevation_fom, 60);
+        assert_eq!(measurements[0].slot_index, 3);
+        assert_eq!(measurements[0].rssi, 0x50);
+        assert_eq!(measurements[0].get_link_quality().noise_floor_dbm, -90);
+        assert_eq!(measurements[0].get_link_quality().cir_quality, 75);
+        assert_eq!(measurements[0].get_link_quality().rssi_per_receiver, vec![-70, -72]);
+        assert_eq!(measurements[1].mac_address, 0x5678);
+        assert_eq!(measurements[1].status, ProtoStatusCode::UCI_STATUS_RANGING_RX_TIMEOUT);
+        assert!(!measurements[1].has_link_quality());
+    }
+
+    #[test]
+    fn test_dltdoa_session_range_data_timestamps() {
+        let mut bytes = vec![
+            0x34, 0x12, 0x00, 0x01, // 2(Mac address), Status, Message Type
+            0x02, 0x00, 0x00, 0x00, // 2(Message control: 64-bit TX timestamp), 2(Block Index)
+            0x00, // Round Index
+        ];
+        // NLoS, AoA, RSSI, the 64-bit TX timestamp, the 40-bit RX timestamp, CFO, the reply times
+        // and ToF.
+        bytes.extend([0; 8]);
+        bytes.extend([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        bytes.extend([0x11, 0x12, 0x13, 0x14, 0x15]);
+        bytes.extend([0; 14]);
+        let range_data = SessionRangeData {
+            sequence_number: 7,
+            session_token: 0x123,
+            current_ranging_interval_ms: 96,
+            ranging_measurement_type: RangingMeasurementType::DlTdoa,
+            ranging_measurements: RangingMeasurements::ShortAddressDltdoa(
+                ShortAddressDlTdoaRangingMeasurement::parse(&bytes, 1).unwrap(),
+            ),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+
+        let proto_range_data: ProtoSessionRangeData = range_data.into();
+        let measurements = &proto_range_data.dltdoa_ranging_measurements;
+        assert_eq!(measurements.len(), 1);
+        assert_eq!(measurements[0].mac_address, 0x1234);
+        assert_eq!(measurements[0].tx_timestamp, 0x0807060504030201);
+        assert_eq!(measurements[0].rx_timestamp, 0x1514131211);
+    }
+}
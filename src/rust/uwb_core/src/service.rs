@@ -31,3 +31,5 @@ pub use uwb_service::{
 };
 pub use uwb_service_builder::{default_runtime, UwbServiceBuilder};
 pub use uwb_service_callback_builder::UwbServiceCallbackSendBuilder;
+
+pub use crate::session::session_manager::{ManagerSnapshot, SessionSnapshot};
@@ -18,7 +18,7 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::Notify;
 use tokio::time::{timeout, Duration};
 
-use crate::params::{DeviceState, ReasonCode, SessionId, SessionState};
+use crate::params::{CoexistenceSource, DeviceState, ReasonCode, SessionId, SessionState};
 use crate::service::uwb_service::UwbServiceCallback;
 use crate::uci::SessionRangeData;
 
@@ -62,6 +62,23 @@ impl MockUwbServiceCallback {
         self.push_expected_call(ExpectedCall::RangeData { session_id, range_data });
     }
 
+    pub fn expect_on_session_key_rotated(&mut self, session_id: SessionId, rotation_count: u32) {
+        self.push_expected_call(ExpectedCall::SessionKeyRotated { session_id, rotation_count });
+    }
+
+    pub fn expect_on_session_ranging_completed(&mut self, session_id: SessionId) {
+        self.push_expected_call(ExpectedCall::SessionRangingCompleted { session_id });
+    }
+
+    pub fn expect_on_coexistence_event(
+        &mut self,
+        source: CoexistenceSource,
+        preempted: bool,
+        duration_ms: u16,
+    ) {
+        self.push_expected_call(ExpectedCall::Coexistence { source, preempted, duration_ms });
+    }
+
     pub fn expect_on_vendor_notification_received(&mut self, gid: u32, oid: u32, payload: Vec<u8>) {
         self.push_expected_call(ExpectedCall::VendorNotification { gid, oid, payload });
     }
@@ -112,6 +129,29 @@ impl UwbServiceCallback for MockUwbServiceCallback {
         assert_eq!(self.pop_expected_call(), ExpectedCall::RangeData { session_id, range_data });
     }
 
+    fn on_session_key_rotated(&mut self, session_id: SessionId, rotation_count: u32) {
+        assert_eq!(
+            self.pop_expected_call(),
+            ExpectedCall::SessionKeyRotated { session_id, rotation_count }
+        );
+    }
+
+    fn on_session_ranging_completed(&mut self, session_id: SessionId) {
+        assert_eq!(self.pop_expected_call(), ExpectedCall::SessionRangingCompleted { session_id });
+    }
+
+    fn on_coexistence_event(
+        &mut self,
+        source: CoexistenceSource,
+        preempted: bool,
+        duration_ms: u16,
+    ) {
+        assert_eq!(
+            self.pop_expected_call(),
+            ExpectedCall::Coexistence { source, preempted, duration_ms }
+        );
+    }
+
     fn on_vendor_notification_received(&mut self, gid: u32, oid: u32, payload: Vec<u8>) {
         assert_eq!(
             self.pop_expected_call(),
@@ -126,5 +166,8 @@ pub(crate) enum ExpectedCall {
     UciDeviceStatus { state: DeviceState },
     SessionState { session_id: SessionId, session_state: SessionState, reason_code: ReasonCode },
     RangeData { session_id: SessionId, range_data: SessionRangeData },
+    SessionKeyRotated { session_id: SessionId, rotation_count: u32 },
+    SessionRangingCompleted { session_id: SessionId },
+    Coexistence { source: CoexistenceSource, preempted: bool, duration_ms: u16 },
     VendorNotification { gid: u32, oid: u32, payload: Vec<u8> },
 }
@@ -18,17 +18,20 @@
 use log::{debug, error};
 
 use crate::error::{Error, Result};
-use crate::params::{AppConfigParams, DeviceState, ReasonCode, SessionId, SessionState};
+use crate::params::{
+    AppConfigParams, CoexistenceSource, DeviceState, ReasonCode, SessionId, SessionState,
+};
 use crate::proto::bindings::{
     AndroidGetPowerStatsResponse, AndroidSetCountryCodeRequest, AndroidSetCountryCodeResponse,
-    DeinitSessionRequest, DeinitSessionResponse, DisableResponse, EnableResponse,
-    InitSessionRequest, InitSessionResponse, RangeDataReceivedSignal, ReconfigureRequest,
-    ReconfigureResponse, SendVendorCmdRequest, SendVendorCmdResponse, ServiceResetSignal,
-    SessionParamsRequest, SessionParamsResponse, SessionStateChangedSignal, SetLoggerModeRequest,
-    SetLoggerModeResponse, StartRangingRequest, StartRangingResponse, Status as ProtoStatus,
-    StopRangingRequest, StopRangingResponse, UciDeviceStatusChangedSignal,
-    UpdateControllerMulticastListRequest, UpdateControllerMulticastListResponse,
-    VendorNotificationReceivedSignal,
+    CoexistenceEventSignal, DeinitSessionRequest, DeinitSessionResponse, DisableResponse,
+    EnableResponse, InitSessionRequest, InitSessionResponse, RangeDataReceivedSignal,
+    ReconfigureRequest, ReconfigureResponse, SendVendorCmdRequest, SendVendorCmdResponse,
+    ServiceResetSignal, SessionKeyRotatedSignal, SessionParamsRequest, SessionParamsResponse,
+    SessionRangingCompletedSignal, SessionStateChangedSignal, SetAutoStopOnMaxMeasurementsRequest,
+    SetAutoStopOnMaxMeasurementsResponse, SetLoggerModeRequest, SetLoggerModeResponse,
+    StartRangingRequest, StartRangingResponse, Status as ProtoStatus, StopRangingRequest,
+    StopRangingResponse, UciDeviceStatusChangedSignal, UpdateControllerMulticastListRequest,
+    UpdateControllerMulticastListResponse, VendorNotificationReceivedSignal,
 };
 use crate::proto::utils::{parse_from_bytes, write_to_bytes};
 use crate::service::uwb_service::{UwbService, UwbServiceCallback};
@@ -233,6 +236,18 @@ impl ProtoUwbService {
         }
         write_to_bytes(&resp)
     }
+
+    /// Enable or disable stopping the ranging automatically after the max number of measurements.
+    pub fn set_auto_stop_on_max_measurements(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let request = parse_from_bytes::<SetAutoStopOnMaxMeasurementsRequest>(request)?;
+        let mut resp = SetAutoStopOnMaxMeasurementsResponse::new();
+        resp.set_status(
+            self.service
+                .set_auto_stop_on_max_measurements(request.session_id, request.enabled)
+                .into(),
+        );
+        write_to_bytes(&resp)
+    }
 }
 
 /// The trait that provides the same callbacks of UwbServiceCallback. It has the blanket
@@ -250,6 +265,13 @@ pub trait ProtoUwbServiceCallback: 'static {
     fn on_session_state_changed(&mut self, payload: Vec<u8>);
     /// Notify the ranging data of the session is received.
     fn on_range_data_received(&mut self, payload: Vec<u8>);
+    /// Notify the STS key of the session is rotated. Ignored by default.
+    fn on_session_key_rotated(&mut self, _payload: Vec<u8>) {}
+    /// Notify the ranging of the session is stopped after the max number of measurements.
+    /// Ignored by default.
+    fn on_session_ranging_completed(&mut self, _payload: Vec<u8>) {}
+    /// Notify the UWB radio is preempted by or released from another radio. Ignored by default.
+    fn on_coexistence_event(&mut self, _payload: Vec<u8>) {}
     /// Notify the vendor notification is received.
     fn on_vendor_notification_received(&mut self, payload: Vec<u8>);
 }
@@ -310,6 +332,50 @@ impl<C: ProtoUwbServiceCallback> UwbServiceCallback for C {
         }
     }
 
+    fn on_session_key_rotated(&mut self, session_id: SessionId, rotation_count: u32) {
+        debug!("Session {:?}'s key is rotated, rotation count: {}", session_id, rotation_count);
+        let mut msg = SessionKeyRotatedSignal::new();
+        msg.set_session_id(session_id);
+        msg.set_rotation_count(rotation_count);
+        if let Ok(payload) = write_to_bytes(&msg) {
+            ProtoUwbServiceCallback::on_session_key_rotated(self, payload);
+        } else {
+            error!("Failed to call on_session_key_rotated()");
+        }
+    }
+
+    fn on_session_ranging_completed(&mut self, session_id: SessionId) {
+        debug!("Session {:?}'s ranging is completed", session_id);
+        let mut msg = SessionRangingCompletedSignal::new();
+        msg.set_session_id(session_id);
+        if let Ok(payload) = write_to_bytes(&msg) {
+            ProtoUwbServiceCallback::on_session_ranging_completed(self, payload);
+        } else {
+            error!("Failed to call on_session_ranging_completed()");
+        }
+    }
+
+    fn on_coexistence_event(
+        &mut self,
+        source: CoexistenceSource,
+        preempted: bool,
+        duration_ms: u16,
+    ) {
+        debug!(
+            "Coexistence event: source={:?}, preempted={}, duration_ms={}",
+            source, preempted, duration_ms
+        );
+        let mut msg = CoexistenceEventSignal::new();
+        msg.set_source(source.into());
+        msg.set_preempted(preempted);
+        msg.set_duration_ms(duration_ms.into());
+        if let Ok(payload) = write_to_bytes(&msg) {
+            ProtoUwbServiceCallback::on_coexistence_event(self, payload);
+        } else {
+            error!("Failed to call on_coexistence_event()");
+        }
+    }
+
     fn on_vendor_notification_received(&mut self, gid: u32, oid: u32, payload: Vec<u8>) {
         debug!("Received vendor notification: gid={}, oid={}, payload={:?}", gid, oid, payload);
         let mut msg = VendorNotificationReceivedSignal::new();
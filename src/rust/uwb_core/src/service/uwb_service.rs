@@ -22,10 +22,11 @@ use tokio::task;
 use crate::error::{Error, Result};
 use crate::params::app_config_params::AppConfigParams;
 use crate::params::uci_packets::{
-    Controlee, CountryCode, DeviceState, PowerStats, RawUciMessage, ReasonCode, SessionId,
-    SessionState, SessionType, UpdateMulticastListAction,
+    CoexistenceSource, Controlee, CountryCode, DeviceState, MulticastUpdateStatusCode, PowerStats,
+    RawUciMessage, ReasonCode, SessionId, SessionState, SessionTermination, SessionType,
+    UpdateMulticastListAction,
 };
-use crate::session::session_manager::{SessionManager, SessionNotification};
+use crate::session::session_manager::{ManagerSnapshot, SessionManager, SessionNotification};
 use crate::uci::notification::{CoreNotification, SessionRangeData};
 use crate::uci::uci_logger::UciLoggerMode;
 use crate::uci::uci_manager::UciManager;
@@ -57,6 +58,24 @@ pub trait UwbServiceCallback: 'static {
     /// Notify the ranging data of the session with the id |session_id| is received.
     fn on_range_data_received(&mut self, session_id: SessionId, range_data: SessionRangeData);
 
+    /// Notify the STS key of the session with the id |session_id| is rotated. |rotation_count|
+    /// is the number of the key rotations since the session is started. Ignored by default.
+    fn on_session_key_rotated(&mut self, _session_id: SessionId, _rotation_count: u32) {}
+
+    /// Notify the ranging of the session with the id |session_id| is stopped automatically after
+    /// reaching the max number of measurements. Ignored by default.
+    fn on_session_ranging_completed(&mut self, _session_id: SessionId) {}
+
+    /// Notify the UWB radio is preempted by or released from the radio |source|. |duration_ms| is
+    /// the expected duration of the preemption, 0 if unknown. Ignored by default.
+    fn on_coexistence_event(
+        &mut self,
+        _source: CoexistenceSource,
+        _preempted: bool,
+        _duration_ms: u16,
+    ) {
+    }
+
     /// Notify the vendor notification is received.
     fn on_vendor_notification_received(&mut self, gid: u32, oid: u32, payload: Vec<u8>);
 
@@ -199,6 +218,15 @@ impl UwbService {
         Ok(())
     }
 
+    /// Check whether reconfigure() would accept the parameters at the current state of the
+    /// session. The check is done locally, nothing is sent to the UWBS.
+    pub fn can_reconfigure(&self, session_id: SessionId, params: AppConfigParams) -> Result<bool> {
+        match self.block_on_cmd(Command::CanReconfigure { session_id, params })? {
+            Response::Reconfigurable(reconfigurable) => Ok(reconfigurable),
+            _ => panic!("can_reconfigure() should return Reconfigurable"),
+        }
+    }
+
     /// Update the list of the controlees to the ongoing session.
     pub fn update_controller_multicast_list(
         &self,
@@ -242,7 +270,8 @@ impl UwbService {
         }
     }
 
-    /// Get app config params for the given session id
+    /// Get app config params for the given session id. The params are the last ones applied to the
+    /// UWBS successfully, including the reconfiguration, so no UCI command is sent.
     pub fn session_params(&self, session_id: SessionId) -> Result<AppConfigParams> {
         match self.block_on_cmd(Command::GetParams { session_id })? {
             Response::AppConfigParams(params) => Ok(params),
@@ -250,6 +279,63 @@ impl UwbService {
         }
     }
 
+    /// Enable or disable stopping the ranging of the session automatically, once the UWBS reports
+    /// the max number of measurements is reached. The completion is notified via
+    /// UwbServiceCallback::on_session_ranging_completed().
+    pub fn set_auto_stop_on_max_measurements(
+        &self,
+        session_id: SessionId,
+        enabled: bool,
+    ) -> Result<()> {
+        self.block_on_cmd(Command::SetAutoStopOnMaxMeasurements { session_id, enabled })?;
+        Ok(())
+    }
+
+    /// Get how the last ranging of the session is stopped, e.g. to tell whether a controlee dropped
+    /// out before the in-band termination. Return None if the ranging is not stopped yet.
+    pub fn session_termination(&self, session_id: SessionId) -> Result<Option<SessionTermination>> {
+        match self.block_on_cmd(Command::GetTermination { session_id })? {
+            Response::Termination(termination) => Ok(termination),
+            _ => panic!("session_termination() should return Termination"),
+        }
+    }
+
+    /// Get the reason code of the last state change of the session, e.g. to explain why the
+    /// session became idle. Return None if no state change is notified yet.
+    pub fn last_session_reason(&self, session_id: SessionId) -> Result<Option<ReasonCode>> {
+        match self.block_on_cmd(Command::GetLastReason { session_id })? {
+            Response::Reason(reason_code) => Ok(reason_code),
+            _ => panic!("last_session_reason() should return Reason"),
+        }
+    }
+
+    /// Overwrite the tracked state of the session, e.g. when the session is controlled out-of-band.
+    /// It is an escape hatch for the reconciliation, the state is normally tracked by the
+    /// notifications.
+    pub fn force_session_state(
+        &self,
+        session_id: SessionId,
+        session_state: SessionState,
+    ) -> Result<()> {
+        self.block_on_cmd(Command::ForceSessionState { session_id, session_state })?;
+        Ok(())
+    }
+
+    /// Capture the type, the state and the params of all the sessions, which could be restored into
+    /// another UwbService by restore(), e.g. when the UWB stack is reloaded.
+    pub fn snapshot(&self) -> Result<ManagerSnapshot> {
+        match self.block_on_cmd(Command::Snapshot)? {
+            Response::Snapshot(snapshot) => Ok(snapshot),
+            _ => panic!("snapshot() should return ManagerSnapshot"),
+        }
+    }
+
+    /// Re-create the sessions captured by snapshot(). The service should be enabled first.
+    pub fn restore(&self, snapshot: ManagerSnapshot) -> Result<()> {
+        self.block_on_cmd(Command::Restore { snapshot })?;
+        Ok(())
+    }
+
     /// Send the |cmd| to UwbServiceActor and wait until receiving the response.
     fn block_on_cmd(&self, cmd: Command) -> Result<Response> {
         let (result_sender, result_receiver) = oneshot::channel();
@@ -396,11 +482,30 @@ impl<C: UwbServiceCallback, U: UciManager> UwbServiceActor<C, U> {
                     Err(Error::BadParameters)
                 }
             }
+            Command::CanReconfigure { session_id, params } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    let reconfigurable =
+                        session_manager.can_reconfigure(session_id, params).await?;
+                    Ok(Response::Reconfigurable(reconfigurable))
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
             Command::UpdateControllerMulticastList { session_id, action, controlees } => {
                 if let Some(session_manager) = self.session_manager.as_mut() {
-                    session_manager
+                    let status_list = session_manager
                         .update_controller_multicast_list(session_id, action, controlees)
                         .await?;
+                    // All the controlees should be added successfully.
+                    if action == UpdateMulticastListAction::AddControlee
+                        && status_list.iter().any(|(_, status)| {
+                            *status != MulticastUpdateStatusCode::StatusOkMulticastListUpdate
+                        })
+                    {
+                        error!("Failed to add the controlees: {:?}", status_list);
+                        return Err(Error::Unknown);
+                    }
                     Ok(Response::Null)
                 } else {
                     error!("The service is not enabled yet");
@@ -428,6 +533,60 @@ impl<C: UwbServiceCallback, U: UciManager> UwbServiceActor<C, U> {
                     Err(Error::BadParameters)
                 }
             }
+            Command::SetAutoStopOnMaxMeasurements { session_id, enabled } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    session_manager.set_auto_stop_on_max_measurements(session_id, enabled).await?;
+                    Ok(Response::Null)
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
+            Command::GetLastReason { session_id } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    let reason_code = session_manager.last_session_reason(session_id).await?;
+                    Ok(Response::Reason(reason_code))
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
+            Command::GetTermination { session_id } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    let termination = session_manager.session_termination(session_id).await?;
+                    Ok(Response::Termination(termination))
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
+            Command::ForceSessionState { session_id, session_state } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    session_manager.force_session_state(session_id, session_state).await?;
+                    Ok(Response::Null)
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
+            Command::Snapshot => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    let snapshot = session_manager.snapshot().await?;
+                    Ok(Response::Snapshot(snapshot))
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
+            Command::Restore { snapshot } => {
+                if let Some(session_manager) = self.session_manager.as_mut() {
+                    session_manager.restore(&snapshot).await?;
+                    Ok(Response::Null)
+                } else {
+                    error!("The service is not enabled yet");
+                    Err(Error::BadParameters)
+                }
+            }
         }
     }
 
@@ -443,6 +602,10 @@ impl<C: UwbServiceCallback, U: UciManager> UwbServiceActor<C, U> {
                 }
             }
             CoreNotification::GenericError(_status) => {}
+            CoreNotification::Coexistence { source, preempted, duration_ms } => {
+                self.callback.on_coexistence_event(source, preempted, duration_ms);
+            }
+            CoreNotification::Unknown { .. } => {}
         }
     }
 
@@ -454,6 +617,12 @@ impl<C: UwbServiceCallback, U: UciManager> UwbServiceActor<C, U> {
             SessionNotification::RangeData { session_id, range_data } => {
                 self.callback.on_range_data_received(session_id, range_data);
             }
+            SessionNotification::KeyRotation { session_id, rotation_count } => {
+                self.callback.on_session_key_rotated(session_id, rotation_count);
+            }
+            SessionNotification::RangingCompleted { session_id } => {
+                self.callback.on_session_ranging_completed(session_id);
+            }
         }
     }
 
@@ -540,6 +709,10 @@ enum Command {
         session_id: SessionId,
         params: AppConfigParams,
     },
+    CanReconfigure {
+        session_id: SessionId,
+        params: AppConfigParams,
+    },
     UpdateControllerMulticastList {
         session_id: SessionId,
         action: UpdateMulticastListAction,
@@ -558,12 +731,34 @@ enum Command {
     GetParams {
         session_id: SessionId,
     },
+    SetAutoStopOnMaxMeasurements {
+        session_id: SessionId,
+        enabled: bool,
+    },
+    GetTermination {
+        session_id: SessionId,
+    },
+    GetLastReason {
+        session_id: SessionId,
+    },
+    ForceSessionState {
+        session_id: SessionId,
+        session_state: SessionState,
+    },
+    Snapshot,
+    Restore {
+        snapshot: ManagerSnapshot,
+    },
 }
 
 #[derive(Debug)]
 enum Response {
     Null,
     AppConfigParams(AppConfigParams),
+    Reconfigurable(bool),
+    Snapshot(ManagerSnapshot),
+    Termination(Option<SessionTermination>),
+    Reason(Option<ReasonCode>),
     PowerStats(PowerStats),
     RawUciMessage(RawUciMessage),
 }
@@ -583,7 +778,9 @@ mod tests {
         generate_params, range_data_notf, session_range_data, session_status_notf,
     };
     use crate::uci::mock_uci_manager::MockUciManager;
-    use crate::uci::notification::UciNotification;
+    use crate::uci::notification::{
+        SessionNotification as UciSessionNotification, UciNotification,
+    };
 
     fn setup_uwb_service(
         uci_manager: MockUciManager,
@@ -636,6 +833,10 @@ mod tests {
             vec![
                 session_status_notf(session_id, SessionState::SessionStateActive),
                 range_data_notf(range_data.clone()),
+                UciNotification::Session(UciSessionNotification::KeyRotation {
+                    session_token: session_id,
+                    rotation_count: 1,
+                }),
             ],
             Ok(()),
         );
@@ -675,6 +876,7 @@ mod tests {
             ReasonCode::StateChangeWithSessionManagementCommands,
         );
         callback.expect_on_range_data_received(session_id, range_data);
+        callback.expect_on_session_key_rotated(session_id, 1);
         let result = service.start_ranging(session_id);
         assert!(result.is_ok());
         assert!(service.block_on_for_testing(callback.wait_expected_calls_done()));
@@ -703,6 +905,86 @@ mod tests {
         assert!(service.block_on_for_testing(uci_manager.wait_expected_calls_done()));
     }
 
+    #[test]
+    fn test_session_auto_stop_on_max_measurements() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+
+        let mut uci_manager = MockUciManager::new();
+        uci_manager.expect_open_hal(vec![], Ok(()));
+        uci_manager.expect_session_init(
+            session_id,
+            session_type,
+            vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+            Ok(()),
+        );
+        uci_manager.expect_session_set_app_config(
+            session_id,
+            tlvs,
+            vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+            Ok(SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] }),
+        );
+        uci_manager.expect_range_start(
+            session_id,
+            vec![
+                session_status_notf(session_id, SessionState::SessionStateActive),
+                UciNotification::Session(UciSessionNotification::Status {
+                    session_token: session_id,
+                    session_state: SessionState::SessionStateActive,
+                    reason_code: ReasonCode::MaxNumberOfMeasurementsReached.into(),
+                }),
+            ],
+            Ok(()),
+        );
+        uci_manager.expect_range_stop(
+            session_id,
+            vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+            Ok(()),
+        );
+
+        let (service, mut callback, _runtime) = setup_uwb_service(uci_manager.clone());
+        service.enable().unwrap();
+
+        callback.expect_on_session_state_changed(
+            session_id,
+            SessionState::SessionStateInit,
+            ReasonCode::StateChangeWithSessionManagementCommands,
+        );
+        callback.expect_on_session_state_changed(
+            session_id,
+            SessionState::SessionStateIdle,
+            ReasonCode::StateChangeWithSessionManagementCommands,
+        );
+        assert!(service.init_session(session_id, session_type, params).is_ok());
+        assert!(service.set_auto_stop_on_max_measurements(session_id, true).is_ok());
+        assert!(service.block_on_for_testing(callback.wait_expected_calls_done()));
+
+        // The ranging is stopped automatically after the UWBS reports the max number of
+        // measurements is reached.
+        callback.expect_on_session_state_changed(
+            session_id,
+            SessionState::SessionStateActive,
+            ReasonCode::StateChangeWithSessionManagementCommands,
+        );
+        callback.expect_on_session_state_changed(
+            session_id,
+            SessionState::SessionStateActive,
+            ReasonCode::MaxNumberOfMeasurementsReached,
+        );
+        callback.expect_on_session_state_changed(
+            session_id,
+            SessionState::SessionStateIdle,
+            ReasonCode::StateChangeWithSessionManagementCommands,
+        );
+        callback.expect_on_session_ranging_completed(session_id);
+        assert!(service.start_ranging(session_id).is_ok());
+        assert!(service.block_on_for_testing(callback.wait_expected_calls_done()));
+
+        assert!(service.block_on_for_testing(uci_manager.wait_expected_calls_done()));
+    }
+
     #[test]
     fn test_session_api_without_enabled() {
         let session_id = 0x123;
@@ -723,10 +1005,16 @@ mod tests {
         assert!(result.is_err());
         let result = service.stop_ranging(session_id);
         assert!(result.is_err());
+        let result = service.can_reconfigure(session_id, params.clone());
+        assert!(result.is_err());
         let result = service.reconfigure(session_id, params);
         assert!(result.is_err());
         let result = service.update_controller_multicast_list(session_id, action, controlees);
         assert!(result.is_err());
+        let result = service.set_auto_stop_on_max_measurements(session_id, true);
+        assert!(result.is_err());
+        let result = service.last_session_reason(session_id);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -812,6 +1100,23 @@ mod tests {
         assert!(service.block_on_for_testing(callback.wait_expected_calls_done()));
     }
 
+    #[test]
+    fn test_core_coexistence_notification() {
+        let mut uci_manager = MockUciManager::new();
+        uci_manager.expect_open_hal(
+            vec![UciNotification::Core(CoreNotification::Coexistence {
+                source: CoexistenceSource::Bluetooth,
+                preempted: true,
+                duration_ms: 0,
+            })],
+            Ok(()),
+        );
+        let (service, mut callback, _runtime) = setup_uwb_service(uci_manager);
+        callback.expect_on_coexistence_event(CoexistenceSource::Bluetooth, true, 0);
+        service.enable().unwrap();
+        assert!(service.block_on_for_testing(callback.wait_expected_calls_done()));
+    }
+
     #[test]
     fn test_reset_service_after_timeout() {
         let mut uci_manager = MockUciManager::new();
@@ -12,15 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use log::{debug, error, warn};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::error::{Error, Result};
 use crate::params::app_config_params::AppConfigParams;
+use crate::params::fira_app_config_params::UwbAddress;
 use crate::params::uci_packets::{
-    Controlee, ReasonCode, SessionId, SessionState, SessionType, UpdateMulticastListAction,
+    Controlee, MulticastUpdateStatusCode, ReasonCode, SessionId, SessionState, SessionTermination,
+    SessionType, UpdateMulticastListAction,
 };
 use crate::session::uwb_session::{Response as SessionResponse, ResponseSender, UwbSession};
 use crate::uci::notification::{SessionNotification as UciSessionNotification, SessionRangeData};
@@ -34,6 +36,31 @@ const MAX_SESSION_COUNT: usize = 5;
 pub(crate) enum SessionNotification {
     SessionState { session_id: SessionId, session_state: SessionState, reason_code: ReasonCode },
     RangeData { session_id: SessionId, range_data: SessionRangeData },
+    KeyRotation { session_id: SessionId, rotation_count: u32 },
+    RangingCompleted { session_id: SessionId },
+}
+
+/// The state of a session captured by SessionManager::snapshot().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSnapshot {
+    /// The id of the session.
+    pub session_id: SessionId,
+    /// The type of the session.
+    pub session_type: SessionType,
+    /// The state of the session. The ranging is restarted on restore if it is active.
+    pub session_state: SessionState,
+    /// The params last applied to the UWBS successfully.
+    pub params: AppConfigParams,
+    /// Whether the ranging is stopped automatically when reaching the max number of measurements.
+    pub auto_stop_on_max_measurements: bool,
+}
+
+/// The state of all the sessions of a SessionManager, which could be restored into another
+/// SessionManager, e.g. after the UWB stack is reloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManagerSnapshot {
+    /// The sessions in the ascending order of the session id.
+    pub sessions: Vec<SessionSnapshot>,
 }
 
 /// The SessionManager organizes the state machine of the existing UWB ranging sessions, sends
@@ -104,19 +131,38 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Check whether reconfigure() would accept the |params| at the current state of the
+    /// session, without sending any command to the UWBS.
+    pub async fn can_reconfigure(
+        &mut self,
+        session_id: SessionId,
+        params: AppConfigParams,
+    ) -> Result<bool> {
+        match self.send_cmd(SessionCommand::CanReconfigure { session_id, params }).await? {
+            SessionResponse::Reconfigurable(reconfigurable) => Ok(reconfigurable),
+            _ => panic!("can_reconfigure() should reply Reconfigurable result"),
+        }
+    }
+
+    /// Update the multicast list of the session, and return the status of each controlee
+    /// reported by the UWBS.
     pub async fn update_controller_multicast_list(
         &mut self,
         session_id: SessionId,
         action: UpdateMulticastListAction,
         controlees: Vec<Controlee>,
-    ) -> Result<()> {
-        self.send_cmd(SessionCommand::UpdateControllerMulticastList {
-            session_id,
-            action,
-            controlees,
-        })
-        .await?;
-        Ok(())
+    ) -> Result<Vec<(UwbAddress, MulticastUpdateStatusCode)>> {
+        match self
+            .send_cmd(SessionCommand::UpdateControllerMulticastList {
+                session_id,
+                action,
+                controlees,
+            })
+            .await?
+        {
+            SessionResponse::ControleeStatusList(status_list) => Ok(status_list),
+            _ => panic!("update_controller_multicast_list() should reply ControleeStatusList"),
+        }
     }
 
     pub async fn session_params(&mut self, session_id: SessionId) -> Result<AppConfigParams> {
@@ -126,6 +172,94 @@ impl SessionManager {
         }
     }
 
+    /// Enable or disable the auto-stop policy of the session. When enabled, the ranging is stopped
+    /// once the UWBS reports ReasonCode::MaxNumberOfMeasurementsReached, unless the UWBS has
+    /// already moved the session to idle, and then SessionNotification::RangingCompleted is sent.
+    pub async fn set_auto_stop_on_max_measurements(
+        &mut self,
+        session_id: SessionId,
+        enabled: bool,
+    ) -> Result<()> {
+        self.send_cmd(SessionCommand::SetAutoStopOnMaxMeasurements { session_id, enabled }).await?;
+        Ok(())
+    }
+
+    /// Capture the type, the state and the last applied params of all the sessions.
+    pub async fn snapshot(&mut self) -> Result<ManagerSnapshot> {
+        let (session_list, auto_stop_sessions) =
+            match self.send_cmd(SessionCommand::GetSessionList).await? {
+                SessionResponse::SessionList { sessions, auto_stop_sessions } => {
+                    (sessions, auto_stop_sessions)
+                }
+                _ => panic!("snapshot() should reply SessionList result"),
+            };
+
+        let mut sessions = vec![];
+        for (session_id, session_type, session_state) in session_list.into_iter() {
+            sessions.push(SessionSnapshot {
+                session_id,
+                session_type,
+                session_state,
+                params: self.session_params(session_id).await?,
+                auto_stop_on_max_measurements: auto_stop_sessions.contains(&session_id),
+            });
+        }
+        Ok(ManagerSnapshot { sessions })
+    }
+
+    /// Re-create the sessions of the |snapshot|: each session is initialized with its params, and
+    /// the ranging is started again if the session was active.
+    pub async fn restore(&mut self, snapshot: &ManagerSnapshot) -> Result<()> {
+        for session in snapshot.sessions.iter() {
+            self.init_session(session.session_id, session.session_type, session.params.clone())
+                .await?;
+            if session.auto_stop_on_max_measurements {
+                self.set_auto_stop_on_max_measurements(session.session_id, true).await?;
+            }
+            if session.session_state == SessionState::SessionStateActive {
+                self.start_ranging(session.session_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get how the last ranging of the session is stopped. Return None if the session is not
+    /// stopped since the ranging is started.
+    pub async fn session_termination(
+        &mut self,
+        session_id: SessionId,
+    ) -> Result<Option<SessionTermination>> {
+        match self.send_cmd(SessionCommand::GetTermination { session_id }).await? {
+            SessionResponse::Termination(termination) => Ok(termination),
+            _ => panic!("session_termination() should reply Termination result"),
+        }
+    }
+
+    /// Get the reason code of the last state change of the session, which is reported by
+    /// SESSION_STATUS_NTF. Return None if no state change is notified yet.
+    pub async fn last_session_reason(
+        &mut self,
+        session_id: SessionId,
+    ) -> Result<Option<ReasonCode>> {
+        match self.send_cmd(SessionCommand::GetLastReason { session_id }).await? {
+            SessionResponse::Reason(reason_code) => Ok(reason_code),
+            _ => panic!("last_session_reason() should reply Reason result"),
+        }
+    }
+
+    /// Overwrite the tracked state of the session, e.g. to reconcile with the sessions which are
+    /// controlled out-of-band by the embedder. No notification is sent for the forced state, and
+    /// the session is dropped if the state is SessionStateDeinit. Use it with care: the tracked
+    /// state is normally updated by SESSION_STATUS_NTF only.
+    pub async fn force_session_state(
+        &mut self,
+        session_id: SessionId,
+        session_state: SessionState,
+    ) -> Result<()> {
+        self.send_cmd(SessionCommand::ForceSessionState { session_id, session_state }).await?;
+        Ok(())
+    }
+
     // Send the |cmd| to the SessionManagerActor.
     async fn send_cmd(&self, cmd: SessionCommand) -> Result<SessionResponse> {
         let (result_sender, result_receiver) = oneshot::channel();
@@ -152,6 +286,12 @@ struct SessionManagerActor<T: UciManager> {
     uci_notf_receiver: mpsc::UnboundedReceiver<UciSessionNotification>,
 
     active_sessions: BTreeMap<SessionId, UwbSession>,
+    // The sessions which are stopped automatically when reaching the max number of measurements.
+    auto_stop_sessions: BTreeSet<SessionId>,
+    // How the last ranging of each session is stopped.
+    terminations: BTreeMap<SessionId, SessionTermination>,
+    // The reason code of the last state change of each session.
+    last_reasons: BTreeMap<SessionId, ReasonCode>,
 }
 
 impl<T: UciManager> SessionManagerActor<T> {
@@ -167,6 +307,9 @@ impl<T: UciManager> SessionManagerActor<T> {
             uci_manager,
             uci_notf_receiver,
             active_sessions: BTreeMap::new(),
+            auto_stop_sessions: BTreeSet::new(),
+            terminations: BTreeMap::new(),
+            last_reasons: BTreeMap::new(),
         }
     }
 
@@ -224,6 +367,9 @@ impl<T: UciManager> SessionManagerActor<T> {
                 self.active_sessions.insert(session_id, session);
             }
             SessionCommand::DeinitSession { session_id } => {
+                self.auto_stop_sessions.remove(&session_id);
+                self.terminations.remove(&session_id);
+                self.last_reasons.remove(&session_id);
                 match self.active_sessions.remove(&session_id) {
                     None => {
                         warn!("Session {} doesn't exist", session_id);
@@ -241,6 +387,7 @@ impl<T: UciManager> SessionManagerActor<T> {
                         let _ = result_sender.send(Err(Error::BadParameters));
                     }
                     Some(session) => {
+                        self.terminations.remove(&session_id);
                         session.start_ranging(result_sender);
                     }
                 }
@@ -267,6 +414,17 @@ impl<T: UciManager> SessionManagerActor<T> {
                     }
                 }
             }
+            SessionCommand::CanReconfigure { session_id, params } => {
+                match self.active_sessions.get_mut(&session_id) {
+                    None => {
+                        warn!("Session {} doesn't exist", session_id);
+                        let _ = result_sender.send(Err(Error::BadParameters));
+                    }
+                    Some(session) => {
+                        session.can_reconfigure(params, result_sender);
+                    }
+                }
+            }
             SessionCommand::UpdateControllerMulticastList { session_id, action, controlees } => {
                 match self.active_sessions.get_mut(&session_id) {
                     None => {
@@ -289,6 +447,69 @@ impl<T: UciManager> SessionManagerActor<T> {
                     }
                 }
             }
+            SessionCommand::SetAutoStopOnMaxMeasurements { session_id, enabled } => {
+                if !self.active_sessions.contains_key(&session_id) {
+                    warn!("Session {} doesn't exist", session_id);
+                    let _ = result_sender.send(Err(Error::BadParameters));
+                    return;
+                }
+                if enabled {
+                    self.auto_stop_sessions.insert(session_id);
+                } else {
+                    self.auto_stop_sessions.remove(&session_id);
+                }
+                let _ = result_sender.send(Ok(SessionResponse::Null));
+            }
+            SessionCommand::GetTermination { session_id } => {
+                if !self.active_sessions.contains_key(&session_id) {
+                    warn!("Session {} doesn't exist", session_id);
+                    let _ = result_sender.send(Err(Error::BadParameters));
+                    return;
+                }
+                let termination = self.terminations.get(&session_id).copied();
+                let _ = result_sender.send(Ok(SessionResponse::Termination(termination)));
+            }
+            SessionCommand::GetLastReason { session_id } => {
+                if !self.active_sessions.contains_key(&session_id) {
+                    warn!("Session {} doesn't exist", session_id);
+                    let _ = result_sender.send(Err(Error::BadParameters));
+                    return;
+                }
+                let reason_code = self.last_reasons.get(&session_id).copied();
+                let _ = result_sender.send(Ok(SessionResponse::Reason(reason_code)));
+            }
+            SessionCommand::ForceSessionState { session_id, session_state } => {
+                let session = match self.active_sessions.get_mut(&session_id) {
+                    Some(session) => session,
+                    None => {
+                        warn!("Session {} doesn't exist", session_id);
+                        let _ = result_sender.send(Err(Error::BadParameters));
+                        return;
+                    }
+                };
+                warn!("Force the state of session {} to {:?}", session_id, session_state);
+                if session_state == SessionState::SessionStateDeinit {
+                    let _ = self.active_sessions.remove(&session_id);
+                    self.auto_stop_sessions.remove(&session_id);
+                    self.terminations.remove(&session_id);
+                    self.last_reasons.remove(&session_id);
+                } else {
+                    session.on_session_status_changed(session_state);
+                }
+                let _ = result_sender.send(Ok(SessionResponse::Null));
+            }
+            SessionCommand::GetSessionList => {
+                let sessions = self
+                    .active_sessions
+                    .iter()
+                    .map(|(session_id, session)| {
+                        (*session_id, session.session_type(), session.state())
+                    })
+                    .collect();
+                let auto_stop_sessions = self.auto_stop_sessions.iter().copied().collect();
+                let _ = result_sender
+                    .send(Ok(SessionResponse::SessionList { sessions, auto_stop_sessions }));
+            }
         }
     }
 
@@ -308,6 +529,9 @@ impl<T: UciManager> SessionManagerActor<T> {
                 if session_state == SessionState::SessionStateDeinit {
                     debug!("Session {} is deinitialized", session_token);
                     let _ = self.active_sessions.remove(&session_token);
+                    self.auto_stop_sessions.remove(&session_token);
+                    self.terminations.remove(&session_token);
+                    self.last_reasons.remove(&session_token);
                     let _ = self.session_notf_sender.send(SessionNotification::SessionState {
                         session_id: session_token,
                         session_state,
@@ -318,12 +542,29 @@ impl<T: UciManager> SessionManagerActor<T> {
 
                 match self.active_sessions.get_mut(&session_token) {
                     Some(session) => {
+                        // The ranging is stopped when the active session transits to idle.
+                        if session.state() == SessionState::SessionStateActive
+                            && session_state == SessionState::SessionStateIdle
+                        {
+                            if let Some(termination) =
+                                SessionTermination::from_reason_code(reason_code)
+                            {
+                                debug!("Session {} is stopped: {:?}", session_token, termination);
+                                self.terminations.insert(session_token, termination);
+                            }
+                        }
                         session.on_session_status_changed(session_state);
+                        self.last_reasons.insert(session_token, reason_code);
                         let _ = self.session_notf_sender.send(SessionNotification::SessionState {
                             session_id: session_token,
                             session_state,
                             reason_code,
                         });
+                        if reason_code == ReasonCode::MaxNumberOfMeasurementsReached
+                            && self.auto_stop_sessions.contains(&session_token)
+                        {
+                            self.auto_stop_session(session_token);
+                        }
                     }
                     None => {
                         warn!(
@@ -392,8 +633,48 @@ impl<T: UciManager> SessionManagerActor<T> {
                     }
                 }
             }
+            UciSessionNotification::KeyRotation { session_token, rotation_count } => {
+                if self.active_sessions.contains_key(&session_token) {
+                    let _ = self.session_notf_sender.send(SessionNotification::KeyRotation {
+                        session_id: session_token,
+                        rotation_count,
+                    });
+                } else {
+                    warn!(
+                        "Received the key rotation notification of the unknown Session {}",
+                        session_token
+                    );
+                }
+            }
+            UciSessionNotification::Diagnostics { session_token, sequence_number, .. } => {
+                // The diagnostics are only consumed by the clients of the UciManager.
+                debug!(
+                    "Received the diagnostics notification {} of Session {}",
+                    sequence_number, session_token
+                );
+            }
         }
     }
+
+    // Stop the ranging of the session if the UWBS doesn't stop it by itself, then notify the
+    // ranging is completed.
+    fn auto_stop_session(&mut self, session_id: SessionId) {
+        let session = match self.active_sessions.get_mut(&session_id) {
+            Some(session) => session,
+            None => return,
+        };
+        debug!("Session {} reached the max number of measurements, stop ranging", session_id);
+        let (result_sender, result_receiver) = oneshot::channel();
+        session.stop_ranging(result_sender);
+
+        let session_notf_sender = self.session_notf_sender.clone();
+        tokio::spawn(async move {
+            if let Ok(Err(e)) = result_receiver.await {
+                warn!("Failed to stop the ranging of Session {}: {:?}", session_id, e);
+            }
+            let _ = session_notf_sender.send(SessionNotification::RangingCompleted { session_id });
+        });
+    }
 }
 
 impl<T: UciManager> Drop for SessionManagerActor<T> {
@@ -423,6 +704,10 @@ enum SessionCommand {
         session_id: SessionId,
         params: AppConfigParams,
     },
+    CanReconfigure {
+        session_id: SessionId,
+        params: AppConfigParams,
+    },
     UpdateControllerMulticastList {
         session_id: SessionId,
         action: UpdateMulticastListAction,
@@ -431,6 +716,21 @@ enum SessionCommand {
     GetParams {
         session_id: SessionId,
     },
+    SetAutoStopOnMaxMeasurements {
+        session_id: SessionId,
+        enabled: bool,
+    },
+    GetTermination {
+        session_id: SessionId,
+    },
+    GetLastReason {
+        session_id: SessionId,
+    },
+    ForceSessionState {
+        session_id: SessionId,
+        session_state: SessionState,
+    },
+    GetSessionList,
 }
 
 #[cfg(test)]
@@ -504,6 +804,9 @@ pub(crate) mod test_utils {
             ]),
             rcr_indicator: 0,
             raw_ranging_data: vec![0x12, 0x34],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
         }
     }
 
@@ -559,6 +862,7 @@ mod tests {
     };
     use crate::params::utils::{u32_to_bytes, u64_to_bytes, u8_to_bytes};
     use crate::params::{FiraAppConfigParamsBuilder, KeyRotation};
+    use crate::uci::mock_uci_manager::MockUciManager;
     use crate::uci::notification::UciNotification;
 
     #[tokio::test]
@@ -711,26 +1015,22 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_ccc_start_ranging() {
+    async fn test_session_termination() {
         let session_id = 0x123;
-        let session_type = SessionType::Ccc;
-        // params that is passed to UciManager::session_set_app_config().
-        let params = generate_ccc_params();
+        let session_type = SessionType::FiraRangingSession;
+        let params = FiraAppConfigParamsBuilder::from_params(&generate_params())
+            .unwrap()
+            .in_band_termination_attempt_count(3)
+            .build()
+            .unwrap();
         let tlvs = params.generate_tlvs();
-        // The params that is received from UciManager::session_get_app_config().
-        let received_config_map = HashMap::from([
-            (AppConfigTlvType::StsIndex, u32_to_bytes(3)),
-            (AppConfigTlvType::CccHopModeKey, u32_to_bytes(5)),
-            (AppConfigTlvType::CccUwbTime0, u64_to_bytes(7)),
-            (AppConfigTlvType::RangingInterval, u32_to_bytes(96)),
-            (AppConfigTlvType::PreambleCodeIndex, u8_to_bytes(9)),
-        ]);
-        let received_tlvs = received_config_map
-            .iter()
-            .map(|(key, value)| AppConfigTlv::new(*key, value.clone()))
-            .collect();
-        let started_params =
-            CccStartedAppConfigParams::from_config_map(received_config_map).unwrap();
+        let stopped_notf = |reason_code: ReasonCode| {
+            UciNotification::Session(UciSessionNotification::Status {
+                session_token: session_id,
+                session_state: SessionState::SessionStateIdle,
+                reason_code: reason_code.into(),
+            })
+        };
 
         let (mut session_manager, mut mock_uci_manager, _) =
             setup_session_manager(move |uci_manager| {
@@ -749,47 +1049,62 @@ mod tests {
                         config_status: vec![],
                     }),
                 );
+                // The controlee drops out, so the in-band termination is not acknowledged and
+                // the UWBS stops after the retries are exhausted.
                 uci_manager.expect_range_start(
                     session_id,
                     vec![session_status_notf(session_id, SessionState::SessionStateActive)],
                     Ok(()),
                 );
-                uci_manager.expect_session_get_app_config(session_id, vec![], Ok(received_tlvs));
+                uci_manager.expect_range_stop(
+                    session_id,
+                    vec![stopped_notf(ReasonCode::MaxRangingRoundRetryCountReached)],
+                    Ok(()),
+                );
+                // The controlee acknowledges the in-band termination.
+                uci_manager.expect_range_start(
+                    session_id,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+                uci_manager.expect_range_stop(
+                    session_id,
+                    vec![stopped_notf(ReasonCode::SessionStoppedDueToInbandSignal)],
+                    Ok(()),
+                );
             })
             .await;
 
         let result = session_manager.init_session(session_id, session_type, params.clone()).await;
         assert_eq!(result, Ok(()));
-        let result = session_manager.start_ranging(session_id).await;
-        assert_eq!(result, Ok(AppConfigParams::CccStarted(started_params)));
+        assert_eq!(session_manager.session_termination(session_id).await, Ok(None));
+
+        for expected_termination in
+            [SessionTermination::RetryExhausted, SessionTermination::InBandSignal]
+        {
+            let result = session_manager.start_ranging(session_id).await;
+            assert_eq!(result, Ok(params.clone()));
+            assert_eq!(session_manager.session_termination(session_id).await, Ok(None));
+            let result = session_manager.stop_ranging(session_id).await;
+            assert_eq!(result, Ok(()));
+            let termination = session_manager.session_termination(session_id).await.unwrap();
+            assert_eq!(termination, Some(expected_termination));
+        }
+        assert!(!SessionTermination::RetryExhausted.is_graceful());
+        assert!(SessionTermination::InBandSignal.is_graceful());
 
         assert!(mock_uci_manager.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_update_controller_multicast_list() {
+    async fn test_last_session_reason() {
         let session_id = 0x123;
         let session_type = SessionType::FiraRangingSession;
         let params = generate_params();
         let tlvs = params.generate_tlvs();
-        let action = UpdateMulticastListAction::AddControlee;
-        let short_address: [u8; 2] = [0x12, 0x34];
-        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
 
-        let controlees_clone = controlees.clone();
         let (mut session_manager, mut mock_uci_manager, _) =
             setup_session_manager(move |uci_manager| {
-                let multicast_list_notf = vec![UciNotification::Session(
-                    UciSessionNotification::UpdateControllerMulticastList {
-                        session_token: session_id,
-                        remaining_multicast_list_size: 1,
-                        status_list: vec![ControleeStatus {
-                            mac_address: [0x34, 0x12],
-                            subsession_id: 0x24,
-                            status: MulticastUpdateStatusCode::StatusOkMulticastListUpdate,
-                        }],
-                    },
-                )];
                 uci_manager.expect_session_init(
                     session_id,
                     session_type,
@@ -805,34 +1120,48 @@ mod tests {
                         config_status: vec![],
                     }),
                 );
-                uci_manager.expect_session_update_controller_multicast_list(
+                uci_manager.expect_range_start(
                     session_id,
-                    action,
-                    Controlees::NoSessionKey(controlees_clone),
-                    multicast_list_notf,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+                uci_manager.expect_range_stop(
+                    session_id,
+                    vec![UciNotification::Session(UciSessionNotification::Status {
+                        session_token: session_id,
+                        session_state: SessionState::SessionStateIdle,
+                        reason_code: ReasonCode::MaxRangingRoundRetryCountReached.into(),
+                    })],
                     Ok(()),
                 );
             })
             .await;
 
-        let result = session_manager.init_session(session_id, session_type, params).await;
+        // The session doesn't exist.
+        let result = session_manager.last_session_reason(session_id).await;
+        assert_eq!(result, Err(Error::BadParameters));
+
+        let result = session_manager.init_session(session_id, session_type, params.clone()).await;
         assert_eq!(result, Ok(()));
-        let result =
-            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        let result = session_manager.last_session_reason(session_id).await;
+        assert_eq!(result, Ok(Some(ReasonCode::StateChangeWithSessionManagementCommands)));
+
+        let result = session_manager.start_ranging(session_id).await;
+        assert_eq!(result, Ok(params));
+        let result = session_manager.stop_ranging(session_id).await;
         assert_eq!(result, Ok(()));
+        let result = session_manager.last_session_reason(session_id).await;
+        assert_eq!(result, Ok(Some(ReasonCode::MaxRangingRoundRetryCountReached)));
 
         assert!(mock_uci_manager.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_ccc_update_controller_multicast_list() {
+    async fn test_force_session_state() {
         let session_id = 0x123;
-        let session_type = SessionType::Ccc;
-        let params = generate_ccc_params();
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
         let tlvs = params.generate_tlvs();
-        let action = UpdateMulticastListAction::AddControlee;
-        let short_address: [u8; 2] = [0x12, 0x34];
-        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
 
         let (mut session_manager, mut mock_uci_manager, _) =
             setup_session_manager(move |uci_manager| {
@@ -853,30 +1182,38 @@ mod tests {
                 );
             })
             .await;
+        let result = session_manager.init_session(session_id, session_type, params.clone()).await;
+        assert_eq!(result, Ok(()));
 
-        let result = session_manager.init_session(session_id, session_type, params).await;
+        // The ranging is started out-of-band, so no UCI command is sent.
+        let result =
+            session_manager.force_session_state(session_id, SessionState::SessionStateActive).await;
         assert_eq!(result, Ok(()));
-        // CCC session doesn't support update_controller_multicast_list.
+        let snapshot = session_manager.snapshot().await.unwrap();
+        assert_eq!(snapshot.sessions.len(), 1);
+        assert_eq!(snapshot.sessions[0].session_state, SessionState::SessionStateActive);
+
+        // The session is dropped when it's forced to be deinitialized.
         let result =
-            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+            session_manager.force_session_state(session_id, SessionState::SessionStateDeinit).await;
+        assert_eq!(result, Ok(()));
+        assert!(session_manager.snapshot().await.unwrap().sessions.is_empty());
+        let result =
+            session_manager.force_session_state(session_id, SessionState::SessionStateIdle).await;
         assert_eq!(result, Err(Error::BadParameters));
 
         assert!(mock_uci_manager.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_update_controller_multicast_list_without_notification() {
-        let session_id = 0x123;
+    async fn test_snapshot_restore() {
+        let idle_session_id = 0x123;
+        let active_session_id = 0x456;
         let session_type = SessionType::FiraRangingSession;
         let params = generate_params();
         let tlvs = params.generate_tlvs();
-        let action = UpdateMulticastListAction::AddControlee;
-        let short_address: [u8; 2] = [0x12, 0x34];
-        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
-
-        let controlees_clone = controlees.clone();
-        let (mut session_manager, mut mock_uci_manager, _) =
-            setup_session_manager(move |uci_manager| {
+        let expect_init_sessions = move |uci_manager: &mut MockUciManager| {
+            for session_id in [idle_session_id, active_session_id] {
                 uci_manager.expect_session_init(
                     session_id,
                     session_type,
@@ -885,41 +1222,79 @@ mod tests {
                 );
                 uci_manager.expect_session_set_app_config(
                     session_id,
-                    tlvs,
+                    tlvs.clone(),
                     vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
                     Ok(SetAppConfigResponse {
                         status: StatusCode::UciStatusOk,
                         config_status: vec![],
                     }),
                 );
-                uci_manager.expect_session_update_controller_multicast_list(
-                    session_id,
-                    action,
-                    uwb_uci_packets::Controlees::NoSessionKey(controlees_clone),
-                    vec![], // Not sending notification.
-                    Ok(()),
-                );
-            })
-            .await;
+            }
+            uci_manager.expect_range_start(
+                active_session_id,
+                vec![session_status_notf(active_session_id, SessionState::SessionStateActive)],
+                Ok(()),
+            );
+        };
 
-        let result = session_manager.init_session(session_id, session_type, params).await;
-        assert_eq!(result, Ok(()));
-        // This method should timeout waiting for the notification.
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(expect_init_sessions.clone()).await;
+        for session_id in [idle_session_id, active_session_id] {
+            let result =
+                session_manager.init_session(session_id, session_type, params.clone()).await;
+            assert_eq!(result, Ok(()));
+        }
+        let result = session_manager.start_ranging(active_session_id).await;
+        assert_eq!(result, Ok(params.clone()));
         let result =
-            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
-        assert_eq!(result, Err(Error::Timeout));
-
+            session_manager.set_auto_stop_on_max_measurements(active_session_id, true).await;
+        assert_eq!(result, Ok(()));
         assert!(mock_uci_manager.wait_expected_calls_done().await);
+
+        let snapshot = session_manager.snapshot().await.unwrap();
+        assert_eq!(
+            snapshot,
+            ManagerSnapshot {
+                sessions: vec![
+                    SessionSnapshot {
+                        session_id: idle_session_id,
+                        session_type,
+                        session_state: SessionState::SessionStateIdle,
+                        params: params.clone(),
+                        auto_stop_on_max_measurements: false,
+                    },
+                    SessionSnapshot {
+                        session_id: active_session_id,
+                        session_type,
+                        session_state: SessionState::SessionStateActive,
+                        params: params.clone(),
+                        auto_stop_on_max_measurements: true,
+                    },
+                ],
+            }
+        );
+
+        // Restoring into a new SessionManager re-initializes both sessions and restarts the
+        // ranging of the active one.
+        let (mut new_session_manager, mut new_mock_uci_manager, _) =
+            setup_session_manager(expect_init_sessions).await;
+        assert_eq!(new_session_manager.restore(&snapshot).await, Ok(()));
+        assert!(new_mock_uci_manager.wait_expected_calls_done().await);
+        assert_eq!(new_session_manager.snapshot().await, Ok(snapshot));
     }
 
     #[tokio::test]
-    async fn test_receive_session_range_data() {
+    async fn test_auto_stop_on_max_measurements() {
         let session_id = 0x123;
         let session_type = SessionType::FiraRangingSession;
         let params = generate_params();
         let tlvs = params.generate_tlvs();
-        let range_data = session_range_data(session_id);
-        let range_data_clone = range_data.clone();
+        // The UWBS reports the reason with the session already idle.
+        let max_measurements_notf = UciNotification::Session(UciSessionNotification::Status {
+            session_token: session_id,
+            session_state: SessionState::SessionStateIdle,
+            reason_code: ReasonCode::MaxNumberOfMeasurementsReached.into(),
+        });
 
         let (mut session_manager, mut mock_uci_manager, mut session_notf_receiver) =
             setup_session_manager(move |uci_manager| {
@@ -932,17 +1307,536 @@ mod tests {
                 uci_manager.expect_session_set_app_config(
                     session_id,
                     tlvs,
-                    vec![
-                        session_status_notf(session_id, SessionState::SessionStateIdle),
-                        range_data_notf(range_data_clone),
-                    ],
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
                     Ok(SetAppConfigResponse {
                         status: StatusCode::UciStatusOk,
                         config_status: vec![],
                     }),
                 );
-            })
-            .await;
+                uci_manager.expect_range_start(
+                    session_id,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+                uci_manager.expect_range_start(
+                    session_id,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+            })
+            .await;
+
+        // The session should exist before setting the policy.
+        let result = session_manager.set_auto_stop_on_max_measurements(session_id, true).await;
+        assert_eq!(result, Err(Error::BadParameters));
+
+        let result = session_manager.init_session(session_id, session_type, params.clone()).await;
+        assert_eq!(result, Ok(()));
+        let result = session_manager.set_auto_stop_on_max_measurements(session_id, true).await;
+        assert_eq!(result, Ok(()));
+        let result = session_manager.start_ranging(session_id).await;
+        assert_eq!(result, Ok(params.clone()));
+        mock_uci_manager.send_notifications(vec![max_measurements_notf]);
+
+        // Skip the notifications of the Init, Idle and Active states.
+        for _ in 0..3 {
+            assert!(matches!(
+                session_notf_receiver.recv().await.unwrap(),
+                SessionNotification::SessionState { .. }
+            ));
+        }
+        let session_notf = session_notf_receiver.recv().await.unwrap();
+        assert_eq!(
+            session_notf,
+            SessionNotification::SessionState {
+                session_id,
+                session_state: SessionState::SessionStateIdle,
+                reason_code: ReasonCode::MaxNumberOfMeasurementsReached
+            }
+        );
+        let session_notf = session_notf_receiver.recv().await.unwrap();
+        assert_eq!(session_notf, SessionNotification::RangingCompleted { session_id });
+
+        // RANGE_STOP is not sent to the idle session, so the ranging could be started again.
+        assert!(session_notf_receiver.try_recv().is_err());
+        let result = session_manager.start_ranging(session_id).await;
+        assert_eq!(result, Ok(params));
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_ccc_start_ranging() {
+        let session_id = 0x123;
+        let session_type = SessionType::Ccc;
+        // params that is passed to UciManager::session_set_app_config().
+        let params = generate_ccc_params();
+        let tlvs = params.generate_tlvs();
+        // The params that is received from UciManager::session_get_app_config().
+        let received_config_map = HashMap::from([
+            (AppConfigTlvType::StsIndex, u32_to_bytes(3)),
+            (AppConfigTlvType::CccHopModeKey, u32_to_bytes(5)),
+            (AppConfigTlvType::CccUwbTime0, u64_to_bytes(7)),
+            (AppConfigTlvType::RangingInterval, u32_to_bytes(96)),
+            (AppConfigTlvType::PreambleCodeIndex, u8_to_bytes(9)),
+        ]);
+        let received_tlvs = received_config_map
+            .iter()
+            .map(|(key, value)| AppConfigTlv::new(*key, value.clone()))
+            .collect();
+        let started_params =
+            CccStartedAppConfigParams::from_config_map(received_config_map).unwrap();
+
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_range_start(
+                    session_id,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_get_app_config(session_id, vec![], Ok(received_tlvs));
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params.clone()).await;
+        assert_eq!(result, Ok(()));
+        let result = session_manager.start_ranging(session_id).await;
+        assert_eq!(result, Ok(AppConfigParams::CccStarted(started_params)));
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_update_controller_multicast_list() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let short_address: [u8; 2] = [0x12, 0x34];
+        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
+
+        let controlees_clone = controlees.clone();
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                let multicast_list_notf = vec![UciNotification::Session(
+                    UciSessionNotification::UpdateControllerMulticastList {
+                        session_token: session_id,
+                        remaining_multicast_list_size: 1,
+                        status_list: vec![ControleeStatus {
+                            mac_address: [0x34, 0x12],
+                            subsession_id: 0x24,
+                            status: MulticastUpdateStatusCode::StatusOkMulticastListUpdate,
+                        }],
+                    },
+                )];
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_session_update_controller_multicast_list(
+                    session_id,
+                    action,
+                    Controlees::NoSessionKey(controlees_clone),
+                    multicast_list_notf,
+                    Ok(()),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        let result =
+            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        assert_eq!(
+            result,
+            Ok(vec![(
+                UwbAddress::Short([0x34, 0x12]),
+                MulticastUpdateStatusCode::StatusOkMulticastListUpdate
+            )])
+        );
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_update_controller_multicast_list_partially_failed() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let controlees = vec![
+            Controlee { short_address: [0x12, 0x34], subsession_id: 0x24 },
+            Controlee { short_address: [0x56, 0x78], subsession_id: 0x25 },
+        ];
+
+        let controlees_clone = controlees.clone();
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                let multicast_list_notf = vec![UciNotification::Session(
+                    UciSessionNotification::UpdateControllerMulticastList {
+                        session_token: session_id,
+                        remaining_multicast_list_size: 0,
+                        status_list: vec![
+                            ControleeStatus {
+                                mac_address: [0x12, 0x34],
+                                subsession_id: 0x24,
+                                status: MulticastUpdateStatusCode::StatusOkMulticastListUpdate,
+                            },
+                            ControleeStatus {
+                                mac_address: [0x56, 0x78],
+                                subsession_id: 0x25,
+                                status: MulticastUpdateStatusCode::StatusErrorMulticastListFull,
+                            },
+                        ],
+                    },
+                )];
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_session_update_controller_multicast_list(
+                    session_id,
+                    action,
+                    Controlees::NoSessionKey(controlees_clone),
+                    multicast_list_notf,
+                    Ok(()),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        // The status of each controlee is reported, including the failed one.
+        let result =
+            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        assert_eq!(
+            result,
+            Ok(vec![
+                (
+                    UwbAddress::Short([0x12, 0x34]),
+                    MulticastUpdateStatusCode::StatusOkMulticastListUpdate
+                ),
+                (
+                    UwbAddress::Short([0x56, 0x78]),
+                    MulticastUpdateStatusCode::StatusErrorMulticastListFull
+                ),
+            ])
+        );
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_update_controller_multicast_list_multiple_notifications() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let controlees: Vec<Controlee> = (0..10)
+            .map(|i| Controlee { short_address: [0x12, i], subsession_id: i as u32 })
+            .collect();
+        let status_list = |range: std::ops::Range<u8>| {
+            range
+                .map(|i| ControleeStatus {
+                    mac_address: [0x12, i],
+                    subsession_id: i as u32,
+                    status: MulticastUpdateStatusCode::StatusOkMulticastListUpdate,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let controlees_clone = controlees.clone();
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                // The list is split into 2 commands, and each of them has its own notification.
+                let multicast_list_notf = vec![
+                    UciNotification::Session(
+                        UciSessionNotification::UpdateControllerMulticastList {
+                            session_token: session_id,
+                            remaining_multicast_list_size: 2,
+                            status_list: status_list(0..8),
+                        },
+                    ),
+                    UciNotification::Session(
+                        UciSessionNotification::UpdateControllerMulticastList {
+                            session_token: session_id,
+                            remaining_multicast_list_size: 0,
+                            status_list: status_list(8..10),
+                        },
+                    ),
+                ];
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_session_update_controller_multicast_list(
+                    session_id,
+                    action,
+                    Controlees::NoSessionKey(controlees_clone),
+                    multicast_list_notf,
+                    Ok(()),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        // The statuses of all the 10 controlees are reported.
+        let result =
+            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        assert_eq!(
+            result,
+            Ok((0..10)
+                .map(|i| (
+                    UwbAddress::Short([0x12, i]),
+                    MulticastUpdateStatusCode::StatusOkMulticastListUpdate
+                ))
+                .collect())
+        );
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_update_controller_multicast_list_fewer_statuses() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let controlees: Vec<Controlee> = (0..3)
+            .map(|i| Controlee { short_address: [0x12, i], subsession_id: i as u32 })
+            .collect();
+
+        let controlees_clone = controlees.clone();
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                // The notification only reports the failed controlee.
+                let multicast_list_notf = vec![UciNotification::Session(
+                    UciSessionNotification::UpdateControllerMulticastList {
+                        session_token: session_id,
+                        remaining_multicast_list_size: 0,
+                        status_list: vec![ControleeStatus {
+                            mac_address: [0x12, 2],
+                            subsession_id: 2,
+                            status: MulticastUpdateStatusCode::StatusErrorMulticastListFull,
+                        }],
+                    },
+                )];
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_session_update_controller_multicast_list(
+                    session_id,
+                    action,
+                    Controlees::NoSessionKey(controlees_clone),
+                    multicast_list_notf,
+                    Ok(()),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        // The update returns once the notification is received, without waiting for the timeout
+        // of the notification, which is 1 second.
+        let result = tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            session_manager.update_controller_multicast_list(session_id, action, controlees),
+        )
+        .await;
+        assert_eq!(
+            result,
+            Ok(Ok(vec![(
+                UwbAddress::Short([0x12, 2]),
+                MulticastUpdateStatusCode::StatusErrorMulticastListFull
+            )]))
+        );
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_ccc_update_controller_multicast_list() {
+        let session_id = 0x123;
+        let session_type = SessionType::Ccc;
+        let params = generate_ccc_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let short_address: [u8; 2] = [0x12, 0x34];
+        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
+
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        // CCC session doesn't support update_controller_multicast_list.
+        let result =
+            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        assert_eq!(result, Err(Error::BadParameters));
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_update_controller_multicast_list_without_notification() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let action = UpdateMulticastListAction::AddControlee;
+        let short_address: [u8; 2] = [0x12, 0x34];
+        let controlees = vec![Controlee { short_address, subsession_id: 0x24 }];
+
+        let controlees_clone = controlees.clone();
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_session_update_controller_multicast_list(
+                    session_id,
+                    action,
+                    uwb_uci_packets::Controlees::NoSessionKey(controlees_clone),
+                    vec![], // Not sending notification.
+                    Ok(()),
+                );
+            })
+            .await;
+
+        let result = session_manager.init_session(session_id, session_type, params).await;
+        assert_eq!(result, Ok(()));
+        // This method should timeout waiting for the notification.
+        let result =
+            session_manager.update_controller_multicast_list(session_id, action, controlees).await;
+        assert_eq!(result, Err(Error::Timeout));
+
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_receive_session_range_data() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let range_data = session_range_data(session_id);
+        let range_data_clone = range_data.clone();
+
+        let (mut session_manager, mut mock_uci_manager, mut session_notf_receiver) =
+            setup_session_manager(move |uci_manager| {
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![
+                        session_status_notf(session_id, SessionState::SessionStateIdle),
+                        range_data_notf(range_data_clone),
+                    ],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+            })
+            .await;
 
         let result = session_manager.init_session(session_id, session_type, params).await;
         assert_eq!(result, Ok(()));
@@ -1068,6 +1962,73 @@ mod tests {
         assert!(mock_uci_manager.wait_expected_calls_done().await);
     }
 
+    #[tokio::test]
+    async fn test_can_reconfigure() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+
+        let params = generate_params();
+        let tlvs = params.generate_tlvs();
+        let key_rotation_params = FiraAppConfigParamsBuilder::from_params(&params)
+            .unwrap()
+            .key_rotation(KeyRotation::Enable)
+            .build()
+            .unwrap();
+        let block_stride_params = FiraAppConfigParamsBuilder::from_params(&params)
+            .unwrap()
+            .block_stride_length(2)
+            .build()
+            .unwrap();
+
+        let (mut session_manager, mut mock_uci_manager, _) =
+            setup_session_manager(move |uci_manager| {
+                uci_manager.expect_session_init(
+                    session_id,
+                    session_type,
+                    vec![session_status_notf(session_id, SessionState::SessionStateInit)],
+                    Ok(()),
+                );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    tlvs,
+                    vec![session_status_notf(session_id, SessionState::SessionStateIdle)],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
+                uci_manager.expect_range_start(
+                    session_id,
+                    vec![session_status_notf(session_id, SessionState::SessionStateActive)],
+                    Ok(()),
+                );
+            })
+            .await;
+
+        // The session doesn't exist.
+        let result = session_manager.can_reconfigure(session_id, params.clone()).await;
+        assert_eq!(result, Err(Error::BadParameters));
+
+        let result = session_manager.init_session(session_id, session_type, params.clone()).await;
+        assert_eq!(result, Ok(()));
+
+        // Any parameter can be reconfigured during idle state.
+        let result = session_manager.can_reconfigure(session_id, key_rotation_params.clone()).await;
+        assert_eq!(result, Ok(true));
+
+        let result = session_manager.start_ranging(session_id).await;
+        assert_eq!(result, Ok(params));
+
+        // Only some parameters can be reconfigured during active state.
+        let result = session_manager.can_reconfigure(session_id, key_rotation_params).await;
+        assert_eq!(result, Ok(false));
+        let result = session_manager.can_reconfigure(session_id, block_stride_params).await;
+        assert_eq!(result, Ok(true));
+
+        // No command is sent to the UWBS.
+        assert!(mock_uci_manager.wait_expected_calls_done().await);
+    }
+
     #[tokio::test]
     async fn test_session_params() {
         let session_id = 0x123;
@@ -1075,6 +2036,13 @@ mod tests {
 
         let params = generate_params();
         let tlvs = params.generate_tlvs();
+        let updated_params = FiraAppConfigParamsBuilder::from_params(&params)
+            .unwrap()
+            .key_rotation(KeyRotation::Enable)
+            .build()
+            .unwrap();
+        let updated_tlvs =
+            updated_params.generate_updated_tlvs(&params, SessionState::SessionStateIdle).unwrap();
 
         let (mut session_manager, mut mock_uci_manager, _) =
             setup_session_manager(move |uci_manager| {
@@ -1093,6 +2061,15 @@ mod tests {
                         config_status: vec![],
                     }),
                 );
+                uci_manager.expect_session_set_app_config(
+                    session_id,
+                    updated_tlvs,
+                    vec![],
+                    Ok(SetAppConfigResponse {
+                        status: StatusCode::UciStatusOk,
+                        config_status: vec![],
+                    }),
+                );
             })
             .await;
 
@@ -1107,6 +2084,12 @@ mod tests {
         let result = session_manager.session_params(session_id).await;
         assert_eq!(result, Ok(params));
 
+        // The params should be updated after the reconfiguration.
+        let result = session_manager.reconfigure(session_id, updated_params.clone()).await;
+        assert_eq!(result, Ok(()));
+        let result = session_manager.session_params(session_id).await;
+        assert_eq!(result, Ok(updated_params));
+
         assert!(mock_uci_manager.wait_expected_calls_done().await);
     }
 }
@@ -23,12 +23,13 @@ use tokio::time::timeout;
 use crate::error::{Error, Result};
 use crate::params::app_config_params::AppConfigParams;
 use crate::params::ccc_started_app_config_params::CccStartedAppConfigParams;
+use crate::params::fira_app_config_params::UwbAddress;
 use crate::params::uci_packets::{
-    Controlee, ControleeStatus, Controlees, MulticastUpdateStatusCode, SessionId, SessionState,
-    SessionType, UpdateMulticastListAction,
+    Controlee, ControleeStatus, Controlees, MulticastUpdateStatusCode, ReasonCode, SessionId,
+    SessionState, SessionTermination, SessionType, UpdateMulticastListAction,
 };
 use crate::uci::error::status_code_to_result;
-use crate::uci::uci_manager::UciManager;
+use crate::uci::uci_manager::{UciManager, MAX_CONTROLEES_PER_MULTICAST_LIST_CMD};
 
 const NOTIFICATION_TIMEOUT_MS: u64 = 1000;
 
@@ -36,13 +37,22 @@ const NOTIFICATION_TIMEOUT_MS: u64 = 1000;
 pub(super) enum Response {
     Null,
     AppConfigParams(AppConfigParams),
+    Reconfigurable(bool),
+    ControleeStatusList(Vec<(UwbAddress, MulticastUpdateStatusCode)>),
+    Termination(Option<SessionTermination>),
+    Reason(Option<ReasonCode>),
+    SessionList {
+        sessions: Vec<(SessionId, SessionType, SessionState)>,
+        auto_stop_sessions: Vec<SessionId>,
+    },
 }
 pub(super) type ResponseSender = oneshot::Sender<Result<Response>>;
 
 pub(super) struct UwbSession {
+    session_type: SessionType,
     cmd_sender: mpsc::UnboundedSender<(Command, ResponseSender)>,
     state_sender: watch::Sender<SessionState>,
-    controlee_status_notf_sender: Option<oneshot::Sender<Vec<ControleeStatus>>>,
+    controlee_status_notf_sender: Option<mpsc::UnboundedSender<Vec<ControleeStatus>>>,
 }
 
 impl UwbSession {
@@ -65,7 +75,15 @@ impl UwbSession {
         );
         tokio::spawn(async move { actor.run().await });
 
-        Self { cmd_sender, state_sender, controlee_status_notf_sender: None }
+        Self { session_type, cmd_sender, state_sender, controlee_status_notf_sender: None }
+    }
+
+    pub fn session_type(&self) -> SessionType {
+        self.session_type
+    }
+
+    pub fn state(&self) -> SessionState {
+        *self.state_sender.borrow()
     }
 
     pub fn initialize(&mut self, params: AppConfigParams, result_sender: ResponseSender) {
@@ -88,13 +106,17 @@ impl UwbSession {
         let _ = self.cmd_sender.send((Command::Reconfigure { params }, result_sender));
     }
 
+    pub fn can_reconfigure(&mut self, params: AppConfigParams, result_sender: ResponseSender) {
+        let _ = self.cmd_sender.send((Command::CanReconfigure { params }, result_sender));
+    }
+
     pub fn update_controller_multicast_list(
         &mut self,
         action: UpdateMulticastListAction,
         controlees: Vec<Controlee>,
         result_sender: ResponseSender,
     ) {
-        let (notf_sender, notf_receiver) = oneshot::channel();
+        let (notf_sender, notf_receiver) = mpsc::unbounded_channel();
         self.controlee_status_notf_sender = Some(notf_sender);
         let _ = self.cmd_sender.send((
             Command::UpdateControllerMulticastList { action, controlees, notf_receiver },
@@ -111,7 +133,9 @@ impl UwbSession {
     }
 
     pub fn on_controller_multicast_list_udpated(&mut self, status_list: Vec<ControleeStatus>) {
-        if let Some(sender) = self.controlee_status_notf_sender.take() {
+        // The update of a long list is split into multiple commands, and each of them has its own
+        // notification, so keep the sender until the next update.
+        if let Some(sender) = self.controlee_status_notf_sender.as_ref() {
             let _ = sender.send(status_list);
         }
     }
@@ -153,6 +177,9 @@ impl<T: UciManager> UwbSessionActor<T> {
                                 Command::StartRanging => self.start_ranging().await,
                                 Command::StopRanging => self.stop_ranging().await,
                                 Command::Reconfigure { params } => self.reconfigure(params).await,
+                                Command::CanReconfigure { params } => {
+                                    self.can_reconfigure(params).await
+                                }
                                 Command::UpdateControllerMulticastList {
                                     action,
                                     controlees,
@@ -195,7 +222,9 @@ impl<T: UciManager> UwbSessionActor<T> {
     }
 
     async fn start_ranging(&mut self) -> Result<Response> {
-        let state = *self.state_receiver.borrow();
+        // Mark the state as seen, e.g. the idle state which the UWBS transits to by itself, so
+        // wait_state() only returns on the state notified after the command.
+        let state = *self.state_receiver.borrow_and_update();
         match state {
             SessionState::SessionStateActive => {
                 warn!("Session {} is already running", self.session_id);
@@ -239,7 +268,7 @@ impl<T: UciManager> UwbSessionActor<T> {
     }
 
     async fn stop_ranging(&mut self) -> Result<Response> {
-        let state = *self.state_receiver.borrow();
+        let state = *self.state_receiver.borrow_and_update();
         match state {
             SessionState::SessionStateIdle => {
                 warn!("Session {} is already stopped", self.session_id);
@@ -290,11 +319,20 @@ impl<T: UciManager> UwbSessionActor<T> {
         Ok(Response::Null)
     }
 
+    async fn can_reconfigure(&mut self, params: AppConfigParams) -> Result<Response> {
+        let state = *self.state_receiver.borrow();
+        let reconfigurable = match self.params.as_ref() {
+            Some(prev_params) => params.generate_updated_tlvs(prev_params, state).is_some(),
+            None => true,
+        };
+        Ok(Response::Reconfigurable(reconfigurable))
+    }
+
     async fn update_controller_multicast_list(
         &mut self,
         action: UpdateMulticastListAction,
         controlees: Vec<Controlee>,
-        notf_receiver: oneshot::Receiver<Vec<ControleeStatus>>,
+        mut notf_receiver: mpsc::UnboundedReceiver<Vec<ControleeStatus>>,
     ) -> Result<Response> {
         if self.session_type == SessionType::Ccc {
             error!("Cannot update multicast list for CCC session");
@@ -307,6 +345,9 @@ impl<T: UciManager> UwbSessionActor<T> {
             return Err(Error::BadParameters);
         }
 
+        // The UciManager splits a long list into multiple commands, and each of them has its own
+        // notification.
+        let notification_count = controlees.len().div_ceil(MAX_CONTROLEES_PER_MULTICAST_LIST_CMD);
         self.uci_manager
             .session_update_controller_multicast_list(
                 self.session_id,
@@ -315,29 +356,36 @@ impl<T: UciManager> UwbSessionActor<T> {
             )
             .await?;
 
-        // Wait for the notification of the update status.
-        let results = timeout(Duration::from_millis(NOTIFICATION_TIMEOUT_MS), notf_receiver)
-            .await
-            .map_err(|_| {
-                error!("Timeout waiting for the multicast list notification");
-                Error::Timeout
-            })?
-            .map_err(|_| {
-                error!("oneshot sender is dropped.");
-                Error::Unknown
-            })?;
+        // Wait for the notification of each command. The number of the statuses is not counted,
+        // because the UWBS may report fewer statuses than the controlees, e.g. only the failed
+        // ones.
+        let mut results = vec![];
+        for _ in 0..notification_count {
+            let mut status_list =
+                timeout(Duration::from_millis(NOTIFICATION_TIMEOUT_MS), notf_receiver.recv())
+                    .await
+                    .map_err(|_| {
+                        error!("Timeout waiting for the multicast list notification");
+                        Error::Timeout
+                    })?
+                    .ok_or_else(|| {
+                        error!("mpsc sender is dropped.");
+                        Error::Unknown
+                    })?;
+            results.append(&mut status_list);
+        }
 
-        // Check the update status for adding new controlees.
-        if action == UpdateMulticastListAction::AddControlee {
-            for result in results.iter() {
-                if result.status != MulticastUpdateStatusCode::StatusOkMulticastListUpdate {
-                    error!("Failed to update multicast list: {:?}", result);
-                    return Err(Error::Unknown);
-                }
+        for result in results.iter() {
+            if result.status != MulticastUpdateStatusCode::StatusOkMulticastListUpdate {
+                warn!("Failed to update multicast list: {:?}", result);
             }
         }
-
-        Ok(Response::Null)
+        Ok(Response::ControleeStatusList(
+            results
+                .into_iter()
+                .map(|result| (UwbAddress::Short(result.mac_address), result.status))
+                .collect(),
+        ))
     }
 
     async fn wait_state(&mut self, expected_state: SessionState) -> Result<()> {
@@ -384,10 +432,13 @@ enum Command {
     Reconfigure {
         params: AppConfigParams,
     },
+    CanReconfigure {
+        params: AppConfigParams,
+    },
     UpdateControllerMulticastList {
         action: UpdateMulticastListAction,
         controlees: Vec<Controlee>,
-        notf_receiver: oneshot::Receiver<Vec<ControleeStatus>>,
+        notf_receiver: mpsc::UnboundedReceiver<Vec<ControleeStatus>>,
     },
     GetParams,
 }
@@ -31,6 +31,11 @@ pub mod uci_logger_factory;
 pub mod uci_logger_pcapng;
 pub mod uci_manager_sync;
 
+#[cfg(feature = "latency-stats")]
+pub mod latency_stats;
+#[cfg(feature = "test-mode")]
+pub mod rf_test;
+
 #[cfg(test)]
 pub(crate) mod mock_uci_hal;
 #[cfg(test)]
@@ -41,9 +46,10 @@ pub mod mock_uci_manager;
 // Re-export the public elements.
 pub use command::UciCommand;
 pub use notification::{
-    CoreNotification, DataRcvNotification, RangingMeasurements, SessionNotification,
-    SessionRangeData, UciNotification,
+    AoaMeasurement, CoreNotification, DataRcvNotification, DistanceEstimate, DistanceUnit,
+    LinkQuality, LinkQualityDecoder, MeasurementTimestamps, RangingMeasurements,
+    SessionNotification, SessionRangeData, UciNotification,
 };
 pub use uci_hal::{NopUciHal, UciHal, UciHalPacket};
 pub use uci_logger_factory::{NopUciLoggerFactory, UciLoggerFactory};
-pub use uci_manager::UciManagerImpl;
+pub use uci_manager::{CommandMiddleware, UciManagerImpl};
@@ -20,7 +20,7 @@ use log::error;
 use crate::error::{Error, Result};
 use crate::params::uci_packets::{
     AppConfigTlv, AppConfigTlvType, Controlees, CountryCode, DeviceConfigId, DeviceConfigTlv,
-    ResetConfig, SessionId, SessionToken, SessionType, UpdateMulticastListAction,
+    PhaseList, ResetConfig, SessionId, SessionToken, SessionType, UpdateMulticastListAction,
 };
 use uwb_uci_packets::{build_session_update_controller_multicast_list_cmd, GroupId, MessageType};
 
@@ -39,6 +39,7 @@ pub enum UciCommand {
     CoreGetConfig {
         cfg_id: Vec<DeviceConfigId>,
     },
+    CoreQueryTimeStamp,
     SessionInit {
         session_id: SessionId,
         session_type: SessionType,
@@ -70,6 +71,11 @@ pub enum UciCommand {
     SessionQueryMaxDataSize {
         session_token: SessionToken,
     },
+    SessionSetHybridConfig {
+        session_token: SessionToken,
+        update_time: [u8; 8],
+        phase_list: Vec<PhaseList>,
+    },
     SessionStart {
         session_token: SessionToken,
     },
@@ -128,6 +134,9 @@ impl TryFrom<UciCommand> for uwb_uci_packets::UciControlPacket {
             }
             .build()
             .into(),
+            UciCommand::CoreQueryTimeStamp => {
+                uwb_uci_packets::CoreQueryTimeStampCmdBuilder {}.build().into()
+            }
             UciCommand::SessionSetAppConfig { session_token, config_tlvs } => {
                 uwb_uci_packets::SessionSetAppConfigCmdBuilder {
                     session_token,
@@ -153,6 +162,15 @@ impl TryFrom<UciCommand> for uwb_uci_packets::UciControlPacket {
             }
             .build()
             .into(),
+            UciCommand::SessionSetHybridConfig { session_token, update_time, phase_list } => {
+                uwb_uci_packets::SessionSetHybridConfigCmdBuilder {
+                    session_token,
+                    update_time,
+                    phase_list,
+                }
+                .build()
+                .into()
+            }
             UciCommand::AndroidGetPowerStats => {
                 uwb_uci_packets::AndroidGetPowerStatsCmdBuilder {}.build().into()
             }
@@ -258,6 +276,10 @@ mod tests {
         packet = uwb_uci_packets::UciControlPacket::try_from(cmd.clone()).unwrap();
         assert_eq!(packet, uwb_uci_packets::GetConfigCmdBuilder { cfg_id: vec![0] }.build().into());
 
+        cmd = UciCommand::CoreQueryTimeStamp {};
+        packet = uwb_uci_packets::UciControlPacket::try_from(cmd.clone()).unwrap();
+        assert_eq!(packet, uwb_uci_packets::CoreQueryTimeStampCmdBuilder {}.build().into());
+
         cmd = UciCommand::SessionInit {
             session_id: 1,
             session_type: SessionType::FiraRangingSession,
@@ -349,6 +371,31 @@ mod tests {
             uwb_uci_packets::SessionQueryMaxDataSizeCmdBuilder { session_token: 1 }.build().into()
         );
 
+        cmd = UciCommand::SessionSetHybridConfig {
+            session_token: 1,
+            update_time: [0; 8],
+            phase_list: vec![PhaseList {
+                session_token: 2,
+                start_slot_index: 1,
+                end_slot_index: 5,
+            }],
+        };
+        packet = uwb_uci_packets::UciControlPacket::try_from(cmd.clone()).unwrap();
+        assert_eq!(
+            packet,
+            uwb_uci_packets::SessionSetHybridConfigCmdBuilder {
+                session_token: 1,
+                update_time: [0; 8],
+                phase_list: vec![PhaseList {
+                    session_token: 2,
+                    start_slot_index: 1,
+                    end_slot_index: 5
+                }],
+            }
+            .build()
+            .into()
+        );
+
         cmd = UciCommand::SessionStart { session_token: 1 };
         packet = uwb_uci_packets::UciControlPacket::try_from(cmd.clone()).unwrap();
         assert_eq!(
@@ -0,0 +1,91 @@
+// Copyright 2022, The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module records the latency of the UCI commands, from sending the command to receiving the
+//! response, for the performance tuning.
+
+use std::time::Duration;
+
+/// The upper bounds of the histogram buckets, in the unit of ms. The latency longer than the last
+/// bound is counted in the extra last bucket.
+pub const LATENCY_BUCKET_BOUNDS_MS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+/// The latency statistics of the commands with the same opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The number of the recorded commands.
+    pub count: u64,
+    /// The shortest latency.
+    pub min: Duration,
+    /// The longest latency.
+    pub max: Duration,
+    /// The sum of the latency of all the commands.
+    pub total: Duration,
+    /// The number of the commands in each bucket of LATENCY_BUCKET_BOUNDS_MS.
+    pub histogram: [u64; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            total: Duration::ZERO,
+            histogram: [0; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+        }
+    }
+}
+
+impl LatencyStats {
+    /// The average latency of the commands.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+
+    pub(crate) fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.min = self.min.min(latency);
+        self.max = self.max.max(latency);
+        self.total += latency;
+        let bucket = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| latency <= Duration::from_millis(*bound))
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.histogram[bucket] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_latency() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.mean(), Duration::ZERO);
+
+        stats.record(Duration::from_micros(500));
+        stats.record(Duration::from_millis(30));
+        stats.record(Duration::from_millis(700));
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, Duration::from_micros(500));
+        assert_eq!(stats.max, Duration::from_millis(700));
+        assert_eq!(stats.mean(), Duration::from_micros(243500));
+        assert_eq!(stats.histogram, [1, 0, 0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+}
@@ -29,9 +29,9 @@ use crate::error::{Error, Result};
 use crate::params::uci_packets::{
     app_config_tlvs_eq, device_config_tlvs_eq, AppConfigTlv, AppConfigTlvType, CapTlv, Controlees,
     CoreSetConfigResponse, CountryCode, DeviceConfigId, DeviceConfigTlv, FiraComponent,
-    GetDeviceInfoResponse, PowerStats, RawUciMessage, ResetConfig, SessionId, SessionState,
-    SessionToken, SessionType, SessionUpdateDtTagRangingRoundsResponse, SetAppConfigResponse,
-    UpdateMulticastListAction,
+    GetDeviceInfoResponse, HybridSessionConfig, PowerStats, RawUciMessage, ResetConfig, SessionId,
+    SessionState, SessionToken, SessionType, SessionUpdateDtTagRangingRoundsResponse,
+    SetAppConfigResponse, UpdateMulticastListAction,
 };
 use crate::uci::notification::{
     CoreNotification, DataRcvNotification, SessionNotification, UciNotification,
@@ -48,6 +48,7 @@ pub struct MockUciManager {
     session_notf_sender: mpsc::UnboundedSender<SessionNotification>,
     vendor_notf_sender: mpsc::UnboundedSender<RawUciMessage>,
     data_rcv_notf_sender: mpsc::UnboundedSender<DataRcvNotification>,
+    ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
 }
 
 #[allow(dead_code)]
@@ -61,6 +62,7 @@ impl MockUciManager {
             session_notf_sender: mpsc::unbounded_channel().0,
             vendor_notf_sender: mpsc::unbounded_channel().0,
             data_rcv_notf_sender: mpsc::unbounded_channel().0,
+            ordered_notf_sender: None,
         }
     }
 
@@ -293,6 +295,22 @@ impl MockUciManager {
             .push_back(ExpectedCall::SessionQueryMaxDataSize { expected_session_id, out });
     }
 
+    /// Prepare Mock to expect session_set_hybrid_config.
+    ///
+    /// MockUciManager expects call with parameters, returns out as response.
+    pub fn expect_session_set_hybrid_config(
+        &mut self,
+        expected_session_id: SessionId,
+        expected_config: HybridSessionConfig,
+        out: Result<()>,
+    ) {
+        self.expected_calls.lock().unwrap().push_back(ExpectedCall::SessionSetHybridConfig {
+            expected_session_id,
+            expected_config,
+            out,
+        });
+    }
+
     /// Prepare Mock to expect range_start.
     ///
     /// MockUciManager expects call with parameters, returns out as response, followed by notfs
@@ -405,8 +423,12 @@ impl MockUciManager {
     }
 
     /// Call Mock to send notifications.
-    fn send_notifications(&self, notfs: Vec<UciNotification>) {
+    pub(crate) fn send_notifications(&self, notfs: Vec<UciNotification>) {
         for notf in notfs.into_iter() {
+            if let Some(ordered_notf_sender) = self.ordered_notf_sender.as_ref() {
+                let _ = ordered_notf_sender.send(notf);
+                continue;
+            }
             match notf {
                 UciNotification::Core(notf) => {
                     let _ = self.core_notf_sender.send(notf);
@@ -457,6 +479,12 @@ impl UciManager for MockUciManager {
     ) {
         self.data_rcv_notf_sender = data_rcv_notf_sender;
     }
+    async fn set_ordered_notification_sender(
+        &mut self,
+        ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    ) {
+        self.ordered_notf_sender = ordered_notf_sender;
+    }
 
     async fn open_hal(&self) -> Result<()> {
         let mut expected_calls = self.expected_calls.lock().unwrap();
@@ -769,6 +797,29 @@ impl UciManager for MockUciManager {
         }
     }
 
+    async fn session_set_hybrid_config(
+        &self,
+        session_id: SessionId,
+        config: HybridSessionConfig,
+    ) -> Result<()> {
+        let mut expected_calls = self.expected_calls.lock().unwrap();
+        match expected_calls.pop_front() {
+            Some(ExpectedCall::SessionSetHybridConfig {
+                expected_session_id,
+                expected_config,
+                out,
+            }) if expected_session_id == session_id && expected_config == config => {
+                self.expect_call_consumed.notify_one();
+                out
+            }
+            Some(call) => {
+                expected_calls.push_front(call);
+                Err(Error::MockUndefined)
+            }
+            None => Err(Error::MockUndefined),
+        }
+    }
+
     async fn range_start(&self, session_id: SessionId) -> Result<()> {
         let mut expected_calls = self.expected_calls.lock().unwrap();
         match expected_calls.pop_front() {
@@ -1000,6 +1051,11 @@ enum ExpectedCall {
         expected_session_id: SessionId,
         out: Result<u16>,
     },
+    SessionSetHybridConfig {
+        expected_session_id: SessionId,
+        expected_config: HybridSessionConfig,
+        out: Result<()>,
+    },
     RangeStart {
         expected_session_id: SessionId,
         notfs: Vec<UciNotification>,
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::convert::{TryFrom, TryInto};
+use std::time::SystemTime;
 
 use log::{debug, error};
 use uwb_uci_packets::{parse_diagnostics_ntf, Packet, UCI_PACKET_HEADER_LEN};
@@ -20,11 +21,13 @@ use uwb_uci_packets::{parse_diagnostics_ntf, Packet, UCI_PACKET_HEADER_LEN};
 use crate::error::{Error, Result};
 use crate::params::fira_app_config_params::UwbAddress;
 use crate::params::uci_packets::{
-    ControleeStatus, CreditAvailability, DataRcvStatusCode, DataTransferNtfStatusCode, DeviceState,
+    CoexistenceSource, Controlee, ControleeStatus, CreditAvailability, DataRcvStatusCode,
+    DataTransferNtfStatusCode, DeviceState, DlTdoaRangingMeasurement,
     ExtendedAddressDlTdoaRangingMeasurement, ExtendedAddressOwrAoaRangingMeasurement,
-    ExtendedAddressTwoWayRangingMeasurement, FiraComponent, RangingMeasurementType, RawUciMessage,
-    SessionState, SessionToken, ShortAddressDlTdoaRangingMeasurement,
-    ShortAddressOwrAoaRangingMeasurement, ShortAddressTwoWayRangingMeasurement, StatusCode,
+    ExtendedAddressTwoWayRangingMeasurement, FiraComponent, ParsedFrameReport,
+    RangingMeasurementType, RawUciMessage, SessionState, SessionToken,
+    ShortAddressDlTdoaRangingMeasurement, ShortAddressOwrAoaRangingMeasurement,
+    ShortAddressTwoWayRangingMeasurement, StatusCode, VendorNotificationIds,
 };
 
 /// enum of all UCI notifications with structured fields.
@@ -45,6 +48,26 @@ pub enum CoreNotification {
     DeviceStatus(DeviceState),
     /// GenericErrorPacket equivalent.
     GenericError(StatusCode),
+    /// Reported when the UWB radio is preempted by or released from another radio sharing the
+    /// antenna or the spectrum, decoded from the vendor notification set by
+    /// VendorNotificationIds::coexistence.
+    Coexistence {
+        /// The radio which preempts the UWB radio.
+        source: CoexistenceSource,
+        /// Whether the UWB radio is preempted. False when the UWB radio is released.
+        preempted: bool,
+        /// The expected duration of the preemption in milliseconds, 0 if unknown.
+        duration_ms: u16,
+    },
+    /// The notification whose GID or OID is not recognized, e.g. a new event of the firmware.
+    Unknown {
+        /// The group id of the notification.
+        gid: u32,
+        /// The opcode of the notification.
+        oid: u32,
+        /// The payload of the notification.
+        payload: Vec<u8>,
+    },
 }
 
 /// UCI SessionNotification.
@@ -68,7 +91,8 @@ pub enum SessionNotification {
         /// list of controlees.
         status_list: Vec<ControleeStatus>,
     },
-    /// (Short/Extended)Mac()SessionInfoNtf equivalent
+    /// (Short/Extended)Mac()SessionInfoNtf equivalent. It is still forwarded when no measurement
+    /// is produced in the ranging round, i.e. the ranging measurements are empty.
     SessionInfo(SessionRangeData),
     /// DataCreditNtf equivalent.
     DataCredit {
@@ -86,6 +110,24 @@ pub enum SessionNotification {
         /// Data Transfer Status Code
         status: DataTransferNtfStatusCode,
     },
+    /// The STS key of the session is rotated, decoded from the vendor notification set by
+    /// VendorNotificationIds::key_rotation.
+    KeyRotation {
+        /// SessionToken : u32
+        session_token: SessionToken,
+        /// The number of the STS key rotations since the session is started.
+        rotation_count: u32,
+    },
+    /// AndroidRangeDiagnosticsNtf equivalent. Only sent by the UWBS when the ENABLE_DIAGNOSTICS
+    /// app config of the session is enabled.
+    Diagnostics {
+        /// SessionToken : u32
+        session_token: SessionToken,
+        /// The sequence counter of the diagnostics notifications.
+        sequence_number: u32,
+        /// The diagnostic report of each frame in the ranging round.
+        frame_reports: Vec<ParsedFrameReport>,
+    },
 }
 
 /// The session range data.
@@ -112,6 +154,135 @@ pub struct SessionRangeData {
     /// The raw data of the notification message.
     /// (b/243555651): It's not at FiRa specification, only used by vendor's extension.
     pub raw_ranging_data: Vec<u8>,
+
+    /// The vendor specific data following the ranging measurements.
+    pub vendor_data: Vec<u8>,
+
+    /// The link quality of each ranging measurement, in the same order as the measurements.
+    /// Only filled by the LinkQualityDecoder registered to the UciManager, otherwise empty.
+    pub link_qualities: Vec<Option<LinkQuality>>,
+
+    /// The host time when the notification is received by the UciManager, which is used to
+    /// correlate the measurements with the data of the other sensors. None if the data is not
+    /// received from the UWBS.
+    pub received_at: Option<SystemTime>,
+}
+
+/// The link quality indicators of a ranging measurement, which are reported by some UWBS in
+/// the vendor specific data of the SESSION_INFO_NTF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkQuality {
+    /// The noise floor of the channel in dBm.
+    pub noise_floor_dbm: i8,
+    /// The quality of the channel impulse response, from 0 (worst) to 100 (best).
+    pub cir_quality: u8,
+    /// The RSSI of each RX antenna in dBm, in the order of the antennas, reported by the UWBS
+    /// with multiple RX antennas. Empty if it is not reported.
+    pub rssi_per_receiver: Vec<i16>,
+}
+
+/// The decoder of the vendor specific link quality indicators.
+pub trait LinkQualityDecoder: 'static + Send + Sync {
+    /// Decode the link quality of each measurement from the vendor data of the SESSION_INFO_NTF
+    /// with |measurement_count| measurements. The result is in the same order as the
+    /// measurements, and is None for the measurements without the link quality.
+    fn decode(&self, vendor_data: &[u8], measurement_count: usize) -> Vec<Option<LinkQuality>>;
+}
+
+/// The distance of a two-way ranging measurement with its reliability.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceEstimate {
+    /// The distance in centimeters.
+    pub cm: u16,
+    /// The confidence of the distance, from 0.0 (unreliable) to 1.0 (reliable).
+    pub confidence: f32,
+}
+
+impl DistanceEstimate {
+    /// Build the estimate from the fields of a two-way ranging measurement. The confidence is
+    /// lowered when the measurement is in non-line-of-sight, or when it can't be determined.
+    /// Return None if the status of the measurement is not OK, because the distance is invalid.
+    pub fn new(status: StatusCode, distance: u16, nlos: u8) -> Option<Self> {
+        if status != StatusCode::UciStatusOk {
+            return None;
+        }
+        let confidence = match nlos {
+            0 => 1.0, // Line of sight.
+            1 => 0.5, // Non-line of sight.
+            _ => 0.75,
+        };
+        Some(Self { cm: distance, confidence })
+    }
+
+    /// The distance in |unit|.
+    pub fn distance_in(&self, unit: DistanceUnit) -> f32 {
+        match unit {
+            DistanceUnit::Millimeters => self.cm as f32 * 10.0,
+            DistanceUnit::Centimeters => self.cm as f32,
+            DistanceUnit::Meters => self.cm as f32 / 100.0,
+        }
+    }
+}
+
+/// The unit to present the distances in. The UWBS reports the distances in centimeters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceUnit {
+    /// Millimeters.
+    Millimeters,
+    /// Centimeters.
+    Centimeters,
+    /// Meters.
+    Meters,
+}
+
+/// The AoA results of a ranging measurement. The angles are in the signed Q9.7 format in
+/// degrees, and the FoM of an angle is 0 if the angle is not available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AoaMeasurement {
+    /// The azimuth angle.
+    pub azimuth: u16,
+    /// The figure of merit of the azimuth angle.
+    pub azimuth_fom: u8,
+    /// The elevation angle.
+    pub elevation: u16,
+    /// The figure of merit of the elevation angle.
+    pub elevation_fom: u8,
+}
+
+impl AoaMeasurement {
+    /// The azimuth angle in degrees, or None if it is not available.
+    pub fn azimuth_degrees(&self) -> Option<f32> {
+        (self.azimuth_fom != 0).then(|| q9_7_to_degrees(self.azimuth))
+    }
+
+    /// The elevation angle in degrees, or None if it is not available.
+    pub fn elevation_degrees(&self) -> Option<f32> {
+        (self.elevation_fom != 0).then(|| q9_7_to_degrees(self.elevation))
+    }
+
+    /// The unit vector pointing to the peer, in the frame where x is straight ahead, y is to the
+    /// positive azimuth and z is to the positive elevation. The elevation is taken as 0 if only
+    /// the azimuth is available. Return None if the azimuth is not available.
+    pub fn direction_vector(&self) -> Option<[f32; 3]> {
+        let azimuth = self.azimuth_degrees()?.to_radians();
+        let elevation = self.elevation_degrees().unwrap_or(0.0).to_radians();
+        Some([elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(), elevation.sin()])
+    }
+}
+
+/// The raw TX and RX timestamps the UWBS used for a measurement, in the unit of the RSTU clock
+/// ticks of the UWBS. The 40-bit timestamps are zero-extended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeasurementTimestamps {
+    /// The TX timestamp of the message.
+    pub tx_timestamp: u64,
+    /// The RX timestamp of the message.
+    pub rx_timestamp: u64,
+}
+
+// Convert the angle in the signed Q9.7 format to degrees.
+fn q9_7_to_degrees(angle: u16) -> f32 {
+    angle as i16 as f32 / 128.0
 }
 
 /// The ranging measurements.
@@ -136,6 +307,360 @@ pub enum RangingMeasurements {
     ExtendedAddressOwrAoa(ExtendedAddressOwrAoaRangingMeasurement),
 }
 
+impl SessionRangeData {
+    /// The number of the ranging measurements.
+    pub fn measurement_count(&self) -> usize {
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => measurements.len(),
+            RangingMeasurements::ExtendedAddressTwoWay(measurements) => measurements.len(),
+            RangingMeasurements::ShortAddressDltdoa(measurements) => measurements.len(),
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => measurements.len(),
+            RangingMeasurements::ShortAddressOwrAoa(_)
+            | RangingMeasurements::ExtendedAddressOwrAoa(_) => 1,
+        }
+    }
+
+    /// Fill the link qualities by decoding the vendor data with |decoder|. The result of the
+    /// decoder is truncated or padded with None to the number of the measurements.
+    pub fn decode_link_qualities(&mut self, decoder: &dyn LinkQualityDecoder) {
+        let measurement_count = self.measurement_count();
+        let mut link_qualities = decoder.decode(&self.vendor_data, measurement_count);
+        link_qualities.resize(measurement_count, None);
+        self.link_qualities = link_qualities;
+    }
+
+    /// The distance estimate of each two-way measurement, in the same order as the measurements.
+    /// The estimate is None for the failed measurements, and the result is empty for the other
+    /// measurement types.
+    pub fn distance_estimates(&self) -> Vec<Option<DistanceEstimate>> {
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| DistanceEstimate::new(m.status, m.distance, m.nlos))
+                .collect(),
+            RangingMeasurements::ExtendedAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| DistanceEstimate::new(m.status, m.distance, m.nlos))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The distance of each two-way measurement in |unit|, like distance_estimates().
+    pub fn distances_in(&self, unit: DistanceUnit) -> Vec<Option<f32>> {
+        self.distance_estimates()
+            .iter()
+            .map(|estimate| estimate.as_ref().map(|e| e.distance_in(unit)))
+            .collect()
+    }
+
+    /// The index of the ranging round each measurement came from, in the same order as the
+    /// measurements. Only the DL-TDoA measurements carry the round index, so it is None for the
+    /// other measurement types.
+    pub fn ranging_round_indexes(&self) -> Vec<Option<u8>> {
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressDltdoa(measurements) => {
+                measurements.iter().map(|m| Some(m.measurement.round_index)).collect()
+            }
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => {
+                measurements.iter().map(|m| Some(m.measurement.round_index)).collect()
+            }
+            _ => vec![None; self.measurement_count()],
+        }
+    }
+
+    /// The TX and RX timestamps of each measurement, in the same order as the measurements. Only
+    /// the DL-TDoA measurements carry the timestamps, so it is None for the other measurement
+    /// types.
+    pub fn measurement_timestamps(&self) -> Vec<Option<MeasurementTimestamps>> {
+        let timestamps = |m: &DlTdoaRangingMeasurement| {
+            Some(MeasurementTimestamps {
+                tx_timestamp: m.tx_timestamp,
+                rx_timestamp: m.rx_timestamp,
+            })
+        };
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressDltdoa(measurements) => {
+                measurements.iter().map(|m| timestamps(&m.measurement)).collect()
+            }
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => {
+                measurements.iter().map(|m| timestamps(&m.measurement)).collect()
+            }
+            _ => vec![None; self.measurement_count()],
+        }
+    }
+
+    /// The AoA results of each measurement, in the same order as the measurements.
+    pub fn aoa_measurements(&self) -> Vec<AoaMeasurement> {
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    aoa_measurement(
+                        m.aoa_azimuth,
+                        m.aoa_azimuth_fom,
+                        m.aoa_elevation,
+                        m.aoa_elevation_fom,
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ExtendedAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    aoa_measurement(
+                        m.aoa_azimuth,
+                        m.aoa_azimuth_fom,
+                        m.aoa_elevation,
+                        m.aoa_elevation_fom,
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ShortAddressDltdoa(measurements) => measurements
+                .iter()
+                .map(|m| &m.measurement)
+                .map(|m| {
+                    aoa_measurement(
+                        m.aoa_azimuth,
+                        m.aoa_azimuth_fom,
+                        m.aoa_elevation,
+                        m.aoa_elevation_fom,
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => measurements
+                .iter()
+                .map(|m| &m.measurement)
+                .map(|m| {
+                    aoa_measurement(
+                        m.aoa_azimuth,
+                        m.aoa_azimuth_fom,
+                        m.aoa_elevation,
+                        m.aoa_elevation_fom,
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ShortAddressOwrAoa(m) => {
+                vec![aoa_measurement(
+                    m.aoa_azimuth,
+                    m.aoa_azimuth_fom,
+                    m.aoa_elevation,
+                    m.aoa_elevation_fom,
+                )]
+            }
+            RangingMeasurements::ExtendedAddressOwrAoa(m) => {
+                vec![aoa_measurement(
+                    m.aoa_azimuth,
+                    m.aoa_azimuth_fom,
+                    m.aoa_elevation,
+                    m.aoa_elevation_fom,
+                )]
+            }
+        }
+    }
+
+    /// Zero the AoA results whose FoM is below |min_fom|, together with their FoM, so the noisy
+    /// angles are flagged as unreliable. Each angle of a measurement is checked separately.
+    pub fn discard_low_fom_aoa(&mut self, min_fom: u8) {
+        match &mut self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => {
+                for m in measurements.iter_mut() {
+                    discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                    discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+                    discard_aoa(
+                        &mut m.aoa_destination_azimuth,
+                        &mut m.aoa_destination_azimuth_fom,
+                        min_fom,
+                    );
+                    discard_aoa(
+                        &mut m.aoa_destination_elevation,
+                        &mut m.aoa_destination_elevation_fom,
+                        min_fom,
+                    );
+                }
+            }
+            RangingMeasurements::ExtendedAddressTwoWay(measurements) => {
+                for m in measurements.iter_mut() {
+                    discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                    discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+                    discard_aoa(
+                        &mut m.aoa_destination_azimuth,
+                        &mut m.aoa_destination_azimuth_fom,
+                        min_fom,
+                    );
+                    discard_aoa(
+                        &mut m.aoa_destination_elevation,
+                        &mut m.aoa_destination_elevation_fom,
+                        min_fom,
+                    );
+                }
+            }
+            RangingMeasurements::ShortAddressDltdoa(measurements) => {
+                for m in measurements.iter_mut().map(|m| &mut m.measurement) {
+                    discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                    discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+                }
+            }
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => {
+                for m in measurements.iter_mut().map(|m| &mut m.measurement) {
+                    discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                    discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+                }
+            }
+            RangingMeasurements::ShortAddressOwrAoa(m) => {
+                discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+            }
+            RangingMeasurements::ExtendedAddressOwrAoa(m) => {
+                discard_aoa(&mut m.aoa_azimuth, &mut m.aoa_azimuth_fom, min_fom);
+                discard_aoa(&mut m.aoa_elevation, &mut m.aoa_elevation_fom, min_fom);
+            }
+        }
+    }
+
+    /// The header of the CSV rows generated by to_csv_rows().
+    pub fn csv_header() -> &'static str {
+        "sequence_number,mac_address,status,distance,aoa_azimuth,aoa_elevation,rssi"
+    }
+
+    /// Generate one CSV row for each measurement. The MAC address is in hex with the width of
+    /// the address, and the other fields are the raw values of the notification. The distance
+    /// or the RSSI is left empty if the measurement type doesn't report it.
+    pub fn to_csv_rows(&self) -> Vec<String> {
+        let seq = self.sequence_number;
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    csv_row(
+                        seq,
+                        format!("{:04x}", m.mac_address),
+                        u8::from(m.status),
+                        Some(m.distance),
+                        (m.aoa_azimuth, m.aoa_elevation),
+                        Some(m.rssi),
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ExtendedAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    csv_row(
+                        seq,
+                        format!("{:016x}", m.mac_address),
+                        u8::from(m.status),
+                        Some(m.distance),
+                        (m.aoa_azimuth, m.aoa_elevation),
+                        Some(m.rssi),
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ShortAddressDltdoa(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    csv_row(
+                        seq,
+                        format!("{:04x}", m.mac_address),
+                        m.measurement.status,
+                        None,
+                        (m.measurement.aoa_azimuth, m.measurement.aoa_elevation),
+                        Some(m.measurement.rssi),
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ExtendedAddressDltdoa(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    csv_row(
+                        seq,
+                        format!("{:016x}", m.mac_address),
+                        m.measurement.status,
+                        None,
+                        (m.measurement.aoa_azimuth, m.measurement.aoa_elevation),
+                        Some(m.measurement.rssi),
+                    )
+                })
+                .collect(),
+            RangingMeasurements::ShortAddressOwrAoa(m) => vec![csv_row(
+                seq,
+                format!("{:04x}", m.mac_address),
+                u8::from(m.status),
+                None,
+                (m.aoa_azimuth, m.aoa_elevation),
+                None,
+            )],
+            RangingMeasurements::ExtendedAddressOwrAoa(m) => vec![csv_row(
+                seq,
+                format!("{:016x}", m.mac_address),
+                u8::from(m.status),
+                None,
+                (m.aoa_azimuth, m.aoa_elevation),
+                None,
+            )],
+        }
+    }
+
+    /// Pair each two-way measurement with the controlee whose short address matches the MAC
+    /// address of the measurement. The controlee is None if no controlee in |controlees|
+    /// matches. Only the two-way measurements with short address are returned, because the
+    /// controlees of the multicast session are identified by the short address.
+    pub fn measurements_by_controlee(
+        &self,
+        controlees: &[Controlee],
+    ) -> Vec<(Option<Controlee>, ShortAddressTwoWayRangingMeasurement)> {
+        match &self.ranging_measurements {
+            RangingMeasurements::ShortAddressTwoWay(measurements) => measurements
+                .iter()
+                .map(|m| {
+                    let controlee = controlees
+                        .iter()
+                        .find(|c| u16::from_le_bytes(c.short_address) == m.mac_address)
+                        .cloned();
+                    (controlee, m.clone())
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn aoa_measurement(
+    azimuth: u16,
+    azimuth_fom: u8,
+    elevation: u16,
+    elevation_fom: u8,
+) -> AoaMeasurement {
+    AoaMeasurement { azimuth, azimuth_fom, elevation, elevation_fom }
+}
+
+// Zero the AoA result and its FoM if the FoM is below |min_fom|.
+fn discard_aoa(aoa: &mut u16, fom: &mut u8, min_fom: u8) {
+    if *fom < min_fom {
+        *aoa = 0;
+        *fom = 0;
+    }
+}
+
+// Generate one CSV row in the order of SessionRangeData::csv_header().
+fn csv_row(
+    sequence_number: u32,
+    mac_address: String,
+    status: u8,
+    distance: Option<u16>,
+    (aoa_azimuth, aoa_elevation): (u16, u16),
+    rssi: Option<u8>,
+) -> String {
+    format!(
+        "{},{},{},{},{},{},{}",
+        sequence_number,
+        mac_address,
+        status,
+        distance.map(|d| d.to_string()).unwrap_or_default(),
+        aoa_azimuth,
+        aoa_elevation,
+        rssi.map(|r| r.to_string()).unwrap_or_default(),
+    )
+}
+
 /// The DATA_RCV packet
 #[derive(Debug, Clone)]
 pub struct DataRcvNotification {
@@ -197,12 +722,20 @@ impl TryFrom<uwb_uci_packets::UciNotification> for UciNotification {
         use uwb_uci_packets::UciNotificationChild;
         match evt.specialize() {
             UciNotificationChild::CoreNotification(evt) => Ok(Self::Core(evt.try_into()?)),
-            UciNotificationChild::SessionConfigNotification(evt) => {
-                Ok(Self::Session(evt.try_into()?))
-            }
-            UciNotificationChild::SessionControlNotification(evt) => {
-                Ok(Self::Session(evt.try_into()?))
-            }
+            UciNotificationChild::SessionConfigNotification(evt) => match evt.specialize() {
+                uwb_uci_packets::SessionConfigNotificationChild::Payload(_)
+                | uwb_uci_packets::SessionConfigNotificationChild::None => {
+                    Ok(Self::Core(unknown_notification(evt.into())))
+                }
+                _ => Ok(Self::Session(evt.try_into()?)),
+            },
+            UciNotificationChild::SessionControlNotification(evt) => match evt.specialize() {
+                uwb_uci_packets::SessionControlNotificationChild::Payload(_)
+                | uwb_uci_packets::SessionControlNotificationChild::None => {
+                    Ok(Self::Core(unknown_notification(evt.into())))
+                }
+                _ => Ok(Self::Session(evt.try_into()?)),
+            },
             UciNotificationChild::AndroidNotification(evt) => evt.try_into(),
             UciNotificationChild::UciVendor_9_Notification(evt) => vendor_notification(evt.into()),
             UciNotificationChild::UciVendor_A_Notification(evt) => vendor_notification(evt.into()),
@@ -210,10 +743,7 @@ impl TryFrom<uwb_uci_packets::UciNotification> for UciNotification {
             UciNotificationChild::UciVendor_E_Notification(evt) => vendor_notification(evt.into()),
             UciNotificationChild::UciVendor_F_Notification(evt) => vendor_notification(evt.into()),
             UciNotificationChild::TestNotification(evt) => vendor_notification(evt.into()),
-            _ => {
-                error!("Unknown UciNotification: {:?}", evt);
-                Err(Error::Unknown)
-            }
+            _ => Ok(Self::Core(unknown_notification(evt))),
         }
     }
 }
@@ -227,10 +757,7 @@ impl TryFrom<uwb_uci_packets::CoreNotification> for CoreNotification {
                 Ok(Self::DeviceStatus(evt.get_device_state()))
             }
             CoreNotificationChild::GenericError(evt) => Ok(Self::GenericError(evt.get_status())),
-            _ => {
-                error!("Unknown CoreNotification: {:?}", evt);
-                Err(Error::Unknown)
-            }
+            _ => Ok(unknown_notification(evt.into())),
         }
     }
 }
@@ -294,18 +821,22 @@ impl TryFrom<uwb_uci_packets::SessionInfoNtf> for SessionNotification {
     fn try_from(evt: uwb_uci_packets::SessionInfoNtf) -> std::result::Result<Self, Self::Error> {
         let raw_ranging_data = evt.clone().to_bytes()[UCI_PACKET_HEADER_LEN..].to_vec();
         use uwb_uci_packets::SessionInfoNtfChild;
+        let mut vendor_data = vec![];
         let ranging_measurements = match evt.specialize() {
             SessionInfoNtfChild::ShortMacTwoWaySessionInfoNtf(evt) => {
+                vendor_data = evt.get_vendor_data().clone();
                 RangingMeasurements::ShortAddressTwoWay(
                     evt.get_two_way_ranging_measurements().clone(),
                 )
             }
             SessionInfoNtfChild::ExtendedMacTwoWaySessionInfoNtf(evt) => {
+                vendor_data = evt.get_vendor_data().clone();
                 RangingMeasurements::ExtendedAddressTwoWay(
                     evt.get_two_way_ranging_measurements().clone(),
                 )
             }
             SessionInfoNtfChild::ShortMacOwrAoaSessionInfoNtf(evt) => {
+                vendor_data = evt.get_vendor_data().clone();
                 if evt.get_owr_aoa_ranging_measurements().clone().len() == 1 {
                     RangingMeasurements::ShortAddressOwrAoa(
                         match evt.get_owr_aoa_ranging_measurements().clone().pop() {
@@ -325,6 +856,7 @@ impl TryFrom<uwb_uci_packets::SessionInfoNtf> for SessionNotification {
                 }
             }
             SessionInfoNtfChild::ExtendedMacOwrAoaSessionInfoNtf(evt) => {
+                vendor_data = evt.get_vendor_data().clone();
                 if evt.get_owr_aoa_ranging_measurements().clone().len() == 1 {
                     RangingMeasurements::ExtendedAddressOwrAoa(
                         match evt.get_owr_aoa_ranging_measurements().clone().pop() {
@@ -388,6 +920,9 @@ impl TryFrom<uwb_uci_packets::SessionInfoNtf> for SessionNotification {
             ranging_measurements,
             rcr_indicator: evt.get_rcr_indicator(),
             raw_ranging_data,
+            vendor_data,
+            link_qualities: vec![],
+            received_at: None,
         }))
     }
 }
@@ -399,16 +934,66 @@ impl TryFrom<uwb_uci_packets::AndroidNotification> for UciNotification {
     ) -> std::result::Result<Self, Self::Error> {
         use uwb_uci_packets::AndroidNotificationChild;
 
-        // (b/241336806): Currently we don't process the diagnostic packet, just log it only.
-        if let AndroidNotificationChild::AndroidRangeDiagnosticsNtf(ntf) = evt.specialize() {
-            debug!("Received diagnostic packet: {:?}", parse_diagnostics_ntf(ntf));
-        } else {
-            error!("Received unknown AndroidNotification: {:?}", evt);
+        match evt.specialize() {
+            AndroidNotificationChild::AndroidRangeDiagnosticsNtf(ntf) => {
+                let parsed = parse_diagnostics_ntf(ntf).map_err(|e| {
+                    error!("Failed to parse the diagnostics notification: {:?}", e);
+                    Error::BadParameters
+                })?;
+                Ok(Self::Session(SessionNotification::Diagnostics {
+                    session_token: parsed.session_token,
+                    sequence_number: parsed.sequence_number,
+                    frame_reports: parsed.frame_reports,
+                }))
+            }
+            _ => Ok(Self::Core(unknown_notification(evt.into()))),
         }
-        Err(Error::Unknown)
     }
 }
 
+// Keep the notification with unrecognized GID or OID as raw bytes, so it can still be handled by
+// the clients.
+fn unknown_notification(evt: uwb_uci_packets::UciNotification) -> CoreNotification {
+    debug!("Received unknown UciNotification: {:?}", evt);
+    CoreNotification::Unknown {
+        gid: evt.get_group_id().into(),
+        oid: evt.get_opcode().into(),
+        payload: evt.to_bytes()[UCI_PACKET_HEADER_LEN..].to_vec(),
+    }
+}
+
+/// Decode the vendor notification which reports an event the UCI spec doesn't define, by the ids
+/// supplied by the integrator. The notification is returned as is if it doesn't match any id, or
+/// its payload is invalid.
+pub(crate) fn decode_vendor_notification(
+    notf: RawUciMessage,
+    ids: &VendorNotificationIds,
+) -> UciNotification {
+    let id = Some((notf.gid, notf.oid));
+    if id == ids.key_rotation {
+        if let [t0, t1, t2, t3, c0, c1, c2, c3] = notf.payload[..] {
+            return UciNotification::Session(SessionNotification::KeyRotation {
+                session_token: u32::from_le_bytes([t0, t1, t2, t3]),
+                rotation_count: u32::from_le_bytes([c0, c1, c2, c3]),
+            });
+        }
+        error!("Invalid key rotation notification: {:?}", notf.payload);
+    }
+    if id == ids.coexistence {
+        if let [source, preempted, d0, d1] = notf.payload[..] {
+            if let Ok(source) = CoexistenceSource::try_from(source) {
+                return UciNotification::Core(CoreNotification::Coexistence {
+                    source,
+                    preempted: preempted != 0,
+                    duration_ms: u16::from_le_bytes([d0, d1]),
+                });
+            }
+        }
+        error!("Invalid coexistence notification: {:?}", notf.payload);
+    }
+    UciNotification::Vendor(notf)
+}
+
 fn vendor_notification(evt: uwb_uci_packets::UciNotification) -> Result<UciNotification> {
     Ok(UciNotification::Vendor(RawUciMessage {
         gid: evt.get_group_id().into(),
@@ -474,6 +1059,290 @@ fn get_vendor_uci_payload(evt: uwb_uci_packets::UciNotification) -> Result<Vec<u
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_session_range_data_to_csv_rows() {
+        let measurement = ShortAddressTwoWayRangingMeasurement {
+            mac_address: 0x1234,
+            status: StatusCode::UciStatusOk,
+            nlos: 0,
+            distance: 150,
+            aoa_azimuth: 5,
+            aoa_azimuth_fom: 6,
+            aoa_elevation: 7,
+            aoa_elevation_fom: 8,
+            aoa_destination_azimuth: 9,
+            aoa_destination_azimuth_fom: 10,
+            aoa_destination_elevation: 11,
+            aoa_destination_elevation_fom: 12,
+            slot_index: 0,
+            rssi: 200,
+        };
+        let mut range_data = SessionRangeData {
+            sequence_number: 3,
+            session_token: 0x123,
+            current_ranging_interval_ms: 200,
+            ranging_measurement_type: RangingMeasurementType::TwoWay,
+            ranging_measurements: RangingMeasurements::ShortAddressTwoWay(vec![
+                measurement.clone(),
+                ShortAddressTwoWayRangingMeasurement {
+                    mac_address: 0x5678,
+                    status: StatusCode::UciStatusRangingRxTimeout,
+                    ..measurement
+                },
+            ]),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+        assert_eq!(
+            SessionRangeData::csv_header(),
+            "sequence_number,mac_address,status,distance,aoa_azimuth,aoa_elevation,rssi"
+        );
+        assert_eq!(range_data.to_csv_rows(), vec!["3,1234,0,150,5,7,200", "3,5678,33,150,5,7,200"]);
+
+        range_data.ranging_measurement_type = RangingMeasurementType::OwrAoa;
+        range_data.ranging_measurements =
+            RangingMeasurements::ExtendedAddressOwrAoa(ExtendedAddressOwrAoaRangingMeasurement {
+                mac_address: 0x1234_5678_90ab,
+                status: StatusCode::UciStatusOk,
+                nlos: 0,
+                frame_sequence_number: 1,
+                block_index: 2,
+                aoa_azimuth: 3,
+                aoa_azimuth_fom: 4,
+                aoa_elevation: 5,
+                aoa_elevation_fom: 6,
+            });
+        assert_eq!(range_data.to_csv_rows(), vec!["3,00001234567890ab,0,,3,5,"]);
+    }
+
+    #[test]
+    fn test_session_range_data_distance_estimates() {
+        let measurement = ExtendedAddressTwoWayRangingMeasurement {
+            mac_address: 0x1234_5678_90ab,
+            status: StatusCode::UciStatusOk,
+            nlos: 1,
+            distance: 150,
+            aoa_azimuth: 5,
+            aoa_azimuth_fom: 6,
+            aoa_elevation: 7,
+            aoa_elevation_fom: 8,
+            aoa_destination_azimuth: 9,
+            aoa_destination_azimuth_fom: 10,
+            aoa_destination_elevation: 11,
+            aoa_destination_elevation_fom: 12,
+            slot_index: 0,
+            rssi: 200,
+        };
+        let mut range_data = SessionRangeData {
+            sequence_number: 3,
+            session_token: 0x123,
+            current_ranging_interval_ms: 200,
+            ranging_measurement_type: RangingMeasurementType::TwoWay,
+            ranging_measurements: RangingMeasurements::ExtendedAddressTwoWay(vec![
+                measurement.clone(),
+                ExtendedAddressTwoWayRangingMeasurement {
+                    status: StatusCode::UciStatusRangingRxTimeout,
+                    ..measurement
+                },
+            ]),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+        assert_eq!(
+            range_data.distance_estimates(),
+            vec![Some(DistanceEstimate { cm: 150, confidence: 0.5 }), None]
+        );
+        assert_eq!(range_data.distances_in(DistanceUnit::Meters), vec![Some(1.5), None]);
+        assert_eq!(
+            range_data.aoa_measurements(),
+            vec![AoaMeasurement { azimuth: 5, azimuth_fom: 6, elevation: 7, elevation_fom: 8 }; 2]
+        );
+
+        range_data.ranging_measurements = RangingMeasurements::ShortAddressDltdoa(vec![]);
+        assert!(range_data.distance_estimates().is_empty());
+    }
+
+    #[test]
+    fn test_session_range_data_ranging_round_indexes() {
+        let mut bytes = vec![
+            0x34, 0x12, 0x00, 0x01, // 2(Mac address), Status, Message Type
+            0x00, 0x00, 0x02, 0x00, // 2(Message control), 2(Block Index)
+            0x07, // Round Index
+        ];
+        // NLoS, AoA, RSSI, the 40-bit timestamps, CFO, the reply times and ToF.
+        bytes.extend([0; 32]);
+        let measurements = ShortAddressDlTdoaRangingMeasurement::parse(&bytes, 1).unwrap();
+        let mut range_data = SessionRangeData {
+            sequence_number: 3,
+            session_token: 0x123,
+            current_ranging_interval_ms: 200,
+            ranging_measurement_type: RangingMeasurementType::DlTdoa,
+            ranging_measurements: RangingMeasurements::ShortAddressDltdoa(measurements),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+        assert_eq!(range_data.ranging_round_indexes(), vec![Some(0x07)]);
+
+        range_data.ranging_measurement_type = RangingMeasurementType::OwrAoa;
+        range_data.ranging_measurements =
+            RangingMeasurements::ShortAddressOwrAoa(ShortAddressOwrAoaRangingMeasurement {
+                mac_address: 0x1234,
+                status: StatusCode::UciStatusOk,
+                nlos: 0,
+                frame_sequence_number: 1,
+                block_index: 2,
+                aoa_azimuth: 3,
+                aoa_azimuth_fom: 4,
+                aoa_elevation: 5,
+                aoa_elevation_fom: 6,
+            });
+        assert_eq!(range_data.ranging_round_indexes(), vec![None]);
+    }
+
+    #[test]
+    fn test_session_range_data_measurement_timestamps() {
+        let mut bytes = vec![
+            0x34, 0x12, 0x00, 0x01, // 2(Mac address), Status, Message Type
+            0x02, 0x00, 0x00, 0x00, // 2(Message control: 64-bit TX timestamp), 2(Block Index)
+            0x00, // Round Index
+        ];
+        // NLoS, AoA, RSSI.
+        bytes.extend([0; 8]);
+        // The 64-bit TX timestamp and the 40-bit RX timestamp.
+        bytes.extend([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        bytes.extend([0x11, 0x12, 0x13, 0x14, 0x15]);
+        // CFO, the reply times and ToF.
+        bytes.extend([0; 14]);
+        let measurements = ShortAddressDlTdoaRangingMeasurement::parse(&bytes, 1).unwrap();
+        let mut range_data = SessionRangeData {
+            sequence_number: 3,
+            session_token: 0x123,
+            current_ranging_interval_ms: 200,
+            ranging_measurement_type: RangingMeasurementType::DlTdoa,
+            ranging_measurements: RangingMeasurements::ShortAddressDltdoa(measurements),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+        assert_eq!(
+            range_data.measurement_timestamps(),
+            vec![Some(MeasurementTimestamps {
+                tx_timestamp: 0x0807060504030201,
+                rx_timestamp: 0x1514131211,
+            })]
+        );
+
+        range_data.ranging_measurement_type = RangingMeasurementType::TwoWay;
+        range_data.ranging_measurements = RangingMeasurements::ShortAddressTwoWay(vec![]);
+        assert!(range_data.measurement_timestamps().is_empty());
+    }
+
+    #[test]
+    fn test_distance_estimate() {
+        assert_eq!(
+            DistanceEstimate::new(StatusCode::UciStatusOk, 100, 0),
+            Some(DistanceEstimate { cm: 100, confidence: 1.0 })
+        );
+        assert_eq!(DistanceEstimate::new(StatusCode::UciStatusRangingRxTimeout, 100, 0), None);
+    }
+
+    #[test]
+    fn test_distance_in() {
+        let estimate = DistanceEstimate { cm: 150, confidence: 1.0 };
+        assert_eq!(estimate.distance_in(DistanceUnit::Millimeters), 1500.0);
+        assert_eq!(estimate.distance_in(DistanceUnit::Centimeters), 150.0);
+        assert_eq!(estimate.distance_in(DistanceUnit::Meters), 1.5);
+    }
+
+    #[test]
+    fn test_aoa_measurement_direction_vector() {
+        // The angles are in the Q9.7 format, i.e. 128 for 1 degree.
+        let aoa = |azimuth_deg: i16, elevation_deg: i16| AoaMeasurement {
+            azimuth: (azimuth_deg * 128) as u16,
+            azimuth_fom: 100,
+            elevation: (elevation_deg * 128) as u16,
+            elevation_fom: 100,
+        };
+        let assert_vector_eq = |actual: Option<[f32; 3]>, expected: [f32; 3]| {
+            let actual = actual.unwrap();
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert!((a - e).abs() < 1e-6, "{:?} != {:?}", actual, expected);
+            }
+        };
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+
+        // Straight ahead.
+        assert_vector_eq(aoa(0, 0).direction_vector(), [1.0, 0.0, 0.0]);
+        // 90 degrees azimuth.
+        assert_vector_eq(aoa(90, 0).direction_vector(), [0.0, 1.0, 0.0]);
+        assert_vector_eq(aoa(-90, 0).direction_vector(), [0.0, -1.0, 0.0]);
+        // 45 degrees elevation.
+        assert_vector_eq(aoa(0, 45).direction_vector(), [half_sqrt2, 0.0, half_sqrt2]);
+
+        // The elevation is taken as 0 if it is not available.
+        assert_vector_eq(
+            AoaMeasurement { elevation_fom: 0, ..aoa(90, 45) }.direction_vector(),
+            [0.0, 1.0, 0.0],
+        );
+        // No direction without the azimuth.
+        assert_eq!(AoaMeasurement { azimuth_fom: 0, ..aoa(0, 45) }.direction_vector(), None);
+    }
+
+    #[test]
+    fn test_session_range_data_measurements_by_controlee() {
+        let controlee1 = Controlee { short_address: [0x34, 0x12], subsession_id: 0x24 };
+        let controlee2 = Controlee { short_address: [0x78, 0x56], subsession_id: 0x25 };
+        let measurement1 = ShortAddressTwoWayRangingMeasurement {
+            mac_address: 0x1234,
+            status: StatusCode::UciStatusOk,
+            nlos: 0,
+            distance: 150,
+            aoa_azimuth: 5,
+            aoa_azimuth_fom: 6,
+            aoa_elevation: 7,
+            aoa_elevation_fom: 8,
+            aoa_destination_azimuth: 9,
+            aoa_destination_azimuth_fom: 10,
+            aoa_destination_elevation: 11,
+            aoa_destination_elevation_fom: 12,
+            slot_index: 0,
+            rssi: 200,
+        };
+        let measurement2 =
+            ShortAddressTwoWayRangingMeasurement { mac_address: 0x9abc, ..measurement1.clone() };
+        let range_data = SessionRangeData {
+            sequence_number: 3,
+            session_token: 0x123,
+            current_ranging_interval_ms: 200,
+            ranging_measurement_type: RangingMeasurementType::TwoWay,
+            ranging_measurements: RangingMeasurements::ShortAddressTwoWay(vec![
+                measurement1.clone(),
+                measurement2.clone(),
+            ]),
+            rcr_indicator: 0,
+            raw_ranging_data: vec![],
+            vendor_data: vec![],
+            link_qualities: vec![],
+            received_at: None,
+        };
+
+        assert_eq!(
+            range_data.measurements_by_controlee(&[controlee2, controlee1.clone()]),
+            vec![(Some(controlee1), measurement1), (None, measurement2)]
+        );
+    }
+
     #[test]
     fn test_ranging_measurements_trait() {
         let empty_short_ranging_measurements = RangingMeasurements::ShortAddressTwoWay(vec![]);
@@ -522,6 +1391,36 @@ mod tests {
         );
     }
     #[test]
+    fn test_uci_notification_casting_from_unknown_opcode() {
+        // A Session Config notification with the unknown opcode 0x3f.
+        let packet = uwb_uci_packets::UciNotification::parse(&[
+            0x61, 0x3f, 0x00, 0x02, 0x00, 0x00, 0x00, // header with the 32-bit payload size
+            0xab, 0xcd, // payload
+        ])
+        .unwrap();
+        assert_eq!(
+            UciNotification::try_from(packet).unwrap(),
+            UciNotification::Core(CoreNotification::Unknown {
+                gid: 0x1,
+                oid: 0x3f,
+                payload: vec![0xab, 0xcd]
+            })
+        );
+
+        // A Core notification with the unknown opcode 0x3e and no payload.
+        let packet =
+            uwb_uci_packets::UciNotification::parse(&[0x60, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00])
+                .unwrap();
+        assert_eq!(
+            UciNotification::try_from(packet).unwrap(),
+            UciNotification::Core(CoreNotification::Unknown {
+                gid: 0x0,
+                oid: 0x3e,
+                payload: vec![]
+            })
+        );
+    }
+    #[test]
     fn test_core_notification_casting_from_device_status_ntf() {
         let device_status_ntf_packet = uwb_uci_packets::DeviceStatusNtfBuilder {
             device_state: uwb_uci_packets::DeviceState::DeviceStateActive,
@@ -586,6 +1485,9 @@ mod tests {
                 ]),
                 rcr_indicator: 0x12,
                 raw_ranging_data,
+                vendor_data: vec![],
+                link_qualities: vec![],
+                received_at: None,
             }))
         );
     }
@@ -636,10 +1538,35 @@ mod tests {
                 ]),
                 rcr_indicator: 0x12,
                 raw_ranging_data,
+                vendor_data: vec![0x02, 0x01],
+                link_qualities: vec![],
+                received_at: None,
             }))
         );
     }
 
+    #[test]
+    fn test_session_notification_casting_from_session_info_ntf_without_measurement() {
+        let short_two_way_session_info_ntf = uwb_uci_packets::ShortMacTwoWaySessionInfoNtfBuilder {
+            sequence_number: 0x10,
+            session_token: 0x11,
+            rcr_indicator: 0x12,
+            current_ranging_interval: 0x13,
+            two_way_ranging_measurements: vec![],
+            vendor_data: vec![],
+        }
+        .build();
+        let range_notification: uwb_uci_packets::SessionInfoNtf =
+            short_two_way_session_info_ntf.into();
+        let range_data = match SessionNotification::try_from(range_notification).unwrap() {
+            SessionNotification::SessionInfo(range_data) => range_data,
+            notf => panic!("Unexpected notification: {:?}", notf),
+        };
+        assert_eq!(range_data.sequence_number, 0x10);
+        assert_eq!(range_data.current_ranging_interval_ms, 0x13);
+        assert_eq!(range_data.measurement_count(), 0);
+    }
+
     #[test]
     fn test_session_notification_casting_from_extended_mac_owr_aoa_session_info_ntf() {
         let extended_measurement = uwb_uci_packets::ExtendedAddressOwrAoaRangingMeasurement {
@@ -682,6 +1609,9 @@ mod tests {
                 ),
                 rcr_indicator: 0x12,
                 raw_ranging_data,
+                vendor_data: vec![],
+                link_qualities: vec![],
+                received_at: None,
             }))
         );
     }
@@ -725,6 +1655,9 @@ mod tests {
                 ranging_measurements: RangingMeasurements::ShortAddressOwrAoa(short_measurement),
                 rcr_indicator: 0x12,
                 raw_ranging_data,
+                vendor_data: vec![],
+                link_qualities: vec![],
+                received_at: None,
             }))
         );
     }
@@ -755,6 +1688,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_vendor_key_rotation_notification() {
+        let ids = VendorNotificationIds { key_rotation: Some((0xe, 0x20)), ..Default::default() };
+        let notf = RawUciMessage {
+            gid: 0xe,
+            oid: 0x20,
+            payload: vec![0x20, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00],
+        };
+        assert_eq!(
+            decode_vendor_notification(notf.clone(), &ids),
+            UciNotification::Session(SessionNotification::KeyRotation {
+                session_token: 0x20,
+                rotation_count: 3,
+            })
+        );
+        // The notification is kept as is when the id is not set, or the payload is invalid.
+        assert_eq!(
+            decode_vendor_notification(notf.clone(), &VendorNotificationIds::default()),
+            UciNotification::Vendor(notf)
+        );
+        let invalid_notf = RawUciMessage { gid: 0xe, oid: 0x20, payload: vec![0x20] };
+        assert_eq!(
+            decode_vendor_notification(invalid_notf.clone(), &ids),
+            UciNotification::Vendor(invalid_notf)
+        );
+    }
+
+    #[test]
+    fn test_decode_vendor_coexistence_notification() {
+        let ids = VendorNotificationIds { coexistence: Some((0xe, 0x21)), ..Default::default() };
+        let notf = RawUciMessage { gid: 0xe, oid: 0x21, payload: vec![0x01, 0x01, 0x64, 0x00] };
+        assert_eq!(
+            decode_vendor_notification(notf, &ids),
+            UciNotification::Core(CoreNotification::Coexistence {
+                source: CoexistenceSource::Bluetooth,
+                preempted: true,
+                duration_ms: 100,
+            })
+        );
+        // The notification is kept as is when the source is unknown.
+        let invalid_notf =
+            RawUciMessage { gid: 0xe, oid: 0x21, payload: vec![0x05, 0x01, 0x64, 0x00] };
+        assert_eq!(
+            decode_vendor_notification(invalid_notf.clone(), &ids),
+            UciNotification::Vendor(invalid_notf)
+        );
+    }
+
+    #[test]
+    fn test_session_notification_casting_from_android_range_diagnostics_ntf() {
+        let frame_report = uwb_uci_packets::FrameReport {
+            uwb_msg_id: 1,
+            action: 0,
+            antenna_set: 2,
+            frame_report_tlvs: vec![uwb_uci_packets::FrameReportTlv {
+                t: uwb_uci_packets::FrameReportTlvType::Rssi,
+                v: vec![0x10, 0x20],
+            }],
+        };
+        let diagnostics_ntf = uwb_uci_packets::AndroidRangeDiagnosticsNtfBuilder {
+            session_token: 0x20,
+            sequence_number: 5,
+            frame_reports: vec![frame_report],
+        }
+        .build();
+        let uci_notification =
+            UciNotification::try_from(uwb_uci_packets::UciNotification::from(diagnostics_ntf))
+                .unwrap();
+        assert_eq!(
+            uci_notification,
+            UciNotification::Session(SessionNotification::Diagnostics {
+                session_token: 0x20,
+                sequence_number: 5,
+                frame_reports: vec![ParsedFrameReport {
+                    uwb_msg_id: 1,
+                    action: 0,
+                    antenna_set: 2,
+                    rssi: vec![0x10, 0x20],
+                    aoa: vec![],
+                    cir: vec![],
+                }],
+            })
+        );
+    }
+
     #[test]
     fn test_session_notification_casting_from_session_update_controller_multicast_list_ntf_packet()
     {
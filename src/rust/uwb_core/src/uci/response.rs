@@ -33,6 +33,7 @@ pub(super) enum UciResponse {
     CoreGetCapsInfo(Result<Vec<CapTlv>>),
     CoreSetConfig(CoreSetConfigResponse),
     CoreGetConfig(Result<Vec<DeviceConfigTlv>>),
+    CoreQueryTimeStamp(Result<u64>),
     SessionInit(Result<Option<SessionHandle>>),
     SessionDeinit(Result<()>),
     SessionSetAppConfig(SetAppConfigResponse),
@@ -42,6 +43,7 @@ pub(super) enum UciResponse {
     SessionUpdateControllerMulticastList(Result<()>),
     SessionUpdateDtTagRangingRounds(Result<SessionUpdateDtTagRangingRoundsResponse>),
     SessionQueryMaxDataSize(Result<u16>),
+    SessionSetHybridConfig(Result<()>),
     SessionStart(Result<()>),
     SessionStop(Result<()>),
     SessionGetRangingCount(Result<usize>),
@@ -59,6 +61,7 @@ impl UciResponse {
             Self::CoreGetDeviceInfo(result) => Self::matches_result_retry(result),
             Self::CoreGetCapsInfo(result) => Self::matches_result_retry(result),
             Self::CoreGetConfig(result) => Self::matches_result_retry(result),
+            Self::CoreQueryTimeStamp(result) => Self::matches_result_retry(result),
             Self::SessionInit(result) => Self::matches_result_retry(result),
             Self::SessionDeinit(result) => Self::matches_result_retry(result),
             Self::SessionGetAppConfig(result) => Self::matches_result_retry(result),
@@ -79,11 +82,47 @@ impl UciResponse {
             Self::SessionSetAppConfig(resp) => Self::matches_status_retry(&resp.status),
 
             Self::SessionQueryMaxDataSize(result) => Self::matches_result_retry(result),
+            Self::SessionSetHybridConfig(result) => Self::matches_result_retry(result),
             // TODO(b/273376343): Implement retry logic for Data packet send.
             Self::SendUciData(_result) => false,
         }
     }
 
+    // The error of the failed command, or None if the command succeeds.
+    pub fn error(&self) -> Option<Error> {
+        match self {
+            Self::SetNotification | Self::OpenHal | Self::CloseHal | Self::SetLoggerMode => None,
+            Self::DeviceReset(result) => Self::result_error(result),
+            Self::CoreGetDeviceInfo(result) => Self::result_error(result),
+            Self::CoreGetCapsInfo(result) => Self::result_error(result),
+            Self::CoreGetConfig(result) => Self::result_error(result),
+            Self::CoreQueryTimeStamp(result) => Self::result_error(result),
+            Self::SessionInit(result) => Self::result_error(result),
+            Self::SessionDeinit(result) => Self::result_error(result),
+            Self::SessionGetAppConfig(result) => Self::result_error(result),
+            Self::SessionGetCount(result) => Self::result_error(result),
+            Self::SessionGetState(result) => Self::result_error(result),
+            Self::SessionUpdateControllerMulticastList(result) => Self::result_error(result),
+            Self::SessionUpdateDtTagRangingRounds(result) => Self::result_error(result),
+            Self::SessionQueryMaxDataSize(result) => Self::result_error(result),
+            Self::SessionSetHybridConfig(result) => Self::result_error(result),
+            Self::SessionStart(result) => Self::result_error(result),
+            Self::SessionStop(result) => Self::result_error(result),
+            Self::SessionGetRangingCount(result) => Self::result_error(result),
+            Self::AndroidSetCountryCode(result) => Self::result_error(result),
+            Self::AndroidGetPowerStats(result) => Self::result_error(result),
+            Self::RawUciCmd(result) => Self::result_error(result),
+            Self::SendUciData(result) => Self::result_error(result),
+
+            Self::CoreSetConfig(resp) => status_code_to_result(resp.status).err(),
+            Self::SessionSetAppConfig(resp) => status_code_to_result(resp.status).err(),
+        }
+    }
+
+    fn result_error<T>(result: &Result<T>) -> Option<Error> {
+        result.as_ref().err().cloned()
+    }
+
     fn matches_result_retry<T>(result: &Result<T>) -> bool {
         matches!(result, Err(Error::CommandRetry))
     }
@@ -141,6 +180,9 @@ impl TryFrom<uwb_uci_packets::CoreResponse> for UciResponse {
             CoreResponseChild::GetConfigRsp(evt) => Ok(UciResponse::CoreGetConfig(
                 status_code_to_result(evt.get_status()).map(|_| evt.get_tlvs().clone()),
             )),
+            CoreResponseChild::CoreQueryTimeStampRsp(evt) => Ok(UciResponse::CoreQueryTimeStamp(
+                status_code_to_result(evt.get_status()).map(|_| evt.get_timestamp()),
+            )),
             _ => Err(Error::Unknown),
         }
     }
@@ -201,6 +243,9 @@ impl TryFrom<uwb_uci_packets::SessionConfigResponse> for UciResponse {
             SessionConfigResponseChild::SessionQueryMaxDataSizeRsp(evt) => {
                 Ok(UciResponse::SessionQueryMaxDataSize(Ok(evt.get_max_data_size())))
             }
+            SessionConfigResponseChild::SessionSetHybridConfigRsp(evt) => {
+                Ok(UciResponse::SessionSetHybridConfig(status_code_to_result(evt.get_status())))
+            }
             _ => Err(Error::Unknown),
         }
     }
@@ -0,0 +1,296 @@
+// Copyright 2022, The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module provides the RF test mode, which is used for the RF certification and the hardware
+//! validation. It is not intended to be used in production.
+//!
+//! The RF test commands are sent via the UCI Test group, and require a session initialized with
+//! SessionType::DeviceTestMode.
+
+use std::convert::TryFrom;
+
+use log::error;
+
+use crate::error::{Error, Result};
+use crate::params::uci_packets::{GroupId, MessageType, SessionId, StatusCode};
+use crate::params::utils::{u32_to_bytes, u8_to_bytes};
+use crate::uci::error::status_code_to_result;
+use crate::uci::uci_manager::UciManager;
+
+// The opcodes of the UCI Test group.
+const TEST_CONFIG_SET_OID: u32 = 0x00;
+const TEST_PERIODIC_TX_OID: u32 = 0x02;
+const TEST_PER_RX_OID: u32 = 0x03;
+const TEST_STOP_SESSION_OID: u32 = 0x07;
+
+// The types of the test configuration TLVs.
+const NUM_PACKETS_CFG_ID: u8 = 0x00;
+const T_GAP_CFG_ID: u8 = 0x01;
+const T_START_CFG_ID: u8 = 0x02;
+const T_WIN_CFG_ID: u8 = 0x03;
+const RANDOMIZE_PSDU_CFG_ID: u8 = 0x04;
+const PHR_RANGING_BIT_CFG_ID: u8 = 0x05;
+
+/// The type of the RF test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RfTestType {
+    /// Transmit the packets periodically, i.e. TEST_PERIODIC_TX_CMD.
+    PeriodicTx,
+    /// Receive the packets and report the packet error rate, i.e. TEST_PER_RX_CMD.
+    PerRx,
+}
+
+impl RfTestType {
+    fn oid(&self) -> u32 {
+        match self {
+            Self::PeriodicTx => TEST_PERIODIC_TX_OID,
+            Self::PerRx => TEST_PER_RX_OID,
+        }
+    }
+}
+
+/// The configuration of the RF test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RfTestConfig {
+    /// The type of the RF test.
+    pub test_type: RfTestType,
+    /// The number of the packets to be sent or received.
+    pub num_packets: u32,
+    /// The gap between the start of two consecutive packets, in the unit of us.
+    pub t_gap_us: u32,
+    /// The start time of the reception, in the unit of us.
+    pub t_start_us: u32,
+    /// The duration of the reception window, in the unit of us.
+    pub t_win_us: u32,
+    /// Randomize the PSDU for each packet.
+    pub randomize_psdu: bool,
+    /// The value of the ranging bit in the PHR.
+    pub phr_ranging_bit: bool,
+    /// The PSDU data of the packets.
+    pub psdu_data: Vec<u8>,
+}
+
+impl RfTestConfig {
+    fn config_tlvs(&self) -> Vec<(u8, Vec<u8>)> {
+        vec![
+            (NUM_PACKETS_CFG_ID, u32_to_bytes(self.num_packets)),
+            (T_GAP_CFG_ID, u32_to_bytes(self.t_gap_us)),
+            (T_START_CFG_ID, u32_to_bytes(self.t_start_us)),
+            (T_WIN_CFG_ID, u32_to_bytes(self.t_win_us)),
+            (RANDOMIZE_PSDU_CFG_ID, u8_to_bytes(self.randomize_psdu as u8)),
+            (PHR_RANGING_BIT_CFG_ID, u8_to_bytes(self.phr_ranging_bit as u8)),
+        ]
+    }
+
+    // Generate the payload of TEST_CONFIG_SET_CMD.
+    fn generate_config_set_payload(&self, session_token: u32) -> Vec<u8> {
+        let tlvs = self.config_tlvs();
+        let mut payload = u32_to_bytes(session_token);
+        payload.push(tlvs.len() as u8);
+        for (cfg_id, value) in tlvs.into_iter() {
+            payload.push(cfg_id);
+            payload.push(value.len() as u8);
+            payload.extend(value);
+        }
+        payload
+    }
+}
+
+/// Configure the RF test of the session, then start it.
+pub async fn start_rf_test<T: UciManager>(
+    uci_manager: &T,
+    session_id: SessionId,
+    config: RfTestConfig,
+) -> Result<()> {
+    let session_token = uci_manager.get_session_token_from_session_id(session_id).await?;
+    send_test_cmd(
+        uci_manager,
+        TEST_CONFIG_SET_OID,
+        config.generate_config_set_payload(session_token),
+    )
+    .await?;
+    send_test_cmd(uci_manager, config.test_type.oid(), config.psdu_data).await
+}
+
+/// Stop the ongoing RF test.
+pub async fn stop_rf_test<T: UciManager>(uci_manager: &T) -> Result<()> {
+    send_test_cmd(uci_manager, TEST_STOP_SESSION_OID, vec![]).await
+}
+
+// Send the command of the UCI Test group, and convert the status of the response.
+async fn send_test_cmd<T: UciManager>(uci_manager: &T, oid: u32, payload: Vec<u8>) -> Result<()> {
+    let resp = uci_manager
+        .raw_uci_cmd(
+            u8::from(MessageType::Command).into(),
+            u8::from(GroupId::Test).into(),
+            oid,
+            payload,
+        )
+        .await?;
+    let status = resp.payload.first().copied().ok_or_else(|| {
+        error!("The response of the test command {} is empty", oid);
+        Error::Unknown
+    })?;
+    status_code_to_result(StatusCode::try_from(status).map_err(|_| {
+        error!("Unknown status {} of the test command {}", status, oid);
+        Error::Unknown
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::sync::mpsc;
+
+    use crate::params::uci_packets::SessionType;
+    use crate::uci::command::UciCommand;
+    use crate::uci::mock_uci_hal::MockUciHal;
+    use crate::uci::mock_uci_logger::MockUciLogger;
+    use crate::uci::uci_hal::UciHalPacket;
+    use crate::uci::uci_logger::UciLoggerMode;
+    use crate::uci::uci_manager::UciManagerImpl;
+    use crate::utils::init_test_logging;
+
+    fn into_uci_hal_packets<T: Into<uwb_uci_packets::UciControlPacket>>(
+        builder: T,
+    ) -> Vec<UciHalPacket> {
+        let packets: Vec<uwb_uci_packets::UciControlPacketHal> = builder.into().into();
+        packets.into_iter().map(|packet| packet.into()).collect()
+    }
+
+    // Construct the response packet of the UCI Test group.
+    fn build_test_rsp(oid: u32, status: StatusCode) -> Vec<UciHalPacket> {
+        vec![vec![0x40 | u8::from(GroupId::Test), oid as u8, 0x00, 0x01, u8::from(status)]]
+    }
+
+    fn test_cmd(oid: u32, payload: Vec<u8>) -> UciCommand {
+        UciCommand::RawUciCmd {
+            mt: u8::from(MessageType::Command).into(),
+            gid: u8::from(GroupId::Test).into(),
+            oid,
+            payload,
+        }
+    }
+
+    async fn setup_uci_manager_with_test_session(
+        session_id: SessionId,
+    ) -> (UciManagerImpl, MockUciHal) {
+        init_test_logging();
+
+        let session_type = SessionType::DeviceTestMode;
+        let mut hal = MockUciHal::new();
+        let notf = into_uci_hal_packets(uwb_uci_packets::DeviceStatusNtfBuilder {
+            device_state: uwb_uci_packets::DeviceState::DeviceStateReady,
+        });
+        hal.expected_open(Some(notf), Ok(()));
+        let resp = into_uci_hal_packets(uwb_uci_packets::GetDeviceInfoRspBuilder {
+            status: StatusCode::UciStatusOk,
+            uci_version: 0x1234,
+            mac_version: 0x5678,
+            phy_version: 0x90ab,
+            uci_test_version: 0x1357,
+            vendor_spec_info: vec![],
+        });
+        hal.expected_send_command(UciCommand::CoreGetDeviceInfo, resp, Ok(()));
+        let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionInitRspBuilder {
+            status: StatusCode::UciStatusOk,
+        });
+        resp.append(&mut into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+            session_token: session_id,
+            session_state: uwb_uci_packets::SessionState::SessionStateInit,
+            reason_code: uwb_uci_packets::ReasonCode::StateChangeWithSessionManagementCommands
+                .into(),
+        }));
+        hal.expected_send_command(
+            UciCommand::SessionInit { session_id, session_type },
+            resp,
+            Ok(()),
+        );
+        hal.expected_notify_session_initialized(session_id, Ok(()));
+
+        let uci_manager = UciManagerImpl::new(
+            hal.clone(),
+            MockUciLogger::new(mpsc::unbounded_channel().0),
+            UciLoggerMode::Disabled,
+        );
+        assert!(uci_manager.open_hal().await.is_ok());
+        assert!(uci_manager.session_init(session_id, session_type).await.is_ok());
+        assert!(hal.wait_expected_calls_done().await);
+
+        (uci_manager, hal)
+    }
+
+    #[tokio::test]
+    async fn test_start_rf_test() {
+        let session_id = 0x123;
+        let config = RfTestConfig {
+            test_type: RfTestType::PeriodicTx,
+            num_packets: 1000,
+            t_gap_us: 2000,
+            t_start_us: 450,
+            t_win_us: 750,
+            randomize_psdu: false,
+            phr_ranging_bit: true,
+            psdu_data: vec![0x11, 0x22, 0x33],
+        };
+        let (uci_manager, mut hal) = setup_uci_manager_with_test_session(session_id).await;
+
+        let config_payload = vec![
+            0x23, 0x01, 0x00, 0x00, // session token
+            0x06, // number of TLVs
+            0x00, 0x04, 0xe8, 0x03, 0x00, 0x00, // NUM_PACKETS
+            0x01, 0x04, 0xd0, 0x07, 0x00, 0x00, // T_GAP
+            0x02, 0x04, 0xc2, 0x01, 0x00, 0x00, // T_START
+            0x03, 0x04, 0xee, 0x02, 0x00, 0x00, // T_WIN
+            0x04, 0x01, 0x00, // RANDOMIZE_PSDU
+            0x05, 0x01, 0x01, // PHR_RANGING_BIT
+        ];
+        hal.expected_send_command(
+            test_cmd(TEST_CONFIG_SET_OID, config_payload),
+            build_test_rsp(TEST_CONFIG_SET_OID, StatusCode::UciStatusOk),
+            Ok(()),
+        );
+        hal.expected_send_command(
+            test_cmd(TEST_PERIODIC_TX_OID, vec![0x11, 0x22, 0x33]),
+            build_test_rsp(TEST_PERIODIC_TX_OID, StatusCode::UciStatusOk),
+            Ok(()),
+        );
+
+        let result = start_rf_test(&uci_manager, session_id, config).await;
+        assert_eq!(result, Ok(()));
+        assert!(hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_stop_rf_test() {
+        let session_id = 0x123;
+        let (uci_manager, mut hal) = setup_uci_manager_with_test_session(session_id).await;
+
+        hal.expected_send_command(
+            test_cmd(TEST_STOP_SESSION_OID, vec![]),
+            build_test_rsp(TEST_STOP_SESSION_OID, StatusCode::UciStatusOk),
+            Ok(()),
+        );
+        assert_eq!(stop_rf_test(&uci_manager).await, Ok(()));
+
+        hal.expected_send_command(
+            test_cmd(TEST_STOP_SESSION_OID, vec![]),
+            build_test_rsp(TEST_STOP_SESSION_OID, StatusCode::UciStatusRejected),
+            Ok(()),
+        );
+        assert_eq!(stop_rf_test(&uci_manager).await, Err(Error::Unknown));
+        assert!(hal.wait_expected_calls_done().await);
+    }
+}
@@ -13,39 +13,72 @@
 // limitations under the License.
 
 use std::convert::TryInto;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
 use log::{debug, error, info, warn};
-use tokio::sync::{mpsc, oneshot, Mutex};
+use num_traits::FromPrimitive;
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::time::Instant;
 
+#[cfg(feature = "metrics-export")]
+use crate::proto::{
+    bindings::LatencyStatsReport as ProtoLatencyStatsReport, utils::write_to_bytes,
+};
 use crate::uci::command::UciCommand;
+#[cfg(feature = "latency-stats")]
+use crate::uci::latency_stats::LatencyStats;
 //use crate::uci::error::{Error, Result};
 use crate::error::{Error, Result};
+use crate::params::app_config_params::AppConfigParams;
+use crate::params::fira_app_config_params::DeviceType;
+use crate::params::phy_params::PhyParams;
 use crate::params::uci_packets::{
-    AppConfigTlv, AppConfigTlvType, CapTlv, Controlees, CoreSetConfigResponse, CountryCode,
-    CreditAvailability, DeviceConfigId, DeviceConfigTlv, DeviceState, FiraComponent,
-    GetDeviceInfoResponse, GroupId, MessageType, PowerStats, RawUciMessage, ResetConfig, SessionId,
-    SessionState, SessionToken, SessionType, SessionUpdateDtTagRangingRoundsResponse,
-    SetAppConfigResponse, UciDataPacket, UciDataPacketHal, UpdateMulticastListAction,
+    AntennaPair, AntennaPairIds, AppConfigTlv, AppConfigTlvType, CapTlv, CapTlvType, CommandError,
+    CommandOutcome, Controlees, CoreSetConfigResponse, CountryCode, CreditAvailability,
+    DataTransferRole, DeviceConfigId, DeviceConfigTlv, DeviceState, FieldMismatch, FiraComponent,
+    GetDeviceInfoResponse, GroupId, HybridSessionConfig, MessageType, PhaseList, PowerStats,
+    RawUciMessage, ResetConfig, SessionId, SessionState, SessionToken, SessionType,
+    SessionUpdateDtTagRangingRoundsResponse, SetAppConfigResponse, StatusCode, UciDataPacket,
+    UciDataPacketHal, UciOpcode, UpdateMulticastListAction, VendorCapIds, VendorCommandIds,
+    VendorNotificationIds,
 };
-use crate::params::utils::bytes_to_u64;
+use crate::params::utils::{bytes_to_u16, bytes_to_u64, bytes_to_u8, u64_to_bytes};
+use crate::uci::error::status_code_to_result;
 use crate::uci::message::UciMessage;
 use crate::uci::notification::{
-    CoreNotification, DataRcvNotification, SessionNotification, SessionRangeData, UciNotification,
+    decode_vendor_notification, CoreNotification, DataRcvNotification, LinkQualityDecoder,
+    SessionNotification, SessionRangeData, UciNotification,
 };
 use crate::uci::response::UciResponse;
 use crate::uci::timeout_uci_hal::TimeoutUciHal;
 use crate::uci::uci_hal::{UciHal, UciHalPacket};
 use crate::uci::uci_logger::{UciLogger, UciLoggerMode, UciLoggerWrapper};
 use crate::utils::{clean_mpsc_receiver, PinSleep};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use uwb_uci_packets::{Packet, RawUciControlPacket, UciDataSnd, UciDefragPacket};
 
 const UCI_TIMEOUT_MS: u64 = 800;
 const MAX_RETRY_COUNT: usize = 3;
 
+// The range of the UCI major versions whose packets could be parsed by uwb_uci_packets.
+const SUPPORTED_UCI_MAJOR_VERSIONS: std::ops::RangeInclusive<u8> = 1..=2;
+// The maximum number of the controlees in one SESSION_UPDATE_CONTROLLER_MULTICAST_LIST_CMD.
+pub(crate) const MAX_CONTROLEES_PER_MULTICAST_LIST_CMD: usize = 8;
+// The maximum number of the config ids in one SESSION_GET_APP_CONFIG_CMD, which keeps the response
+// of each command in a moderate size.
+const MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD: usize = 32;
+// The default number of the failed UCI commands kept by UciManagerImpl.
+const DEFAULT_RECENT_ERRORS_CAPACITY: usize = 16;
+
+/// The middleware which inspects and rewrites the UCI commands before they are sent to the
+/// UciHal. The gid and oid of the message should not be changed, otherwise the response can't
+/// be matched to the command.
+pub type CommandMiddleware = dyn Fn(&mut RawUciMessage) + Send + Sync;
+
 /// The UciManager organizes the state machine of the UWB HAL, and provides the interface which
 /// abstracts the UCI commands, responses, and notifications.
 #[async_trait]
@@ -68,6 +101,13 @@ pub trait UciManager: 'static + Send + Sync + Clone {
         &mut self,
         data_rcv_notf_sender: mpsc::UnboundedSender<DataRcvNotification>,
     );
+    // Set the sender which receives the core, session and vendor notifications in the order they
+    // are produced, instead of the senders of each kind. Set None to restore the senders of each
+    // kind.
+    async fn set_ordered_notification_sender(
+        &mut self,
+        ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    );
 
     // Open the UCI HAL.
     // All the UCI commands should be called after the open_hal() completes successfully.
@@ -118,6 +158,13 @@ pub trait UciManager: 'static + Send + Sync + Clone {
 
     async fn session_query_max_data_size(&self, session_id: SessionId) -> Result<u16>;
 
+    // Set the hybrid UWB scheduling (HUS) config of the controller session.
+    async fn session_set_hybrid_config(
+        &self,
+        session_id: SessionId,
+        config: HybridSessionConfig,
+    ) -> Result<()>;
+
     async fn range_start(&self, session_id: SessionId) -> Result<()>;
     async fn range_stop(&self, session_id: SessionId) -> Result<()>;
     async fn range_get_ranging_count(&self, session_id: SessionId) -> Result<usize>;
@@ -157,11 +204,72 @@ pub trait UciManager: 'static + Send + Sync + Clone {
 #[derive(Clone)]
 pub struct UciManagerImpl {
     cmd_sender: mpsc::UnboundedSender<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
+    // CloseHal is sent through its own channel, so it isn't blocked behind the pending command.
+    close_sender: mpsc::UnboundedSender<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
 
     // FIRA version 2 introduces a UWBS generated session handle to use as identifier for all
     // session related commands. This map stores the app provided session id to UWBS generated
     // session handle mapping if provided, else reuses session id.
     session_id_to_token_map: Arc<Mutex<HashMap<SessionId, SessionToken>>>,
+
+    // When enabled, session_get_state() reports SessionStateDeinit instead of an error for the
+    // sessions which were initialized before the last device reset.
+    report_deinit_after_reset: Arc<AtomicBool>,
+    // The session ids which were tracked when the device was reset or the HAL was reopened by
+    // reset_and_reopen(), and haven't been initialized again since then.
+    session_ids_before_reset: Arc<Mutex<HashSet<SessionId>>>,
+    // The type of each session, recorded when the session is initialized.
+    session_types: Arc<Mutex<HashMap<SessionId, SessionType>>>,
+
+    // The max number of the UCI commands which could wait for the responses at the same time.
+    max_in_flight_cmds: Arc<AtomicUsize>,
+    // The number of the UCI commands which are waiting for the responses, updated by
+    // UciManagerActor.
+    in_flight_cmd_count: Arc<AtomicUsize>,
+    // The decoder of the link qualities in the SessionRangeData, used by UciManagerActor.
+    link_quality_decoder: Arc<std::sync::Mutex<Option<Arc<dyn LinkQualityDecoder>>>>,
+    // The ids of the vendor notifications decoded by UciManagerActor.
+    vendor_notification_ids: Arc<std::sync::Mutex<VendorNotificationIds>>,
+    // The last failed UCI commands, recorded by UciManagerActor.
+    recent_errors: Arc<std::sync::Mutex<RecentErrors>>,
+    // The middleware of the outgoing UCI commands, used by UciManagerActor.
+    command_middleware: Arc<std::sync::Mutex<Option<Arc<CommandMiddleware>>>>,
+    // The STS key rotation count of each session, recorded by UciManagerActor.
+    key_rotation_counts: Arc<std::sync::Mutex<HashMap<SessionToken, u32>>>,
+    // The latest device state reported by UciManagerActor, None when the HAL is closed.
+    device_state_receiver: watch::Receiver<Option<DeviceState>>,
+
+    // The UCI version of the UWBS, updated when CORE_GET_DEVICE_INFO_CMD succeeds.
+    uci_version: Arc<Mutex<Option<u16>>>,
+    // The country code of the UWBS, updated when ANDROID_SET_COUNTRY_CODE_CMD succeeds and
+    // cleared when CORE_DEVICE_RESET_CMD succeeds.
+    country_code: Arc<Mutex<Option<CountryCode>>>,
+    // The max number of the sessions supported by the UWBS, updated when CORE_GET_CAPS_INFO_CMD
+    // succeeds.
+    max_sessions: Arc<Mutex<Option<u32>>>,
+    // The max number of the CCC sessions supported by the UWBS, updated when
+    // CORE_GET_CAPS_INFO_CMD succeeds.
+    max_ccc_sessions: Arc<Mutex<Option<u32>>>,
+    // The vendor-specific ids of the capabilities, set by the integrator.
+    vendor_cap_ids: Arc<std::sync::Mutex<VendorCapIds>>,
+    // The vendor-specific ids of the commands, set by the integrator.
+    vendor_command_ids: Arc<std::sync::Mutex<VendorCommandIds>>,
+    // The data transfer roles supported by the UWBS, updated when CORE_GET_CAPS_INFO_CMD succeeds.
+    data_transfer_roles: Arc<Mutex<Option<Vec<DataTransferRole>>>>,
+    // The vendor-specific ids of the AoA antenna pair selection, set by the integrator.
+    antenna_pair_ids: Arc<std::sync::Mutex<Option<AntennaPairIds>>>,
+    // The number of the antennas of the UWBS, updated when CORE_GET_CAPS_INFO_CMD succeeds.
+    antenna_count: Arc<Mutex<Option<u8>>>,
+    // The upper bound of MAX_NUMBER_OF_MEASUREMENTS, updated when CORE_GET_CAPS_INFO_CMD succeeds.
+    max_number_of_measurements: Arc<Mutex<Option<u16>>>,
+    // When enabled, core_get_device_info() fails if the UCI version of the UWBS is not supported.
+    strict_uci_version_check: Arc<AtomicBool>,
+    // When enabled, the duplicated or stale SessionRangeData is dropped by UciManagerActor.
+    range_data_sequence_check: Arc<AtomicBool>,
+
+    // The latency statistics of the UCI commands, recorded by UciManagerActor.
+    #[cfg(feature = "latency-stats")]
+    latency_stats: Arc<std::sync::Mutex<HashMap<UciOpcode, LatencyStats>>>,
 }
 
 impl UciManagerImpl {
@@ -174,22 +282,203 @@ impl UciManagerImpl {
         let (cmd_sender, cmd_receiver) = mpsc::unbounded_channel();
         let session_id_to_token_map: Arc<Mutex<HashMap<SessionId, SessionToken>>> =
             Arc::new(Mutex::new(HashMap::new()));
+        let max_in_flight_cmds = Arc::new(AtomicUsize::new(1));
+        let range_data_sequence_check = Arc::new(AtomicBool::new(false));
         let mut actor = UciManagerActor::new(
             hal,
             logger,
             logger_mode,
             cmd_receiver,
             session_id_to_token_map.clone(),
+            max_in_flight_cmds.clone(),
+            range_data_sequence_check.clone(),
         );
+        let close_sender = actor.close_sender.clone();
+        let in_flight_cmd_count = actor.in_flight_cmd_count.clone();
+        let link_quality_decoder = actor.link_quality_decoder.clone();
+        let vendor_notification_ids = actor.vendor_notification_ids.clone();
+        let recent_errors = actor.recent_errors.clone();
+        let command_middleware = actor.command_middleware.clone();
+        let key_rotation_counts = actor.key_rotation_counts.clone();
+        let device_state_receiver = actor.device_state_sender.subscribe();
+        #[cfg(feature = "latency-stats")]
+        let latency_stats = actor.latency_stats.clone();
         tokio::spawn(async move { actor.run().await });
 
-        Self { cmd_sender, session_id_to_token_map }
+        Self {
+            cmd_sender,
+            close_sender,
+            session_id_to_token_map,
+            report_deinit_after_reset: Arc::new(AtomicBool::new(false)),
+            session_ids_before_reset: Arc::new(Mutex::new(HashSet::new())),
+            session_types: Arc::new(Mutex::new(HashMap::new())),
+            max_in_flight_cmds,
+            in_flight_cmd_count,
+            link_quality_decoder,
+            vendor_notification_ids,
+            recent_errors,
+            command_middleware,
+            key_rotation_counts,
+            device_state_receiver,
+            uci_version: Arc::new(Mutex::new(None)),
+            country_code: Arc::new(Mutex::new(None)),
+            max_sessions: Arc::new(Mutex::new(None)),
+            max_ccc_sessions: Arc::new(Mutex::new(None)),
+            vendor_cap_ids: Arc::new(std::sync::Mutex::new(VendorCapIds::default())),
+            vendor_command_ids: Arc::new(std::sync::Mutex::new(VendorCommandIds::default())),
+            data_transfer_roles: Arc::new(Mutex::new(None)),
+            antenna_pair_ids: Arc::new(std::sync::Mutex::new(None)),
+            antenna_count: Arc::new(Mutex::new(None)),
+            max_number_of_measurements: Arc::new(Mutex::new(None)),
+            strict_uci_version_check: Arc::new(AtomicBool::new(false)),
+            range_data_sequence_check,
+            #[cfg(feature = "latency-stats")]
+            latency_stats,
+        }
+    }
+
+    /// Get the latency statistics of the UCI commands, keyed by the opcode of the commands. The
+    /// latency is measured from sending the command to receiving the final response.
+    #[cfg(feature = "latency-stats")]
+    pub fn latency_stats(&self) -> HashMap<UciOpcode, LatencyStats> {
+        self.latency_stats.lock().unwrap().clone()
+    }
+
+    /// Serialize the latency statistics of the UCI commands to a LatencyStatsReport protobuf
+    /// message, e.g. to ship them to a telemetry backend.
+    #[cfg(feature = "metrics-export")]
+    pub fn export_latency_stats(&self) -> Result<Vec<u8>> {
+        write_to_bytes(&ProtoLatencyStatsReport::from(self.latency_stats()))
+    }
+
+    /// Set the max number of the UCI commands which could wait for the responses at the same
+    /// time. The responses are matched to the commands by the GID and OID, so the commands with
+    /// the same opcode are never pipelined. SESSION_INIT, DEVICE_RESET and the raw UCI commands
+    /// are always sent after all the previous commands are responded.
+    ///
+    /// The default value is 1, which follows the UCI spec that the host waits for the response
+    /// before sending the next command.
+    pub fn set_max_in_flight_commands(&self, max_in_flight_cmds: usize) {
+        self.max_in_flight_cmds.store(max_in_flight_cmds.max(1), Ordering::Relaxed);
+    }
+
+    /// Get the number of the UCI commands which are sent to the UWBS and waiting for the
+    /// responses. It is at most the value set by set_max_in_flight_commands(), and a count that
+    /// stays non-zero indicates the UWBS stops responding.
+    pub fn pending_command_count(&self) -> usize {
+        self.in_flight_cmd_count.load(Ordering::Relaxed)
+    }
+
+    /// Register the decoder of the vendor specific link quality indicators. The link qualities
+    /// of the SessionRangeData are decoded from its vendor data before being forwarded to the
+    /// session notification sender. Set None to stop decoding them.
+    pub fn set_link_quality_decoder(&self, decoder: Option<Arc<dyn LinkQualityDecoder>>) {
+        *self.link_quality_decoder.lock().unwrap() = decoder;
+    }
+
+    /// Set the ids of the vendor notifications which report the events the UCI spec doesn't
+    /// define, i.e. SessionNotification::KeyRotation and CoreNotification::Coexistence. The vendor
+    /// notification matching an id is decoded and forwarded as the event, instead of as a raw
+    /// vendor notification.
+    pub fn set_vendor_notification_ids(&self, ids: VendorNotificationIds) {
+        *self.vendor_notification_ids.lock().unwrap() = ids;
+    }
+
+    /// Register the middleware which is invoked on every outgoing UCI command, i.e. for testing
+    /// or shimming the UWBS. The command is sent to the UciHal as a raw UCI command when the
+    /// middleware modifies it. Set None to remove the middleware.
+    pub fn set_command_middleware(&self, middleware: Option<Arc<CommandMiddleware>>) {
+        *self.command_middleware.lock().unwrap() = middleware;
+    }
+
+    /// Get the last failed UCI commands, from the oldest to the newest. A command fails when it
+    /// times out, or the status of its response is not OK. The raw UCI commands are not recorded.
+    pub fn recent_errors(&self) -> Vec<CommandError> {
+        self.recent_errors.lock().unwrap().errors.iter().cloned().collect()
+    }
+
+    /// Clear the failed UCI commands returned by recent_errors().
+    pub fn clear_recent_errors(&self) {
+        self.recent_errors.lock().unwrap().errors.clear();
+    }
+
+    /// Set the max number of the failed UCI commands returned by recent_errors(). The oldest ones
+    /// are dropped when the number exceeds the capacity. The default value is 16.
+    pub fn set_recent_errors_capacity(&self, capacity: usize) {
+        self.recent_errors.lock().unwrap().set_capacity(capacity);
+    }
+
+    /// Set the vendor-specific ids of the capabilities which the FiRa UCI spec doesn't define. It
+    /// should be set before CORE_GET_CAPS_INFO_CMD is sent.
+    pub fn set_vendor_cap_ids(&self, ids: VendorCapIds) {
+        *self.vendor_cap_ids.lock().unwrap() = ids;
+    }
+
+    /// Set the vendor-specific ids of the commands which query the information the FiRa UCI spec
+    /// doesn't define, e.g. session_get_possible_ranging_rounds().
+    pub fn set_vendor_command_ids(&self, ids: VendorCommandIds) {
+        *self.vendor_command_ids.lock().unwrap() = ids;
+    }
+
+    /// Set the vendor-specific ids of the AoA antenna pair selection of the UWBS, which enables
+    /// session_get_aoa_antenna_pairs() and the check of the antenna pairs against the antenna
+    /// count capability. It should be set before CORE_GET_CAPS_INFO_CMD is sent.
+    pub fn set_antenna_pair_ids(&self, ids: Option<AntennaPairIds>) {
+        *self.antenna_pair_ids.lock().unwrap() = ids;
+    }
+
+    /// Opt in to fail core_get_device_info() with Error::UnsupportedUciVersion when the UCI
+    /// version of the UWBS is out of the range supported by the packet definitions. Otherwise
+    /// only a warning is logged.
+    pub fn set_strict_uci_version_check(&self, enabled: bool) {
+        self.strict_uci_version_check.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Opt in to track the sequence number of the SessionRangeData of each session since its
+    /// ranging is started. The range data which is not newer than the last forwarded one, e.g.
+    /// duplicated or stale after reconnecting the UWBS, is dropped, and a warning is logged when
+    /// some sequence numbers are skipped.
+    pub fn set_range_data_sequence_check(&self, enabled: bool) {
+        self.range_data_sequence_check.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Opt in to report SessionStateDeinit from session_get_state() for the sessions that were
+    /// lost by a device reset or reset_and_reopen(), instead of the error caused by
+    /// UciStatusSessionNotExist.
+    pub fn set_report_deinit_after_reset(&self, enabled: bool) {
+        self.report_deinit_after_reset.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Register a one-shot matcher of the vendor notification with |gid| and |oid|. The returned
+    /// receiver is resolved with the first matching vendor notification, which is not forwarded
+    /// to the vendor notification sender then. The matchers with the same GID and OID are
+    /// resolved in the order of registration. It is used by the vendor protocols which reply the
+    /// command via the notification, and the matcher should be registered before sending the
+    /// command to avoid missing the notification.
+    pub async fn register_vendor_notification_matcher(
+        &self,
+        gid: u32,
+        oid: u32,
+    ) -> Result<oneshot::Receiver<RawUciMessage>> {
+        let (notf_sender, notf_receiver) = oneshot::channel();
+        match self
+            .send_cmd(UciManagerCmd::RegisterVendorNotificationMatcher { gid, oid, notf_sender })
+            .await
+        {
+            Ok(UciResponse::SetNotification) => Ok(notf_receiver),
+            Ok(_) => Err(Error::Unknown),
+            Err(e) => Err(e),
+        }
     }
 
     // Send the |cmd| to the UciManagerActor.
     async fn send_cmd(&self, cmd: UciManagerCmd) -> Result<UciResponse> {
         let (result_sender, result_receiver) = oneshot::channel();
-        match self.cmd_sender.send((cmd, result_sender)) {
+        let sender = match cmd {
+            UciManagerCmd::CloseHal { .. } => &self.close_sender,
+            _ => &self.cmd_sender,
+        };
+        match sender.send((cmd, result_sender)) {
             Ok(()) => result_receiver.await.unwrap_or(Err(Error::Unknown)),
             Err(cmd) => {
                 error!("Failed to send cmd: {:?}", cmd.0);
@@ -198,6 +487,628 @@ impl UciManagerImpl {
         }
     }
 
+    /// Send UCI command for deinitiating session, and return the status code of the response
+    /// as-is instead of converting it to an error.
+    pub async fn session_deinit_with_outcome(
+        &self,
+        session_id: SessionId,
+    ) -> Result<CommandOutcome> {
+        let cmd =
+            UciCommand::SessionDeinit { session_token: self.get_session_token(&session_id).await? };
+        let outcome = self.send_cmd_with_outcome(cmd).await?;
+        if outcome.is_ok() {
+            self.session_types.lock().await.remove(&session_id);
+        }
+        Ok(outcome)
+    }
+
+    /// Send UCI command for starting ranging of the session, and return the status code of the
+    /// response as-is instead of converting it to an error.
+    pub async fn range_start_with_outcome(&self, session_id: SessionId) -> Result<CommandOutcome> {
+        let cmd =
+            UciCommand::SessionStart { session_token: self.get_session_token(&session_id).await? };
+        self.send_cmd_with_outcome(cmd).await
+    }
+
+    /// Send UCI command for stopping ranging of the session, and return the status code of the
+    /// response as-is instead of converting it to an error.
+    pub async fn range_stop_with_outcome(&self, session_id: SessionId) -> Result<CommandOutcome> {
+        let cmd =
+            UciCommand::SessionStop { session_token: self.get_session_token(&session_id).await? };
+        self.send_cmd_with_outcome(cmd).await
+    }
+
+    /// Send UCI command for getting the device info, and return the raw payload of the response
+    /// along with the decoded one, so the fields not decoded by this crate can be inspected.
+    /// Unlike core_get_device_info(), the UCI version of the UWBS is not checked.
+    pub async fn core_get_device_info_raw(&self) -> Result<(GetDeviceInfoResponse, Vec<u8>)> {
+        match self.send_cmd_with_raw_resp(UciCommand::CoreGetDeviceInfo).await? {
+            (UciResponse::CoreGetDeviceInfo(resp), payload) => Ok((resp?, payload)),
+            _ => Err(Error::Unknown),
+        }
+    }
+
+    /// Set the app config of the session with the all-or-nothing semantics. Only the configs
+    /// which differ between |new_params| and |prev_params| are sent. If the UWBS rejects any of
+    /// them, the same configs are set back to the values of |prev_params| and the error is
+    /// returned.
+    pub async fn session_set_app_config_transactional(
+        &self,
+        session_id: SessionId,
+        new_params: &AppConfigParams,
+        prev_params: &AppConfigParams,
+    ) -> Result<()> {
+        let session_state = self.session_get_state(session_id).await?;
+        let (tlvs, rollback_tlvs) = new_params
+            .generate_updated_and_rollback_tlvs(prev_params, session_state)
+            .ok_or_else(|| {
+                error!(
+                    "Cannot update the app config at state {:?}: {:?}",
+                    session_state, new_params
+                );
+                Error::BadParameters
+            })?;
+        if tlvs.is_empty() {
+            return Ok(());
+        }
+
+        let resp = self.session_set_app_config(session_id, tlvs).await?;
+        if resp.status == StatusCode::UciStatusOk
+            && resp.config_status.iter().all(|status| status.status == StatusCode::UciStatusOk)
+        {
+            return Ok(());
+        }
+        for config_status in resp.config_status.iter() {
+            warn!(
+                "AppConfig {:?} is not applied: {:?}",
+                config_status.cfg_id, config_status.status
+            );
+        }
+
+        match self.session_set_app_config(session_id, rollback_tlvs).await {
+            Ok(rollback_resp) if rollback_resp.status == StatusCode::UciStatusOk => {}
+            rollback_result => {
+                error!(
+                    "Failed to restore the app config of session {}: {:?}",
+                    session_id, rollback_result
+                );
+            }
+        }
+        Err(status_code_to_result(resp.status).err().unwrap_or(Error::BadParameters))
+    }
+
+    /// Set |params| to the session, read the configs back, and return the configs which the UWBS
+    /// rejects or holds with a different value, e.g. clamped to the supported range. If |rollback|
+    /// is true, the configs are read before they are set and restored afterwards, so the session
+    /// is left unchanged.
+    pub async fn validate_params_on_device(
+        &self,
+        session_id: SessionId,
+        params: &AppConfigParams,
+        rollback: bool,
+    ) -> Result<Vec<FieldMismatch>> {
+        let mut tlvs = params.generate_tlvs();
+        tlvs.sort_by_key(|tlv| u8::from(tlv.cfg_id));
+        let prev_tlvs = match rollback {
+            true => {
+                let cfg_ids = tlvs.iter().map(|tlv| tlv.cfg_id).collect();
+                Some(self.session_get_app_config(session_id, cfg_ids).await?)
+            }
+            false => None,
+        };
+
+        let result = self.diff_params_on_device(session_id, tlvs).await;
+        if let Some(prev_tlvs) = prev_tlvs {
+            let resp = self.session_set_app_config(session_id, prev_tlvs).await?;
+            if resp.status != StatusCode::UciStatusOk {
+                error!("Failed to restore the app config of session {}: {:?}", session_id, resp);
+                return Err(status_code_to_result(resp.status).err().unwrap_or(Error::Unknown));
+            }
+        }
+        result
+    }
+
+    // Set the |tlvs| and compare them with the values read back from the UWBS.
+    async fn diff_params_on_device(
+        &self,
+        session_id: SessionId,
+        tlvs: Vec<AppConfigTlv>,
+    ) -> Result<Vec<FieldMismatch>> {
+        let resp = self.session_set_app_config(session_id, tlvs.clone()).await?;
+        if resp.status != StatusCode::UciStatusOk && resp.config_status.is_empty() {
+            return Err(status_code_to_result(resp.status).err().unwrap_or(Error::Unknown));
+        }
+        let rejected = resp.rejected();
+        let accepted_cfg_ids = tlvs
+            .iter()
+            .map(|tlv| tlv.cfg_id)
+            .filter(|cfg_id| !rejected.iter().any(|(id, _)| id == cfg_id))
+            .collect::<Vec<_>>();
+        let actual_tlvs = match accepted_cfg_ids.is_empty() {
+            true => vec![],
+            false => self.session_get_app_config(session_id, accepted_cfg_ids).await?,
+        };
+
+        Ok(tlvs
+            .iter()
+            .filter_map(|tlv| {
+                if let Some((_, status)) = rejected.iter().find(|(id, _)| *id == tlv.cfg_id) {
+                    return Some(FieldMismatch {
+                        cfg_id: tlv.cfg_id,
+                        requested: tlv.v.clone(),
+                        actual: None,
+                        status: *status,
+                    });
+                }
+                let actual = actual_tlvs
+                    .iter()
+                    .find(|actual_tlv| actual_tlv.cfg_id == tlv.cfg_id)
+                    .map(|actual_tlv| actual_tlv.v.clone());
+                (actual.as_ref() != Some(&tlv.v)).then(|| FieldMismatch {
+                    cfg_id: tlv.cfg_id,
+                    requested: tlv.v.clone(),
+                    actual,
+                    status: StatusCode::UciStatusOk,
+                })
+            })
+            .collect())
+    }
+
+    /// Get the country code of the UWBS, i.e. the last one set by android_set_country_code()
+    /// successfully. The UWBS doesn't report its country code, so it is tracked on the host, and
+    /// None is returned if it is not set since the UWBS is reset.
+    pub async fn get_country_code(&self) -> Option<CountryCode> {
+        self.country_code.lock().await.clone()
+    }
+
+    /// Query the current UWB time of the UWBS, in the unit of microseconds.
+    pub async fn get_uwb_time(&self) -> Result<u64> {
+        let cmd = UciCommand::CoreQueryTimeStamp;
+        match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
+            Ok(UciResponse::CoreQueryTimeStamp(resp)) => resp,
+            Ok(_) => Err(Error::Unknown),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Query the indexes of the ranging rounds which could be used by the DL-TDoA session, e.g.
+    /// before selecting the active rounds by session_update_dt_tag_ranging_rounds(). The UCI spec
+    /// doesn't define the query, so it is sent as the vendor command set by
+    /// set_vendor_command_ids(), and Error::NotSupported is returned if the command id is not set.
+    pub async fn session_get_possible_ranging_rounds(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Vec<u8>> {
+        let (gid, oid) =
+            self.vendor_command_ids.lock().unwrap().possible_ranging_rounds.ok_or_else(|| {
+                warn!("The command id of querying the possible ranging rounds is not set");
+                Error::NotSupported
+            })?;
+        let session_token = self.get_session_token(&session_id).await?;
+        let resp = self
+            .raw_uci_cmd(
+                u8::from(MessageType::Command).into(),
+                gid,
+                oid,
+                session_token.to_le_bytes().to_vec(),
+            )
+            .await?;
+        decode_possible_ranging_rounds(&resp.payload)
+    }
+
+    /// Start ranging of the session at the absolute UWB time |uwb_time|, in the unit of
+    /// microseconds. The time is set to the UWB_INITIATION_TIME app config before the session is
+    /// started, so the devices that share the same UWB time start ranging together. |uwb_time|
+    /// should be later than the current UWB time returned by get_uwb_time().
+    pub async fn range_start_at(&self, session_id: SessionId, uwb_time: u64) -> Result<()> {
+        let current_uwb_time = self.get_uwb_time().await?;
+        if uwb_time <= current_uwb_time {
+            error!(
+                "The start time {} of session {} is not later than the UWB time {}",
+                uwb_time, session_id, current_uwb_time
+            );
+            return Err(Error::BadParameters);
+        }
+
+        let tlv = AppConfigTlv::new(AppConfigTlvType::UwbInitiationTime, u64_to_bytes(uwb_time));
+        let resp = self.session_set_app_config(session_id, vec![tlv]).await?;
+        status_code_to_result(resp.status)?;
+        if let Some(config_status) =
+            resp.config_status.iter().find(|status| status.status != StatusCode::UciStatusOk)
+        {
+            error!("Failed to set the start time of session {}: {:?}", session_id, config_status);
+            return Err(status_code_to_result(config_status.status)
+                .err()
+                .unwrap_or(Error::BadParameters));
+        }
+        self.range_start(session_id).await
+    }
+
+    async fn query_max_data_size_from_caps(&self) -> Result<u16> {
+        self.core_get_caps_info()
+            .await?
+            .into_iter()
+            .find(|tlv| tlv.t == CapTlvType::SupportedMaxDataPacketPayloadSize)
+            .and_then(|tlv| bytes_to_u16(tlv.v))
+            .ok_or_else(|| {
+                error!("SUPPORTED_MAX_DATA_PACKET_PAYLOAD_SIZE is not found in the capabilities");
+                Error::Unknown
+            })
+    }
+
+    /// Get the clock accuracy of the UWBS in ppm, e.g. to estimate the distance error caused by
+    /// the clock drift. It is read from the vendor-specific capability set by
+    /// set_vendor_cap_ids(), and Error::NotSupported is returned if the capability id is not set
+    /// or the UWBS doesn't report it.
+    pub async fn clock_accuracy_ppm(&self) -> Result<f32> {
+        let cap_id = self.vendor_cap_ids.lock().unwrap().clock_accuracy.ok_or_else(|| {
+            warn!("The capability id of the clock accuracy is not set");
+            Error::NotSupported
+        })?;
+        let tlv =
+            self.core_get_caps_info().await?.into_iter().find(|tlv| tlv.t == cap_id).ok_or_else(
+                || {
+                    warn!("The clock accuracy is not found in the capabilities");
+                    Error::NotSupported
+                },
+            )?;
+        let value = bytes_to_u16(tlv.v).ok_or_else(|| {
+            error!("Invalid clock accuracy capability");
+            Error::Unknown
+        })?;
+        Ok(value as f32 / 100.0)
+    }
+
+    /// Force closing and reopening the UCI HAL, which recovers the UWBS after it reports
+    /// DEVICE_STATE_ERROR. All the commands except CORE_DEVICE_RESET_CMD fail with
+    /// Error::DeviceError until it succeeds, or the UWBS reports it is ready again.
+    pub async fn reset_and_reopen(&self) -> Result<()> {
+        self.close_hal(true).await?;
+        // The sessions are lost when the HAL is closed, even without CORE_DEVICE_RESET_CMD.
+        let session_ids = self.session_id_to_token_map.lock().await;
+        self.session_ids_before_reset.lock().await.extend(session_ids.keys());
+        drop(session_ids);
+        self.open_hal().await
+    }
+
+    /// Return a future which resolves once the UWBS reports DEVICE_STATE_READY (or
+    /// DEVICE_STATE_ACTIVE), so the readiness could be awaited separately from open_hal(). It
+    /// resolves immediately if the UWBS is already ready.
+    pub fn device_ready(&self) -> impl Future<Output = Result<()>> {
+        let mut device_state_receiver = self.device_state_receiver.clone();
+        async move {
+            device_state_receiver
+                .wait_for(|state| {
+                    matches!(
+                        state,
+                        Some(DeviceState::DeviceStateReady | DeviceState::DeviceStateActive)
+                    )
+                })
+                .await
+                .map(|_| ())
+                .map_err(|_| {
+                    error!("UciManagerActor is dropped before the UWBS is ready");
+                    Error::HalClosed
+                })
+        }
+    }
+
+    /// Open the UCI HAL like open_hal(), and retry up to |max_attempts| times in total with
+    /// |delay| between the attempts, e.g. while the UWBS is still booting. The HAL is force closed
+    /// before retrying when the readiness notification is not received, because the HAL is still
+    /// opened in that case. Return the error of the last attempt.
+    pub async fn open_hal_with_retry(&self, max_attempts: usize, delay: Duration) -> Result<()> {
+        let mut result = Err(Error::BadParameters);
+        for attempt in 1..=max_attempts {
+            result = self.open_hal().await;
+            let e = match &result {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            warn!("Failed to open the HAL, attempt {}/{}: {:?}", attempt, max_attempts, e);
+            if attempt == max_attempts {
+                break;
+            }
+            if *e == Error::Timeout {
+                self.close_hal(true).await?;
+            }
+            tokio::time::sleep(delay).await;
+        }
+        result
+    }
+
+    /// Get the max number of the sessions which could be initialized at the same time, from the
+    /// SUPPORTED_MAX_RANGING_SESSION_NUMBER capability. The capabilities are only queried when
+    /// they haven't been received yet. Once the value is known, session_init() fails with
+    /// Error::MaxSessionsExceeded without sending the command if there is no room for the session.
+    /// The CCC_SUPPORTED_MAX_RANGING_SESSION_NUMBER capability is applied to the CCC sessions in
+    /// the same way.
+    pub async fn max_sessions(&self) -> Result<u32> {
+        if let Some(max_sessions) = *self.max_sessions.lock().await {
+            return Ok(max_sessions);
+        }
+        self.core_get_caps_info().await?;
+        self.max_sessions.lock().await.ok_or_else(|| {
+            error!("SUPPORTED_MAX_RANGING_SESSION_NUMBER is not found in the capabilities");
+            Error::Unknown
+        })
+    }
+
+    /// Whether the UWBS supports the role in the data transfer, from the vendor-specific
+    /// capability set by set_vendor_cap_ids(). The capabilities are only queried when they
+    /// haven't been received yet. Once the roles are known, session_set_app_config() of a
+    /// FIRA_DATA_TRANSFER session fails with Error::BadParameters without sending the command if
+    /// the DEVICE_TYPE maps to an unsupported role.
+    pub async fn supports_data_transfer_role(&self, role: DataTransferRole) -> bool {
+        if self.data_transfer_roles.lock().await.is_none() {
+            if let Err(e) = self.core_get_caps_info().await {
+                error!("Failed to get the capabilities: {:?}", e);
+            }
+        }
+        match self.data_transfer_roles.lock().await.as_ref() {
+            Some(roles) => roles.contains(&role),
+            None => {
+                error!("The data transfer roles are not found in the capabilities");
+                false
+            }
+        }
+    }
+
+    // Reject the DEVICE_TYPE of a FIRA_DATA_TRANSFER session if it maps to a data transfer role
+    // which the UWBS doesn't support. Nothing is checked before the capabilities are received.
+    async fn check_data_transfer_role(
+        &self,
+        session_id: SessionId,
+        config_tlvs: &[AppConfigTlv],
+    ) -> Result<()> {
+        if self.session_types.lock().await.get(&session_id) != Some(&SessionType::FiraDataTransfer)
+        {
+            return Ok(());
+        }
+        let role = match config_tlvs
+            .iter()
+            .find(|tlv| tlv.cfg_id == AppConfigTlvType::DeviceType)
+            .and_then(|tlv| DataTransferRole::from_device_type(&tlv.v))
+        {
+            Some(role) => role,
+            None => return Ok(()),
+        };
+        match self.data_transfer_roles.lock().await.as_ref() {
+            Some(roles) if !roles.contains(&role) => {
+                error!(
+                    "Failed to configure the session {} as {:?}, the UWBS supports {:?}",
+                    session_id, role, roles
+                );
+                Err(Error::BadParameters)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the AoA antenna pairs of the session, in the order of (azimuth, elevation). The pair
+    /// is None when it is not set, i.e. the UWBS selects the antennas itself. It fails with
+    /// Error::NotSupported unless the ids are set by set_antenna_pair_ids(). Once the antenna
+    /// count capability is received, session_set_app_config() fails with Error::BadParameters
+    /// without sending the command if a pair refers to a missing antenna.
+    pub async fn session_get_aoa_antenna_pairs(
+        &self,
+        session_id: SessionId,
+    ) -> Result<(Option<AntennaPair>, Option<AntennaPair>)> {
+        let ids = self.antenna_pair_ids.lock().unwrap().ok_or_else(|| {
+            error!("The ids of the AoA antenna pairs are not set");
+            Error::NotSupported
+        })?;
+        let tlvs =
+            self.session_get_app_config(session_id, vec![ids.azimuth, ids.elevation]).await?;
+        let get_pair = |cfg_id: AppConfigTlvType| -> Result<Option<AntennaPair>> {
+            match tlvs.iter().find(|tlv| tlv.cfg_id == cfg_id) {
+                Some(tlv) => AntennaPair::from_bytes(&tlv.v).map(Some).ok_or_else(|| {
+                    error!("Invalid {:?}: {:?}", cfg_id, tlv.v);
+                    Error::Unknown
+                }),
+                None => Ok(None),
+            }
+        };
+        Ok((get_pair(ids.azimuth)?, get_pair(ids.elevation)?))
+    }
+
+    // Reject the AoA antenna pairs which refer to the antennas the UWBS doesn't have. Nothing is
+    // checked before the ids are set and the capabilities are received.
+    async fn check_antenna_pairs(&self, config_tlvs: &[AppConfigTlv]) -> Result<()> {
+        let ids = match *self.antenna_pair_ids.lock().unwrap() {
+            Some(ids) => ids,
+            None => return Ok(()),
+        };
+        let antenna_count = match *self.antenna_count.lock().await {
+            Some(count) => count,
+            None => return Ok(()),
+        };
+        for tlv in config_tlvs
+            .iter()
+            .filter(|tlv| tlv.cfg_id == ids.azimuth || tlv.cfg_id == ids.elevation)
+        {
+            match AntennaPair::from_bytes(&tlv.v) {
+                Some(pair) if pair.fits_antenna_count(antenna_count) => {}
+                _ => {
+                    error!(
+                        "Invalid {:?}: {:?}, the UWBS has {} antennas",
+                        tlv.cfg_id, tlv.v, antenna_count
+                    );
+                    return Err(Error::BadParameters);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check MAX_NUMBER_OF_MEASUREMENTS against the capability of the UWBS, once the capability is
+    // received. The value 0 means unlimited measurements, which is always accepted.
+    async fn check_max_number_of_measurements(&self, config_tlvs: &[AppConfigTlv]) -> Result<()> {
+        let upper_bound = match *self.max_number_of_measurements.lock().await {
+            Some(upper_bound) => upper_bound,
+            None => return Ok(()),
+        };
+        let tlv = match config_tlvs
+            .iter()
+            .find(|tlv| tlv.cfg_id == AppConfigTlvType::MaxNumberOfMeasurements)
+        {
+            Some(tlv) => tlv,
+            None => return Ok(()),
+        };
+        match bytes_to_u16(tlv.v.clone()) {
+            Some(value) if value <= upper_bound => Ok(()),
+            Some(value) => {
+                error!(
+                    "MAX_NUMBER_OF_MEASUREMENTS {} exceeds the capability {}",
+                    value, upper_bound
+                );
+                Err(Error::MaxNumberOfMeasurementsExceeded)
+            }
+            None => {
+                error!("Invalid MAX_NUMBER_OF_MEASUREMENTS: {:?}", tlv.v);
+                Err(Error::BadParameters)
+            }
+        }
+    }
+
+    /// Get the number of the STS key rotations of the session, from the latest key rotation
+    /// notification of the session. The UWBS doesn't report the count on request, so it is 0
+    /// before the first notification.
+    pub async fn session_get_key_rotation_count(&self, session_id: SessionId) -> Result<u32> {
+        let session_token = self.get_session_token(&session_id).await?;
+        Ok(self.key_rotation_counts.lock().unwrap().get(&session_token).copied().unwrap_or(0))
+    }
+
+    /// Get the type of the session. The UWBS doesn't report the session type, so the type given
+    /// to session_init() is returned, after SESSION_GET_STATE_CMD confirms the session still
+    /// exists on the UWBS. It is used to rebuild the session tracking after reconnecting the UWBS.
+    pub async fn session_get_type(&self, session_id: SessionId) -> Result<SessionType> {
+        let session_type = self.session_types.lock().await.get(&session_id).copied();
+        let session_type = session_type.ok_or_else(|| {
+            error!("The type of the session {} is unknown", session_id);
+            Error::BadParameters
+        })?;
+        self.session_get_state(session_id).await?;
+        Ok(session_type)
+    }
+
+    /// Read back the PHY parameters in effect on the UWBS by SESSION_GET_APP_CONFIG_CMD, which may
+    /// be adapted from the requested ones after the ranging is started.
+    pub async fn session_effective_phy(&self, session_id: SessionId) -> Result<PhyParams> {
+        let tlvs = self.session_get_app_config(session_id, PhyParams::TLV_TYPES.to_vec()).await?;
+        let config_map = tlvs
+            .into_iter()
+            .map(|tlv| {
+                let tlv = tlv.into_inner();
+                (tlv.cfg_id, tlv.v.clone())
+            })
+            .collect();
+        PhyParams::from_config_map(config_map).ok_or_else(|| {
+            error!("Failed to decode the PHY params of the session {}", session_id);
+            Error::Unknown
+        })
+    }
+
+    /// Read back whether the device is the controller or the controlee of the session by
+    /// SESSION_GET_APP_CONFIG_CMD, e.g. for the session found on the UWBS after reconnecting.
+    pub async fn session_device_type(&self, session_id: SessionId) -> Result<DeviceType> {
+        let tlvs =
+            self.session_get_app_config(session_id, vec![AppConfigTlvType::DeviceType]).await?;
+        tlvs.into_iter()
+            .map(|tlv| tlv.into_inner())
+            .find(|tlv| tlv.cfg_id == AppConfigTlvType::DeviceType)
+            .and_then(|tlv| bytes_to_u8(tlv.v.clone()).and_then(DeviceType::from_u8))
+            .ok_or_else(|| {
+                error!("Failed to read the device type of the session {}", session_id);
+                Error::Unknown
+            })
+    }
+
+    // Check there is room for initializing the session |session_id| of |session_type|, if the
+    // capabilities report the max number of the sessions. The CCC cap only applies to the CCC
+    // sessions.
+    async fn check_max_sessions(
+        &self,
+        session_id: SessionId,
+        session_type: SessionType,
+    ) -> Result<()> {
+        let mut caps = vec![(*self.max_sessions.lock().await, None)];
+        if session_type == SessionType::Ccc {
+            caps.push((*self.max_ccc_sessions.lock().await, Some(SessionType::Ccc)));
+        }
+        for (max_sessions, counted_type) in caps {
+            let max_sessions = match max_sessions {
+                Some(max_sessions) => max_sessions,
+                None => continue,
+            };
+            if self.initialized_session_count(session_id, counted_type).await
+                >= max_sessions as usize
+            {
+                error!(
+                    "Failed to initialize the session {}, the UWBS supports at most {} sessions",
+                    session_id, max_sessions
+                );
+                return Err(Error::MaxSessionsExceeded);
+            }
+        }
+        Ok(())
+    }
+
+    // Get the number of the initialized sessions except |session_id|, only of |session_type| if
+    // it is set. The sessions initialized before the last device reset are not counted.
+    async fn initialized_session_count(
+        &self,
+        session_id: SessionId,
+        session_type: Option<SessionType>,
+    ) -> usize {
+        let session_ids_before_reset = self.session_ids_before_reset.lock().await;
+        let session_types = self.session_types.lock().await;
+        self.session_id_to_token_map
+            .lock()
+            .await
+            .keys()
+            .filter(|id| **id != session_id && !session_ids_before_reset.contains(id))
+            .filter(|id| session_type.is_none() || session_types.get(id) == session_type.as_ref())
+            .count()
+    }
+
+    // Send the |cmd|, and keep the status code and the rest of the payload of the response,
+    // which are dropped when the response is decoded.
+    async fn send_cmd_with_outcome(&self, cmd: UciCommand) -> Result<CommandOutcome> {
+        let opcode = uci_cmd_opcode(&cmd);
+        let (raw_resp_sender, raw_resp_receiver) = oneshot::channel();
+        self.send_cmd(UciManagerCmd::SendUciCommandWithRawResp { cmd, raw_resp_sender }).await?;
+        let raw_payload = raw_resp_receiver.await.map_err(|_| Error::Unknown)?;
+        let (status, payload) = raw_payload.split_first().ok_or_else(|| {
+            error!("The response of the command {:?} is empty", opcode);
+            Error::Unknown
+        })?;
+        let status = StatusCode::try_from(*status).map_err(|_| {
+            error!("Unknown status {} of the command {:?}", status, opcode);
+            Error::Unknown
+        })?;
+        Ok(CommandOutcome { status, payload: payload.to_vec() })
+    }
+
+    // Send the |cmd| as a raw UCI command, and decode the response payload as the response of
+    // |cmd|. The raw payload is returned along with the decoded response.
+    async fn send_cmd_with_raw_resp(&self, cmd: UciCommand) -> Result<(UciResponse, Vec<u8>)> {
+        let packet = uwb_uci_packets::UciControlPacket::try_from(cmd)?;
+        let group_id = packet.get_group_id();
+        let opcode = packet.get_opcode();
+        let resp = self
+            .raw_uci_cmd(
+                u8::from(MessageType::Command).into(),
+                u8::from(group_id).into(),
+                opcode.into(),
+                packet.to_raw_payload(),
+            )
+            .await?;
+
+        let decoded = decode_raw_response(group_id, opcode, resp.payload.clone())?;
+        Ok((decoded, resp.payload))
+    }
+
     async fn get_session_token(&self, session_id: &SessionId) -> Result<SessionToken> {
         self.session_id_to_token_map
             .lock()
@@ -246,6 +1157,14 @@ impl UciManager for UciManagerImpl {
             .send_cmd(UciManagerCmd::SetDataRcvNotificationSender { data_rcv_notf_sender })
             .await;
     }
+    async fn set_ordered_notification_sender(
+        &mut self,
+        ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    ) {
+        let _ = self
+            .send_cmd(UciManagerCmd::SetOrderedNotificationSender { ordered_notf_sender })
+            .await;
+    }
 
     async fn open_hal(&self) -> Result<()> {
         match self.send_cmd(UciManagerCmd::OpenHal).await {
@@ -274,7 +1193,14 @@ impl UciManager for UciManagerImpl {
     async fn device_reset(&self, reset_config: ResetConfig) -> Result<()> {
         let cmd = UciCommand::DeviceReset { reset_config };
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::DeviceReset(resp)) => resp,
+            Ok(UciResponse::DeviceReset(resp)) => {
+                if resp.is_ok() {
+                    let session_ids = self.session_id_to_token_map.lock().await;
+                    self.session_ids_before_reset.lock().await.extend(session_ids.keys());
+                    self.country_code.lock().await.take();
+                }
+                resp
+            }
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
         }
@@ -283,7 +1209,26 @@ impl UciManager for UciManagerImpl {
     async fn core_get_device_info(&self) -> Result<GetDeviceInfoResponse> {
         let cmd = UciCommand::CoreGetDeviceInfo;
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::CoreGetDeviceInfo(resp)) => resp,
+            Ok(UciResponse::CoreGetDeviceInfo(resp)) => {
+                if let Ok(device_info) = &resp {
+                    self.uci_version.lock().await.replace(device_info.uci_version);
+                    if !is_supported_uci_version(device_info.uci_version) {
+                        if self.strict_uci_version_check.load(Ordering::Relaxed) {
+                            error!(
+                                "The UCI version {:#06x} of the UWBS is not supported",
+                                device_info.uci_version
+                            );
+                            return Err(Error::UnsupportedUciVersion);
+                        }
+                        warn!(
+                            "The UCI version {:#06x} of the UWBS is not supported, the packets \
+                             might be parsed incorrectly",
+                            device_info.uci_version
+                        );
+                    }
+                }
+                resp
+            }
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
         }
@@ -292,12 +1237,50 @@ impl UciManager for UciManagerImpl {
     async fn core_get_caps_info(&self) -> Result<Vec<CapTlv>> {
         let cmd = UciCommand::CoreGetCapsInfo;
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::CoreGetCapsInfo(resp)) => resp,
-            Ok(_) => Err(Error::Unknown),
-            Err(e) => Err(e),
-        }
-    }
-
+            Ok(UciResponse::CoreGetCapsInfo(resp)) => {
+                if let Some(max_sessions) = resp.as_ref().ok().and_then(|tlvs| {
+                    max_sessions(tlvs, CapTlvType::SupportedMaxRangingSessionNumber)
+                }) {
+                    self.max_sessions.lock().await.replace(max_sessions);
+                }
+                if let Some(max_sessions) = resp.as_ref().ok().and_then(|tlvs| {
+                    max_sessions(tlvs, CapTlvType::CccSupportedMaxRangingSessionNumber)
+                }) {
+                    self.max_ccc_sessions.lock().await.replace(max_sessions);
+                }
+                let cap_ids = *self.vendor_cap_ids.lock().unwrap();
+                if let Some(roles) = resp
+                    .as_ref()
+                    .ok()
+                    .zip(cap_ids.data_transfer_roles)
+                    .and_then(|(tlvs, cap_id)| DataTransferRole::from_caps(tlvs, cap_id))
+                {
+                    self.data_transfer_roles.lock().await.replace(roles);
+                }
+                let antenna_count_id =
+                    self.antenna_pair_ids.lock().unwrap().map(|ids| ids.antenna_count);
+                if let Some(count) =
+                    resp.as_ref().ok().zip(antenna_count_id).and_then(|(tlvs, cap_id)| {
+                        AntennaPair::antenna_count_from_caps(tlvs, cap_id)
+                    })
+                {
+                    self.antenna_count.lock().await.replace(count);
+                }
+                if let Some(max) = resp
+                    .as_ref()
+                    .ok()
+                    .zip(cap_ids.max_number_of_measurements)
+                    .and_then(|(tlvs, cap_id)| max_number_of_measurements(tlvs, cap_id))
+                {
+                    self.max_number_of_measurements.lock().await.replace(max);
+                }
+                resp
+            }
+            Ok(_) => Err(Error::Unknown),
+            Err(e) => Err(e),
+        }
+    }
+
     async fn core_set_config(
         &self,
         config_tlvs: Vec<DeviceConfigTlv>,
@@ -320,9 +1303,16 @@ impl UciManager for UciManagerImpl {
     }
 
     async fn session_init(&self, session_id: SessionId, session_type: SessionType) -> Result<()> {
+        self.check_max_sessions(session_id, session_type).await?;
         let cmd = UciCommand::SessionInit { session_id, session_type };
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::SessionInit(resp)) => resp.map(|_| {}),
+            Ok(UciResponse::SessionInit(resp)) => {
+                if resp.is_ok() {
+                    self.session_ids_before_reset.lock().await.remove(&session_id);
+                    self.session_types.lock().await.insert(session_id, session_type);
+                }
+                resp.map(|_| {})
+            }
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
         }
@@ -332,7 +1322,12 @@ impl UciManager for UciManagerImpl {
         let cmd =
             UciCommand::SessionDeinit { session_token: self.get_session_token(&session_id).await? };
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::SessionDeinit(resp)) => resp,
+            Ok(UciResponse::SessionDeinit(resp)) => {
+                if resp.is_ok() {
+                    self.session_types.lock().await.remove(&session_id);
+                }
+                resp
+            }
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
         }
@@ -343,6 +1338,13 @@ impl UciManager for UciManagerImpl {
         session_id: SessionId,
         config_tlvs: Vec<AppConfigTlv>,
     ) -> Result<SetAppConfigResponse> {
+        if let Some(tlv) = config_tlvs.iter().find(|tlv| tlv.cfg_id.is_read_only()) {
+            error!("The app config {:?} is read-only", tlv.cfg_id);
+            return Err(Error::ReadOnlyAppConfig);
+        }
+        self.check_data_transfer_role(session_id, &config_tlvs).await?;
+        self.check_antenna_pairs(&config_tlvs).await?;
+        self.check_max_number_of_measurements(&config_tlvs).await?;
         let cmd = UciCommand::SessionSetAppConfig {
             session_token: self.get_session_token(&session_id).await?,
             config_tlvs,
@@ -359,15 +1361,27 @@ impl UciManager for UciManagerImpl {
         session_id: SessionId,
         app_cfg: Vec<AppConfigTlvType>,
     ) -> Result<Vec<AppConfigTlv>> {
-        let cmd = UciCommand::SessionGetAppConfig {
-            session_token: self.get_session_token(&session_id).await?,
-            app_cfg,
+        // The empty list requests all the app configs, which is sent in one command. Otherwise the
+        // config ids which don't fit in one command are read by the following commands, and the
+        // responses are merged. Stop at the first failed command.
+        let app_cfg_chunks = match app_cfg.is_empty() {
+            true => vec![vec![]],
+            false => app_cfg
+                .chunks(MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD)
+                .map(|chunk| chunk.to_vec())
+                .collect(),
         };
-        match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::SessionGetAppConfig(resp)) => resp,
-            Ok(_) => Err(Error::Unknown),
-            Err(e) => Err(e),
+        let session_token = self.get_session_token(&session_id).await?;
+        let mut tlvs = vec![];
+        for app_cfg in app_cfg_chunks {
+            let cmd = UciCommand::SessionGetAppConfig { session_token, app_cfg };
+            match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
+                Ok(UciResponse::SessionGetAppConfig(resp)) => tlvs.extend(resp?),
+                Ok(_) => return Err(Error::Unknown),
+                Err(e) => return Err(e),
+            }
         }
+        Ok(tlvs)
     }
 
     async fn session_get_count(&self) -> Result<u8> {
@@ -384,6 +1398,14 @@ impl UciManager for UciManagerImpl {
             session_token: self.get_session_token(&session_id).await?,
         };
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
+            // The UWBS responds UciStatusSessionNotExist, which is mapped to ProtocolSpecific,
+            // for the sessions that were dropped by the device reset.
+            Ok(UciResponse::SessionGetState(Err(Error::ProtocolSpecific)))
+                if self.report_deinit_after_reset.load(Ordering::Relaxed)
+                    && self.session_ids_before_reset.lock().await.contains(&session_id) =>
+            {
+                Ok(SessionState::SessionStateDeinit)
+            }
             Ok(UciResponse::SessionGetState(resp)) => resp,
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
@@ -401,20 +1423,26 @@ impl UciManager for UciManagerImpl {
             Controlees::ShortSessionKey(ref controlee_vec) => controlee_vec.len(),
             Controlees::LongSessionKey(ref controlee_vec) => controlee_vec.len(),
         };
-        if !(1..=8).contains(&controlees_len) {
-            warn!("Number of controlees should be between 1 to 8");
+        if controlees_len == 0 {
+            warn!("Number of controlees should not be 0");
             return Err(Error::BadParameters);
         }
-        let cmd = UciCommand::SessionUpdateControllerMulticastList {
-            session_token: self.get_session_token(&session_id).await?,
-            action,
-            controlees,
-        };
-        match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::SessionUpdateControllerMulticastList(resp)) => resp,
-            Ok(_) => Err(Error::Unknown),
-            Err(e) => Err(e),
+        // The controlees which don't fit in one command are sent by the following commands. Stop
+        // at the first failed command.
+        let session_token = self.get_session_token(&session_id).await?;
+        for controlees in split_controlees(controlees, MAX_CONTROLEES_PER_MULTICAST_LIST_CMD) {
+            let cmd = UciCommand::SessionUpdateControllerMulticastList {
+                session_token,
+                action,
+                controlees,
+            };
+            match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
+                Ok(UciResponse::SessionUpdateControllerMulticastList(resp)) => resp?,
+                Ok(_) => return Err(Error::Unknown),
+                Err(e) => return Err(e),
+            }
         }
+        Ok(())
     }
 
     async fn session_update_dt_tag_ranging_rounds(
@@ -434,6 +1462,13 @@ impl UciManager for UciManagerImpl {
     }
 
     async fn session_query_max_data_size(&self, session_id: SessionId) -> Result<u16> {
+        // SESSION_QUERY_DATA_SIZE_IN_RANGING_CMD is introduced in UCI 2.0, fall back to the
+        // SUPPORTED_MAX_DATA_PACKET_PAYLOAD_SIZE capability for the earlier UWBS.
+        let uci_version = *self.uci_version.lock().await;
+        if matches!(uci_version, Some(uci_version) if uci_major_version(uci_version) < 2) {
+            return self.query_max_data_size_from_caps().await;
+        }
+
         let cmd = UciCommand::SessionQueryMaxDataSize {
             session_token: self.get_session_token(&session_id).await?,
         };
@@ -444,6 +1479,37 @@ impl UciManager for UciManagerImpl {
         }
     }
 
+    async fn session_set_hybrid_config(
+        &self,
+        session_id: SessionId,
+        config: HybridSessionConfig,
+    ) -> Result<()> {
+        if !config.is_valid() {
+            error!("The hybrid session config is invalid: {:?}", config);
+            return Err(Error::BadParameters);
+        }
+        // The phases are configured with the session ids of the sub-sessions, which are mapped
+        // to the session tokens as the controller session.
+        let mut phase_list = vec![];
+        for phase in config.phases {
+            phase_list.push(PhaseList {
+                session_token: self.get_session_token(&phase.session_id).await?,
+                start_slot_index: phase.start_slot_index,
+                end_slot_index: phase.end_slot_index,
+            });
+        }
+        let cmd = UciCommand::SessionSetHybridConfig {
+            session_token: self.get_session_token(&session_id).await?,
+            update_time: config.update_time.to_le_bytes(),
+            phase_list,
+        };
+        match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
+            Ok(UciResponse::SessionSetHybridConfig(resp)) => resp,
+            Ok(_) => Err(Error::Unknown),
+            Err(e) => Err(e),
+        }
+    }
+
     async fn range_start(&self, session_id: SessionId) -> Result<()> {
         let cmd =
             UciCommand::SessionStart { session_token: self.get_session_token(&session_id).await? };
@@ -476,9 +1542,14 @@ impl UciManager for UciManagerImpl {
     }
 
     async fn android_set_country_code(&self, country_code: CountryCode) -> Result<()> {
-        let cmd = UciCommand::AndroidSetCountryCode { country_code };
+        let cmd = UciCommand::AndroidSetCountryCode { country_code: country_code.clone() };
         match self.send_cmd(UciManagerCmd::SendUciCommand { cmd }).await {
-            Ok(UciResponse::AndroidSetCountryCode(resp)) => resp,
+            Ok(UciResponse::AndroidSetCountryCode(resp)) => {
+                if resp.is_ok() {
+                    self.country_code.lock().await.replace(country_code);
+                }
+                resp
+            }
             Ok(_) => Err(Error::Unknown),
             Err(e) => Err(e),
         }
@@ -554,9 +1625,15 @@ struct UciManagerActor<T: UciHal, U: UciLogger> {
     logger: UciLoggerWrapper<U>,
     // Receive the commands and the corresponding response senders from UciManager.
     cmd_receiver: mpsc::UnboundedReceiver<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
+    // Receive the CloseHal commands from UciManager, even when there is a pending command.
+    close_sender: mpsc::UnboundedSender<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
+    close_receiver: mpsc::UnboundedReceiver<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
 
     // Set to true when |hal| is opened successfully.
     is_hal_opened: bool,
+    // Set to true when the UWBS reports DEVICE_STATE_ERROR after |hal| is opened. The commands
+    // fail fast until |hal| is reopened.
+    is_device_error: bool,
     // Receive response, notification and data packets from |mut hal|. Only used when |hal| is opened
     // successfully.
     packet_receiver: mpsc::UnboundedReceiver<UciHalPacket>,
@@ -578,12 +1655,26 @@ struct UciManagerActor<T: UciHal, U: UciLogger> {
     // The timeout of waiting for the notification of device ready notification.
     wait_device_status_timeout: PinSleep,
 
-    // Used for the logic of retrying the command. Only valid when waiting for the response of a
-    // UCI command.
-    uci_cmd_retryer: Option<UciCmdRetryer>,
-    // The timeout of waiting for the response. Only used when waiting for the response of a UCI
-    // command.
+    // Used for the logic of retrying the commands, in the order of sending. Each of them is waiting
+    // for the response of a UCI command.
+    in_flight_cmds: Vec<UciCmdRetryer>,
+    // The max number of |in_flight_cmds|, shared with UciManagerImpl.
+    max_in_flight_cmds: Arc<AtomicUsize>,
+    // The number of |in_flight_cmds|, shared with UciManagerImpl.
+    in_flight_cmd_count: Arc<AtomicUsize>,
+    // Whether to drop the duplicated or stale SessionRangeData, shared with UciManagerImpl.
+    range_data_sequence_check: Arc<AtomicBool>,
+    // The sequence number of the last forwarded SessionRangeData of each session.
+    last_range_data_sequence_numbers: HashMap<SessionToken, u32>,
+    // The command which is received from UciManager, but can't be handled until some of the
+    // in-flight commands are responded.
+    pending_cmd: Option<(UciManagerCmd, oneshot::Sender<Result<UciResponse>>)>,
+    // The timeout of waiting for the earliest response. Only used when waiting for the response of
+    // a UCI command.
     wait_resp_timeout: PinSleep,
+    // The latency statistics of the UCI commands, shared with UciManagerImpl.
+    #[cfg(feature = "latency-stats")]
+    latency_stats: Arc<std::sync::Mutex<HashMap<UciOpcode, LatencyStats>>>,
 
     // Used for the logic of retrying the DataSnd packet. Only valid when waiting for the
     // DATA_TRANSFER_STATUS_NTF.
@@ -593,11 +1684,31 @@ struct UciManagerActor<T: UciHal, U: UciLogger> {
     // a raw packet as a response to the sender.
     last_raw_cmd: Option<RawUciControlPacket>,
 
+    // The opcode of the last sent UCI command, which is the command that the generic error
+    // notification with the COMMAND_RETRY status refers to.
+    last_sent_cmd_opcode: Option<UciOpcode>,
+
     // Send the notifications to the caller of UciManager.
     core_notf_sender: mpsc::UnboundedSender<CoreNotification>,
     session_notf_sender: mpsc::UnboundedSender<SessionNotification>,
     vendor_notf_sender: mpsc::UnboundedSender<RawUciMessage>,
     data_rcv_notf_sender: mpsc::UnboundedSender<DataRcvNotification>,
+    // Send the core, session and vendor notifications in order instead of the senders above.
+    ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    // The decoder of the link qualities in the SessionRangeData, shared with UciManagerImpl.
+    link_quality_decoder: Arc<std::sync::Mutex<Option<Arc<dyn LinkQualityDecoder>>>>,
+    // The ids of the vendor notifications to decode, shared with UciManagerImpl.
+    vendor_notification_ids: Arc<std::sync::Mutex<VendorNotificationIds>>,
+    // The last failed UCI commands, shared with UciManagerImpl.
+    recent_errors: Arc<std::sync::Mutex<RecentErrors>>,
+    // The middleware of the outgoing UCI commands, shared with UciManagerImpl.
+    command_middleware: Arc<std::sync::Mutex<Option<Arc<CommandMiddleware>>>>,
+    // The latest STS key rotation count of each session, shared with UciManagerImpl.
+    key_rotation_counts: Arc<std::sync::Mutex<HashMap<SessionToken, u32>>>,
+    // Publish the device state of the latest DEVICE_STATUS_NTF to UciManagerImpl.
+    device_state_sender: watch::Sender<Option<DeviceState>>,
+    // The one-shot matchers of the vendor notifications, keyed by the GID and OID.
+    vendor_notf_matchers: HashMap<(u32, u32), VecDeque<oneshot::Sender<RawUciMessage>>>,
 
     // Used to store the last init session id to help map the session handle sent
     // in session int response can be correctly mapped.
@@ -618,26 +1729,48 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
             oneshot::Sender<Result<UciResponse>>,
         )>,
         session_id_to_token_map: Arc<Mutex<HashMap<SessionId, SessionToken>>>,
+        max_in_flight_cmds: Arc<AtomicUsize>,
+        range_data_sequence_check: Arc<AtomicBool>,
     ) -> Self {
+        let (close_sender, close_receiver) = mpsc::unbounded_channel();
         Self {
             hal: TimeoutUciHal::new(hal),
             logger: UciLoggerWrapper::new(logger, logger_mode),
             cmd_receiver,
+            close_sender,
+            close_receiver,
             is_hal_opened: false,
+            is_device_error: false,
             packet_receiver: mpsc::unbounded_channel().1,
             defrager: Default::default(),
             open_hal_result_sender: None,
             data_credit_map: HashMap::new(),
             data_packet_fragments_map: HashMap::new(),
             wait_device_status_timeout: PinSleep::new(Duration::MAX),
-            uci_cmd_retryer: None,
+            in_flight_cmds: vec![],
+            max_in_flight_cmds,
+            in_flight_cmd_count: Arc::new(AtomicUsize::new(0)),
+            range_data_sequence_check,
+            last_range_data_sequence_numbers: HashMap::new(),
+            pending_cmd: None,
             uci_data_snd_retryer: None,
             wait_resp_timeout: PinSleep::new(Duration::MAX),
+            #[cfg(feature = "latency-stats")]
+            latency_stats: Default::default(),
             last_raw_cmd: None,
+            last_sent_cmd_opcode: None,
             core_notf_sender: mpsc::unbounded_channel().0,
             session_notf_sender: mpsc::unbounded_channel().0,
             vendor_notf_sender: mpsc::unbounded_channel().0,
             data_rcv_notf_sender: mpsc::unbounded_channel().0,
+            ordered_notf_sender: None,
+            link_quality_decoder: Default::default(),
+            vendor_notification_ids: Default::default(),
+            recent_errors: Default::default(),
+            command_middleware: Default::default(),
+            key_rotation_counts: Default::default(),
+            device_state_sender: watch::channel(None).0,
+            vendor_notf_matchers: HashMap::new(),
             last_init_session_id: None,
             session_id_to_token_map,
         }
@@ -645,21 +1778,37 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
 
     async fn run(&mut self) {
         loop {
+            // Handle the pending command once the conflicting in-flight commands are responded.
+            if matches!(&self.pending_cmd, Some((cmd, _)) if self.could_handle_cmd(cmd)) {
+                if let Some((cmd, result_sender)) = self.pending_cmd.take() {
+                    self.handle_cmd(cmd, result_sender).await;
+                }
+            }
+
             tokio::select! {
-                // Handle the next command. Only when the previous command already received the
-                // response.
-                cmd = self.cmd_receiver.recv(), if !self.is_waiting_resp() => {
+                // Handle the next command. Only when there is no pending command and the number
+                // of the in-flight commands doesn't reach the limit.
+                cmd = self.cmd_receiver.recv(), if self.could_receive_cmd() => {
                     match cmd {
                         None => {
                             debug!("UciManager is about to drop.");
                             break;
                         },
                         Some((cmd, result_sender)) => {
-                            self.handle_cmd(cmd, result_sender).await;
+                            if self.could_handle_cmd(&cmd) {
+                                self.handle_cmd(cmd, result_sender).await;
+                            } else {
+                                self.pending_cmd = Some((cmd, result_sender));
+                            }
                         }
                     }
                 }
 
+                // Handle CloseHal, even when there is a pending command.
+                Some((cmd, result_sender)) = self.close_receiver.recv() => {
+                    self.handle_cmd(cmd, result_sender).await;
+                }
+
                 // Handle the UCI response, notification or data packet from HAL. Only when HAL
                 // is opened.
                 packet = self.packet_receiver.recv(), if self.is_hal_opened => {
@@ -668,9 +1817,7 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
 
                 // Timeout waiting for the response of the UCI command.
                 _ = &mut self.wait_resp_timeout, if self.is_waiting_resp() => {
-                    if let Some(uci_cmd_retryer) = self.uci_cmd_retryer.take() {
-                        uci_cmd_retryer.send_result(Err(Error::Timeout));
-                    }
+                    self.handle_wait_resp_timeout();
                 }
 
                 // Timeout waiting for the notification of the device status.
@@ -771,6 +1918,14 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                 self.data_rcv_notf_sender = data_rcv_notf_sender;
                 let _ = result_sender.send(Ok(UciResponse::SetNotification));
             }
+            UciManagerCmd::SetOrderedNotificationSender { ordered_notf_sender } => {
+                self.ordered_notf_sender = ordered_notf_sender;
+                let _ = result_sender.send(Ok(UciResponse::SetNotification));
+            }
+            UciManagerCmd::RegisterVendorNotificationMatcher { gid, oid, notf_sender } => {
+                self.vendor_notf_matchers.entry((gid, oid)).or_default().push_back(notf_sender);
+                let _ = result_sender.send(Ok(UciResponse::SetNotification));
+            }
             UciManagerCmd::OpenHal => {
                 if self.is_hal_opened {
                     warn!("The UCI HAL is already opened, skip.");
@@ -819,72 +1974,168 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
             }
 
             UciManagerCmd::SendUciCommand { cmd } => {
-                debug_assert!(self.uci_cmd_retryer.is_none());
-
-                self.save_session_id_if_init_cmd(&cmd);
-
-                // Remember that this command is a raw UCI command, we'll use this later
-                // to send a raw UCI response.
-                if let UciCommand::RawUciCmd { mt: _, gid, oid, payload: _ } = cmd.clone() {
-                    let gid_u8 = u8::try_from(gid);
-                    if gid_u8.is_err() || GroupId::try_from(gid_u8.unwrap()).is_err() {
-                        error!("Received an invalid GID={} for RawUciCmd", gid);
-                        let _ = result_sender.send(Err(Error::BadParameters));
-                        return;
-                    }
-
-                    let oid_u8 = u8::try_from(oid);
-                    if oid_u8.is_err() {
-                        error!("Received an invalid OID={} for RawUciCmd", oid);
-                        let _ = result_sender.send(Err(Error::BadParameters));
-                        return;
-                    }
-                    self.last_raw_cmd = Some(RawUciControlPacket {
-                        mt: u8::from(MessageType::Command),
-                        gid: gid_u8.unwrap(), // Safe as we check gid_u8.is_err() above.
-                        oid: oid_u8.unwrap(), // Safe as we check uid_i8.is_err() above.
-                        payload: Vec::new(),  // There's no need to store the Raw UCI CMD's payload.
-                    });
-                }
-
-                self.uci_cmd_retryer =
-                    Some(UciCmdRetryer { cmd, result_sender, retry_count: MAX_RETRY_COUNT });
-
-                // Reset DataSndRetryer so if a CORE_GENERIC_ERROR_NTF with STATUS_UCI_PACKET_RETRY
-                // is received, only this UCI CMD packet will be retried.
-                let _ = self.uci_data_snd_retryer.take();
-
-                self.retry_uci_cmd().await;
+                self.handle_send_uci_cmd(cmd, result_sender, None).await;
+            }
+            UciManagerCmd::SendUciCommandWithRawResp { cmd, raw_resp_sender } => {
+                self.handle_send_uci_cmd(cmd, result_sender, Some(raw_resp_sender)).await;
             }
 
             UciManagerCmd::SendUciData { data_snd_packet } => {
+                if self.is_device_error {
+                    error!("The UWBS is in the error state, drop the data packet");
+                    let _ = result_sender.send(Err(Error::DeviceError));
+                    return;
+                }
                 let result = self.handle_data_snd_packet(data_snd_packet).await;
                 let _ = result_sender.send(result);
             }
         }
     }
 
-    async fn retry_uci_cmd(&mut self) {
-        if let Some(mut uci_cmd_retryer) = self.uci_cmd_retryer.take() {
-            if !uci_cmd_retryer.could_retry() {
-                error!("Out of retries for Uci Cmd packet");
-                uci_cmd_retryer.send_result(Err(Error::Timeout));
+    async fn handle_send_uci_cmd(
+        &mut self,
+        cmd: UciCommand,
+        result_sender: oneshot::Sender<Result<UciResponse>>,
+        raw_resp_sender: Option<oneshot::Sender<Vec<u8>>>,
+    ) {
+        // CORE_DEVICE_RESET_CMD is the way to recover the UWBS from the error state.
+        if self.is_device_error && !matches!(cmd, UciCommand::DeviceReset { .. }) {
+            error!("The UWBS is in the error state, drop the command {:?}", cmd);
+            let _ = result_sender.send(Err(Error::DeviceError));
+            return;
+        }
+        self.save_session_id_if_init_cmd(&cmd);
+
+        // Remember that this command is a raw UCI command, we'll use this later
+        // to send a raw UCI response.
+        if let UciCommand::RawUciCmd { mt: _, gid, oid, payload: _ } = cmd.clone() {
+            let gid_u8 = u8::try_from(gid);
+            if gid_u8.is_err() || GroupId::try_from(gid_u8.unwrap()).is_err() {
+                error!("Received an invalid GID={} for RawUciCmd", gid);
+                let _ = result_sender.send(Err(Error::BadParameters));
+                return;
+            }
+
+            let oid_u8 = u8::try_from(oid);
+            if oid_u8.is_err() {
+                error!("Received an invalid OID={} for RawUciCmd", oid);
+                let _ = result_sender.send(Err(Error::BadParameters));
                 return;
             }
+            self.last_raw_cmd = Some(RawUciControlPacket {
+                mt: u8::from(MessageType::Command),
+                gid: gid_u8.unwrap(), // Safe as we check gid_u8.is_err() above.
+                oid: oid_u8.unwrap(), // Safe as we check uid_i8.is_err() above.
+                payload: Vec::new(),  // There's no need to store the Raw UCI CMD's payload.
+            });
+        }
 
-            match self.send_uci_command(uci_cmd_retryer.cmd.clone()).await {
-                Ok(_) => {
-                    self.wait_resp_timeout = PinSleep::new(Duration::from_millis(UCI_TIMEOUT_MS));
-                    self.uci_cmd_retryer = Some(uci_cmd_retryer);
-                }
-                Err(e) => {
-                    error!("Uci Cmd send resulted in error:{}", e);
-                    uci_cmd_retryer.send_result(Err(e));
-                }
+        let uci_cmd_retryer = UciCmdRetryer {
+            opcode: uci_cmd_opcode(&cmd),
+            cmd,
+            result_sender,
+            raw_resp_sender,
+            retry_count: MAX_RETRY_COUNT,
+            deadline: Instant::now(),
+            #[cfg(feature = "latency-stats")]
+            sent_at: Instant::now(),
+        };
+
+        // Reset DataSndRetryer so if a CORE_GENERIC_ERROR_NTF with STATUS_UCI_PACKET_RETRY
+        // is received, only this UCI CMD packet will be retried.
+        let _ = self.uci_data_snd_retryer.take();
+
+        self.retry_uci_cmd(uci_cmd_retryer).await;
+    }
+
+    async fn retry_uci_cmd(&mut self, mut uci_cmd_retryer: UciCmdRetryer) {
+        if !uci_cmd_retryer.could_retry() {
+            error!("Out of retries for Uci Cmd packet");
+            self.send_cmd_result(uci_cmd_retryer, Err(Error::Timeout));
+            return;
+        }
+
+        if uci_cmd_retryer.raw_resp_sender.is_some() {
+            // Receive the response as a raw packet, since the payload is trimmed to the known
+            // fields when it is decoded.
+            self.last_raw_cmd = uci_cmd_retryer.opcode.map(|(gid, oid)| RawUciControlPacket {
+                mt: u8::from(MessageType::Command),
+                gid: u8::from(gid),
+                oid,
+                payload: Vec::new(),
+            });
+        }
+        match self.send_uci_command(uci_cmd_retryer.cmd.clone()).await {
+            Ok(_) => {
+                uci_cmd_retryer.deadline = Instant::now() + Duration::from_millis(UCI_TIMEOUT_MS);
+                self.last_sent_cmd_opcode = uci_cmd_retryer.opcode;
+                self.in_flight_cmds.push(uci_cmd_retryer);
+                self.update_in_flight_cmd_count();
+                self.reset_wait_resp_timeout();
+            }
+            Err(e) => {
+                error!("Uci Cmd send resulted in error:{}", e);
+                self.send_cmd_result(uci_cmd_retryer, Err(e));
             }
         }
     }
 
+    // Reply Timeout to the in-flight commands which reach the deadline.
+    fn handle_wait_resp_timeout(&mut self) {
+        let now = Instant::now();
+        let (expired_cmds, in_flight_cmds) = std::mem::take(&mut self.in_flight_cmds)
+            .into_iter()
+            .partition(|retryer| retryer.deadline <= now);
+        self.in_flight_cmds = in_flight_cmds;
+        self.update_in_flight_cmd_count();
+        for uci_cmd_retryer in expired_cmds {
+            self.send_cmd_result(uci_cmd_retryer, Err(Error::Timeout));
+        }
+        self.reset_wait_resp_timeout();
+    }
+
+    // Wait for the earliest deadline of the in-flight commands.
+    fn reset_wait_resp_timeout(&mut self) {
+        if let Some(deadline) = self.in_flight_cmds.iter().map(|retryer| retryer.deadline).min() {
+            self.wait_resp_timeout =
+                PinSleep::new(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    // Take the in-flight command which the response with |opcode| belongs to.
+    fn take_in_flight_cmd(&mut self, opcode: Option<UciOpcode>) -> Option<UciCmdRetryer> {
+        let index = match self.in_flight_cmds.iter().position(|retryer| retryer.opcode == opcode) {
+            Some(index) => index,
+            // Keep the behavior without pipelining, that the response belongs to the only command.
+            None if self.in_flight_cmds.len() == 1 => 0,
+            None => return None,
+        };
+        let uci_cmd_retryer = self.in_flight_cmds.remove(index);
+        self.update_in_flight_cmd_count();
+        Some(uci_cmd_retryer)
+    }
+
+    fn update_in_flight_cmd_count(&self) {
+        self.in_flight_cmd_count.store(self.in_flight_cmds.len(), Ordering::Relaxed);
+    }
+
+    // Reply the result to the command, and record it when the command fails.
+    fn send_cmd_result(&self, uci_cmd_retryer: UciCmdRetryer, result: Result<UciResponse>) {
+        let error = match &result {
+            Ok(resp) => resp.error(),
+            Err(e) => Some(e.clone()),
+        };
+        if let (Some(error), Some((gid, oid))) = (error, uci_cmd_retryer.opcode) {
+            self.recent_errors.lock().unwrap().push(CommandError {
+                gid,
+                oid,
+                error,
+                timestamp: SystemTime::now(),
+            });
+        }
+        uci_cmd_retryer.send_result(result);
+    }
+
     async fn retry_uci_data_snd(&mut self) {
         if let Some(mut uci_data_snd_retryer) = self.uci_data_snd_retryer.take() {
             let data_packet_session_token = uci_data_snd_retryer.data_packet_session_token;
@@ -915,6 +2166,7 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
             warn!("The UCI HAL is already closed, skip.");
             return Err(Error::BadParameters);
         }
+        let cmd = self.apply_command_middleware(cmd);
         let result = self.hal.send_command(cmd.clone()).await;
         if result.is_ok() {
             self.logger.log_uci_command(&cmd);
@@ -922,6 +2174,32 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
         result
     }
 
+    // Let the middleware rewrite the command. The command is unchanged if there is no middleware,
+    // or the middleware doesn't modify it.
+    fn apply_command_middleware(&self, cmd: UciCommand) -> UciCommand {
+        let middleware = match self.command_middleware.lock().unwrap().clone() {
+            Some(middleware) => middleware,
+            None => return cmd,
+        };
+        let packet = match uwb_uci_packets::UciControlPacket::try_from(cmd.clone()) {
+            Ok(packet) => packet,
+            Err(_) => return cmd,
+        };
+        let msg: RawUciMessage = packet.into();
+        let mut rewritten_msg = msg.clone();
+        middleware(&mut rewritten_msg);
+        if rewritten_msg == msg {
+            return cmd;
+        }
+        debug!("The command is rewritten by the middleware: {:?}", rewritten_msg);
+        UciCommand::RawUciCmd {
+            mt: u8::from(MessageType::Command).into(),
+            gid: rewritten_msg.gid,
+            oid: rewritten_msg.oid,
+            payload: rewritten_msg.payload,
+        }
+    }
+
     async fn handle_data_snd_packet(&mut self, data_snd_packet: UciDataSnd) -> Result<UciResponse> {
         // Verify that there's an entry for the Session in the CreditAvailability map.
         let data_packet_session_token = data_snd_packet.get_session_token();
@@ -1043,9 +2321,10 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
             UciDefragPacket::Control(packet) => {
                 self.logger.log_uci_response_or_notification(&packet);
 
+                let opcode = (packet.get_group_id(), packet.get_opcode());
                 match packet.try_into() {
                     Ok(UciMessage::Response(resp)) => {
-                        self.handle_response(resp).await;
+                        self.handle_response(resp, Some(opcode), None).await;
                     }
                     Ok(UciMessage::Notification(notf)) => {
                         self.handle_notification(notf).await;
@@ -1060,6 +2339,26 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                 self.handle_data_rcv(packet);
             }
             UciDefragPacket::Raw(result, raw_uci_control_packet) => {
+                self.last_raw_cmd = None;
+                let opcode = GroupId::try_from(raw_uci_control_packet.gid)
+                    .ok()
+                    .map(|gid| (gid, raw_uci_control_packet.oid));
+                // The typed command waiting for the raw payload gets the decoded response too.
+                let wait_raw_resp = self
+                    .in_flight_cmds
+                    .iter()
+                    .any(|retryer| retryer.opcode == opcode && retryer.raw_resp_sender.is_some());
+                if let (true, Ok(()), Some((gid, oid))) = (wait_raw_resp, &result, opcode) {
+                    let payload = raw_uci_control_packet.payload;
+                    match decode_raw_response(gid, oid, payload.clone()) {
+                        Ok(resp) => self.handle_response(resp, opcode, Some(payload)).await,
+                        Err(e) => {
+                            self.handle_response(UciResponse::RawUciCmd(Err(e)), opcode, None).await
+                        }
+                    }
+                    return;
+                }
+
                 // Handle response to raw UCI cmd. We want to send it back as
                 // raw UCI message instead of standard response message.
                 let resp = match result {
@@ -1075,41 +2374,83 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                     // lib.rs and defined in the PDL uci_packets.rs) and the uwb_core::Error enums.
                     Err(_) => UciResponse::RawUciCmd(Err(Error::Unknown)),
                 };
-                self.handle_response(resp).await;
-                self.last_raw_cmd = None;
+                self.handle_response(resp, opcode, None).await;
             }
         }
     }
 
-    async fn handle_response(&mut self, resp: UciResponse) {
+    async fn handle_response(
+        &mut self,
+        resp: UciResponse,
+        opcode: Option<UciOpcode>,
+        raw_payload: Option<Vec<u8>>,
+    ) {
+        let mut uci_cmd_retryer = match self.take_in_flight_cmd(opcode) {
+            Some(uci_cmd_retryer) => uci_cmd_retryer,
+            None => {
+                warn!("Received an UCI response unexpectedly: {:?}", resp);
+                return;
+            }
+        };
         if resp.need_retry() {
-            self.retry_uci_cmd().await;
+            self.retry_uci_cmd(uci_cmd_retryer).await;
             return;
         }
-        if let Err(_e) = self.store_session_token_if_init_resp(&resp).await {
+        if let Err(e) = self.store_session_token_if_init_resp(&resp).await {
             error!("Session init response received without a sesson id stored! Something has gone badly wrong: {:?}", resp);
+            self.send_cmd_result(uci_cmd_retryer, Err(e));
+            self.reset_wait_resp_timeout();
             return;
         }
 
-        if let Some(uci_cmd_retryer) = self.uci_cmd_retryer.take() {
-            uci_cmd_retryer.send_result(Ok(resp));
-        } else {
-            warn!("Received an UCI response unexpectedly: {:?}", resp);
+        #[cfg(feature = "latency-stats")]
+        if let Some(opcode) = uci_cmd_retryer.opcode {
+            self.latency_stats
+                .lock()
+                .unwrap()
+                .entry(opcode)
+                .or_default()
+                .record(uci_cmd_retryer.sent_at.elapsed());
+        }
+        if let (Some(raw_resp_sender), Some(raw_payload)) =
+            (uci_cmd_retryer.raw_resp_sender.take(), raw_payload)
+        {
+            let _ = raw_resp_sender.send(raw_payload);
         }
+        self.send_cmd_result(uci_cmd_retryer, Ok(resp));
+        self.reset_wait_resp_timeout();
     }
 
     async fn handle_notification(&mut self, notf: UciNotification) {
         if notf.need_retry() {
             // Retry sending both last sent UCI CMD and UCI DataSnd packet since the notification
             // could be for either of them.
-            self.retry_uci_cmd().await;
+            // The in-flight commands other than the last sent one are not retried, because the
+            // notification doesn't carry the opcode.
+            let last_sent_cmd_opcode = self.last_sent_cmd_opcode;
+            if let Some(index) = self
+                .in_flight_cmds
+                .iter()
+                .position(|r| r.opcode.is_some() && r.opcode == last_sent_cmd_opcode)
+            {
+                let uci_cmd_retryer = self.in_flight_cmds.remove(index);
+                self.update_in_flight_cmd_count();
+                self.retry_uci_cmd(uci_cmd_retryer).await;
+            }
             self.retry_uci_data_snd().await;
             return;
         }
 
+        let notf = match notf {
+            UciNotification::Vendor(raw) => {
+                decode_vendor_notification(raw, &self.vendor_notification_ids.lock().unwrap())
+            }
+            _ => notf,
+        };
         match notf {
             UciNotification::Core(core_notf) => {
                 if let CoreNotification::DeviceStatus(status) = core_notf {
+                    self.device_state_sender.send_replace(Some(status));
                     if let Some(result_sender) = self.open_hal_result_sender.take() {
                         let result = match status {
                             DeviceState::DeviceStateReady | DeviceState::DeviceStateActive => {
@@ -1118,11 +2459,19 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                             _ => Err(Error::Unknown),
                         };
                         let _ = result_sender.send(result);
+                    } else {
+                        match status {
+                            DeviceState::DeviceStateError => self.on_device_error(),
+                            DeviceState::DeviceStateReady | DeviceState::DeviceStateActive => {
+                                self.is_device_error = false
+                            }
+                        }
                     }
                 }
-                let _ = self.core_notf_sender.send(core_notf);
+                self.send_notification(UciNotification::Core(core_notf));
             }
             UciNotification::Session(orig_session_notf) => {
+                let received_at = SystemTime::now();
                 let mod_session_notf = {
                     match self
                         .replace_session_token_with_session_id(orig_session_notf.clone())
@@ -1176,14 +2525,84 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                         // Reset the UciDataSnd Retryer since we received a DataTransferStatusNtf.
                         let _ = self.uci_data_snd_retryer.take();
                     }
+                    SessionNotification::KeyRotation { session_token, rotation_count } => {
+                        self.key_rotation_counts
+                            .lock()
+                            .unwrap()
+                            .insert(session_token, rotation_count);
+                    }
+                    SessionNotification::SessionInfo(range_data)
+                        if !self.check_range_data_sequence(
+                            range_data.session_token,
+                            range_data.sequence_number,
+                        ) =>
+                    {
+                        return;
+                    }
                     _ => {}
                 }
-                let _ = self.session_notf_sender.send(mod_session_notf);
+                let mod_session_notf = stamp_received_time(mod_session_notf, received_at);
+                let session_notf = self.decode_link_qualities(mod_session_notf);
+                self.send_notification(UciNotification::Session(session_notf));
             }
             UciNotification::Vendor(vendor_notf) => {
-                let _ = self.vendor_notf_sender.send(vendor_notf);
+                if let Some(vendor_notf) = self.match_vendor_notification(vendor_notf) {
+                    self.send_notification(UciNotification::Vendor(vendor_notf));
+                }
+            }
+        }
+    }
+
+    // Send the notification to the caller, via the ordered sender if it is set.
+    fn send_notification(&self, notf: UciNotification) {
+        if let Some(ordered_notf_sender) = self.ordered_notf_sender.as_ref() {
+            let _ = ordered_notf_sender.send(notf);
+            return;
+        }
+        match notf {
+            UciNotification::Core(notf) => {
+                let _ = self.core_notf_sender.send(notf);
             }
+            UciNotification::Session(notf) => {
+                let _ = self.session_notf_sender.send(notf);
+            }
+            UciNotification::Vendor(notf) => {
+                let _ = self.vendor_notf_sender.send(notf);
+            }
+        }
+    }
+
+    // Fill the link qualities of the SessionRangeData if a LinkQualityDecoder is registered.
+    fn decode_link_qualities(&self, session_notf: SessionNotification) -> SessionNotification {
+        match (session_notf, self.link_quality_decoder.lock().unwrap().as_ref()) {
+            (SessionNotification::SessionInfo(mut range_data), Some(decoder)) => {
+                range_data.decode_link_qualities(decoder.as_ref());
+                SessionNotification::SessionInfo(range_data)
+            }
+            (session_notf, _) => session_notf,
+        }
+    }
+
+    // Resolve the oldest live matcher of the vendor notification. Return the notification back if
+    // there is no matcher for it.
+    fn match_vendor_notification(&mut self, vendor_notf: RawUciMessage) -> Option<RawUciMessage> {
+        let key = (vendor_notf.gid, vendor_notf.oid);
+        let matchers = match self.vendor_notf_matchers.get_mut(&key) {
+            Some(matchers) => matchers,
+            None => return Some(vendor_notf),
+        };
+        let mut vendor_notf = Some(vendor_notf);
+        while let Some(notf_sender) = matchers.pop_front() {
+            // The caller might have dropped the receiver, then try the next matcher.
+            match notf_sender.send(vendor_notf.take().unwrap()) {
+                Ok(()) => break,
+                Err(notf) => vendor_notf = Some(notf),
+            }
+        }
+        if matchers.is_empty() {
+            self.vendor_notf_matchers.remove(&key);
         }
+        vendor_notf
     }
 
     // Modify session_token field in all session related notifications with session id.
@@ -1220,6 +2639,9 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                     ranging_measurements: session_range_data.ranging_measurements,
                     rcr_indicator: session_range_data.rcr_indicator,
                     raw_ranging_data: session_range_data.raw_ranging_data,
+                    vendor_data: session_range_data.vendor_data,
+                    link_qualities: session_range_data.link_qualities,
+                    received_at: session_range_data.received_at,
                 }))
             }
             SessionNotification::DataTransferStatus {
@@ -1237,6 +2659,19 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
                     credit_availability,
                 })
             }
+            SessionNotification::KeyRotation { session_token, rotation_count } => {
+                Ok(SessionNotification::KeyRotation {
+                    session_token: self.get_session_id(&session_token).await?,
+                    rotation_count,
+                })
+            }
+            SessionNotification::Diagnostics { session_token, sequence_number, frame_reports } => {
+                Ok(SessionNotification::Diagnostics {
+                    session_token: self.get_session_id(&session_token).await?,
+                    sequence_number,
+                    frame_reports,
+                })
+            }
         }
     }
 
@@ -1254,17 +2689,51 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
             SessionState::SessionStateActive => {
                 self.data_credit_map.insert(session_token, CreditAvailability::CreditAvailable);
                 self.data_packet_fragments_map.insert(session_token, VecDeque::new());
+                // The sequence number starts with 0 when the ranging is started.
+                self.last_range_data_sequence_numbers.remove(&session_token);
             }
             SessionState::SessionStateIdle => {
                 self.data_credit_map.remove(&session_token);
                 self.data_packet_fragments_map.remove(&session_token);
             }
             SessionState::SessionStateDeinit => {
+                self.last_range_data_sequence_numbers.remove(&session_token);
+                self.key_rotation_counts.lock().unwrap().remove(&session_token);
                 self.remove_session_token(&session_token).await;
             }
         }
     }
 
+    // Return false if the range data should be dropped because it's not newer than the last
+    // forwarded one of the session.
+    fn check_range_data_sequence(
+        &mut self,
+        session_token: SessionToken,
+        sequence_number: u32,
+    ) -> bool {
+        if !self.range_data_sequence_check.load(Ordering::Relaxed) {
+            return true;
+        }
+        match self.last_range_data_sequence_numbers.get(&session_token) {
+            Some(&last) if sequence_number <= last => {
+                warn!(
+                    "Drop the range data of session {} with sequence number {}, the last one is {}",
+                    session_token, sequence_number, last
+                );
+                return false;
+            }
+            Some(&last) if sequence_number - last > 1 => {
+                warn!(
+                    "The range data of session {} are missing between sequence number {} and {}",
+                    session_token, last, sequence_number
+                );
+            }
+            _ => {}
+        }
+        self.last_range_data_sequence_numbers.insert(session_token, sequence_number);
+        true
+    }
+
     fn handle_data_rcv(&mut self, packet: UciDataPacket) {
         match packet.try_into() {
             Ok(data_rcv) => {
@@ -1278,17 +2747,67 @@ impl<T: UciHal, U: UciLogger> UciManagerActor<T, U> {
 
     fn on_hal_open(&mut self, packet_receiver: mpsc::UnboundedReceiver<UciHalPacket>) {
         self.is_hal_opened = true;
+        self.is_device_error = false;
         self.packet_receiver = packet_receiver;
     }
 
+    fn on_device_error(&mut self) {
+        error!("The UWBS reports DEVICE_STATE_ERROR, fail the commands until it is reset");
+        self.is_device_error = true;
+
+        // The UWBS won't respond to the in-flight commands.
+        let in_flight_cmds = std::mem::take(&mut self.in_flight_cmds);
+        self.update_in_flight_cmd_count();
+        for uci_cmd_retryer in in_flight_cmds {
+            self.send_cmd_result(uci_cmd_retryer, Err(Error::DeviceError));
+        }
+    }
+
     fn on_hal_closed(&mut self) {
         self.is_hal_opened = false;
+        self.device_state_sender.send_replace(None);
         self.packet_receiver = mpsc::unbounded_channel().1;
         self.last_raw_cmd = None;
+
+        // The responses of the in-flight commands will never be received.
+        let in_flight_cmds = std::mem::take(&mut self.in_flight_cmds);
+        self.update_in_flight_cmd_count();
+        for uci_cmd_retryer in in_flight_cmds {
+            self.send_cmd_result(uci_cmd_retryer, Err(Error::HalClosed));
+        }
+        // The pending command was waiting for the in-flight commands, fail it as well.
+        if let Some((_, result_sender)) = self.pending_cmd.take() {
+            let _ = result_sender.send(Err(Error::HalClosed));
+        }
     }
 
     fn is_waiting_resp(&self) -> bool {
-        self.uci_cmd_retryer.is_some()
+        !self.in_flight_cmds.is_empty()
+    }
+    // The number of the in-flight commands is limited by could_handle_cmd(), and CloseHal is
+    // received from |close_receiver|, so it could still be received when the limit is reached.
+    fn could_receive_cmd(&self) -> bool {
+        self.pending_cmd.is_none()
+    }
+    // Only the UCI commands are pipelined, all the other requests wait for the in-flight commands,
+    // except CloseHal which fails the in-flight commands.
+    fn could_handle_cmd(&self, cmd: &UciManagerCmd) -> bool {
+        if self.in_flight_cmds.is_empty() {
+            return true;
+        }
+        match cmd {
+            UciManagerCmd::CloseHal { .. } => true,
+            UciManagerCmd::SendUciCommand { cmd } => {
+                let opcode = uci_cmd_opcode(cmd);
+                self.in_flight_cmds.len() < self.max_in_flight_cmds.load(Ordering::Relaxed)
+                    && could_pipeline_cmd(cmd)
+                    && self
+                        .in_flight_cmds
+                        .iter()
+                        .all(|retryer| could_pipeline_cmd(&retryer.cmd) && retryer.opcode != opcode)
+            }
+            _ => false,
+        }
     }
     fn is_waiting_device_status(&self) -> bool {
         self.open_hal_result_sender.is_some()
@@ -1302,10 +2821,172 @@ impl<T: UciHal, U: UciLogger> Drop for UciManagerActor<T, U> {
     }
 }
 
+// The major version is in the first octet of the UCI version.
+fn uci_major_version(uci_version: u16) -> u8 {
+    (uci_version & 0xff) as u8
+}
+
+fn is_supported_uci_version(uci_version: u16) -> bool {
+    SUPPORTED_UCI_MAJOR_VERSIONS.contains(&uci_major_version(uci_version))
+}
+
+// Decode the SUPPORTED_MAX_RANGING_SESSION_NUMBER or CCC_SUPPORTED_MAX_RANGING_SESSION_NUMBER
+// capability |cap_type|, which is in little-endian.
+fn max_sessions(tlvs: &[CapTlv], cap_type: CapTlvType) -> Option<u32> {
+    let tlv = tlvs.iter().find(|tlv| tlv.t == cap_type)?;
+    if tlv.v.is_empty() || tlv.v.len() > 4 {
+        error!("Invalid {:?}: {:?}", cap_type, tlv.v);
+        return None;
+    }
+    Some(tlv.v.iter().rev().fold(0, |acc, byte| (acc << 8) | *byte as u32))
+}
+
+// Decode the upper bound of MAX_NUMBER_OF_MEASUREMENTS from the capability |cap_id|, which is in
+// little-endian.
+fn max_number_of_measurements(tlvs: &[CapTlv], cap_id: CapTlvType) -> Option<u16> {
+    let tlv = tlvs.iter().find(|tlv| tlv.t == cap_id)?;
+    bytes_to_u16(tlv.v.clone()).or_else(|| {
+        error!("Invalid max number of measurements capability: {:?}", tlv.v);
+        None
+    })
+}
+
+// Split the controlees into the lists with at most |max_len| controlees.
+fn split_controlees(controlees: Controlees, max_len: usize) -> Vec<Controlees> {
+    match controlees {
+        Controlees::NoSessionKey(controlee_vec) => controlee_vec
+            .chunks(max_len)
+            .map(|chunk| Controlees::NoSessionKey(chunk.to_vec()))
+            .collect(),
+        Controlees::ShortSessionKey(controlee_vec) => controlee_vec
+            .chunks(max_len)
+            .map(|chunk| Controlees::ShortSessionKey(chunk.to_vec()))
+            .collect(),
+        Controlees::LongSessionKey(controlee_vec) => controlee_vec
+            .chunks(max_len)
+            .map(|chunk| Controlees::LongSessionKey(chunk.to_vec()))
+            .collect(),
+    }
+}
+
+// Decode the response payload of the vendor command querying the possible ranging rounds, which
+// is the status, the number of the indexes, and the 1-byte indexes.
+fn decode_possible_ranging_rounds(payload: &[u8]) -> Result<Vec<u8>> {
+    let (status, count, indexes) = match payload {
+        [status, count, indexes @ ..] => (*status, *count as usize, indexes),
+        _ => {
+            error!("The possible ranging rounds response is too short: {:?}", payload);
+            return Err(Error::Unknown);
+        }
+    };
+    let status = StatusCode::try_from(status).map_err(|_| {
+        error!("Invalid status of the possible ranging rounds response: {:?}", status);
+        Error::Unknown
+    })?;
+    status_code_to_result(status)?;
+    if indexes.len() != count {
+        error!("The possible ranging rounds response has {} indexes, not {}", indexes.len(), count);
+        return Err(Error::Unknown);
+    }
+    Ok(indexes.to_vec())
+}
+
+// Decode the raw |payload| of the response to the command with |group_id| and |opcode|.
+fn decode_raw_response(group_id: GroupId, opcode: u8, payload: Vec<u8>) -> Result<UciResponse> {
+    let resp_packet = uwb_uci_packets::build_uci_control_packet(
+        MessageType::Response,
+        group_id,
+        opcode,
+        Some(payload.into()),
+    )
+    .ok_or(Error::Unknown)?;
+    let resp_packet =
+        uwb_uci_packets::UciControlPacket::parse(&resp_packet.to_bytes()).map_err(|e| {
+            error!("Failed to parse the response of gid={:?} oid={}: {:?}", group_id, opcode, e);
+            Error::Unknown
+        })?;
+    match resp_packet.try_into()? {
+        UciMessage::Response(decoded) => Ok(decoded),
+        UciMessage::Notification(_) => Err(Error::Unknown),
+    }
+}
+
+fn uci_cmd_opcode(cmd: &UciCommand) -> Option<UciOpcode> {
+    uwb_uci_packets::UciControlPacket::try_from(cmd.clone())
+        .ok()
+        .map(|packet| (packet.get_group_id(), packet.get_opcode()))
+}
+
+// SESSION_INIT relies on |last_init_session_id| to map the session token, and the raw UCI command
+// relies on |last_raw_cmd| to parse the response, so they are never pipelined. Neither is
+// DEVICE_RESET, which drops all the states of the UWBS.
+fn could_pipeline_cmd(cmd: &UciCommand) -> bool {
+    !matches!(
+        cmd,
+        UciCommand::SessionInit { .. }
+            | UciCommand::DeviceReset { .. }
+            | UciCommand::RawUciCmd { .. }
+    )
+}
+
+// Stamp the SessionRangeData with the host time when it is received.
+fn stamp_received_time(
+    session_notf: SessionNotification,
+    received_at: SystemTime,
+) -> SessionNotification {
+    match session_notf {
+        SessionNotification::SessionInfo(range_data) => {
+            SessionNotification::SessionInfo(SessionRangeData {
+                received_at: Some(received_at),
+                ..range_data
+            })
+        }
+        session_notf => session_notf,
+    }
+}
+
+// The ring buffer of the last failed UCI commands.
+struct RecentErrors {
+    errors: VecDeque<CommandError>,
+    capacity: usize,
+}
+
+impl Default for RecentErrors {
+    fn default() -> Self {
+        Self { errors: VecDeque::new(), capacity: DEFAULT_RECENT_ERRORS_CAPACITY }
+    }
+}
+
+impl RecentErrors {
+    fn push(&mut self, error: CommandError) {
+        self.errors.push_back(error);
+        self.truncate();
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.errors.len() > self.capacity {
+            self.errors.pop_front();
+        }
+    }
+}
+
 struct UciCmdRetryer {
     cmd: UciCommand,
+    opcode: Option<UciOpcode>,
     result_sender: oneshot::Sender<Result<UciResponse>>,
+    // Receives the raw payload of the response along with the decoded one, if set.
+    raw_resp_sender: Option<oneshot::Sender<Vec<u8>>>,
     retry_count: usize,
+    // The deadline of waiting for the response.
+    deadline: Instant,
+    // The time when the command is sent at the first time.
+    #[cfg(feature = "latency-stats")]
+    sent_at: Instant,
 }
 
 impl UciCmdRetryer {
@@ -1361,6 +3042,14 @@ enum UciManagerCmd {
     SetDataRcvNotificationSender {
         data_rcv_notf_sender: mpsc::UnboundedSender<DataRcvNotification>,
     },
+    SetOrderedNotificationSender {
+        ordered_notf_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    },
+    RegisterVendorNotificationMatcher {
+        gid: u32,
+        oid: u32,
+        notf_sender: oneshot::Sender<RawUciMessage>,
+    },
     OpenHal,
     CloseHal {
         force: bool,
@@ -1368,6 +3057,10 @@ enum UciManagerCmd {
     SendUciCommand {
         cmd: UciCommand,
     },
+    SendUciCommandWithRawResp {
+        cmd: UciCommand,
+        raw_resp_sender: oneshot::Sender<Vec<u8>>,
+    },
     SendUciData {
         data_snd_packet: UciDataSnd,
     },
@@ -1381,12 +3074,18 @@ mod tests {
     use tokio::macros::support::Future;
     use uwb_uci_packets::{SessionGetCountCmdBuilder, SessionGetCountRspBuilder};
 
+    use crate::params::fira_app_config_params::{
+        FiraAppConfigParamsBuilder, PreambleDuration, PrfMode, PsduDataRate, UwbChannel,
+    };
     use crate::params::uci_packets::{
-        AppConfigStatus, AppConfigTlvType, CapTlvType, Controlee, DataTransferNtfStatusCode,
-        StatusCode,
+        AppConfigStatus, AppConfigTlvType, CapTlvType, CoexistenceSource, Controlee,
+        DataTransferNtfStatusCode, HybridSessionPhase, ReasonCode, StatusCode,
     };
+    use crate::params::utils::{u16_to_bytes, u32_to_bytes};
+    use crate::session::session_manager::test_utils::generate_params;
     use crate::uci::mock_uci_hal::MockUciHal;
     use crate::uci::mock_uci_logger::{MockUciLogger, UciLogEvent};
+    use crate::uci::notification::{LinkQuality, RangingMeasurements};
     use crate::uci::uci_logger::NopUciLogger;
     use crate::utils::init_test_logging;
 
@@ -1463,10 +3162,167 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_open_hal_without_notification() {
-        init_test_logging();
-
-        let mut hal = MockUciHal::new();
+    async fn test_device_error() {
+        let caps_resp = || {
+            into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                status: StatusCode::UciStatusOk,
+                tlvs: vec![],
+            })
+        };
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The firmware crashes instead of responding to the command.
+                let notf = into_uci_hal_packets(uwb_uci_packets::DeviceStatusNtfBuilder {
+                    device_state: DeviceState::DeviceStateError,
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, notf, Ok(()));
+                hal.expected_close(Ok(()));
+                setup_hal_for_open(&mut hal);
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, caps_resp(), Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        // Both the in-flight command and the following command fail without the timeout.
+        let timeout = Duration::from_millis(UCI_TIMEOUT_MS / 2);
+        let result = tokio::time::timeout(timeout, uci_manager.core_get_caps_info()).await;
+        assert_eq!(result, Ok(Err(Error::DeviceError)));
+        let result = tokio::time::timeout(timeout, uci_manager.core_get_caps_info()).await;
+        assert_eq!(result, Ok(Err(Error::DeviceError)));
+
+        assert!(uci_manager.reset_and_reopen().await.is_ok());
+        assert_eq!(uci_manager.core_get_caps_info().await, Ok(vec![]));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_device_error_recovered_by_device_reset() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let notf = into_uci_hal_packets(uwb_uci_packets::DeviceStatusNtfBuilder {
+                    device_state: DeviceState::DeviceStateError,
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, notf, Ok(()));
+
+                // The UWBS is ready again after the reset.
+                let cmd = UciCommand::DeviceReset { reset_config: ResetConfig::UwbsReset };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::DeviceResetRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                });
+                resp.append(&mut into_uci_hal_packets(uwb_uci_packets::DeviceStatusNtfBuilder {
+                    device_state: DeviceState::DeviceStateReady,
+                }));
+                hal.expected_send_command(cmd, resp, Ok(()));
+
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![],
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        assert_eq!(uci_manager.core_get_caps_info().await, Err(Error::DeviceError));
+        assert_eq!(uci_manager.device_reset(ResetConfig::UwbsReset).await, Ok(()));
+        assert_eq!(uci_manager.device_ready().await, Ok(()));
+        assert_eq!(uci_manager.core_get_caps_info().await, Ok(vec![]));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_device_ready() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let notf = into_uci_hal_packets(uwb_uci_packets::DeviceStatusNtfBuilder {
+                    device_state: DeviceState::DeviceStateError,
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, notf, Ok(()));
+                hal.expected_close(Ok(()));
+                setup_hal_for_open(&mut hal);
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        // The UWBS is ready after open_hal().
+        assert_eq!(uci_manager.device_ready().await, Ok(()));
+
+        // The UWBS crashes, and is ready again after reopening the HAL.
+        assert_eq!(uci_manager.core_get_caps_info().await, Err(Error::DeviceError));
+        let device_ready = tokio::spawn(uci_manager.device_ready());
+        let timeout = Duration::from_millis(100);
+        assert!(tokio::time::timeout(timeout, uci_manager.device_ready()).await.is_err());
+        assert!(uci_manager.reset_and_reopen().await.is_ok());
+        assert_eq!(device_ready.await.unwrap(), Ok(()));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_coexistence_ntf() {
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![],
+                });
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::UciVendor_F_NotificationBuilder {
+                        opcode: 0x21,
+                        payload: Some(Bytes::from(vec![0x00, 0x01, 0x64, 0x00])),
+                    },
+                ));
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        let (core_notf_sender, mut core_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_core_notification_sender(core_notf_sender).await;
+        uci_manager.set_vendor_notification_ids(VendorNotificationIds {
+            coexistence: Some((0xf, 0x21)),
+            ..Default::default()
+        });
+
+        assert!(uci_manager.core_get_caps_info().await.is_ok());
+        assert_eq!(
+            core_notf_receiver.recv().await,
+            Some(CoreNotification::Coexistence {
+                source: CoexistenceSource::Wifi,
+                preempted: true,
+                duration_ms: 100,
+            })
+        );
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_open_hal_with_retry() {
+        init_test_logging();
+
+        let mut hal = MockUciHal::new();
+        // The first attempt fails while the UWBS is booting.
+        hal.expected_open(None, Err(Error::Unknown));
+        setup_hal_for_open(&mut hal);
+        let uci_manager =
+            UciManagerImpl::new(hal.clone(), NopUciLogger::default(), UciLoggerMode::Disabled);
+
+        let result = uci_manager.open_hal_with_retry(3, Duration::from_millis(10)).await;
+        assert!(result.is_ok());
+        assert!(hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_open_hal_without_notification() {
+        init_test_logging();
+
+        let mut hal = MockUciHal::new();
         hal.expected_open(None, Ok(()));
         let uci_manager =
             UciManagerImpl::new(hal.clone(), NopUciLogger::default(), UciLoggerMode::Disabled);
@@ -1492,6 +3348,72 @@ mod tests {
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
+    #[tokio::test]
+    async fn test_close_hal_with_in_flight_command() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The response of the command is never received.
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, vec![], Ok(()));
+                hal.expected_close(Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        let start = Instant::now();
+        let cmd_manager = uci_manager.clone();
+        let cmd_task = tokio::spawn(async move { cmd_manager.core_get_device_info().await });
+        while uci_manager.pending_command_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(uci_manager.close_hal(false).await.is_ok());
+        assert_eq!(cmd_task.await.unwrap(), Err(Error::HalClosed));
+        assert!(start.elapsed() < Duration::from_millis(UCI_TIMEOUT_MS));
+        assert_eq!(uci_manager.pending_command_count(), 0);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_close_hal_with_pending_command() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The responses of the in-flight commands are never received.
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, vec![], Ok(()));
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+                hal.expected_close(Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        uci_manager.set_max_in_flight_commands(2);
+
+        let start = Instant::now();
+        let cmd_manager = uci_manager.clone();
+        let device_info_task =
+            tokio::spawn(async move { cmd_manager.core_get_device_info().await });
+        let cmd_manager = uci_manager.clone();
+        let count_task = tokio::spawn(async move { cmd_manager.session_get_count().await });
+        while uci_manager.pending_command_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        // SessionInit is not pipelined, it waits for the in-flight commands.
+        let cmd_manager = uci_manager.clone();
+        let init_task = tokio::spawn(async move {
+            cmd_manager.session_init(0x123, SessionType::FiraRangingSession).await
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(uci_manager.close_hal(false).await.is_ok());
+        assert_eq!(device_info_task.await.unwrap(), Err(Error::HalClosed));
+        assert_eq!(count_task.await.unwrap(), Err(Error::HalClosed));
+        assert_eq!(init_task.await.unwrap(), Err(Error::HalClosed));
+        assert!(start.elapsed() < Duration::from_millis(UCI_TIMEOUT_MS));
+        assert_eq!(uci_manager.pending_command_count(), 0);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
     #[tokio::test]
     async fn test_close_hal_when_exit() {
         let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
@@ -1582,6 +3504,45 @@ mod tests {
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
+    #[tokio::test]
+    async fn test_core_get_device_info_raw() {
+        // The vendor_spec_info is followed by the bytes the UWBS appended in a newer firmware.
+        let payload = vec![
+            0x00, 0x34, 0x12, 0x78, 0x56, 0xab, 0x90, 0x57, 0x13, 0x02, 0x01, 0x02, 0xfe, 0xed,
+        ];
+        let payload_clone = payload.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let cmd = UciCommand::RawUciCmd {
+                    mt: u8::from(MessageType::Command).into(),
+                    gid: u8::from(GroupId::Core).into(),
+                    oid: 0x2,
+                    payload: vec![],
+                };
+                let resp = build_uci_packet(0x2, 0, 0x0, 0x2, payload_clone);
+                hal.expected_send_command(cmd, vec![resp], Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        let (device_info, raw) = uci_manager.core_get_device_info_raw().await.unwrap();
+        assert_eq!(
+            device_info,
+            GetDeviceInfoResponse {
+                uci_version: 0x1234,
+                mac_version: 0x5678,
+                phy_version: 0x90ab,
+                uci_test_version: 0x1357,
+                vendor_spec_info: vec![0x1, 0x2],
+            }
+        );
+        assert_eq!(raw, payload);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
     #[tokio::test]
     async fn test_core_get_caps_info_ok() {
         let tlv = CapTlv { t: CapTlvType::SupportedFiraPhyVersionRange, v: vec![0x12, 0x34, 0x56] };
@@ -1608,103 +3569,452 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_core_set_config_ok() {
-        let tlv = DeviceConfigTlv {
-            cfg_id: uwb_uci_packets::DeviceConfigId::DeviceState,
-            v: vec![0x12, 0x34, 0x56],
-        };
-        let tlv_clone = tlv.clone();
-        let status = StatusCode::UciStatusOk;
-        let config_status = vec![];
-        let config_status_clone = config_status.clone();
+    async fn test_max_sessions() {
+        let session_id = 0x123;
+        let session_token = 0x123;
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::CoreSetConfig { config_tlvs: vec![tlv_clone] };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SetConfigRspBuilder {
-                    status,
-                    cfg_status: config_status_clone,
+                let cmd = UciCommand::CoreGetCapsInfo;
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv {
+                        t: CapTlvType::SupportedMaxRangingSessionNumber,
+                        v: vec![0x01],
+                    }],
                 });
-
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
         )
         .await;
 
-        let expected_result = CoreSetConfigResponse { status, config_status };
-        let result = uci_manager.core_set_config(vec![tlv]).await.unwrap();
-        assert_eq!(result, expected_result);
+        // The capabilities are queried only once.
+        assert_eq!(uci_manager.max_sessions().await, Ok(1));
+        assert_eq!(uci_manager.max_sessions().await, Ok(1));
+
+        // The second session is rejected without sending SESSION_INIT_CMD.
+        assert_eq!(
+            uci_manager.session_init(0x456, SessionType::FiraRangingSession).await,
+            Err(Error::MaxSessionsExceeded)
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_core_get_config_ok() {
-        let cfg_id = DeviceConfigId::DeviceState;
-        let tlv = DeviceConfigTlv { cfg_id, v: vec![0x12, 0x34, 0x56] };
-        let tlv_clone = tlv.clone();
+    async fn test_max_ccc_sessions() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let fira_session_id = 0x456;
+        let ccc_session_id = 0x789;
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::CoreGetConfig { cfg_id: vec![cfg_id] };
-                let resp = into_uci_hal_packets(uwb_uci_packets::GetConfigRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    tlvs: vec![tlv_clone],
+                let cmd = UciCommand::CoreGetCapsInfo;
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv {
+                        t: CapTlvType::CccSupportedMaxRangingSessionNumber,
+                        v: vec![0x01],
+                    }],
                 });
-
                 hal.expected_send_command(cmd, resp, Ok(()));
+                for (session_id, session_type) in [
+                    (fira_session_id, SessionType::FiraRangingSession),
+                    (ccc_session_id, SessionType::Ccc),
+                ] {
+                    let cmd = UciCommand::SessionInit { session_id, session_type };
+                    let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionInitRspBuilder {
+                        status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    });
+                    resp
+                        .append(&mut into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+                        session_token: session_id,
+                        session_state: uwb_uci_packets::SessionState::SessionStateInit,
+                        reason_code:
+                            uwb_uci_packets::ReasonCode::StateChangeWithSessionManagementCommands
+                                .into(),
+                    }));
+                    hal.expected_send_command(cmd, resp, Ok(()));
+                    hal.expected_notify_session_initialized(session_id, Ok(()));
+                }
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
         )
         .await;
 
-        let expected_result = vec![tlv];
-        let result = uci_manager.core_get_config(vec![cfg_id]).await.unwrap();
-        assert_eq!(result, expected_result);
+        assert!(uci_manager.core_get_caps_info().await.is_ok());
+        // The CCC cap doesn't limit the FiRa sessions.
+        assert_eq!(
+            uci_manager.session_init(fira_session_id, SessionType::FiraRangingSession).await,
+            Ok(())
+        );
+        assert_eq!(uci_manager.session_init(ccc_session_id, SessionType::Ccc).await, Ok(()));
+        // The second CCC session is rejected without sending SESSION_INIT_CMD.
+        assert_eq!(
+            uci_manager.session_init(0xabc, SessionType::Ccc).await,
+            Err(Error::MaxSessionsExceeded)
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
-    fn setup_hal_for_session_initialize(
-        hal: &mut MockUciHal,
-        session_type: SessionType,
-        session_id: u32,
-        session_token: u32,
-    ) {
-        // Setup for hal open.
-        setup_hal_for_open(hal);
-
-        // Setup session init.
-        let cmd = UciCommand::SessionInit { session_id, session_type };
-        let mut resp = if session_id == session_token {
-            into_uci_hal_packets(uwb_uci_packets::SessionInitRspBuilder {
-                status: uwb_uci_packets::StatusCode::UciStatusOk,
-            })
-        } else {
-            // This is testing FIRA v2 flow where a session handle is provided by UWBS.
-            into_uci_hal_packets(uwb_uci_packets::SessionInitRsp_V2Builder {
-                status: uwb_uci_packets::StatusCode::UciStatusOk,
-                session_handle: session_token,
-            })
+    #[tokio::test]
+    async fn test_aoa_antenna_pairs() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let ids = AntennaPairIds {
+            azimuth: AppConfigTlvType::try_from(0xf0).unwrap(),
+            elevation: AppConfigTlvType::try_from(0xf1).unwrap(),
+            antenna_count: CapTlvType::try_from(0xf0).unwrap(),
         };
-        let mut notf = into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+        let azimuth_tlv = AppConfigTlv::new(ids.azimuth, vec![0, 1]);
+        let azimuth_tlv_clone = azimuth_tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::CoreGetCapsInfo;
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv { t: ids.antenna_count, v: vec![3] }],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![azimuth_tlv_clone.clone()],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionGetAppConfig {
+                    session_token,
+                    app_cfg: vec![ids.azimuth, ids.elevation],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![azimuth_tlv_clone.into_inner()],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
             session_token,
-            session_state: uwb_uci_packets::SessionState::SessionStateInit,
-            reason_code: uwb_uci_packets::ReasonCode::StateChangeWithSessionManagementCommands
-                .into(),
-        });
-        resp.append(&mut notf);
-        hal.expected_send_command(cmd, resp, Ok(()));
-        hal.expected_notify_session_initialized(session_token, Ok(()));
+        )
+        .await;
+
+        // The ids are not defined by the FiRa UCI spec, so nothing is read before they are set.
+        assert_eq!(
+            uci_manager.session_get_aoa_antenna_pairs(session_id).await,
+            Err(Error::NotSupported)
+        );
+        uci_manager.set_antenna_pair_ids(Some(ids));
+        uci_manager.core_get_caps_info().await.unwrap();
+        assert!(uci_manager.session_set_app_config(session_id, vec![azimuth_tlv]).await.is_ok());
+        // The UWBS doesn't have the antenna 3, so the command is not sent.
+        let elevation_tlv = AppConfigTlv::new(ids.elevation, vec![0, 3]);
+        assert_eq!(
+            uci_manager.session_set_app_config(session_id, vec![elevation_tlv]).await,
+            Err(Error::BadParameters)
+        );
+        assert_eq!(
+            uci_manager.session_get_aoa_antenna_pairs(session_id).await,
+            Ok((Some(AntennaPair { first: 0, second: 1 }), None))
+        );
+        assert!(mock_hal.wait_expected_calls_done().await);
     }
 
-    async fn setup_uci_manager_with_session_initialized<F, Fut>(
-        setup_hal_fn: F,
-        uci_logger_mode: UciLoggerMode,
-        log_sender: mpsc::UnboundedSender<UciLogEvent>,
-        session_id: u32,
-        session_token: u32,
+    #[tokio::test]
+    async fn test_max_number_of_measurements() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let valid_tlv = AppConfigTlv::new(AppConfigTlvType::MaxNumberOfMeasurements, vec![100, 0]);
+        let valid_tlv_clone = valid_tlv.clone();
+        let cap_ids = VendorCapIds {
+            max_number_of_measurements: Some(CapTlvType::try_from(0xf0).unwrap()),
+            ..Default::default()
+        };
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::CoreGetCapsInfo;
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv {
+                        t: cap_ids.max_number_of_measurements.unwrap(),
+                        v: vec![100, 0],
+                    }],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![valid_tlv_clone],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        uci_manager.set_vendor_cap_ids(cap_ids);
+        uci_manager.core_get_caps_info().await.unwrap();
+        assert!(uci_manager.session_set_app_config(session_id, vec![valid_tlv]).await.is_ok());
+        // 101 exceeds the capability, so the command is not sent.
+        let invalid_tlv =
+            AppConfigTlv::new(AppConfigTlvType::MaxNumberOfMeasurements, vec![101, 0]);
+        assert_eq!(
+            uci_manager.session_set_app_config(session_id, vec![invalid_tlv]).await,
+            Err(Error::MaxNumberOfMeasurementsExceeded)
+        );
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_data_transfer_roles() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let dt_session_id = 0x456;
+        let dt_session_token = 0x456;
+        let receiver_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x00]);
+        let receiver_tlv_clone = receiver_tlv.clone();
+        let cap_ids = VendorCapIds {
+            data_transfer_roles: Some(CapTlvType::try_from(0xf0).unwrap()),
+            ..Default::default()
+        };
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                // The UWBS only supports receiving the data.
+                let cmd = UciCommand::CoreGetCapsInfo;
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv { t: cap_ids.data_transfer_roles.unwrap(), v: vec![0x02] }],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionInit {
+                    session_id: dt_session_id,
+                    session_type: SessionType::FiraDataTransfer,
+                };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionInitRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                });
+                resp.append(&mut into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+                    session_token: dt_session_token,
+                    session_state: SessionState::SessionStateInit,
+                    reason_code: ReasonCode::StateChangeWithSessionManagementCommands.into(),
+                }));
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token: dt_session_token,
+                    config_tlvs: vec![receiver_tlv_clone],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        // The capabilities are queried only once.
+        uci_manager.set_vendor_cap_ids(cap_ids);
+        assert!(!uci_manager.supports_data_transfer_role(DataTransferRole::Sender).await);
+        assert!(uci_manager.supports_data_transfer_role(DataTransferRole::Receiver).await);
+
+        uci_manager.session_init(dt_session_id, SessionType::FiraDataTransfer).await.unwrap();
+        // The controller is rejected without sending SESSION_SET_APP_CONFIG_CMD.
+        let sender_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x01]);
+        assert_eq!(
+            uci_manager.session_set_app_config(dt_session_id, vec![sender_tlv]).await,
+            Err(Error::BadParameters)
+        );
+        assert!(uci_manager
+            .session_set_app_config(dt_session_id, vec![receiver_tlv])
+            .await
+            .is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[test]
+    fn test_max_sessions_from_caps() {
+        let cap_type = CapTlvType::SupportedMaxRangingSessionNumber;
+        let tlv = |v| CapTlv { t: cap_type, v };
+        assert_eq!(max_sessions(&[tlv(vec![0x05])], cap_type), Some(5));
+        assert_eq!(max_sessions(&[tlv(vec![0x01, 0x02])], cap_type), Some(0x201));
+        assert_eq!(max_sessions(&[tlv(vec![])], cap_type), None);
+        assert_eq!(max_sessions(&[tlv(vec![0; 5])], cap_type), None);
+        assert_eq!(
+            max_sessions(&[CapTlv { t: CapTlvType::SupportedChannels, v: vec![0x05] }], cap_type),
+            None
+        );
+        assert_eq!(
+            max_sessions(&[tlv(vec![0x05])], CapTlvType::CccSupportedMaxRangingSessionNumber),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_core_set_config_ok() {
+        let tlv = DeviceConfigTlv {
+            cfg_id: uwb_uci_packets::DeviceConfigId::DeviceState,
+            v: vec![0x12, 0x34, 0x56],
+        };
+        let tlv_clone = tlv.clone();
+        let status = StatusCode::UciStatusOk;
+        let config_status = vec![];
+        let config_status_clone = config_status.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let cmd = UciCommand::CoreSetConfig { config_tlvs: vec![tlv_clone] };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SetConfigRspBuilder {
+                    status,
+                    cfg_status: config_status_clone,
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        let expected_result = CoreSetConfigResponse { status, config_status };
+        let result = uci_manager.core_set_config(vec![tlv]).await.unwrap();
+        assert_eq!(result, expected_result);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_core_get_config_ok() {
+        let cfg_id = DeviceConfigId::DeviceState;
+        let tlv = DeviceConfigTlv { cfg_id, v: vec![0x12, 0x34, 0x56] };
+        let tlv_clone = tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let cmd = UciCommand::CoreGetConfig { cfg_id: vec![cfg_id] };
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    tlvs: vec![tlv_clone],
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        let expected_result = vec![tlv];
+        let result = uci_manager.core_get_config(vec![cfg_id]).await.unwrap();
+        assert_eq!(result, expected_result);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_get_country_code() {
+        let country_code = CountryCode::new(b"US").unwrap();
+        let country_code_clone = country_code.clone();
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The failed command doesn't change the country code.
+                let cmd = UciCommand::AndroidSetCountryCode {
+                    country_code: CountryCode::new(b"KR").unwrap(),
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::AndroidSetCountryCodeRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusFailed,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+
+                let cmd = UciCommand::AndroidSetCountryCode { country_code: country_code_clone };
+                let resp = into_uci_hal_packets(uwb_uci_packets::AndroidSetCountryCodeRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+
+                let cmd = UciCommand::DeviceReset { reset_config: ResetConfig::UwbsReset };
+                let resp = into_uci_hal_packets(uwb_uci_packets::DeviceResetRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        assert_eq!(uci_manager.get_country_code().await, None);
+        let result = uci_manager.android_set_country_code(CountryCode::new(b"KR").unwrap()).await;
+        assert!(result.is_err());
+        assert_eq!(uci_manager.get_country_code().await, None);
+        let result = uci_manager.android_set_country_code(country_code.clone()).await;
+        assert!(result.is_ok());
+        assert_eq!(uci_manager.get_country_code().await, Some(country_code));
+        // The UWBS forgets the country code after the reset.
+        assert!(uci_manager.device_reset(ResetConfig::UwbsReset).await.is_ok());
+        assert_eq!(uci_manager.get_country_code().await, None);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    fn setup_hal_for_session_initialize(
+        hal: &mut MockUciHal,
+        session_type: SessionType,
+        session_id: u32,
+        session_token: u32,
+    ) {
+        // Setup for hal open.
+        setup_hal_for_open(hal);
+
+        // Setup session init.
+        let cmd = UciCommand::SessionInit { session_id, session_type };
+        let mut resp = if session_id == session_token {
+            into_uci_hal_packets(uwb_uci_packets::SessionInitRspBuilder {
+                status: uwb_uci_packets::StatusCode::UciStatusOk,
+            })
+        } else {
+            // This is testing FIRA v2 flow where a session handle is provided by UWBS.
+            into_uci_hal_packets(uwb_uci_packets::SessionInitRsp_V2Builder {
+                status: uwb_uci_packets::StatusCode::UciStatusOk,
+                session_handle: session_token,
+            })
+        };
+        let mut notf = into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+            session_token,
+            session_state: uwb_uci_packets::SessionState::SessionStateInit,
+            reason_code: uwb_uci_packets::ReasonCode::StateChangeWithSessionManagementCommands
+                .into(),
+        });
+        resp.append(&mut notf);
+        hal.expected_send_command(cmd, resp, Ok(()));
+        hal.expected_notify_session_initialized(session_token, Ok(()));
+    }
+
+    async fn setup_uci_manager_with_session_initialized<F, Fut>(
+        setup_hal_fn: F,
+        uci_logger_mode: UciLoggerMode,
+        log_sender: mpsc::UnboundedSender<UciLogEvent>,
+        session_id: u32,
+        session_token: u32,
     ) -> (UciManagerImpl, MockUciHal)
     where
         F: FnOnce(MockUciHal) -> Fut,
@@ -1784,23 +4094,1226 @@ mod tests {
         )
         .await;
 
-        let result = uci_manager.session_deinit(session_id).await;
-        assert!(result.is_ok());
+        let result = uci_manager.session_deinit(session_id).await;
+        assert!(result.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_deinit_v2_ok() {
+        let session_id = 0x123;
+        let session_token = 0x321; // different session handle
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionDeinit { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionDeinitRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_deinit(session_id).await;
+        assert!(result.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_set_app_config_ok() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let config_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
+        let config_tlv_clone = config_tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![config_tlv_clone],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let expected_result =
+            SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] };
+        let result =
+            uci_manager.session_set_app_config(session_id, vec![config_tlv]).await.unwrap();
+        assert_eq!(result, expected_result);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_set_app_config_read_only() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        // No command is sent if any of the TLVs is read-only.
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |_hal| async move {},
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let config_tlvs = vec![
+            AppConfigTlv::new(AppConfigTlvType::RangingInterval, vec![0xc8, 0x00, 0x00, 0x00]),
+            AppConfigTlv::new(AppConfigTlvType::CccUwbTime0, vec![0x00; 8]),
+        ];
+        let result = uci_manager.session_set_app_config(session_id, config_tlvs).await;
+        assert_eq!(result, Err(Error::ReadOnlyAppConfig));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_set_app_config_v2_ok() {
+        let session_id = 0x123;
+        let session_token = 0x321;
+        let config_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
+        let config_tlv_clone = config_tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![config_tlv_clone],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let expected_result =
+            SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] };
+        let result =
+            uci_manager.session_set_app_config(session_id, vec![config_tlv]).await.unwrap();
+        assert_eq!(result, expected_result);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_app_config_ok() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let config_id = AppConfigTlvType::DeviceType;
+        let tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
+        let tlv_clone = tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd =
+                    UciCommand::SessionGetAppConfig { session_token, app_cfg: vec![config_id] };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    tlvs: vec![tlv_clone.into_inner()],
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let expected_result = vec![tlv];
+        let result = uci_manager.session_get_app_config(session_id, vec![config_id]).await.unwrap();
+        assert_eq!(result, expected_result);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_app_config_split() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        // The last id doesn't fit in the first command.
+        let mut config_ids =
+            vec![AppConfigTlvType::DeviceType; MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD];
+        config_ids.push(AppConfigTlvType::ChannelNumber);
+        let device_type_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x01]);
+        let channel_tlv = AppConfigTlv::new(AppConfigTlvType::ChannelNumber, vec![0x09]);
+        let device_type_tlv_clone = device_type_tlv.clone();
+        let channel_tlv_clone = channel_tlv.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetAppConfig {
+                    session_token,
+                    app_cfg: vec![
+                        AppConfigTlvType::DeviceType;
+                        MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD
+                    ],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![device_type_tlv_clone.into_inner()],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+                let cmd = UciCommand::SessionGetAppConfig {
+                    session_token,
+                    app_cfg: vec![AppConfigTlvType::ChannelNumber],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![channel_tlv_clone.into_inner()],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_get_app_config(session_id, config_ids).await;
+        assert_eq!(result, Ok(vec![device_type_tlv, channel_tlv]));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_effective_phy() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        // The UWBS adapted the channel and the preamble code index from the requested ones.
+        let tlvs = vec![
+            AppConfigTlv::new(AppConfigTlvType::ChannelNumber, vec![5]),
+            AppConfigTlv::new(AppConfigTlvType::PrfMode, vec![0]),
+            AppConfigTlv::new(AppConfigTlvType::PreambleCodeIndex, vec![11]),
+            AppConfigTlv::new(AppConfigTlvType::SfdId, vec![2]),
+            AppConfigTlv::new(AppConfigTlvType::PsduDataRate, vec![0]),
+            AppConfigTlv::new(AppConfigTlvType::PreambleDuration, vec![1]),
+        ];
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetAppConfig {
+                    session_token,
+                    app_cfg: PhyParams::TLV_TYPES.to_vec(),
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    tlvs: tlvs.into_iter().map(|tlv| tlv.into_inner()).collect(),
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_effective_phy(session_id).await;
+        assert_eq!(
+            result,
+            Ok(PhyParams {
+                channel_number: UwbChannel::Channel5,
+                prf_mode: PrfMode::Bprf,
+                preamble_code_index: 11,
+                sfd_id: 2,
+                psdu_data_rate: PsduDataRate::Rate6m81,
+                preamble_duration: PreambleDuration::T64Symbols,
+            })
+        );
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_device_type() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetAppConfig {
+                    session_token,
+                    app_cfg: vec![AppConfigTlvType::DeviceType],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    tlvs: vec![
+                        AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![1]).into_inner()
+                    ],
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_device_type(session_id).await;
+        assert_eq!(result, Ok(DeviceType::Controller));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_count_ok() {
+        let session_count = 5;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetCount;
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    session_count,
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        let result = uci_manager.session_get_count().await.unwrap();
+        assert_eq!(result, session_count);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[cfg(feature = "latency-stats")]
+    #[tokio::test]
+    async fn test_latency_stats() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                for session_count in [1, 2] {
+                    let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        session_count,
+                    });
+                    hal.expected_send_command(UciCommand::SessionGetCount, resp, Ok(()));
+                }
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        assert!(uci_manager.session_get_count().await.is_ok());
+        assert!(uci_manager.session_get_count().await.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+
+        let latency_stats = uci_manager.latency_stats();
+        // CORE_GET_DEVICE_INFO_CMD is sent by open_hal().
+        assert_eq!(latency_stats.len(), 2);
+        assert_eq!(latency_stats[&(GroupId::Core, 0x02)].count, 1);
+        let stats = &latency_stats[&(GroupId::SessionConfig, 0x05)];
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.histogram.iter().sum::<u64>(), 2);
+        assert!(stats.min <= stats.max);
+    }
+
+    #[cfg(feature = "metrics-export")]
+    #[tokio::test]
+    async fn test_export_latency_stats() {
+        use crate::proto::utils::parse_from_bytes;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                for session_count in [1, 2] {
+                    let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        session_count,
+                    });
+                    hal.expected_send_command(UciCommand::SessionGetCount, resp, Ok(()));
+                }
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        assert!(uci_manager.session_get_count().await.is_ok());
+        assert!(uci_manager.session_get_count().await.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+
+        let bytes = uci_manager.export_latency_stats().unwrap();
+        let report = parse_from_bytes::<ProtoLatencyStatsReport>(&bytes).unwrap();
+        // CORE_GET_DEVICE_INFO_CMD is sent by open_hal(), and sorted before SESSION_GET_COUNT_CMD.
+        let stats = report.get_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].get_gid(), stats[0].get_oid(), stats[0].get_count()), (0x0, 0x02, 1));
+        assert_eq!((stats[1].get_gid(), stats[1].get_oid(), stats[1].get_count()), (0x1, 0x05, 2));
+        assert_eq!(stats[1].get_histogram().iter().sum::<u64>(), 2);
+        assert!(stats[1].get_min_us() <= stats[1].get_max_us());
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_commands_match_responses_by_opcode() {
+        let session_count = 5;
+        let uci_version = 0x1234;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The responses are received after both commands are sent, in the reverse order.
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::GetDeviceInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    uci_version,
+                    mac_version: 0x5678,
+                    phy_version: 0x90ab,
+                    uci_test_version: 0x1357,
+                    vendor_spec_info: vec![],
+                });
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::SessionGetCountRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        session_count,
+                    },
+                ));
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        uci_manager.set_max_in_flight_commands(2);
+
+        let (count_result, device_info_result) =
+            tokio::join!(uci_manager.session_get_count(), uci_manager.core_get_device_info());
+        assert_eq!(count_result.unwrap(), session_count);
+        assert_eq!(device_info_result.unwrap().uci_version, uci_version);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_commands_with_same_opcode_are_serialized() {
+        let session_count = 5;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    session_count,
+                });
+                hal.expected_send_command(UciCommand::SessionGetCount, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        uci_manager.set_max_in_flight_commands(2);
+
+        // The second command is sent after the first one is timeout, so the response belongs to
+        // the second one.
+        let (first_result, second_result) =
+            tokio::join!(uci_manager.session_get_count(), uci_manager.session_get_count());
+        assert_eq!(first_result, Err(Error::Timeout));
+        assert_eq!(second_result.unwrap(), session_count);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_commands_retry_notification_retries_last_sent_command() {
+        let session_count = 5;
+        let uci_version = 0x1234;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let retry_ntf = into_uci_hal_packets(uwb_uci_packets::GenericErrorBuilder {
+                    status: StatusCode::UciStatusCommandRetry,
+                });
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::GetDeviceInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    uci_version,
+                    mac_version: 0x5678,
+                    phy_version: 0x90ab,
+                    uci_test_version: 0x1357,
+                    vendor_spec_info: vec![],
+                });
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::SessionGetCountRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        session_count,
+                    },
+                ));
+                // Only the last sent command is retried.
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, retry_ntf, Ok(()));
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        uci_manager.set_max_in_flight_commands(2);
+
+        let (count_result, device_info_result) =
+            tokio::join!(uci_manager.session_get_count(), uci_manager.core_get_device_info());
+        assert_eq!(count_result.unwrap(), session_count);
+        assert_eq!(device_info_result.unwrap().uci_version, uci_version);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_pending_command_count() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        assert_eq!(uci_manager.pending_command_count(), 0);
+
+        // The command is blocked until timeout because the UWBS doesn't respond.
+        let uci_manager_clone = uci_manager.clone();
+        let handle = tokio::spawn(async move { uci_manager_clone.session_get_count().await });
+        while uci_manager.pending_command_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(uci_manager.pending_command_count(), 1);
+
+        assert_eq!(handle.await.unwrap(), Err(Error::Timeout));
+        assert_eq!(uci_manager.pending_command_count(), 0);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_recent_errors() {
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                for status in [
+                    StatusCode::UciStatusInvalidParam,
+                    StatusCode::UciStatusMaxSessionsExceeded,
+                    StatusCode::UciStatusRejected,
+                ] {
+                    let cmd = UciCommand::SessionGetCount;
+                    let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
+                        status,
+                        session_count: 0,
+                    });
+                    hal.expected_send_command(cmd, resp, Ok(()));
+                }
+                hal.expected_send_command(UciCommand::SessionGetCount, vec![], Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        assert!(uci_manager.recent_errors().is_empty());
+
+        uci_manager.set_recent_errors_capacity(2);
+        assert_eq!(uci_manager.session_get_count().await, Err(Error::BadParameters));
+        assert_eq!(uci_manager.session_get_count().await, Err(Error::MaxSessionsExceeded));
+        assert_eq!(uci_manager.session_get_count().await, Err(Error::Unknown));
+
+        // The oldest error is dropped.
+        let errors = uci_manager.recent_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].gid, GroupId::SessionConfig);
+        assert_eq!(errors[0].error, Error::MaxSessionsExceeded);
+        assert_eq!(errors[1].error, Error::Unknown);
+        assert!(errors[0].timestamp <= errors[1].timestamp);
+
+        // The timeout is recorded too.
+        uci_manager.clear_recent_errors();
+        assert!(uci_manager.recent_errors().is_empty());
+        assert_eq!(uci_manager.session_get_count().await, Err(Error::Timeout));
+        let errors = uci_manager.recent_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, Error::Timeout);
+
+        uci_manager.set_recent_errors_capacity(0);
+        assert!(uci_manager.recent_errors().is_empty());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_state_ok() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let session_state = SessionState::SessionStateActive;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetState { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    session_state,
+                });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_get_state(session_id).await.unwrap();
+        assert_eq!(result, session_state);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_type() {
+        let session_id = 0x123;
+        let session_token = 0x321;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionGetState { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    session_state: SessionState::SessionStateIdle,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        assert_eq!(
+            uci_manager.session_get_type(session_id).await,
+            Ok(SessionType::FiraRangingSession)
+        );
+        // The unknown session fails without sending any command.
+        assert_eq!(uci_manager.session_get_type(0x456).await, Err(Error::BadParameters));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_command_middleware() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let forced_session_token: u32 = 0x456;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                // The session token in the payload is rewritten by the middleware.
+                let cmd = UciCommand::RawUciCmd {
+                    mt: u8::from(MessageType::Command).into(),
+                    gid: u8::from(GroupId::SessionConfig).into(),
+                    oid: 0x06,
+                    payload: forced_session_token.to_le_bytes().to_vec(),
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    session_state: SessionState::SessionStateIdle,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+
+                // The command which is not modified by the middleware is sent as it is.
+                let cmd = UciCommand::SessionGetCount;
+                let resp = into_uci_hal_packets(SessionGetCountRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    session_count: 1,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+        uci_manager.set_command_middleware(Some(Arc::new(move |msg: &mut RawUciMessage| {
+            if msg.gid == u8::from(GroupId::SessionConfig) as u32 && msg.oid == 0x06 {
+                msg.payload = forced_session_token.to_le_bytes().to_vec();
+            }
+        })));
+
+        assert_eq!(
+            uci_manager.session_get_state(session_id).await,
+            Ok(SessionState::SessionStateIdle)
+        );
+        assert_eq!(uci_manager.session_get_count().await, Ok(1));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_state_after_reset_and_reopen() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                hal.expected_close(Ok(()));
+                setup_hal_for_open(&mut hal);
+
+                let cmd = UciCommand::SessionGetState { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusSessionNotExist,
+                    session_state: SessionState::SessionStateInit,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+        uci_manager.set_report_deinit_after_reset(true);
+
+        // The session is lost by reopening the HAL without CORE_DEVICE_RESET_CMD.
+        assert!(uci_manager.reset_and_reopen().await.is_ok());
+        let result = uci_manager.session_get_state(session_id).await;
+        assert_eq!(result, Ok(SessionState::SessionStateDeinit));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_state_after_device_reset() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::DeviceReset { reset_config: ResetConfig::UwbsReset };
+                let resp = into_uci_hal_packets(uwb_uci_packets::DeviceResetRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+
+                for _ in 0..2 {
+                    let cmd = UciCommand::SessionGetState { session_token };
+                    let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                        status: uwb_uci_packets::StatusCode::UciStatusSessionNotExist,
+                        session_state: SessionState::SessionStateInit,
+                    });
+                    hal.expected_send_command(cmd, resp, Ok(()));
+                }
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.device_reset(ResetConfig::UwbsReset).await;
+        assert!(result.is_ok());
+
+        // The error is reported by default.
+        let result = uci_manager.session_get_state(session_id).await;
+        assert_eq!(result, Err(Error::ProtocolSpecific));
+
+        uci_manager.set_report_deinit_after_reset(true);
+        let result = uci_manager.session_get_state(session_id).await;
+        assert_eq!(result, Ok(SessionState::SessionStateDeinit));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_update_controller_multicast_list_ok() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let action = UpdateMulticastListAction::AddControlee;
+        let short_address: [u8; 2] = [0x45, 0x67];
+        let controlee = Controlee { short_address, subsession_id: 0x90ab };
+        let controlee_clone = controlee.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionUpdateControllerMulticastList {
+                    session_token,
+                    action,
+                    controlees: Controlees::NoSessionKey(vec![controlee_clone]),
+                };
+                let resp = into_uci_hal_packets(
+                    uwb_uci_packets::SessionUpdateControllerMulticastListRspBuilder {
+                        status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    },
+                );
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager
+            .session_update_controller_multicast_list(
+                session_id,
+                action,
+                uwb_uci_packets::Controlees::NoSessionKey(vec![controlee]),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_update_controller_multicast_list_split() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let action = UpdateMulticastListAction::AddControlee;
+        let controlees: Vec<Controlee> = (0..10u16)
+            .map(|i| Controlee { short_address: i.to_le_bytes(), subsession_id: i.into() })
+            .collect();
+        let controlees_clone = controlees.clone();
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                // The first 8 controlees are sent by the first command, and the rest are sent by
+                // the second command.
+                for chunk in controlees_clone.chunks(8) {
+                    let cmd = UciCommand::SessionUpdateControllerMulticastList {
+                        session_token,
+                        action,
+                        controlees: Controlees::NoSessionKey(chunk.to_vec()),
+                    };
+                    let resp = into_uci_hal_packets(
+                        uwb_uci_packets::SessionUpdateControllerMulticastListRspBuilder {
+                            status: uwb_uci_packets::StatusCode::UciStatusOk,
+                        },
+                    );
+                    hal.expected_send_command(cmd, resp, Ok(()));
+                }
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager
+            .session_update_controller_multicast_list(
+                session_id,
+                action,
+                Controlees::NoSessionKey(controlees),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_set_active_dt_tag_ranging_rounds() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let ranging_rounds = SessionUpdateDtTagRangingRoundsResponse {
+            status: StatusCode::UciStatusErrorRoundIndexNotActivated,
+            ranging_round_indexes: vec![3],
+        };
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionUpdateDtTagRangingRounds {
+                    session_token,
+                    ranging_round_indexes: vec![3, 5],
+                };
+                let resp = into_uci_hal_packets(
+                    uwb_uci_packets::SessionUpdateDtTagRangingRoundsRspBuilder {
+                        status: StatusCode::UciStatusErrorRoundIndexNotActivated,
+                        ranging_round_indexes: vec![3],
+                    },
+                );
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result =
+            uci_manager.session_update_dt_tag_ranging_rounds(session_id, vec![3, 5]).await.unwrap();
+
+        assert_eq!(result, ranging_rounds);
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_set_hybrid_config() {
+        let session_id = 0x123;
+        let session_token = 0x321;
+        let sub_session_id = 0x456;
+        let sub_session_token = 0x654;
+        let update_time = 0x1122334455;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionInit {
+                    session_id: sub_session_id,
+                    session_type: SessionType::FiraRangingSession,
+                };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionInitRsp_V2Builder {
+                    status: StatusCode::UciStatusOk,
+                    session_handle: sub_session_token,
+                });
+                let mut notf = into_uci_hal_packets(uwb_uci_packets::SessionStatusNtfBuilder {
+                    session_token: sub_session_token,
+                    session_state: SessionState::SessionStateInit,
+                    reason_code: ReasonCode::StateChangeWithSessionManagementCommands.into(),
+                });
+                resp.append(&mut notf);
+                hal.expected_send_command(cmd, resp, Ok(()));
+                hal.expected_notify_session_initialized(sub_session_token, Ok(()));
+
+                // The session ids are replaced with the session tokens.
+                let cmd = UciCommand::SessionSetHybridConfig {
+                    session_token,
+                    update_time: [0x55, 0x44, 0x33, 0x22, 0x11, 0, 0, 0],
+                    phase_list: vec![
+                        PhaseList { session_token, start_slot_index: 0, end_slot_index: 9 },
+                        PhaseList {
+                            session_token: sub_session_token,
+                            start_slot_index: 10,
+                            end_slot_index: 19,
+                        },
+                    ],
+                };
+                let resp =
+                    into_uci_hal_packets(uwb_uci_packets::SessionSetHybridConfigRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                    });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+        assert!(uci_manager
+            .session_init(sub_session_id, SessionType::FiraRangingSession)
+            .await
+            .is_ok());
+
+        let phase = |session_id, start_slot_index, end_slot_index| HybridSessionPhase {
+            session_id,
+            start_slot_index,
+            end_slot_index,
+        };
+        let config = HybridSessionConfig {
+            update_time,
+            phases: vec![phase(session_id, 0, 9), phase(sub_session_id, 10, 19)],
+        };
+        // The overlapped phases and the unknown sub-session are rejected before sending the
+        // command.
+        let overlapped_config = HybridSessionConfig {
+            phases: vec![phase(session_id, 0, 10), phase(sub_session_id, 10, 19)],
+            ..config.clone()
+        };
+        assert_eq!(
+            uci_manager.session_set_hybrid_config(session_id, overlapped_config).await,
+            Err(Error::BadParameters)
+        );
+        let unknown_sub_session_config = HybridSessionConfig {
+            phases: vec![phase(session_id, 0, 9), phase(0x789, 10, 19)],
+            ..config.clone()
+        };
+        assert_eq!(
+            uci_manager.session_set_hybrid_config(session_id, unknown_sub_session_config).await,
+            Err(Error::BadParameters)
+        );
+
+        assert!(uci_manager.session_set_hybrid_config(session_id, config).await.is_ok());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_query_max_data_size_ok() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let max_data_size = 0x0107;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionQueryMaxDataSize { session_token };
+                let resp =
+                    into_uci_hal_packets(uwb_uci_packets::SessionQueryMaxDataSizeRspBuilder {
+                        session_token,
+                        max_data_size,
+                    });
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        let result = uci_manager.session_query_max_data_size(session_id).await;
+        assert_eq!(result, Ok(max_data_size));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_query_max_data_size_fallback_to_caps() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                // The UWBS supports UCI 1.1.
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetDeviceInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    uci_version: 0x1001,
+                    mac_version: 0x1001,
+                    phy_version: 0x1001,
+                    uci_test_version: 0x1001,
+                    vendor_spec_info: vec![],
+                });
+                hal.expected_send_command(UciCommand::CoreGetDeviceInfo, resp, Ok(()));
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv {
+                        t: CapTlvType::SupportedMaxDataPacketPayloadSize,
+                        v: vec![0xf0, 0x00],
+                    }],
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        assert!(uci_manager.core_get_device_info().await.is_ok());
+        let result = uci_manager.session_query_max_data_size(session_id).await;
+        assert_eq!(result, Ok(0xf0));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_clock_accuracy_ppm() {
+        let cap_ids = VendorCapIds {
+            clock_accuracy: Some(CapTlvType::try_from(0xf0).unwrap()),
+            ..Default::default()
+        };
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // 20 ppm, in the unit of 0.01 ppm.
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv { t: cap_ids.clock_accuracy.unwrap(), v: vec![0xd0, 0x07] }],
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, resp, Ok(()));
+                let resp = into_uci_hal_packets(uwb_uci_packets::GetCapsInfoRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    tlvs: vec![CapTlv { t: CapTlvType::SupportedChannels, v: vec![0x05] }],
+                });
+                hal.expected_send_command(UciCommand::CoreGetCapsInfo, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        // The capabilities are not queried before the capability id is set.
+        assert_eq!(uci_manager.clock_accuracy_ppm().await, Err(Error::NotSupported));
+        uci_manager.set_vendor_cap_ids(cap_ids);
+        assert_eq!(uci_manager.clock_accuracy_ppm().await, Ok(20.0));
+        assert_eq!(uci_manager.clock_accuracy_ppm().await, Err(Error::NotSupported));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_possible_ranging_rounds() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+
+        let gid = 0xF; // Vendor reserved GID.
+        let oid = 0x30;
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::RawUciCmd {
+                    mt: 0x1,
+                    gid,
+                    oid,
+                    payload: u32::to_le_bytes(session_token).to_vec(),
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::UciVendor_F_ResponseBuilder {
+                    opcode: oid as u8,
+                    payload: Some(Bytes::from(vec![0x00, 0x03, 0x00, 0x02, 0x05])),
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+
+        // The command is not sent until its id is set.
+        let result = uci_manager.session_get_possible_ranging_rounds(session_id).await;
+        assert_eq!(result, Err(Error::NotSupported));
+
+        uci_manager
+            .set_vendor_command_ids(VendorCommandIds { possible_ranging_rounds: Some((gid, oid)) });
+        let result = uci_manager.session_get_possible_ranging_rounds(session_id).await;
+        assert_eq!(result, Ok(vec![0, 2, 5]));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[test]
+    fn test_decode_possible_ranging_rounds() {
+        assert_eq!(decode_possible_ranging_rounds(&[0x00, 0x00]), Ok(vec![]));
+        assert_eq!(decode_possible_ranging_rounds(&[0x00, 0x01, 0x07]), Ok(vec![7]));
+        // The status is not OK.
+        assert_eq!(decode_possible_ranging_rounds(&[0x04, 0x00]), Err(Error::BadParameters));
+        // The number of the indexes doesn't match.
+        assert_eq!(decode_possible_ranging_rounds(&[0x00, 0x02, 0x07]), Err(Error::Unknown));
+        assert_eq!(decode_possible_ranging_rounds(&[0x00]), Err(Error::Unknown));
+    }
+
+    #[tokio::test]
+    async fn test_core_get_device_info_unsupported_uci_version() {
+        assert!(is_supported_uci_version(0x1001));
+        assert!(is_supported_uci_version(0x0002));
+        assert!(!is_supported_uci_version(0x1003));
+
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                // The UWBS reports UCI 3.0, which is not supported.
+                for _ in 0..2 {
+                    let resp = into_uci_hal_packets(uwb_uci_packets::GetDeviceInfoRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        uci_version: 0x0003,
+                        mac_version: 0x5678,
+                        phy_version: 0x90ab,
+                        uci_test_version: 0x1357,
+                        vendor_spec_info: vec![],
+                    });
+                    hal.expected_send_command(UciCommand::CoreGetDeviceInfo, resp, Ok(()));
+                }
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+
+        // Only a warning is logged by default.
+        assert_eq!(uci_manager.core_get_device_info().await.unwrap().uci_version, 0x0003);
+
+        uci_manager.set_strict_uci_version_check(true);
+        assert_eq!(uci_manager.core_get_device_info().await, Err(Error::UnsupportedUciVersion));
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_key_rotation_ntf() {
+        let session_id = 0x123;
+        let session_token = 0x321;
+        let rotation_count: u32 = 2;
+
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+            |mut hal| async move {
+                let cmd = UciCommand::SessionStart { session_token };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                });
+                let mut payload = session_token.to_le_bytes().to_vec();
+                payload.extend_from_slice(&rotation_count.to_le_bytes());
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::UciVendor_F_NotificationBuilder {
+                        opcode: 0x20,
+                        payload: Some(Bytes::from(payload)),
+                    },
+                ));
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
+        )
+        .await;
+        let (session_notf_sender, mut session_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_session_notification_sender(session_notf_sender).await;
+        uci_manager.set_vendor_notification_ids(VendorNotificationIds {
+            key_rotation: Some((0xf, 0x20)),
+            ..Default::default()
+        });
+        assert_eq!(uci_manager.session_get_key_rotation_count(session_id).await, Ok(0));
+
+        assert!(uci_manager.range_start(session_id).await.is_ok());
+        // The session token of the notification is replaced with the session id.
+        assert_eq!(
+            session_notf_receiver.recv().await,
+            Some(SessionNotification::KeyRotation { session_token: session_id, rotation_count })
+        );
+        assert_eq!(
+            uci_manager.session_get_key_rotation_count(session_id).await,
+            Ok(rotation_count)
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_deinit_v2_ok() {
+    async fn test_session_diagnostics_ntf() {
         let session_id = 0x123;
-        let session_token = 0x321; // different session handle
+        let session_token = 0x321;
+        let frame_report = uwb_uci_packets::FrameReport {
+            uwb_msg_id: 1,
+            action: 0,
+            antenna_set: 2,
+            frame_report_tlvs: vec![uwb_uci_packets::FrameReportTlv {
+                t: uwb_uci_packets::FrameReportTlvType::Rssi,
+                v: vec![0x10, 0x20],
+            }],
+        };
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionDeinit { session_token };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionDeinitRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                let cmd = UciCommand::SessionStart { session_token };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: StatusCode::UciStatusOk,
                 });
-
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::AndroidRangeDiagnosticsNtfBuilder {
+                        session_token,
+                        sequence_number: 5,
+                        frame_reports: vec![frame_report],
+                    },
+                ));
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
@@ -1809,30 +5322,54 @@ mod tests {
             session_token,
         )
         .await;
+        let (session_notf_sender, mut session_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_session_notification_sender(session_notf_sender).await;
 
-        let result = uci_manager.session_deinit(session_id).await;
-        assert!(result.is_ok());
+        assert!(uci_manager.range_start(session_id).await.is_ok());
+        // The session token of the notification is replaced with the session id.
+        match session_notf_receiver.recv().await {
+            Some(SessionNotification::Diagnostics {
+                session_token,
+                sequence_number,
+                frame_reports,
+            }) => {
+                assert_eq!(session_token, session_id);
+                assert_eq!(sequence_number, 5);
+                assert_eq!(frame_reports.len(), 1);
+                assert_eq!(frame_reports[0].antenna_set, 2);
+                assert_eq!(frame_reports[0].rssi, vec![0x10, 0x20]);
+            }
+            ntf => panic!("Unexpected notification: {:?}", ntf),
+        }
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_set_app_config_ok() {
+    async fn test_range_data_sequence_check() {
         let session_id = 0x123;
-        let session_token = 0x123;
-        let config_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
-        let config_tlv_clone = config_tlv.clone();
+        let session_token = 0x321;
+        let range_data_ntf = |sequence_number| {
+            into_uci_hal_packets(uwb_uci_packets::ShortMacTwoWaySessionInfoNtfBuilder {
+                sequence_number,
+                session_token,
+                rcr_indicator: 0,
+                current_ranging_interval: 100,
+                two_way_ranging_measurements: vec![],
+                vendor_data: vec![],
+            })
+        };
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionSetAppConfig {
-                    session_token,
-                    config_tlvs: vec![config_tlv_clone],
-                };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    cfg_status: vec![],
+                let cmd = UciCommand::SessionStart { session_token };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: StatusCode::UciStatusOk,
                 });
-
+                // The range data with sequence number 1 is duplicated, 0 is stale, and 2 is
+                // missing.
+                for sequence_number in [0, 1, 1, 0, 3] {
+                    resp.append(&mut range_data_ntf(sequence_number));
+                }
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
@@ -1841,33 +5378,46 @@ mod tests {
             session_token,
         )
         .await;
-
-        let expected_result =
-            SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] };
-        let result =
-            uci_manager.session_set_app_config(session_id, vec![config_tlv]).await.unwrap();
-        assert_eq!(result, expected_result);
+        let (session_notf_sender, mut session_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_session_notification_sender(session_notf_sender).await;
+        uci_manager.set_range_data_sequence_check(true);
+
+        assert!(uci_manager.range_start(session_id).await.is_ok());
+        for expected_sequence_number in [0, 1, 3] {
+            match session_notf_receiver.recv().await {
+                Some(SessionNotification::SessionInfo(range_data)) => {
+                    assert_eq!(range_data.sequence_number, expected_sequence_number);
+                }
+                notf => panic!("Unexpected notification: {:?}", notf),
+            }
+        }
         assert!(mock_hal.wait_expected_calls_done().await);
+        assert!(session_notf_receiver.try_recv().is_err());
     }
 
     #[tokio::test]
-    async fn test_session_set_app_config_v2_ok() {
+    async fn test_range_data_received_at() {
         let session_id = 0x123;
         let session_token = 0x321;
-        let config_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
-        let config_tlv_clone = config_tlv.clone();
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionSetAppConfig {
-                    session_token,
-                    config_tlvs: vec![config_tlv_clone],
-                };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    cfg_status: vec![],
+                let cmd = UciCommand::SessionStart { session_token };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: StatusCode::UciStatusOk,
                 });
-
+                for sequence_number in [0, 1] {
+                    resp.append(&mut into_uci_hal_packets(
+                        uwb_uci_packets::ShortMacTwoWaySessionInfoNtfBuilder {
+                            sequence_number,
+                            session_token,
+                            rcr_indicator: 0,
+                            current_ranging_interval: 100,
+                            two_way_ranging_measurements: vec![],
+                            vendor_data: vec![],
+                        },
+                    ));
+                }
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
@@ -1876,32 +5426,90 @@ mod tests {
             session_token,
         )
         .await;
-
-        let expected_result =
-            SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] };
-        let result =
-            uci_manager.session_set_app_config(session_id, vec![config_tlv]).await.unwrap();
-        assert_eq!(result, expected_result);
+        let (session_notf_sender, mut session_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_session_notification_sender(session_notf_sender).await;
+
+        let start_time = SystemTime::now();
+        assert!(uci_manager.range_start(session_id).await.is_ok());
+        let mut last_received_at = start_time;
+        for _ in 0..2 {
+            match session_notf_receiver.recv().await {
+                Some(SessionNotification::SessionInfo(range_data)) => {
+                    let received_at = range_data.received_at.unwrap();
+                    assert!(received_at >= last_received_at);
+                    last_received_at = received_at;
+                }
+                notf => panic!("Unexpected notification: {:?}", notf),
+            }
+        }
+        assert!(last_received_at <= SystemTime::now());
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_get_app_config_ok() {
+    async fn test_link_quality_decoder() {
+        // The vendor data contains the noise floor, the CIR quality and the RSSI of the 2 RX
+        // antennas of each measurement.
+        struct TestLinkQualityDecoder;
+        impl LinkQualityDecoder for TestLinkQualityDecoder {
+            fn decode(&self, vendor_data: &[u8], _: usize) -> Vec<Option<LinkQuality>> {
+                vendor_data
+                    .chunks_exact(4)
+                    .map(|chunk| {
+                        Some(LinkQuality {
+                            noise_floor_dbm: chunk[0] as i8,
+                            cir_quality: chunk[1],
+                            rssi_per_receiver: vec![chunk[2] as i8 as i16, chunk[3] as i8 as i16],
+                        })
+                    })
+                    .collect()
+            }
+        }
+
         let session_id = 0x123;
-        let session_token = 0x123;
-        let config_id = AppConfigTlvType::DeviceType;
-        let tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12, 0x34, 0x56]);
-        let tlv_clone = tlv.clone();
+        let session_token = 0x321;
+        let measurement = uwb_uci_packets::ShortAddressTwoWayRangingMeasurement {
+            mac_address: 0x1234,
+            status: StatusCode::UciStatusOk,
+            nlos: 0,
+            distance: 150,
+            aoa_azimuth: 5,
+            aoa_azimuth_fom: 6,
+            aoa_elevation: 7,
+            aoa_elevation_fom: 8,
+            aoa_destination_azimuth: 9,
+            aoa_destination_azimuth_fom: 10,
+            aoa_destination_elevation: 11,
+            aoa_destination_elevation_fom: 12,
+            slot_index: 0,
+            rssi: 200,
+        };
+        let measurements = vec![
+            measurement.clone(),
+            uwb_uci_packets::ShortAddressTwoWayRangingMeasurement {
+                mac_address: 0x5678,
+                ..measurement
+            },
+        ];
+        let measurements_clone = measurements.clone();
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd =
-                    UciCommand::SessionGetAppConfig { session_token, app_cfg: vec![config_id] };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    tlvs: vec![tlv_clone.into_inner()],
+                let cmd = UciCommand::SessionStart { session_token };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: StatusCode::UciStatusOk,
                 });
-
+                // Only the first measurement has the link quality.
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::ShortMacTwoWaySessionInfoNtfBuilder {
+                        sequence_number: 0,
+                        session_token,
+                        rcr_indicator: 0,
+                        current_ranging_interval: 100,
+                        two_way_ranging_measurements: measurements_clone,
+                        vendor_data: vec![0xa6, 0x4b, 0xba, 0xb8],
+                    },
+                ));
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
@@ -1910,52 +5518,169 @@ mod tests {
             session_token,
         )
         .await;
-
-        let expected_result = vec![tlv];
-        let result = uci_manager.session_get_app_config(session_id, vec![config_id]).await.unwrap();
-        assert_eq!(result, expected_result);
+        let (session_notf_sender, mut session_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_session_notification_sender(session_notf_sender).await;
+        uci_manager.set_link_quality_decoder(Some(Arc::new(TestLinkQualityDecoder)));
+
+        assert!(uci_manager.range_start(session_id).await.is_ok());
+        match session_notf_receiver.recv().await {
+            Some(SessionNotification::SessionInfo(range_data)) => {
+                assert_eq!(
+                    range_data.ranging_measurements,
+                    RangingMeasurements::ShortAddressTwoWay(measurements)
+                );
+                assert_eq!(range_data.vendor_data, vec![0xa6, 0x4b, 0xba, 0xb8]);
+                assert_eq!(
+                    range_data.link_qualities,
+                    vec![
+                        Some(LinkQuality {
+                            noise_floor_dbm: -90,
+                            cir_quality: 75,
+                            rssi_per_receiver: vec![-70, -72],
+                        }),
+                        None
+                    ]
+                );
+            }
+            notf => panic!("Unexpected notification: {:?}", notf),
+        }
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_get_count_ok() {
-        let session_count = 5;
+    async fn test_session_set_app_config_transactional_rollback() {
+        let session_id = 0x123;
+        let session_token = 0x123;
+        let prev_params = generate_params();
+        let new_params = FiraAppConfigParamsBuilder::from_params(&prev_params)
+            .unwrap()
+            .slot_duration_rstu(1200)
+            .ranging_interval_ms(100)
+            .build()
+            .unwrap();
+        let prev_slot_duration = match &prev_params {
+            AppConfigParams::Fira(params) => *params.slot_duration_rstu(),
+            _ => panic!("The params should be FiRa params"),
+        };
 
-        let (uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+        let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionGetCount;
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetCountRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    session_count,
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    session_state: SessionState::SessionStateIdle,
+                });
+                hal.expected_send_command(
+                    UciCommand::SessionGetState { session_token },
+                    resp,
+                    Ok(()),
+                );
+
+                // The second TLV is rejected.
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![
+                        AppConfigTlv::new(AppConfigTlvType::SlotDuration, u16_to_bytes(1200)),
+                        AppConfigTlv::new(AppConfigTlvType::RangingInterval, u32_to_bytes(100)),
+                    ],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusInvalidParam,
+                    cfg_status: vec![AppConfigStatus {
+                        cfg_id: AppConfigTlvType::RangingInterval,
+                        status: StatusCode::UciStatusInvalidRange,
+                    }],
                 });
+                hal.expected_send_command(cmd, resp, Ok(()));
 
+                // Then the previous values are restored.
+                let cmd = UciCommand::SessionSetAppConfig {
+                    session_token,
+                    config_tlvs: vec![
+                        AppConfigTlv::new(
+                            AppConfigTlvType::SlotDuration,
+                            u16_to_bytes(prev_slot_duration),
+                        ),
+                        AppConfigTlv::new(AppConfigTlvType::RangingInterval, u32_to_bytes(200)),
+                    ],
+                };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
+            session_id,
+            session_token,
         )
         .await;
 
-        let result = uci_manager.session_get_count().await.unwrap();
-        assert_eq!(result, session_count);
+        let result = uci_manager
+            .session_set_app_config_transactional(session_id, &new_params, &prev_params)
+            .await;
+        assert_eq!(result, Err(Error::BadParameters));
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_get_state_ok() {
+    async fn test_validate_params_on_device() {
         let session_id = 0x123;
         let session_token = 0x123;
-        let session_state = SessionState::SessionStateActive;
+        let params = generate_params();
+        let mut tlvs = params.generate_tlvs();
+        tlvs.sort_by_key(|tlv| u8::from(tlv.cfg_id));
+        let cfg_ids = tlvs.iter().map(|tlv| tlv.cfg_id).collect::<Vec<_>>();
+        let with_ranging_interval = |ranging_interval_ms: u32| {
+            tlvs.iter()
+                .map(|tlv| match tlv.cfg_id {
+                    AppConfigTlvType::RangingInterval => AppConfigTlv::new(
+                        AppConfigTlvType::RangingInterval,
+                        u32_to_bytes(ranging_interval_ms),
+                    ),
+                    _ => tlv.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let prev_tlvs = with_ranging_interval(100);
+        // The UWBS clamps the ranging interval from 200 to 240.
+        let clamped_tlvs = with_ranging_interval(240);
+        let tlvs_clone = tlvs.clone();
 
         let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionGetState { session_token };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionGetStateRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    session_state,
-                });
-
-                hal.expected_send_command(cmd, resp, Ok(()));
+                // The config ids are read by multiple commands.
+                let expect_get_app_config = |hal: &mut MockUciHal, tlvs: &[AppConfigTlv]| {
+                    for (app_cfg, tlvs) in cfg_ids
+                        .chunks(MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD)
+                        .zip(tlvs.chunks(MAX_CONFIG_IDS_PER_GET_APP_CONFIG_CMD))
+                    {
+                        let cmd = UciCommand::SessionGetAppConfig {
+                            session_token,
+                            app_cfg: app_cfg.to_vec(),
+                        };
+                        let resp =
+                            into_uci_hal_packets(uwb_uci_packets::SessionGetAppConfigRspBuilder {
+                                status: StatusCode::UciStatusOk,
+                                tlvs: tlvs.iter().map(|tlv| tlv.clone().into_inner()).collect(),
+                            });
+                        hal.expected_send_command(cmd, resp, Ok(()));
+                    }
+                };
+                let set_app_config_resp = || {
+                    into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                        status: StatusCode::UciStatusOk,
+                        cfg_status: vec![],
+                    })
+                };
+                expect_get_app_config(&mut hal, &prev_tlvs);
+                let cmd =
+                    UciCommand::SessionSetAppConfig { session_token, config_tlvs: tlvs_clone };
+                hal.expected_send_command(cmd, set_app_config_resp(), Ok(()));
+                expect_get_app_config(&mut hal, &clamped_tlvs);
+                // Then the previous values are restored.
+                let cmd = UciCommand::SessionSetAppConfig { session_token, config_tlvs: prev_tlvs };
+                hal.expected_send_command(cmd, set_app_config_resp(), Ok(()));
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
@@ -1964,32 +5689,30 @@ mod tests {
         )
         .await;
 
-        let result = uci_manager.session_get_state(session_id).await.unwrap();
-        assert_eq!(result, session_state);
+        let result = uci_manager.validate_params_on_device(session_id, &params, true).await;
+        assert_eq!(
+            result,
+            Ok(vec![FieldMismatch {
+                cfg_id: AppConfigTlvType::RangingInterval,
+                requested: u32_to_bytes(200),
+                actual: Some(u32_to_bytes(240)),
+                status: StatusCode::UciStatusOk,
+            }])
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_session_update_controller_multicast_list_ok() {
+    async fn test_range_start_ok() {
         let session_id = 0x123;
         let session_token = 0x123;
-        let action = UpdateMulticastListAction::AddControlee;
-        let short_address: [u8; 2] = [0x45, 0x67];
-        let controlee = Controlee { short_address, subsession_id: 0x90ab };
-        let controlee_clone = controlee.clone();
 
         let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionUpdateControllerMulticastList {
-                    session_token,
-                    action,
-                    controlees: Controlees::NoSessionKey(vec![controlee_clone]),
-                };
-                let resp = into_uci_hal_packets(
-                    uwb_uci_packets::SessionUpdateControllerMulticastListRspBuilder {
-                        status: uwb_uci_packets::StatusCode::UciStatusOk,
-                    },
-                );
+                let cmd = UciCommand::SessionStart { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
 
                 hal.expected_send_command(cmd, resp, Ok(()));
             },
@@ -2000,41 +5723,50 @@ mod tests {
         )
         .await;
 
-        let result = uci_manager
-            .session_update_controller_multicast_list(
-                session_id,
-                action,
-                uwb_uci_packets::Controlees::NoSessionKey(vec![controlee]),
-            )
-            .await;
+        let result = uci_manager.range_start(session_id).await;
         assert!(result.is_ok());
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_set_active_dt_tag_ranging_rounds() {
+    async fn test_range_start_at() {
         let session_id = 0x123;
         let session_token = 0x123;
-
-        let ranging_rounds = SessionUpdateDtTagRangingRoundsResponse {
-            status: StatusCode::UciStatusErrorRoundIndexNotActivated,
-            ranging_round_indexes: vec![3],
-        };
+        let current_uwb_time = 0x1122_3344_5566;
+        let start_uwb_time = current_uwb_time + 200_000;
 
         let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
-                let cmd = UciCommand::SessionUpdateDtTagRangingRounds {
+                let query_cmd = UciCommand::CoreQueryTimeStamp;
+                let query_resp =
+                    into_uci_hal_packets(uwb_uci_packets::CoreQueryTimeStampRspBuilder {
+                        status: uwb_uci_packets::StatusCode::UciStatusOk,
+                        timestamp: current_uwb_time,
+                    });
+                hal.expected_send_command(query_cmd.clone(), query_resp.clone(), Ok(()));
+
+                // The start time is encoded as the 8-byte UWB_INITIATION_TIME.
+                let cmd = UciCommand::SessionSetAppConfig {
                     session_token,
-                    ranging_round_indexes: vec![3, 5],
+                    config_tlvs: vec![AppConfigTlv::new(
+                        AppConfigTlvType::UwbInitiationTime,
+                        vec![0xa6, 0x62, 0x47, 0x33, 0x22, 0x11, 0x00, 0x00],
+                    )],
                 };
-                let resp = into_uci_hal_packets(
-                    uwb_uci_packets::SessionUpdateDtTagRangingRoundsRspBuilder {
-                        status: StatusCode::UciStatusErrorRoundIndexNotActivated,
-                        ranging_round_indexes: vec![3],
-                    },
-                );
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionSetAppConfigRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                    cfg_status: vec![],
+                });
+                hal.expected_send_command(cmd, resp, Ok(()));
 
+                let cmd = UciCommand::SessionStart { session_token };
+                let resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
+                    status: uwb_uci_packets::StatusCode::UciStatusOk,
+                });
                 hal.expected_send_command(cmd, resp, Ok(()));
+
+                // The start time in the past is rejected without setting the app config.
+                hal.expected_send_command(query_cmd, query_resp, Ok(()));
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
@@ -2043,26 +5775,30 @@ mod tests {
         )
         .await;
 
-        let result =
-            uci_manager.session_update_dt_tag_ranging_rounds(session_id, vec![3, 5]).await.unwrap();
-
-        assert_eq!(result, ranging_rounds);
+        assert_eq!(uci_manager.range_start_at(session_id, start_uwb_time).await, Ok(()));
+        assert_eq!(
+            uci_manager.range_start_at(session_id, current_uwb_time).await,
+            Err(Error::BadParameters)
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
     #[tokio::test]
-    async fn test_range_start_ok() {
+    async fn test_range_start_with_outcome() {
         let session_id = 0x123;
         let session_token = 0x123;
 
         let (uci_manager, mut mock_hal) = setup_uci_manager_with_session_initialized(
             |mut hal| async move {
                 let cmd = UciCommand::SessionStart { session_token };
-                let resp = into_uci_hal_packets(uwb_uci_packets::SessionStartRspBuilder {
-                    status: uwb_uci_packets::StatusCode::UciStatusOk,
-                });
-
-                hal.expected_send_command(cmd, resp, Ok(()));
+                // The COMMAND_RETRY status is retried as the other commands.
+                let resp = build_uci_packet(0x2, 0, 0x2, 0x0, vec![0x0a]);
+                hal.expected_send_command(cmd.clone(), vec![resp], Ok(()));
+                // The response carries a vendor warning byte after UCI_STATUS_OK.
+                let resp = build_uci_packet(0x2, 0, 0x2, 0x0, vec![0x00, 0xa5]);
+                hal.expected_send_command(cmd.clone(), vec![resp], Ok(()));
+                let resp = build_uci_packet(0x2, 0, 0x2, 0x0, vec![0x01]);
+                hal.expected_send_command(cmd, vec![resp], Ok(()));
             },
             UciLoggerMode::Disabled,
             mpsc::unbounded_channel::<UciLogEvent>().0,
@@ -2071,8 +5807,20 @@ mod tests {
         )
         .await;
 
-        let result = uci_manager.range_start(session_id).await;
-        assert!(result.is_ok());
+        let outcome = uci_manager.range_start_with_outcome(session_id).await.unwrap();
+        assert!(outcome.is_ok());
+        assert_eq!(
+            outcome,
+            CommandOutcome { status: StatusCode::UciStatusOk, payload: vec![0xa5] }
+        );
+
+        // The failure status is surfaced instead of being converted to an error.
+        let outcome = uci_manager.range_start_with_outcome(session_id).await.unwrap();
+        assert!(!outcome.is_ok());
+        assert_eq!(
+            outcome,
+            CommandOutcome { status: StatusCode::UciStatusRejected, payload: vec![] }
+        );
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
@@ -2216,6 +5964,64 @@ mod tests {
         assert!(mock_hal.wait_expected_calls_done().await);
     }
 
+    #[tokio::test]
+    async fn test_vendor_notification_matcher() {
+        let mt = 0x1;
+        let gid = 0xF; // Vendor reserved GID.
+        let oid = 0x3;
+        let notf_oid = 0x4;
+        let other_notf_oid = 0x5;
+        let notf_payload = vec![0x12, 0x34];
+
+        let notf_payload_clone = notf_payload.clone();
+        let (mut uci_manager, mut mock_hal) = setup_uci_manager_with_open_hal(
+            |mut hal| async move {
+                let cmd = UciCommand::RawUciCmd { mt, gid, oid, payload: vec![] };
+                let mut resp = into_uci_hal_packets(uwb_uci_packets::UciVendor_F_ResponseBuilder {
+                    opcode: oid as u8,
+                    payload: None,
+                });
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::UciVendor_F_NotificationBuilder {
+                        opcode: other_notf_oid as u8,
+                        payload: None,
+                    },
+                ));
+                resp.append(&mut into_uci_hal_packets(
+                    uwb_uci_packets::UciVendor_F_NotificationBuilder {
+                        opcode: notf_oid as u8,
+                        payload: Some(Bytes::from(notf_payload_clone)),
+                    },
+                ));
+
+                hal.expected_send_command(cmd, resp, Ok(()));
+            },
+            UciLoggerMode::Disabled,
+            mpsc::unbounded_channel::<UciLogEvent>().0,
+        )
+        .await;
+        let (vendor_notf_sender, mut vendor_notf_receiver) = mpsc::unbounded_channel();
+        uci_manager.set_vendor_notification_sender(vendor_notf_sender).await;
+
+        // Register the matcher before sending the command.
+        let notf_receiver =
+            uci_manager.register_vendor_notification_matcher(gid, notf_oid).await.unwrap();
+        let result = uci_manager.raw_uci_cmd(mt, gid, oid, vec![]).await;
+        assert_eq!(result, Ok(RawUciMessage { gid, oid, payload: vec![] }));
+
+        // The matching notification resolves the matcher, and the other one is forwarded.
+        assert_eq!(
+            notf_receiver.await.unwrap(),
+            RawUciMessage { gid, oid: notf_oid, payload: notf_payload }
+        );
+        assert_eq!(
+            vendor_notf_receiver.recv().await.unwrap(),
+            RawUciMessage { gid, oid: other_notf_oid, payload: vec![] }
+        );
+        assert!(vendor_notf_receiver.try_recv().is_err());
+        assert!(mock_hal.wait_expected_calls_done().await);
+    }
+
     #[tokio::test]
     async fn test_raw_uci_cmd_fira_gid_ok() {
         let mt = 0x1;
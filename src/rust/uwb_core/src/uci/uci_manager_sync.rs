@@ -19,21 +19,36 @@
 //! its behavior aligned with the Android JNI UCI, and routes the UciNotifications to
 //! NotificationManager.
 
-use log::{debug, error};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error, warn};
 use tokio::runtime::{Builder as RuntimeBuilder, Handle};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio::task;
+use tokio::time::{sleep_until, timeout, Instant};
 
 use crate::error::{Error, Result};
+#[cfg(feature = "latency-stats")]
+use crate::params::UciOpcode;
 use crate::params::{
-    AppConfigTlv, AppConfigTlvType, CapTlv, CoreSetConfigResponse, CountryCode, DeviceConfigId,
-    DeviceConfigTlv, FiraComponent, GetDeviceInfoResponse, PowerStats, RawUciMessage, ResetConfig,
-    SessionId, SessionState, SessionType, SessionUpdateDtTagRangingRoundsResponse,
-    SetAppConfigResponse, UpdateMulticastListAction,
+    AppConfigTlv, AppConfigTlvType, CapTlv, ControleeStatus, CoreSetConfigResponse, CountryCode,
+    DeviceConfigId, DeviceConfigTlv, FiraComponent, GetDeviceInfoResponse, HalVersion,
+    HybridSessionConfig, PowerStats, RawUciMessage, ResetConfig, SessionId, SessionState,
+    SessionToken, SessionType, SessionUpdateDtTagRangingRoundsResponse, SetAppConfigResponse,
+    UpdateMulticastListAction,
 };
+#[cfg(feature = "latency-stats")]
+use crate::uci::latency_stats::LatencyStats;
 #[cfg(any(test, feature = "mock-utils"))]
 use crate::uci::mock_uci_manager::MockUciManager;
-use crate::uci::notification::{CoreNotification, DataRcvNotification, SessionNotification};
+use crate::uci::notification::{
+    CoreNotification, DataRcvNotification, SessionNotification, SessionRangeData, UciNotification,
+};
 use crate::uci::uci_hal::UciHal;
 use crate::uci::uci_logger::{UciLogger, UciLoggerMode};
 use crate::uci::uci_manager::{UciManager, UciManagerImpl};
@@ -48,8 +63,50 @@ pub trait NotificationManager: 'static {
     /// Callback for CoreNotification.
     fn on_core_notification(&mut self, core_notification: CoreNotification) -> Result<()>;
 
-    /// Callback for SessionNotification.
-    fn on_session_notification(&mut self, session_notification: SessionNotification) -> Result<()>;
+    /// Callback for SessionNotification. It is the fallback of the specific session callbacks
+    /// below, and receives the SessionNotifications without a specific callback.
+    fn on_session_notification(
+        &mut self,
+        _session_notification: SessionNotification,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Callback for SessionNotification::Status. Forwarded to on_session_notification() by
+    /// default.
+    fn on_session_status(
+        &mut self,
+        session_token: SessionToken,
+        session_state: SessionState,
+        reason_code: u8,
+    ) -> Result<()> {
+        self.on_session_notification(SessionNotification::Status {
+            session_token,
+            session_state,
+            reason_code,
+        })
+    }
+
+    /// Callback for SessionNotification::SessionInfo. Forwarded to on_session_notification() by
+    /// default.
+    fn on_range_data(&mut self, range_data: SessionRangeData) -> Result<()> {
+        self.on_session_notification(SessionNotification::SessionInfo(range_data))
+    }
+
+    /// Callback for SessionNotification::UpdateControllerMulticastList. Forwarded to
+    /// on_session_notification() by default.
+    fn on_multicast_list_update(
+        &mut self,
+        session_token: SessionToken,
+        remaining_multicast_list_size: usize,
+        status_list: Vec<ControleeStatus>,
+    ) -> Result<()> {
+        self.on_session_notification(SessionNotification::UpdateControllerMulticastList {
+            session_token,
+            remaining_multicast_list_size,
+            status_list,
+        })
+    }
 
     /// Callback for RawUciMessage.
     fn on_vendor_notification(&mut self, vendor_notification: RawUciMessage) -> Result<()>;
@@ -69,51 +126,159 @@ pub trait NotificationManagerBuilder: 'static + Send + Sync {
     fn build(self) -> Option<Self::NotificationManager>;
 }
 
-struct NotificationDriver<U: NotificationManager> {
+/// Keeps the first SessionRangeData of each session since its ranging was started, and the
+/// callers waiting for it.
+#[derive(Default)]
+struct FirstMeasurementTracker {
+    measurements: HashMap<SessionId, SessionRangeData>,
+    waiters: Vec<(SessionId, oneshot::Sender<SessionRangeData>)>,
+}
+impl FirstMeasurementTracker {
+    fn reset(&mut self, session_id: SessionId) {
+        self.measurements.remove(&session_id);
+    }
+
+    fn subscribe(&mut self, session_id: SessionId) -> oneshot::Receiver<SessionRangeData> {
+        let (sender, receiver) = oneshot::channel();
+        match self.measurements.get(&session_id) {
+            Some(range_data) => {
+                let _ = sender.send(range_data.clone());
+            }
+            None => {
+                // Drop the waiters which already gave up.
+                self.waiters.retain(|(_, sender)| !sender.is_closed());
+                self.waiters.push((session_id, sender));
+            }
+        }
+        receiver
+    }
+
+    fn on_session_notification(&mut self, session_notification: &SessionNotification) {
+        let range_data = match session_notification {
+            SessionNotification::SessionInfo(range_data) => range_data,
+            _ => return,
+        };
+        let session_id = range_data.session_token;
+        if self.measurements.contains_key(&session_id) {
+            return;
+        }
+        self.measurements.insert(session_id, range_data.clone());
+
+        let (ready, pending) =
+            std::mem::take(&mut self.waiters).into_iter().partition(|(id, _)| *id == session_id);
+        self.waiters = pending;
+        for (_, sender) in ready.into_iter() {
+            let _ = sender.send(range_data.clone());
+        }
+    }
+}
+
+// Builds the NotificationManager on the thread of NotificationDriver, since NotificationManager can
+// be !Send.
+type NotificationManagerFactory = Box<dyn FnOnce() -> Option<Box<dyn NotificationManager>> + Send>;
+type NotificationManagerSwap = (NotificationManagerFactory, oneshot::Sender<Result<()>>);
+// The senders which receive a copy of the SessionRangeData of the session, for
+// drain_measurements().
+type MeasurementTaps = Arc<Mutex<Vec<(SessionId, mpsc::UnboundedSender<SessionRangeData>)>>>;
+// Pause or resume the delivery of the notifications, see pause_notifications().
+enum NotificationPauseCmd {
+    Pause,
+    Resume { flush: bool },
+}
+type NotificationPauseRequest = (NotificationPauseCmd, oneshot::Sender<()>);
+// The max number of the notifications buffered while paused. The oldest one is dropped when the
+// buffer is full, so a pause without resume doesn't grow the memory without bound.
+const MAX_PAUSED_NOTIFICATIONS: usize = 1024;
+
+// The notification delivered to the NotificationManager, which is buffered while paused.
+enum PendingNotification {
+    Uci(UciNotification),
+    DataRcv(DataRcvNotification),
+}
+
+struct NotificationDriver {
     core_notification_receiver: mpsc::UnboundedReceiver<CoreNotification>,
     session_notification_receiver: mpsc::UnboundedReceiver<SessionNotification>,
     vendor_notification_receiver: mpsc::UnboundedReceiver<RawUciMessage>,
     data_rcv_notification_receiver: mpsc::UnboundedReceiver<DataRcvNotification>,
-    notification_manager: U,
+    ordered_notification_receiver: mpsc::UnboundedReceiver<UciNotification>,
+    notification_manager: Box<dyn NotificationManager>,
+    notification_manager_swap_receiver: mpsc::UnboundedReceiver<NotificationManagerSwap>,
+    first_measurement_tracker: Arc<Mutex<FirstMeasurementTracker>>,
+    aoa_fom_thresholds: Arc<Mutex<HashMap<SessionId, u8>>>,
+    measurement_taps: MeasurementTaps,
+    notification_pause_receiver: mpsc::UnboundedReceiver<NotificationPauseRequest>,
+    // The notifications buffered while the delivery is paused, or None when not paused.
+    paused_notifications: Option<VecDeque<PendingNotification>>,
+    // The number of the notifications dropped since paused, because the buffer is full.
+    dropped_paused_notification_count: usize,
 }
-impl<U: NotificationManager> NotificationDriver<U> {
+impl NotificationDriver {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         core_notification_receiver: mpsc::UnboundedReceiver<CoreNotification>,
         session_notification_receiver: mpsc::UnboundedReceiver<SessionNotification>,
         vendor_notification_receiver: mpsc::UnboundedReceiver<RawUciMessage>,
         data_rcv_notification_receiver: mpsc::UnboundedReceiver<DataRcvNotification>,
-        notification_manager: U,
+        ordered_notification_receiver: mpsc::UnboundedReceiver<UciNotification>,
+        notification_manager: Box<dyn NotificationManager>,
+        notification_manager_swap_receiver: mpsc::UnboundedReceiver<NotificationManagerSwap>,
+        first_measurement_tracker: Arc<Mutex<FirstMeasurementTracker>>,
+        aoa_fom_thresholds: Arc<Mutex<HashMap<SessionId, u8>>>,
+        measurement_taps: MeasurementTaps,
+        notification_pause_receiver: mpsc::UnboundedReceiver<NotificationPauseRequest>,
     ) -> Self {
         Self {
             core_notification_receiver,
             session_notification_receiver,
             vendor_notification_receiver,
             data_rcv_notification_receiver,
+            ordered_notification_receiver,
             notification_manager,
+            notification_manager_swap_receiver,
+            first_measurement_tracker,
+            aoa_fom_thresholds,
+            measurement_taps,
+            notification_pause_receiver,
+            paused_notifications: None,
+            dropped_paused_notification_count: 0,
         }
     }
     async fn run(&mut self) {
         loop {
             tokio::select! {
                 Some(ntf) = self.core_notification_receiver.recv() =>{
-                    self.notification_manager.on_core_notification(ntf).unwrap_or_else(|e|{
-                        error!("NotificationDriver: CoreNotification callback error: {:?}",e);
-                    });
+                    self.handle_core_notification(ntf);
                 }
                 Some(ntf) = self.session_notification_receiver.recv() =>{
-                    self.notification_manager.on_session_notification(ntf).unwrap_or_else(|e|{
-                        error!("NotificationDriver: SessionNotification callback error: {:?}",e);
-                    });
+                    self.handle_session_notification(ntf);
                 }
                 Some(ntf) = self.vendor_notification_receiver.recv() =>{
-                    self.notification_manager.on_vendor_notification(ntf).unwrap_or_else(|e|{
-                        error!("NotificationDriver: RawUciMessage callback error: {:?}",e);
-                });
+                    self.handle_vendor_notification(ntf);
+                }
+                Some(ntf) = self.ordered_notification_receiver.recv() =>{
+                    match ntf {
+                        UciNotification::Core(ntf) => self.handle_core_notification(ntf),
+                        UciNotification::Session(ntf) => self.handle_session_notification(ntf),
+                        UciNotification::Vendor(ntf) => self.handle_vendor_notification(ntf),
+                    }
                 }
                 Some(data) = self.data_rcv_notification_receiver.recv() =>{
-                    self.notification_manager.on_data_rcv_notification(data).unwrap_or_else(|e|{
-                        error!("NotificationDriver: OnDataRcv callback error: {:?}",e);
-                });
+                    self.deliver_notification(PendingNotification::DataRcv(data));
+                }
+                Some((cmd, result_sender)) = self.notification_pause_receiver.recv() =>{
+                    self.handle_notification_pause_cmd(cmd);
+                    let _ = result_sender.send(());
+                }
+                Some((factory, result_sender)) = self.notification_manager_swap_receiver.recv() =>{
+                    let result = match factory() {
+                        Some(nm) => {
+                            self.notification_manager = nm;
+                            Ok(())
+                        }
+                        None => Err(Error::Unknown),
+                    };
+                    let _ = result_sender.send(result);
                 }
                 else =>{
                     debug!("NotificationDriver dropping.");
@@ -122,6 +287,133 @@ impl<U: NotificationManager> NotificationDriver<U> {
             }
         }
     }
+
+    fn handle_core_notification(&mut self, ntf: CoreNotification) {
+        self.deliver_notification(PendingNotification::Uci(UciNotification::Core(ntf)));
+    }
+
+    fn handle_session_notification(&mut self, mut ntf: SessionNotification) {
+        self.filter_aoa(&mut ntf);
+        self.first_measurement_tracker.lock().unwrap().on_session_notification(&ntf);
+        self.tap_measurement(&ntf);
+        self.deliver_notification(PendingNotification::Uci(UciNotification::Session(ntf)));
+    }
+
+    fn handle_vendor_notification(&mut self, ntf: RawUciMessage) {
+        self.deliver_notification(PendingNotification::Uci(UciNotification::Vendor(ntf)));
+    }
+
+    fn handle_notification_pause_cmd(&mut self, cmd: NotificationPauseCmd) {
+        match cmd {
+            NotificationPauseCmd::Pause => {
+                self.paused_notifications.get_or_insert_with(VecDeque::new);
+            }
+            NotificationPauseCmd::Resume { flush } => {
+                let paused_notifications = self.paused_notifications.take().unwrap_or_default();
+                if self.dropped_paused_notification_count > 0 {
+                    warn!(
+                        "{} notifications were dropped during the pause",
+                        self.dropped_paused_notification_count
+                    );
+                    self.dropped_paused_notification_count = 0;
+                }
+                if !flush {
+                    debug!("Drop {} paused notifications", paused_notifications.len());
+                    return;
+                }
+                for ntf in paused_notifications {
+                    self.dispatch_notification(ntf);
+                }
+            }
+        }
+    }
+
+    // Dispatch the notification to the NotificationManager, or buffer it while paused.
+    fn deliver_notification(&mut self, ntf: PendingNotification) {
+        match self.paused_notifications.as_mut() {
+            Some(paused_notifications) => {
+                if paused_notifications.len() >= MAX_PAUSED_NOTIFICATIONS {
+                    if self.dropped_paused_notification_count == 0 {
+                        warn!("The paused notification buffer is full, drop the oldest ones");
+                    }
+                    self.dropped_paused_notification_count += 1;
+                    paused_notifications.pop_front();
+                }
+                paused_notifications.push_back(ntf);
+            }
+            None => self.dispatch_notification(ntf),
+        }
+    }
+
+    fn dispatch_notification(&mut self, ntf: PendingNotification) {
+        match ntf {
+            PendingNotification::Uci(UciNotification::Core(ntf)) => {
+                self.notification_manager.on_core_notification(ntf).unwrap_or_else(|e| {
+                    error!("NotificationDriver: CoreNotification callback error: {:?}", e);
+                })
+            }
+            PendingNotification::Uci(UciNotification::Session(ntf)) => {
+                self.dispatch_session_notification(ntf).unwrap_or_else(|e| {
+                    error!("NotificationDriver: SessionNotification callback error: {:?}", e);
+                })
+            }
+            PendingNotification::Uci(UciNotification::Vendor(ntf)) => {
+                self.notification_manager.on_vendor_notification(ntf).unwrap_or_else(|e| {
+                    error!("NotificationDriver: RawUciMessage callback error: {:?}", e);
+                })
+            }
+            PendingNotification::DataRcv(data) => {
+                self.notification_manager.on_data_rcv_notification(data).unwrap_or_else(|e| {
+                    error!("NotificationDriver: OnDataRcv callback error: {:?}", e);
+                })
+            }
+        }
+    }
+
+    // Send a copy of the SessionRangeData to the taps of the session, and drop the closed taps.
+    fn tap_measurement(&self, ntf: &SessionNotification) {
+        if let SessionNotification::SessionInfo(range_data) = ntf {
+            self.measurement_taps.lock().unwrap().retain(|(session_id, sender)| {
+                if *session_id == range_data.session_token {
+                    return sender.send(range_data.clone()).is_ok();
+                }
+                !sender.is_closed()
+            });
+        }
+    }
+
+    // Discard the AoA results below the FoM threshold of the session, if any.
+    fn filter_aoa(&self, ntf: &mut SessionNotification) {
+        if let SessionNotification::SessionInfo(range_data) = ntf {
+            let min_fom =
+                self.aoa_fom_thresholds.lock().unwrap().get(&range_data.session_token).copied();
+            if let Some(min_fom) = min_fom {
+                range_data.discard_low_fom_aoa(min_fom);
+            }
+        }
+    }
+
+    // Dispatch the SessionNotification to the specific callback of the NotificationManager.
+    fn dispatch_session_notification(&mut self, ntf: SessionNotification) -> Result<()> {
+        match ntf {
+            SessionNotification::Status { session_token, session_state, reason_code } => self
+                .notification_manager
+                .on_session_status(session_token, session_state, reason_code),
+            SessionNotification::SessionInfo(range_data) => {
+                self.notification_manager.on_range_data(range_data)
+            }
+            SessionNotification::UpdateControllerMulticastList {
+                session_token,
+                remaining_multicast_list_size,
+                status_list,
+            } => self.notification_manager.on_multicast_list_update(
+                session_token,
+                remaining_multicast_list_size,
+                status_list,
+            ),
+            _ => self.notification_manager.on_session_notification(ntf),
+        }
+    }
 }
 
 /// The UciManagerSync provides a synchornized version of UciManager.
@@ -133,6 +425,21 @@ impl<U: NotificationManager> NotificationDriver<U> {
 pub struct UciManagerSync<U: UciManager> {
     runtime_handle: Handle,
     uci_manager: U,
+    first_measurement_tracker: Arc<Mutex<FirstMeasurementTracker>>,
+    // The min FoM of the AoA results of each session, applied by NotificationDriver.
+    aoa_fom_thresholds: Arc<Mutex<HashMap<SessionId, u8>>>,
+    // Receive the SessionRangeData of the sessions being drained by drain_measurements().
+    measurement_taps: MeasurementTaps,
+    notification_manager_swap_sender: Option<mpsc::UnboundedSender<NotificationManagerSwap>>,
+    notification_pause_sender: Option<mpsc::UnboundedSender<NotificationPauseRequest>>,
+    // Send the notifications to NotificationDriver in order, if set_ordered_notification() enables
+    // it.
+    ordered_notification_sender: Option<mpsc::UnboundedSender<UciNotification>>,
+    ordered_notification: bool,
+    // The sessions stopped by range_pause(), which are waiting for range_resume().
+    paused_sessions: Mutex<HashSet<SessionId>>,
+    // Wake all the pending wait_for_*() methods by cancel_all_waits().
+    cancel_sender: broadcast::Sender<()>,
 }
 impl<U: UciManager> UciManagerSync<U> {
     /// Redirects notification to a new NotificationManager using the notification_manager_builder.
@@ -149,14 +456,29 @@ impl<U: UciManager> UciManagerSync<U> {
             mpsc::unbounded_channel::<RawUciMessage>();
         let (data_rcv_notification_sender, data_rcv_notification_receiver) =
             mpsc::unbounded_channel::<DataRcvNotification>();
+        let (ordered_notification_sender, ordered_notification_receiver) =
+            mpsc::unbounded_channel::<UciNotification>();
+        let ordered_notf_sender =
+            self.ordered_notification.then(|| ordered_notification_sender.clone());
+        self.ordered_notification_sender = Some(ordered_notification_sender);
         self.runtime_handle.to_owned().block_on(async {
             self.uci_manager.set_core_notification_sender(core_notification_sender).await;
             self.uci_manager.set_session_notification_sender(session_notification_sender).await;
             self.uci_manager.set_vendor_notification_sender(vendor_notification_sender).await;
             self.uci_manager.set_data_rcv_notification_sender(data_rcv_notification_sender).await;
+            self.uci_manager.set_ordered_notification_sender(ordered_notf_sender).await;
         });
         // The potentially !Send NotificationManager is created in a separate thread.
         let (driver_status_sender, mut driver_status_receiver) = mpsc::unbounded_channel::<bool>();
+        let first_measurement_tracker = self.first_measurement_tracker.clone();
+        let aoa_fom_thresholds = self.aoa_fom_thresholds.clone();
+        let measurement_taps = self.measurement_taps.clone();
+        let (notification_manager_swap_sender, notification_manager_swap_receiver) =
+            mpsc::unbounded_channel::<NotificationManagerSwap>();
+        self.notification_manager_swap_sender = Some(notification_manager_swap_sender);
+        let (notification_pause_sender, notification_pause_receiver) =
+            mpsc::unbounded_channel::<NotificationPauseRequest>();
+        self.notification_pause_sender = Some(notification_pause_sender);
         std::thread::spawn(move || {
             let notification_runtime =
                 match RuntimeBuilder::new_current_thread().enable_all().build() {
@@ -186,7 +508,13 @@ impl<U: UciManager> UciManagerSync<U> {
                 session_notification_receiver,
                 vendor_notification_receiver,
                 data_rcv_notification_receiver,
-                notification_manager,
+                ordered_notification_receiver,
+                Box::new(notification_manager),
+                notification_manager_swap_receiver,
+                first_measurement_tracker,
+                aoa_fom_thresholds,
+                measurement_taps,
+                notification_pause_receiver,
             );
             local.spawn_local(async move {
                 task::spawn_local(async move { notification_driver.run().await }).await.unwrap();
@@ -199,34 +527,116 @@ impl<U: UciManager> UciManagerSync<U> {
         }
     }
 
+    /// Enable or disable dispatching the core, session and vendor notifications to the
+    /// NotificationManager in the order they are produced, e.g. a session status change is seen
+    /// before the range data that follows it. Otherwise the order across the kinds of the
+    /// notifications is not guaranteed.
+    pub fn set_ordered_notification(&mut self, enabled: bool) {
+        self.ordered_notification = enabled;
+        let ordered_notf_sender =
+            self.ordered_notification_sender.as_ref().filter(|_| enabled).cloned();
+        self.runtime_handle
+            .to_owned()
+            .block_on(self.uci_manager.set_ordered_notification_sender(ordered_notf_sender));
+    }
+
+    /// Replaces the NotificationManager of the running notification thread with the one built by
+    /// notification_manager_builder. Unlike redirect_notification(), the notification channels are
+    /// kept, so no notification is dropped during the swap.
+    pub fn set_notification_manager<T: NotificationManagerBuilder>(
+        &self,
+        notification_manager_builder: T,
+    ) -> Result<()> {
+        let factory: NotificationManagerFactory = Box::new(move || {
+            notification_manager_builder
+                .build()
+                .map(|nm| Box::new(nm) as Box<dyn NotificationManager>)
+        });
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.notification_manager_swap_sender
+            .as_ref()
+            .ok_or(Error::Unknown)?
+            .send((factory, result_sender))
+            .map_err(|_| {
+                error!("The notification thread is not running");
+                Error::Unknown
+            })?;
+        result_receiver.blocking_recv().unwrap_or(Err(Error::Unknown))
+    }
+
+    /// Buffer the notifications in the notification thread instead of delivering them to the
+    /// NotificationManager, until resume_notifications() is called. The waits for the
+    /// measurements, e.g. wait_for_first_measurement(), are not paused. At most 1024
+    /// notifications are buffered, and the oldest ones are dropped beyond that.
+    ///
+    /// Calling it from the NotificationManager callbacks is not supported, and returns
+    /// Error::ReentrantCall.
+    pub fn pause_notifications(&self) -> Result<()> {
+        self.send_notification_pause_cmd(NotificationPauseCmd::Pause)
+    }
+
+    /// Resume delivering the notifications to the NotificationManager. The notifications buffered
+    /// since pause_notifications() are delivered in order before returning if |flush| is true, or
+    /// dropped otherwise.
+    ///
+    /// Calling it from the NotificationManager callbacks is not supported, and returns
+    /// Error::ReentrantCall.
+    pub fn resume_notifications(&self, flush: bool) -> Result<()> {
+        self.send_notification_pause_cmd(NotificationPauseCmd::Resume { flush })
+    }
+
+    fn send_notification_pause_cmd(&self, cmd: NotificationPauseCmd) -> Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.notification_pause_sender
+            .as_ref()
+            .ok_or(Error::Unknown)?
+            .send((cmd, result_sender))
+            .map_err(|_| {
+            error!("The notification thread is not running");
+            Error::Unknown
+        })?;
+        self.block_on(async { result_receiver.await.map_err(|_| Error::Unknown) })
+    }
+
     /// Set logger mode.
     pub fn set_logger_mode(&self, logger_mode: UciLoggerMode) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.set_logger_mode(logger_mode))
+        self.block_on(self.uci_manager.set_logger_mode(logger_mode))
     }
     /// Start UCI HAL and blocking until UCI commands can be sent.
     pub fn open_hal(&self) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.open_hal())
+        self.block_on(self.uci_manager.open_hal())
     }
 
     /// Stop the UCI HAL.
     pub fn close_hal(&self, force: bool) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.close_hal(force))
+        self.block_on(self.uci_manager.close_hal(force))
     }
 
     // Methods for sending UCI commands. Functions are blocked until UCI response is received.
     /// Send UCI command for device reset.
     pub fn device_reset(&self, reset_config: ResetConfig) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.device_reset(reset_config))
+        self.block_on(self.uci_manager.device_reset(reset_config))
     }
 
     /// Send UCI command for getting device info.
     pub fn core_get_device_info(&self) -> Result<GetDeviceInfoResponse> {
-        self.runtime_handle.block_on(self.uci_manager.core_get_device_info())
+        self.block_on(self.uci_manager.core_get_device_info())
+    }
+
+    /// Read the versions of the UWBS from the device info, and return
+    /// Error::HalVersionMismatch if they are not the same as |expected|.
+    pub fn verify_hal_version(&self, expected: HalVersion) -> Result<()> {
+        let actual = HalVersion::from(&self.core_get_device_info()?);
+        if actual != expected {
+            error!("The version of the UWBS is {:?}, expected {:?}", actual, expected);
+            return Err(Error::HalVersionMismatch);
+        }
+        Ok(())
     }
 
     /// Send UCI command for getting capability info
     pub fn core_get_caps_info(&self) -> Result<Vec<CapTlv>> {
-        self.runtime_handle.block_on(self.uci_manager.core_get_caps_info())
+        self.block_on(self.uci_manager.core_get_caps_info())
     }
 
     /// Send UCI command for setting core configuration.
@@ -234,22 +644,39 @@ impl<U: UciManager> UciManagerSync<U> {
         &self,
         config_tlvs: Vec<DeviceConfigTlv>,
     ) -> Result<CoreSetConfigResponse> {
-        self.runtime_handle.block_on(self.uci_manager.core_set_config(config_tlvs))
+        self.block_on(self.uci_manager.core_set_config(config_tlvs))
     }
 
     /// Send UCI command for getting core configuration.
     pub fn core_get_config(&self, config_ids: Vec<DeviceConfigId>) -> Result<Vec<DeviceConfigTlv>> {
-        self.runtime_handle.block_on(self.uci_manager.core_get_config(config_ids))
+        self.block_on(self.uci_manager.core_get_config(config_ids))
     }
 
     /// Send UCI command for initiating session.
     pub fn session_init(&self, session_id: SessionId, session_type: SessionType) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.session_init(session_id, session_type))
+        self.block_on(self.uci_manager.session_init(session_id, session_type))
+    }
+
+    /// Send UCI command for initiating session, and return the SessionGuard of the session.
+    pub fn session_init_guarded(
+        &self,
+        session_id: SessionId,
+        session_type: SessionType,
+    ) -> Result<SessionGuard<'_, U>> {
+        self.session_init(session_id, session_type)?;
+        Ok(SessionGuard {
+            uci_manager_sync: self,
+            session_id,
+            session_type,
+            deinit_on_drop: false,
+            is_deinitialized: false,
+        })
     }
 
     /// Send UCI command for deinitiating session.
     pub fn session_deinit(&self, session_id: SessionId) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.session_deinit(session_id))
+        self.paused_sessions.lock().unwrap().remove(&session_id);
+        self.block_on(self.uci_manager.session_deinit(session_id))
     }
 
     /// Send UCI command for setting app config.
@@ -258,8 +685,7 @@ impl<U: UciManager> UciManagerSync<U> {
         session_id: SessionId,
         config_tlvs: Vec<AppConfigTlv>,
     ) -> Result<SetAppConfigResponse> {
-        self.runtime_handle
-            .block_on(self.uci_manager.session_set_app_config(session_id, config_tlvs))
+        self.block_on(self.uci_manager.session_set_app_config(session_id, config_tlvs))
     }
 
     /// Send UCI command for getting app config.
@@ -268,18 +694,17 @@ impl<U: UciManager> UciManagerSync<U> {
         session_id: SessionId,
         config_ids: Vec<AppConfigTlvType>,
     ) -> Result<Vec<AppConfigTlv>> {
-        self.runtime_handle
-            .block_on(self.uci_manager.session_get_app_config(session_id, config_ids))
+        self.block_on(self.uci_manager.session_get_app_config(session_id, config_ids))
     }
 
     /// Send UCI command for getting count of sessions.
     pub fn session_get_count(&self) -> Result<u8> {
-        self.runtime_handle.block_on(self.uci_manager.session_get_count())
+        self.block_on(self.uci_manager.session_get_count())
     }
 
     /// Send UCI command for getting state of session.
     pub fn session_get_state(&self, session_id: SessionId) -> Result<SessionState> {
-        self.runtime_handle.block_on(self.uci_manager.session_get_state(session_id))
+        self.block_on(self.uci_manager.session_get_state(session_id))
     }
 
     /// Send UCI command for updating multicast list for multicast session.
@@ -289,7 +714,7 @@ impl<U: UciManager> UciManagerSync<U> {
         action: UpdateMulticastListAction,
         controlees: Controlees,
     ) -> Result<()> {
-        self.runtime_handle.block_on(
+        self.block_on(
             self.uci_manager
                 .session_update_controller_multicast_list(session_id, action, controlees),
         )
@@ -301,7 +726,7 @@ impl<U: UciManager> UciManagerSync<U> {
         session_id: u32,
         ranging_round_indexes: Vec<u8>,
     ) -> Result<SessionUpdateDtTagRangingRoundsResponse> {
-        self.runtime_handle.block_on(
+        self.block_on(
             self.uci_manager
                 .session_update_dt_tag_ranging_rounds(session_id, ranging_round_indexes),
         )
@@ -309,32 +734,165 @@ impl<U: UciManager> UciManagerSync<U> {
 
     /// Send UCI command for getting max data size for session.
     pub fn session_query_max_data_size(&self, session_id: SessionId) -> Result<u16> {
-        self.runtime_handle.block_on(self.uci_manager.session_query_max_data_size(session_id))
+        self.block_on(self.uci_manager.session_query_max_data_size(session_id))
+    }
+
+    /// Send UCI command for setting the hybrid UWB scheduling config of the controller session.
+    pub fn session_set_hybrid_config(
+        &self,
+        session_id: SessionId,
+        config: HybridSessionConfig,
+    ) -> Result<()> {
+        self.block_on(self.uci_manager.session_set_hybrid_config(session_id, config))
     }
 
     /// Send UCI command for starting ranging of the session.
     pub fn range_start(&self, session_id: SessionId) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.range_start(session_id))
+        self.first_measurement_tracker.lock().unwrap().reset(session_id);
+        self.block_on(self.uci_manager.range_start(session_id))?;
+        self.paused_sessions.lock().unwrap().remove(&session_id);
+        Ok(())
+    }
+
+    /// Start ranging of the sessions concurrently, and return the result of each session in the
+    /// order of |session_ids|.
+    pub fn range_start_many(&self, session_ids: &[SessionId]) -> Vec<(SessionId, Result<()>)> {
+        for session_id in session_ids.iter() {
+            self.first_measurement_tracker.lock().unwrap().reset(*session_id);
+        }
+        let results = self.block_on(async {
+            let handles = session_ids
+                .iter()
+                .map(|&session_id| {
+                    let uci_manager = self.uci_manager.clone();
+                    let handle =
+                        tokio::spawn(async move { uci_manager.range_start(session_id).await });
+                    (session_id, handle)
+                })
+                .collect::<Vec<_>>();
+            let mut results = vec![];
+            for (session_id, handle) in handles.into_iter() {
+                results.push((session_id, handle.await.unwrap_or(Err(Error::Unknown))));
+            }
+            Ok(results)
+        });
+        let results = match results {
+            Ok(results) => results,
+            Err(e) => session_ids.iter().map(|&session_id| (session_id, Err(e.clone()))).collect(),
+        };
+
+        let mut paused_sessions = self.paused_sessions.lock().unwrap();
+        for (session_id, result) in results.iter() {
+            if result.is_ok() {
+                paused_sessions.remove(session_id);
+            }
+        }
+        results
+    }
+
+    /// Wait until the first ranging result of the session is received after range_start(), or
+    /// return Error::Timeout after |duration|. The result is still forwarded to the
+    /// NotificationManager.
+    pub fn wait_for_first_measurement(
+        &self,
+        session_id: SessionId,
+        duration: Duration,
+    ) -> Result<SessionRangeData> {
+        let receiver = self.first_measurement_tracker.lock().unwrap().subscribe(session_id);
+        self.block_on(self.cancellable(async {
+            match timeout(duration, receiver).await {
+                Ok(Ok(range_data)) => Ok(range_data),
+                Ok(Err(_)) => Err(Error::Unknown),
+                Err(_) => Err(Error::Timeout),
+            }
+        }))
+    }
+
+    /// Collect the ranging results of the session received from now on, until |max_count| results
+    /// are collected or |max_wait| elapses, whichever comes first. The results are still
+    /// forwarded to the NotificationManager. The collected results are returned early if
+    /// cancel_all_waits() is called.
+    pub fn drain_measurements(
+        &self,
+        session_id: SessionId,
+        max_count: usize,
+        max_wait: Duration,
+    ) -> Result<Vec<SessionRangeData>> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        self.measurement_taps.lock().unwrap().push((session_id, sender));
+        let mut cancel_receiver = self.cancel_sender.subscribe();
+        self.block_on(async move {
+            let deadline = Instant::now() + max_wait;
+            let mut measurements = vec![];
+            while measurements.len() < max_count {
+                tokio::select! {
+                    Some(range_data) = receiver.recv() => measurements.push(range_data),
+                    _ = sleep_until(deadline) => break,
+                    _ = cancel_receiver.recv() => break,
+                }
+            }
+            Ok(measurements)
+        })
+    }
+
+    /// Zero the AoA results of the session whose FoM is below |min_fom|, together with their FoM,
+    /// before the SessionRangeData is forwarded to the NotificationManager. Set 0 to forward the
+    /// AoA results as they are.
+    pub fn set_aoa_fom_threshold(&self, session_id: SessionId, min_fom: u8) {
+        let mut aoa_fom_thresholds = self.aoa_fom_thresholds.lock().unwrap();
+        if min_fom == 0 {
+            aoa_fom_thresholds.remove(&session_id);
+        } else {
+            aoa_fom_thresholds.insert(session_id, min_fom);
+        }
+    }
+
+    /// Wake all the pending wait_for_*() methods with Error::Cancelled, e.g. when the app is
+    /// shutting down. The waits which start after this call are not affected.
+    pub fn cancel_all_waits(&self) {
+        let _ = self.cancel_sender.send(());
     }
 
     /// Send UCI command for stopping ranging of the session.
     pub fn range_stop(&self, session_id: SessionId) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.range_stop(session_id))
+        self.block_on(self.uci_manager.range_stop(session_id))
+    }
+
+    /// Pause the ranging of the active session. The ranging is stopped, but the app config of the
+    /// session is kept, so range_resume() only needs to start the ranging again.
+    pub fn range_pause(&self, session_id: SessionId) -> Result<()> {
+        let state = self.session_get_state(session_id)?;
+        if state != SessionState::SessionStateActive {
+            error!("Session {} cannot be paused at {:?}", session_id, state);
+            return Err(Error::BadParameters);
+        }
+        self.range_stop(session_id)?;
+        self.paused_sessions.lock().unwrap().insert(session_id);
+        Ok(())
+    }
+
+    /// Resume the ranging of the session paused by range_pause().
+    pub fn range_resume(&self, session_id: SessionId) -> Result<()> {
+        if !self.paused_sessions.lock().unwrap().contains(&session_id) {
+            error!("Session {} is not paused", session_id);
+            return Err(Error::BadParameters);
+        }
+        self.range_start(session_id)
     }
 
     /// Send UCI command for getting ranging count.
     pub fn range_get_ranging_count(&self, session_id: SessionId) -> Result<usize> {
-        self.runtime_handle.block_on(self.uci_manager.range_get_ranging_count(session_id))
+        self.block_on(self.uci_manager.range_get_ranging_count(session_id))
     }
 
     /// Set the country code. Android-specific method.
     pub fn android_set_country_code(&self, country_code: CountryCode) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.android_set_country_code(country_code))
+        self.block_on(self.uci_manager.android_set_country_code(country_code))
     }
 
     /// Get the power statistics. Android-specific method.
     pub fn android_get_power_stats(&self) -> Result<PowerStats> {
-        self.runtime_handle.block_on(self.uci_manager.android_get_power_stats())
+        self.block_on(self.uci_manager.android_get_power_stats())
     }
 
     /// Send a raw UCI command.
@@ -345,7 +903,7 @@ impl<U: UciManager> UciManagerSync<U> {
         oid: u32,
         payload: Vec<u8>,
     ) -> Result<RawUciMessage> {
-        self.runtime_handle.block_on(self.uci_manager.raw_uci_cmd(mt, gid, oid, payload))
+        self.block_on(self.uci_manager.raw_uci_cmd(mt, gid, oid, payload))
     }
 
     /// Send a data packet
@@ -357,7 +915,7 @@ impl<U: UciManager> UciManagerSync<U> {
         uci_sequence_num: u8,
         app_payload_data: Vec<u8>,
     ) -> Result<()> {
-        self.runtime_handle.block_on(self.uci_manager.send_data_packet(
+        self.block_on(self.uci_manager.send_data_packet(
             session_id,
             address,
             dest_end_point,
@@ -367,7 +925,103 @@ impl<U: UciManager> UciManagerSync<U> {
     }
     /// Get session token for session id.
     pub fn get_session_token(&self, session_id : SessionId) -> Result<u32> {
-        self.runtime_handle.block_on(self.uci_manager.get_session_token_from_session_id(session_id))
+        self.block_on(self.uci_manager.get_session_token_from_session_id(session_id))
+    }
+
+    // Wrap the |future| of a wait_for_*() method, which resolves to Error::Cancelled once
+    // cancel_all_waits() is called. The receiver is subscribed before the future is polled, so
+    // the cancellation is not missed.
+    fn cancellable<T>(
+        &self,
+        future: impl Future<Output = Result<T>>,
+    ) -> impl Future<Output = Result<T>> {
+        let mut cancel_receiver = self.cancel_sender.subscribe();
+        async move {
+            tokio::select! {
+                result = future => result,
+                _ = cancel_receiver.recv() => Err(Error::Cancelled),
+            }
+        }
+    }
+
+    // Block on the |future| with the runtime. Handle::block_on() panics before polling the future
+    // when it is called inside an async execution context, so return Error::ReentrantCall instead
+    // and the caller should use UciManager. Calling it from spawn_blocking() or under
+    // Runtime::enter() is allowed. A panic raised while polling the future is propagated as is.
+    fn block_on<T, F: Future<Output = Result<T>>>(&self, future: F) -> Result<T> {
+        let polled = AtomicBool::new(false);
+        let future = async {
+            polled.store(true, Ordering::Relaxed);
+            future.await
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| self.runtime_handle.block_on(future))) {
+            Ok(result) => result,
+            Err(payload) if polled.load(Ordering::Relaxed) => panic::resume_unwind(payload),
+            Err(_) => {
+                error!(
+                    "UciManagerSync is called inside the runtime, use the async UciManager instead"
+                );
+                Err(Error::ReentrantCall)
+            }
+        }
+    }
+}
+
+/// The guard of an initialized session, which forwards the session-scoped commands to the
+/// UciManagerSync it is created from.
+pub struct SessionGuard<'a, U: UciManager> {
+    uci_manager_sync: &'a UciManagerSync<U>,
+    session_id: SessionId,
+    session_type: SessionType,
+    deinit_on_drop: bool,
+    is_deinitialized: bool,
+}
+
+impl<U: UciManager> SessionGuard<'_, U> {
+    /// The id of the session.
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// The type of the session.
+    pub fn session_type(&self) -> SessionType {
+        self.session_type
+    }
+
+    /// Deinitialize the session automatically when the guard is dropped.
+    pub fn set_deinit_on_drop(&mut self, deinit_on_drop: bool) {
+        self.deinit_on_drop = deinit_on_drop;
+    }
+
+    /// Send UCI command for setting app config of the session.
+    pub fn set_app_config(&self, config_tlvs: Vec<AppConfigTlv>) -> Result<SetAppConfigResponse> {
+        self.uci_manager_sync.session_set_app_config(self.session_id, config_tlvs)
+    }
+
+    /// Send UCI command for starting ranging of the session.
+    pub fn start(&self) -> Result<()> {
+        self.uci_manager_sync.range_start(self.session_id)
+    }
+
+    /// Send UCI command for stopping ranging of the session.
+    pub fn stop(&self) -> Result<()> {
+        self.uci_manager_sync.range_stop(self.session_id)
+    }
+
+    /// Send UCI command for deinitiating the session.
+    pub fn deinit(mut self) -> Result<()> {
+        self.is_deinitialized = true;
+        self.uci_manager_sync.session_deinit(self.session_id)
+    }
+}
+
+impl<U: UciManager> Drop for SessionGuard<'_, U> {
+    fn drop(&mut self) {
+        if self.deinit_on_drop && !self.is_deinitialized {
+            if let Err(e) = self.uci_manager_sync.session_deinit(self.session_id) {
+                error!("Failed to deinit the session {} on drop: {:?}", self.session_id, e);
+            }
+        }
     }
 }
 
@@ -395,10 +1049,67 @@ impl UciManagerSync<UciManagerImpl> {
         // UciManagerImpl::new uses tokio::spawn, so it is called inside the runtime as async fn.
         let uci_manager = runtime_handle
             .block_on(async { UciManagerImpl::new(hal, logger, UciLoggerMode::Disabled) });
-        let mut uci_manager_sync = UciManagerSync { runtime_handle, uci_manager };
+        let mut uci_manager_sync = UciManagerSync {
+            runtime_handle,
+            uci_manager,
+            first_measurement_tracker: Default::default(),
+            aoa_fom_thresholds: Default::default(),
+            measurement_taps: Default::default(),
+            notification_manager_swap_sender: None,
+            notification_pause_sender: None,
+            ordered_notification_sender: None,
+            ordered_notification: false,
+            paused_sessions: Default::default(),
+            cancel_sender: broadcast::channel(1).0,
+        };
         uci_manager_sync.redirect_notification(notification_manager_builder)?;
         Ok(uci_manager_sync)
     }
+
+    /// Get the latency statistics of the UCI commands, keyed by the opcode of the commands.
+    #[cfg(feature = "latency-stats")]
+    pub fn latency_stats(&self) -> HashMap<UciOpcode, LatencyStats> {
+        self.uci_manager.latency_stats()
+    }
+
+    /// Serialize the latency statistics of the UCI commands to a LatencyStatsReport protobuf
+    /// message.
+    #[cfg(feature = "metrics-export")]
+    pub fn export_latency_stats(&self) -> Result<Vec<u8>> {
+        self.uci_manager.export_latency_stats()
+    }
+
+    /// Send the vendor command for querying the ranging rounds which could be used by the DL-TDoA
+    /// session, see UciManagerImpl::session_get_possible_ranging_rounds().
+    pub fn session_get_possible_ranging_rounds(&self, session_id: SessionId) -> Result<Vec<u8>> {
+        self.block_on(self.uci_manager.session_get_possible_ranging_rounds(session_id))
+    }
+
+    /// Wait until the UWBS reports DEVICE_STATE_READY, or return Error::Timeout after |duration|.
+    pub fn wait_device_ready(&self, duration: Duration) -> Result<()> {
+        self.block_on(self.cancellable(async {
+            timeout(duration, self.uci_manager.device_ready()).await.unwrap_or(Err(Error::Timeout))
+        }))
+    }
+
+    /// Get the clock accuracy of the UWBS in ppm. Return Error::NotSupported if the UWBS doesn't
+    /// report the vendor-specific capability set by UciManagerImpl::set_vendor_cap_ids().
+    pub fn clock_accuracy_ppm(&self) -> Result<f32> {
+        self.block_on(self.uci_manager.clock_accuracy_ppm())
+    }
+
+    /// Force closing and reopening the UCI HAL, which recovers the UWBS after it reports
+    /// DEVICE_STATE_ERROR. All the commands except CORE_DEVICE_RESET_CMD fail with
+    /// Error::DeviceError until it succeeds, or the UWBS reports it is ready again.
+    pub fn reset_and_reopen(&self) -> Result<()> {
+        self.block_on(self.uci_manager.reset_and_reopen())
+    }
+
+    /// Start UCI HAL like open_hal(), and retry up to |max_attempts| times in total with |delay|
+    /// between the attempts. Return the error of the last attempt.
+    pub fn open_hal_with_retry(&self, max_attempts: usize, delay: Duration) -> Result<()> {
+        self.block_on(self.uci_manager.open_hal_with_retry(max_attempts, delay))
+    }
 }
 
 #[cfg(any(test, feature = "mock-utils"))]
@@ -409,7 +1120,19 @@ impl UciManagerSync<MockUciManager> {
         runtime_handle: Handle,
         notification_manager_builder: T,
     ) -> Result<Self> {
-        let mut uci_manager_sync = UciManagerSync { uci_manager, runtime_handle };
+        let mut uci_manager_sync = UciManagerSync {
+            uci_manager,
+            runtime_handle,
+            first_measurement_tracker: Default::default(),
+            aoa_fom_thresholds: Default::default(),
+            measurement_taps: Default::default(),
+            notification_manager_swap_sender: None,
+            notification_pause_sender: None,
+            ordered_notification_sender: None,
+            ordered_notification: false,
+            paused_sessions: Default::default(),
+            cancel_sender: broadcast::channel(1).0,
+        };
         uci_manager_sync.redirect_notification(notification_manager_builder)?;
         Ok(uci_manager_sync)
     }
@@ -425,9 +1148,10 @@ mod tests {
     use tokio::runtime::Builder;
     use uwb_uci_packets::DeviceState::DeviceStateReady;
 
-    use crate::params::uci_packets::GetDeviceInfoResponse;
+    use crate::params::uci_packets::{CreditAvailability, GetDeviceInfoResponse, StatusCode};
+    use crate::session::session_manager::test_utils::session_range_data;
     use crate::uci::mock_uci_manager::MockUciManager;
-    use crate::uci::{CoreNotification, UciNotification};
+    use crate::uci::{CoreNotification, RangingMeasurements, UciNotification};
 
     /// Mock NotificationManager forwarding notifications received.
     /// The nonsend_counter is deliberately !send to check UciManagerSync::redirect_notification.
@@ -517,4 +1241,642 @@ mod tests {
         assert!(device_state.is_some());
         assert!(uci_manager_sync.core_get_device_info().is_ok());
     }
+
+    #[test]
+    fn test_sync_uci_verify_hal_version() {
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let device_info = GetDeviceInfoResponse {
+            uci_version: 0x0002,
+            mac_version: 0x0003,
+            phy_version: 0x0004,
+            uci_test_version: 0x0005,
+            vendor_spec_info: vec![0x12],
+        };
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_core_get_device_info(Ok(device_info.clone()));
+        uci_manager_impl.expect_core_get_device_info(Ok(device_info.clone()));
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        let expected = HalVersion::from(&device_info);
+        assert_eq!(uci_manager_sync.verify_hal_version(expected), Ok(()));
+        assert_eq!(
+            uci_manager_sync.verify_hal_version(HalVersion { phy_version: 0x0006, ..expected }),
+            Err(Error::HalVersionMismatch)
+        );
+    }
+
+    #[test]
+    fn test_sync_uci_cancel_all_waits() {
+        let session_id = 0x123;
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let uci_manager_sync = UciManagerSync::new_mock(
+            MockUciManager::new(),
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                uci_manager_sync.wait_for_first_measurement(session_id, Duration::from_secs(10))
+            });
+            // Wait until the waiter subscribes to the cancellation.
+            while uci_manager_sync.cancel_sender.receiver_count() == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            uci_manager_sync.cancel_all_waits();
+            assert_eq!(waiter.join().unwrap(), Err(Error::Cancelled));
+        });
+    }
+
+    #[test]
+    fn test_sync_uci_drain_measurements() {
+        let session_id = 0x123;
+        let other_session_id = 0x456;
+        let range_data = |session_id, sequence_number| SessionRangeData {
+            sequence_number,
+            ..session_range_data(session_id)
+        };
+        let notfs: Vec<_> = [
+            range_data(session_id, 1),
+            range_data(other_session_id, 1),
+            range_data(session_id, 2),
+            range_data(session_id, 3),
+            range_data(session_id, 4),
+        ]
+        .into_iter()
+        .map(|range_data| UciNotification::Session(SessionNotification::SessionInfo(range_data)))
+        .collect();
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(session_id, notfs.clone(), Ok(()));
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        // Nothing is collected before the ranging is started.
+        assert_eq!(
+            uci_manager_sync.drain_measurements(session_id, 3, Duration::from_millis(10)),
+            Ok(vec![])
+        );
+
+        std::thread::scope(|scope| {
+            let drainer = scope.spawn(|| {
+                uci_manager_sync.drain_measurements(session_id, 3, Duration::from_secs(1))
+            });
+            // Wait until the drainer taps the measurements.
+            while uci_manager_sync.measurement_taps.lock().unwrap().is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert!(uci_manager_sync.range_start(session_id).is_ok());
+            assert_eq!(
+                drainer.join().unwrap(),
+                Ok(vec![
+                    range_data(session_id, 1),
+                    range_data(session_id, 2),
+                    range_data(session_id, 3)
+                ])
+            );
+        });
+
+        // All the measurements are still delivered to the NotificationManager.
+        for notf in notfs.into_iter() {
+            assert_eq!(test_rt.block_on(async { notf_receiver.recv().await }), Some(notf));
+        }
+    }
+
+    #[test]
+    fn test_sync_uci_wait_for_first_measurement() {
+        let session_id = 0x123;
+        let range_data = session_range_data(session_id);
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            vec![UciNotification::Session(SessionNotification::SessionInfo(range_data.clone()))],
+            Ok(()),
+        );
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        // No measurement is received before the ranging is started.
+        assert_eq!(
+            uci_manager_sync.wait_for_first_measurement(session_id, Duration::from_millis(10)),
+            Err(Error::Timeout)
+        );
+
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        assert_eq!(
+            uci_manager_sync.wait_for_first_measurement(session_id, Duration::from_secs(1)),
+            Ok(range_data.clone())
+        );
+        // The measurement is still delivered to the NotificationManager.
+        let notf = test_rt.block_on(async { notf_receiver.recv().await });
+        assert_eq!(
+            notf,
+            Some(UciNotification::Session(SessionNotification::SessionInfo(range_data)))
+        );
+    }
+
+    #[test]
+    fn test_sync_uci_ordered_notification() {
+        let session_id = 0x123;
+        let notfs = vec![
+            UciNotification::Core(CoreNotification::DeviceStatus(DeviceStateReady)),
+            UciNotification::Session(SessionNotification::Status {
+                session_token: session_id,
+                session_state: SessionState::SessionStateActive,
+                reason_code: 0,
+            }),
+            UciNotification::Core(CoreNotification::GenericError(StatusCode::UciStatusFailed)),
+            UciNotification::Session(SessionNotification::SessionInfo(session_range_data(
+                session_id,
+            ))),
+            UciNotification::Vendor(RawUciMessage { gid: 0x9, oid: 0x1, payload: vec![0x12] }),
+        ];
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(session_id, notfs.clone(), Ok(()));
+        let mut uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        uci_manager_sync.set_ordered_notification(true);
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        for expected_notf in notfs.into_iter() {
+            let notf = test_rt.block_on(async { notf_receiver.recv().await });
+            assert_eq!(notf, Some(expected_notf));
+        }
+    }
+
+    #[test]
+    fn test_sync_uci_range_start_many() {
+        let session_ids = [0x123, 0x456, 0x789];
+        // The single worker runs the spawned commands in order.
+        let test_rt = Builder::new_multi_thread().worker_threads(1).enable_all().build().unwrap();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(session_ids[0], vec![], Ok(()));
+        uci_manager_impl.expect_range_start(session_ids[1], vec![], Err(Error::BadParameters));
+        uci_manager_impl.expect_range_start(session_ids[2], vec![], Ok(()));
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(mpsc::unbounded_channel().0),
+        )
+        .unwrap();
+
+        assert_eq!(
+            uci_manager_sync.range_start_many(&session_ids),
+            vec![
+                (session_ids[0], Ok(())),
+                (session_ids[1], Err(Error::BadParameters)),
+                (session_ids[2], Ok(())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_uci_range_data_without_measurement() {
+        let session_id = 0x123;
+        let range_data = SessionRangeData {
+            ranging_measurements: RangingMeasurements::ShortAddressTwoWay(vec![]),
+            ..session_range_data(session_id)
+        };
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            vec![UciNotification::Session(SessionNotification::SessionInfo(range_data.clone()))],
+            Ok(()),
+        );
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        let notf = test_rt.block_on(async { notf_receiver.recv().await });
+        assert_eq!(
+            notf,
+            Some(UciNotification::Session(SessionNotification::SessionInfo(range_data)))
+        );
+    }
+
+    #[test]
+    fn test_sync_uci_aoa_fom_threshold() {
+        let session_id = 0x123;
+        // The FoMs of the measurement are 6, 8, 10 and 12.
+        let range_data = session_range_data(session_id);
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            vec![UciNotification::Session(SessionNotification::SessionInfo(range_data.clone()))],
+            Ok(()),
+        );
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        uci_manager_sync.set_aoa_fom_threshold(session_id, 10);
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        let notf = test_rt.block_on(async { notf_receiver.recv().await });
+        let measurement = match notf {
+            Some(UciNotification::Session(SessionNotification::SessionInfo(
+                SessionRangeData {
+                    ranging_measurements: RangingMeasurements::ShortAddressTwoWay(mut measurements),
+                    ..
+                },
+            ))) => measurements.pop().unwrap(),
+            _ => panic!("Unexpected notification: {:?}", notf),
+        };
+        // The AoA results below the threshold are zeroed.
+        assert_eq!((measurement.aoa_azimuth, measurement.aoa_azimuth_fom), (0, 0));
+        assert_eq!((measurement.aoa_elevation, measurement.aoa_elevation_fom), (0, 0));
+        assert_eq!(
+            (measurement.aoa_destination_azimuth, measurement.aoa_destination_azimuth_fom),
+            (9, 10)
+        );
+        assert_eq!(
+            (measurement.aoa_destination_elevation, measurement.aoa_destination_elevation_fom),
+            (11, 12)
+        );
+    }
+
+    /// NotificationManager which only implements the specific session callbacks.
+    struct NarrowNotificationManager {
+        notf_sender: mpsc::UnboundedSender<String>,
+    }
+
+    impl NotificationManager for NarrowNotificationManager {
+        fn on_core_notification(&mut self, _core_notification: CoreNotification) -> Result<()> {
+            Ok(())
+        }
+        fn on_session_status(
+            &mut self,
+            session_token: SessionToken,
+            session_state: SessionState,
+            _reason_code: u8,
+        ) -> Result<()> {
+            self.notf_sender
+                .send(format!("status {} {:?}", session_token, session_state))
+                .map_err(|_| Error::Unknown)
+        }
+        fn on_range_data(&mut self, range_data: SessionRangeData) -> Result<()> {
+            self.notf_sender
+                .send(format!("range_data {}", range_data.session_token))
+                .map_err(|_| Error::Unknown)
+        }
+        fn on_vendor_notification(&mut self, _vendor_notification: RawUciMessage) -> Result<()> {
+            Ok(())
+        }
+        fn on_data_rcv_notification(&mut self, _data_rcv_notf: DataRcvNotification) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct NarrowNotificationManagerBuilder {
+        notf_sender: mpsc::UnboundedSender<String>,
+    }
+
+    impl NotificationManagerBuilder for NarrowNotificationManagerBuilder {
+        type NotificationManager = NarrowNotificationManager;
+
+        fn build(self) -> Option<Self::NotificationManager> {
+            Some(NarrowNotificationManager { notf_sender: self.notf_sender })
+        }
+    }
+
+    #[test]
+    fn test_sync_uci_specific_session_callbacks() {
+        let session_id = 0x123;
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<String>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            vec![
+                UciNotification::Session(SessionNotification::Status {
+                    session_token: session_id,
+                    session_state: SessionState::SessionStateActive,
+                    reason_code: 0,
+                }),
+                UciNotification::Session(SessionNotification::DataCredit {
+                    session_token: session_id,
+                    credit_availability: CreditAvailability::CreditAvailable,
+                }),
+                UciNotification::Session(SessionNotification::SessionInfo(session_range_data(
+                    session_id,
+                ))),
+            ],
+            Ok(()),
+        );
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            NarrowNotificationManagerBuilder { notf_sender },
+        )
+        .unwrap();
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+
+        // The DataCredit notification falls back to the no-op on_session_notification().
+        let notfs = test_rt.block_on(async {
+            vec![notf_receiver.recv().await.unwrap(), notf_receiver.recv().await.unwrap()]
+        });
+        assert_eq!(notfs, vec!["status 291 SessionStateActive", "range_data 291"]);
+    }
+
+    #[test]
+    fn test_sync_uci_set_notification_manager() {
+        let session_id = 0x123;
+        let status_notf = |session_state| {
+            UciNotification::Session(SessionNotification::Status {
+                session_token: session_id,
+                session_state,
+                reason_code: 0,
+            })
+        };
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender1, mut notf_receiver1) = mpsc::unbounded_channel::<UciNotification>();
+        let (notf_sender2, mut notf_receiver2) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            vec![status_notf(SessionState::SessionStateActive)],
+            Ok(()),
+        );
+        uci_manager_impl.expect_range_stop(
+            session_id,
+            vec![status_notf(SessionState::SessionStateIdle)],
+            Ok(()),
+        );
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender1),
+        )
+        .unwrap();
+
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        let notf = test_rt.block_on(async { notf_receiver1.recv().await });
+        assert_eq!(notf, Some(status_notf(SessionState::SessionStateActive)));
+
+        assert!(uci_manager_sync
+            .set_notification_manager(MockNotificationManagerBuilder::new(notf_sender2))
+            .is_ok());
+        assert!(uci_manager_sync.range_stop(session_id).is_ok());
+        let notf = test_rt.block_on(async { notf_receiver2.recv().await });
+        assert_eq!(notf, Some(status_notf(SessionState::SessionStateIdle)));
+        // The previous NotificationManager is dropped.
+        let notf = test_rt.block_on(async { notf_receiver1.recv().await });
+        assert_eq!(notf, None);
+    }
+
+    #[test]
+    fn test_sync_uci_pause_notifications() {
+        let session_id = 0x123;
+        let status_notf = |session_state| {
+            UciNotification::Session(SessionNotification::Status {
+                session_token: session_id,
+                session_state,
+                reason_code: 0,
+            })
+        };
+        let start_notfs = vec![
+            UciNotification::Core(CoreNotification::DeviceStatus(DeviceStateReady)),
+            status_notf(SessionState::SessionStateActive),
+        ];
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(session_id, start_notfs.clone(), Ok(()));
+        uci_manager_impl.expect_range_stop(
+            session_id,
+            vec![status_notf(SessionState::SessionStateIdle)],
+            Ok(()),
+        );
+        let mut uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+        uci_manager_sync.set_ordered_notification(true);
+        let no_notf = |receiver: &mut mpsc::UnboundedReceiver<UciNotification>| {
+            test_rt.block_on(async {
+                timeout(Duration::from_millis(100), receiver.recv()).await.is_err()
+            })
+        };
+
+        // The notifications are buffered during the pause, and delivered in order on resume.
+        assert!(uci_manager_sync.pause_notifications().is_ok());
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        assert!(no_notf(&mut notf_receiver));
+        assert!(uci_manager_sync.resume_notifications(true).is_ok());
+        for expected_notf in start_notfs.into_iter() {
+            assert_eq!(notf_receiver.try_recv().ok(), Some(expected_notf));
+        }
+
+        // The notifications are dropped on resume without flushing.
+        assert!(uci_manager_sync.pause_notifications().is_ok());
+        assert!(uci_manager_sync.range_stop(session_id).is_ok());
+        assert!(no_notf(&mut notf_receiver));
+        assert!(uci_manager_sync.resume_notifications(false).is_ok());
+        assert!(no_notf(&mut notf_receiver));
+    }
+
+    #[test]
+    fn test_sync_uci_pause_notifications_overflow() {
+        let session_id = 0x123;
+        let vendor_notf = |index: usize| {
+            UciNotification::Vendor(RawUciMessage {
+                gid: 0xf,
+                oid: 0x1,
+                payload: index.to_le_bytes().to_vec(),
+            })
+        };
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, mut notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_range_start(
+            session_id,
+            (0..MAX_PAUSED_NOTIFICATIONS + 2).map(vendor_notf).collect(),
+            Ok(()),
+        );
+        let mut uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+        uci_manager_sync.set_ordered_notification(true);
+
+        // The oldest notifications are dropped once the buffer is full.
+        assert!(uci_manager_sync.pause_notifications().is_ok());
+        assert!(uci_manager_sync.range_start(session_id).is_ok());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(uci_manager_sync.resume_notifications(true).is_ok());
+        for index in 2..MAX_PAUSED_NOTIFICATIONS + 2 {
+            assert_eq!(notf_receiver.try_recv().ok(), Some(vendor_notf(index)));
+        }
+        assert!(notf_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sync_uci_session_guard() {
+        let session_id = 0x123;
+        let session_type = SessionType::FiraRangingSession;
+        let config_tlv = AppConfigTlv::new(AppConfigTlvType::DeviceType, vec![0x12]);
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_session_init(session_id, session_type, vec![], Ok(()));
+        uci_manager_impl.expect_session_set_app_config(
+            session_id,
+            vec![config_tlv.clone()],
+            vec![],
+            Ok(SetAppConfigResponse { status: StatusCode::UciStatusOk, config_status: vec![] }),
+        );
+        uci_manager_impl.expect_range_start(session_id, vec![], Ok(()));
+        uci_manager_impl.expect_range_stop(session_id, vec![], Ok(()));
+        uci_manager_impl.expect_session_deinit(session_id, vec![], Ok(()));
+        let mut mock_uci_manager = uci_manager_impl.clone();
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        {
+            let mut guard =
+                uci_manager_sync.session_init_guarded(session_id, session_type).unwrap();
+            guard.set_deinit_on_drop(true);
+            assert_eq!(guard.session_id(), session_id);
+            assert_eq!(guard.session_type(), session_type);
+            assert!(guard.set_app_config(vec![config_tlv]).is_ok());
+            assert!(guard.start().is_ok());
+            assert!(guard.stop().is_ok());
+            // The session is deinitialized when the guard is dropped.
+        }
+        assert!(test_rt.block_on(mock_uci_manager.wait_expected_calls_done()));
+    }
+
+    #[test]
+    fn test_sync_uci_range_pause_resume() {
+        let session_id = 0x123;
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_session_get_state(session_id, Ok(SessionState::SessionStateActive));
+        uci_manager_impl.expect_range_stop(session_id, vec![], Ok(()));
+        uci_manager_impl.expect_range_start(session_id, vec![], Ok(()));
+        let mut mock_uci_manager = uci_manager_impl.clone();
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        // Resuming a session which is not paused should fail.
+        assert_eq!(uci_manager_sync.range_resume(session_id), Err(Error::BadParameters));
+        assert!(uci_manager_sync.range_pause(session_id).is_ok());
+        assert!(uci_manager_sync.range_resume(session_id).is_ok());
+        // The session is not paused anymore after resumed.
+        assert_eq!(uci_manager_sync.range_resume(session_id), Err(Error::BadParameters));
+        assert!(test_rt.block_on(mock_uci_manager.wait_expected_calls_done()));
+    }
+
+    #[test]
+    fn test_sync_uci_range_pause_idle_session() {
+        let session_id = 0x123;
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_session_get_state(session_id, Ok(SessionState::SessionStateIdle));
+        let mut mock_uci_manager = uci_manager_impl.clone();
+        let uci_manager_sync = UciManagerSync::new_mock(
+            uci_manager_impl,
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        // The idle session cannot be paused, and no range_stop() is sent.
+        assert_eq!(uci_manager_sync.range_pause(session_id), Err(Error::BadParameters));
+        assert_eq!(uci_manager_sync.range_resume(session_id), Err(Error::BadParameters));
+        assert!(test_rt.block_on(mock_uci_manager.wait_expected_calls_done()));
+    }
+
+    #[test]
+    fn test_sync_uci_reentrant_call() {
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let uci_manager_sync = UciManagerSync::new_mock(
+            MockUciManager::new(),
+            test_rt.handle().to_owned(),
+            MockNotificationManagerBuilder::new(notf_sender),
+        )
+        .unwrap();
+
+        // Calling the blocking method inside the runtime returns the error instead of panicking.
+        let result = test_rt.block_on(async { uci_manager_sync.session_get_count() });
+        assert_eq!(result, Err(Error::ReentrantCall));
+    }
+
+    #[test]
+    fn test_sync_uci_call_from_spawn_blocking() {
+        let test_rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let (notf_sender, _notf_receiver) = mpsc::unbounded_channel::<UciNotification>();
+        let mut uci_manager_impl = MockUciManager::new();
+        uci_manager_impl.expect_session_get_count(Ok(2));
+        uci_manager_impl.expect_session_get_count(Ok(3));
+        let uci_manager_sync = Arc::new(
+            UciManagerSync::new_mock(
+                uci_manager_impl,
+                test_rt.handle().to_owned(),
+                MockNotificationManagerBuilder::new(notf_sender),
+            )
+            .unwrap(),
+        );
+
+        // The blocking thread is not an async execution context, so the call is allowed.
+        let uci_manager_sync_clone = uci_manager_sync.clone();
+        let result = test_rt
+            .block_on(test_rt.spawn_blocking(move || uci_manager_sync_clone.session_get_count()))
+            .unwrap();
+        assert_eq!(result, Ok(2));
+
+        // Entering the runtime context doesn't make the call reentrant either.
+        let _guard = test_rt.enter();
+        assert_eq!(uci_manager_sync.session_get_count(), Ok(3));
+    }
 }
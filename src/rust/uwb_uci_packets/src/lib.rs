@@ -713,23 +713,21 @@ impl PacketDefrager {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParsedDiagnosticNtfPacket {
-    session_token: u32,
-    sequence_number: u32,
-    frame_reports: Vec<ParsedFrameReport>,
+    pub session_token: u32,
+    pub sequence_number: u32,
+    pub frame_reports: Vec<ParsedFrameReport>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParsedFrameReport {
-    uwb_msg_id: u8,
-    action: u8,
-    antenna_set: u8,
-    rssi: Vec<u8>,
-    aoa: Vec<AoaMeasurement>,
-    cir: Vec<CirValue>,
+    pub uwb_msg_id: u8,
+    pub action: u8,
+    pub antenna_set: u8,
+    pub rssi: Vec<u8>,
+    pub aoa: Vec<AoaMeasurement>,
+    pub cir: Vec<CirValue>,
 }
 
 pub fn parse_diagnostics_ntf(evt: AndroidRangeDiagnosticsNtf) -> Result<ParsedDiagnosticNtfPacket> {
@@ -859,15 +857,146 @@ pub fn build_session_update_controller_multicast_list_cmd(
     .build())
 }
 
-impl Drop for AppConfigTlv {
-    fn drop(&mut self) {
-        if self.cfg_id == AppConfigTlvType::VendorId || self.cfg_id == AppConfigTlvType::StaticStsIv
-        {
+/// The power consumption of the UWBS in each power state, in the unit of mW.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerProfile {
+    pub idle_power_mw: f64,
+    pub tx_power_mw: f64,
+    pub rx_power_mw: f64,
+}
+
+impl PowerStats {
+    /// Estimate the energy consumed by the UWBS in the unit of mJ, from the time spent in each
+    /// power state and the power consumption of the states.
+    pub fn estimated_energy_mj(&self, profile: &PowerProfile) -> f64 {
+        // mW * ms = uJ.
+        (self.idle_time_ms as f64 * profile.idle_power_mw
+            + self.tx_time_ms as f64 * profile.tx_power_mw
+            + self.rx_time_ms as f64 * profile.rx_power_mw)
+            / 1000.0
+    }
+}
+
+impl StatusCode {
+    /// Whether the status is a transient failure of a single ranging round or data exchange, i.e.
+    /// the session is still healthy and the following rounds are expected to succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            StatusCode::UciStatusCommandRetry
+                | StatusCode::UciStatusRangingTxFailed
+                | StatusCode::UciStatusRangingRxTimeout
+                | StatusCode::UciStatusRangingRxPhyDecFailed
+                | StatusCode::UciStatusRangingRxPhyToaFailed
+                | StatusCode::UciStatusRangingRxPhyStsFailed
+                | StatusCode::UciStatusRangingRxMacDecFailed
+                | StatusCode::UciStatusRangingRxMacIeDecFailed
+                | StatusCode::UciStatusRangingRxMacIeMissing
+                | StatusCode::UciStatusDataRxCrcError
+                | StatusCode::UciStatusErrorCccSeBusy
+        )
+    }
+
+    /// Whether the status is caused by an invalid command or session configuration, i.e. retrying
+    /// without changing the configuration of the session is not expected to succeed.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            StatusCode::UciStatusSyntaxError
+                | StatusCode::UciStatusInvalidParam
+                | StatusCode::UciStatusInvalidRange
+                | StatusCode::UciStatusInvalidMsgSize
+                | StatusCode::UciStatusUnknownGid
+                | StatusCode::UciStatusUnknownOid
+                | StatusCode::UciStatusReadOnly
+                | StatusCode::UciStatusSessionNotExist
+                | StatusCode::UciStatusSessionNotConfigured
+                | StatusCode::UciStatusMaxSessionsExceeded
+                | StatusCode::UciStatusErrorUwbInitiationTimeTooOld
+                | StatusCode::UciStatusErrorRoundIndexNotActivated
+                | StatusCode::UciStatusErrorNumberOfActiveRangingRoundsExceeded
+                | StatusCode::UciStatusErrorDlTdoaDeviceAddressNotMatchingInReplyTimeList
+                | StatusCode::UciStatusDataMaxTxPsduSizeExceeded
+                | StatusCode::UciStatusErrorCccLifecycle
+        )
+    }
+}
+
+impl AppConfigTlvType {
+    /// Convert the tag value on the wire to the AppConfigTlvType.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::try_from(value).ok()
+    }
+
+    /// The tag value of the AppConfigTlvType on the wire.
+    pub fn as_u8(&self) -> u8 {
+        u8::from(*self)
+    }
+
+    /// Whether the app config can only be read by SESSION_GET_APP_CONFIG_CMD. These configs are
+    /// derived by the UWBS for the CCC session, and setting them is rejected with
+    /// UCI_STATUS_READ_ONLY.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            AppConfigTlvType::CccHopModeKey
+                | AppConfigTlvType::CccUwbTime0
+                | AppConfigTlvType::CccLastIndexUsed
+        )
+    }
+
+    /// Whether the value of the app config is sensitive, e.g. the STS-related values, which is
+    /// zeroized when the AppConfigTlv is dropped.
+    pub fn is_sensitive(&self) -> bool {
+        matches!(
+            self,
+            AppConfigTlvType::VendorId
+                | AppConfigTlvType::StaticStsIv
+                | AppConfigTlvType::SessionKey
+                | AppConfigTlvType::SubsessionKey
+        )
+    }
+}
+
+impl DeviceConfigId {
+    /// Convert the tag value on the wire to the DeviceConfigId.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::try_from(value).ok()
+    }
+
+    /// The tag value of the DeviceConfigId on the wire.
+    pub fn as_u8(&self) -> u8 {
+        u8::from(*self)
+    }
+}
+
+impl Zeroize for AppConfigTlv {
+    /// Zeroize the value if it is sensitive, the other values are kept.
+    fn zeroize(&mut self) {
+        if self.cfg_id.is_sensitive() {
             self.v.zeroize();
         }
     }
 }
 
+impl Drop for AppConfigTlv {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Drop for Controlee_V2_0_16_Byte_Version {
+    fn drop(&mut self) {
+        self.subsession_key.zeroize();
+    }
+}
+
+impl Drop for Controlee_V2_0_32_Byte_Version {
+    fn drop(&mut self) {
+        self.subsession_key.zeroize();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -914,6 +1043,21 @@ mod tests {
         assert_eq!(cir_vec, parsed_frame_report.cir);
     }
 
+    #[test]
+    fn test_power_stats_estimated_energy() {
+        let power_stats = PowerStats {
+            status: StatusCode::UciStatusOk,
+            idle_time_ms: 10000,
+            tx_time_ms: 200,
+            rx_time_ms: 500,
+            total_wake_count: 3,
+        };
+        let profile = PowerProfile { idle_power_mw: 0.5, tx_power_mw: 120.0, rx_power_mw: 80.0 };
+
+        // 10000 * 0.5 + 200 * 120 + 500 * 80 = 69000 uJ.
+        assert_eq!(power_stats.estimated_energy_mj(&profile), 69.0);
+    }
+
     #[test]
     fn test_write_controlee() {
         let short_address: [u8; 2] = [2, 3];
@@ -947,6 +1091,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_build_multicast_add_with_key_packet() {
+        let controlee = Controlee_V2_0_16_Byte_Version {
+            short_address: [0x12, 0x34],
+            subsession_id: 0x1324_3546,
+            subsession_key: [0xab; 16],
+        };
+        let packet: UciControlPacket = build_session_update_controller_multicast_list_cmd(
+            0x1425_3647,
+            UpdateMulticastListAction::AddControleeWithShortSubSessionKey,
+            Controlees::ShortSessionKey(vec![controlee]),
+        )
+        .unwrap()
+        .into();
+        let packet_fragments: Vec<UciControlPacketHal> = packet.into();
+        let uci_packet: Vec<u8> = packet_fragments[0].clone().into();
+        let mut expected = vec![
+            0x21, 0x07, 0x00, 0x1c, // 2(packet info), RFU, payload length(28)
+            0x47, 0x36, 0x25, 0x14, // 4(session id (LE))
+            0x02, 0x01, 0x12, 0x34, // action, # controlee, 2(short address (LE))
+            0x46, 0x35, 0x24, 0x13, // 4(subsession id (LE))
+        ];
+        expected.extend_from_slice(&[0xab; 16]); // 16(subsession key)
+        assert_eq!(uci_packet, expected);
+
+        // The key is only accepted by the actions with the key of the same length.
+        let controlee = Controlee_V2_0_32_Byte_Version {
+            short_address: [0x12, 0x34],
+            subsession_id: 0x1324_3546,
+            subsession_key: [0xab; 32],
+        };
+        assert!(build_session_update_controller_multicast_list_cmd(
+            0x1425_3647,
+            UpdateMulticastListAction::AddControleeWithShortSubSessionKey,
+            Controlees::LongSessionKey(vec![controlee]),
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_to_raw_payload() {
         let payload = vec![0x11, 0x22, 0x33];
@@ -1154,4 +1337,67 @@ mod tests {
         assert_eq!(measurement.dt_anchor_location, vec![]);
         assert_eq!(measurement.ranging_rounds, vec![0x02, 0x05]);
     }
+
+    #[test]
+    fn test_status_code_classification() {
+        for status in [
+            StatusCode::UciStatusRangingRxTimeout,
+            StatusCode::UciStatusRangingRxPhyStsFailed,
+            StatusCode::UciStatusCommandRetry,
+            StatusCode::UciStatusDataRxCrcError,
+        ] {
+            assert!(status.is_transient());
+            assert!(!status.is_fatal());
+        }
+        for status in [
+            StatusCode::UciStatusInvalidParam,
+            StatusCode::UciStatusSessionNotExist,
+            StatusCode::UciStatusErrorRoundIndexNotActivated,
+            StatusCode::UciStatusDataMaxTxPsduSizeExceeded,
+        ] {
+            assert!(status.is_fatal());
+            assert!(!status.is_transient());
+        }
+        for status in [StatusCode::UciStatusOk, StatusCode::UciStatusOkNegativeDistanceReport] {
+            assert!(!status.is_transient());
+            assert!(!status.is_fatal());
+        }
+    }
+
+    #[test]
+    fn test_config_id_u8_conversion() {
+        for value in 0..=u8::MAX {
+            if let Some(cfg_id) = AppConfigTlvType::from_u8(value) {
+                assert_eq!(cfg_id.as_u8(), value);
+            }
+            if let Some(cfg_id) = DeviceConfigId::from_u8(value) {
+                assert_eq!(cfg_id.as_u8(), value);
+            }
+        }
+        assert_eq!(AppConfigTlvType::from_u8(0x04), Some(AppConfigTlvType::ChannelNumber));
+        assert_eq!(AppConfigTlvType::StsConfig.as_u8(), 0x02);
+        assert_eq!(DeviceConfigId::from_u8(0x01), Some(DeviceConfigId::LowPowerMode));
+        assert_eq!(DeviceConfigId::from_u8(0x02), None);
+        assert_eq!(DeviceConfigId::DeviceState.as_u8(), 0x00);
+    }
+
+    #[test]
+    fn test_app_config_read_only() {
+        assert!(AppConfigTlvType::CccUwbTime0.is_read_only());
+        assert!(AppConfigTlvType::CccHopModeKey.is_read_only());
+        assert!(!AppConfigTlvType::RangingInterval.is_read_only());
+        assert!(!AppConfigTlvType::CccUwbConfigId.is_read_only());
+    }
+
+    #[test]
+    fn test_zeroize_app_config_tlv() {
+        let mut tlv = AppConfigTlv { cfg_id: AppConfigTlvType::SessionKey, v: vec![0xab; 16] };
+        tlv.zeroize();
+        assert!(tlv.v.is_empty());
+
+        // The value which is not sensitive is kept.
+        let mut tlv = AppConfigTlv { cfg_id: AppConfigTlvType::DeviceType, v: vec![1] };
+        tlv.zeroize();
+        assert_eq!(tlv.v, vec![1]);
+    }
 }